@@ -0,0 +1,21 @@
+-- Lobby login
+-- ===========
+-- Membership expiry and the player message inbox shown on the lobby screen.
+-- Safe to run against an existing database.
+
+-- When a membership runs out (NULL for none or no expiry)
+ALTER TABLE users ADD COLUMN IF NOT EXISTS membership_expires_at TIMESTAMPTZ;
+
+-- Messages sent to a player's inbox
+CREATE TABLE IF NOT EXISTS player_messages (
+    id BIGSERIAL PRIMARY KEY,
+    recipient_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    sender_id UUID REFERENCES users(id) ON DELETE SET NULL,  -- NULL for system messages
+    subject VARCHAR(80) NOT NULL,
+    body TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    read_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_player_messages_unread
+    ON player_messages(recipient_id) WHERE read_at IS NULL;
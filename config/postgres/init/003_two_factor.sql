@@ -0,0 +1,25 @@
+-- Two-factor authentication
+-- =========================
+-- TOTP secrets and single-use recovery codes, checked on web and game logins.
+-- Safe to run against an existing database.
+
+-- Two-factor authentication (TOTP)
+CREATE TABLE IF NOT EXISTS user_two_factor (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    secret_encrypted BYTEA NOT NULL,  -- AES-256-GCM, nonce || ciphertext
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,  -- FALSE until the first code is confirmed
+    last_used_step BIGINT,  -- Last accepted time step, so a code is never accepted twice
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    enabled_at TIMESTAMPTZ
+);
+
+-- Two-factor recovery codes (single use)
+CREATE TABLE IF NOT EXISTS user_recovery_codes (
+    id BIGSERIAL PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash VARCHAR(64) NOT NULL,  -- SHA-256 hex
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_recovery_codes_user ON user_recovery_codes(user_id);
@@ -305,7 +305,6 @@ RUSTSCAPE_AUTH_REGISTRATION_ENABLED=true
 RUSTSCAPE_AUTH_MIN_PASSWORD_LENGTH=6
 RUSTSCAPE_AUTH_MAX_LOGIN_ATTEMPTS=5
 RUSTSCAPE_AUTH_LOCKOUT_DURATION=900
-RUSTSCAPE_AUTH_BCRYPT_COST=12
 
 # Server settings
 RUSTSCAPE_WORLD_ID=1
@@ -125,6 +125,9 @@ abstract class GameClient(
     protected var username: String = ""
     protected var password: String = ""
 
+    // Token the server issued for resuming this session (if enabled)
+    protected var resumeToken: String? = null
+
     // Coroutine scope for async operations
     protected val scope = CoroutineScope(SupervisorJob() + Dispatchers.Default)
 
@@ -957,6 +960,7 @@ abstract class GameClient(
             ServerOpcode.BANK_UPDATE -> handleBankUpdate(payload)
             ServerOpcode.BANK_SETTINGS -> handleBankSettings(payload)
             ServerOpcode.BANK_TAB_INFO -> handleBankTabInfo(payload)
+            ServerOpcode.RESUME_TOKEN -> resumeToken = payload.readString()
         }
     }
 
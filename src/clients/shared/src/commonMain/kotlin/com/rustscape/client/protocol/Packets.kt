@@ -121,6 +121,9 @@ object ServerOpcode {
     /** Bank tab sizes info */
     const val BANK_TAB_INFO = 251
 
+    /** Token for resuming the session after a dropped connection */
+    const val RESUME_TOKEN = 252
+
     // ===== EQUIPMENT OPCODES =====
 
     /** Equipment contents update */
@@ -396,7 +399,7 @@ object PacketSize {
         0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // 220-229
         0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // 230-239
         1, 0, 0, 0, 0, 0, 0, 0, 2, -2, // 240-249: 248=BANK_OPEN(2), 249=BANK_UPDATE(var)
-        6, -1, 0, -1, 6, 0             // 250-255: 250=BANK_SETTINGS(6), 251=BANK_TAB_INFO(var)
+        6, -1, -1, -1, 6, 0            // 250-255: 250=BANK_SETTINGS(6), 251=BANK_TAB_INFO(var), 252=RESUME_TOKEN(var)
     )
 
     /** Client packet sizes indexed by opcode */
@@ -0,0 +1,438 @@
+//! Cache and sprite asset endpoints
+//!
+//! This module serves game assets over HTTP so the web client can fetch them
+//! directly from the game server instead of relying on a separate extraction step:
+//! - GET /cache/{index}/{archive} - Raw JS5 container data from the cache store
+//! - GET /sprites/{id}.{png|qoi} - First frame of a sprite archive
+//! - GET /sprites/{id}/atlas.json - Atlas metadata for all frames of a sprite archive
+//! - GET /sprites/{id}/atlas.{png|qoi} - Atlas image for all frames of a sprite archive
+//!
+//! Every response carries a CRC-based `ETag` and honours `If-None-Match`, so
+//! unchanged assets are answered with `304 Not Modified` without being rendered.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{Any, CorsLayer},
+};
+use tracing::debug;
+
+use crate::api::error::ApiError;
+use crate::cache::sprites::{
+    ImageFormat, SpriteDecoder, SpriteSheetConfig, SpriteSheetGenerator, SPRITE_INDEX,
+};
+use crate::cache::CacheStore;
+
+/// Index ID used by JS5 for reference tables and the checksum table
+const META_INDEX: u8 = 255;
+
+/// Cache-Control value for assets: cacheable, but always revalidated via ETag
+const CACHE_CONTROL: &str = "public, no-cache";
+
+/// Shared state for the asset routes
+#[derive(Clone)]
+pub struct AssetState {
+    /// Cache store the assets are read from
+    pub cache: Arc<CacheStore>,
+}
+
+/// Create the asset router
+///
+/// The returned router has its state applied and can be merged into the API
+/// router, or served on its own when the REST API is unavailable.
+pub fn create_router(cache: Arc<CacheStore>) -> Router {
+    Router::new()
+        .route("/cache/:index/:archive", get(get_cache_file))
+        .route("/sprites/:file", get(get_sprite))
+        .route("/sprites/:id/:file", get(get_sprite_atlas))
+        .layer(CompressionLayer::new().gzip(true))
+        .layer(
+            CorsLayer::new()
+                .allow_origin(Any)
+                .allow_methods(Any)
+                .allow_headers(Any)
+                .expose_headers(Any),
+        )
+        .with_state(AssetState { cache })
+}
+
+/// GET /cache/{index}/{archive}
+///
+/// Serve a raw cache container, exactly as it would be sent over JS5
+async fn get_cache_file(
+    State(state): State<AssetState>,
+    Path((index, archive)): Path<(u8, u32)>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let known_crc = archive_crc(&state.cache, index, archive);
+    if let Some(crc) = known_crc {
+        let etag = make_etag(&format!("{}-{}", index, archive), crc);
+        if etag_matches(&headers, &etag) {
+            return Ok(not_modified(&etag));
+        }
+    }
+
+    let data = if index == META_INDEX {
+        if archive == META_INDEX as u32 {
+            state.cache.get_checksum_table()
+        } else if archive < META_INDEX as u32 {
+            state.cache.get_reference_table(archive as u8)
+        } else {
+            return Err(ApiError::NotFound("Reference table".to_string()));
+        }
+    } else {
+        state.cache.get_file(index, archive)
+    }
+    .map_err(|e| ApiError::InternalError(format!("Failed to read cache file: {}", e)))?;
+
+    let crc = known_crc.unwrap_or_else(|| crc32fast::hash(&data));
+    let etag = make_etag(&format!("{}-{}", index, archive), crc);
+    if etag_matches(&headers, &etag) {
+        return Ok(not_modified(&etag));
+    }
+
+    Ok(asset_response(data, "application/octet-stream", &etag))
+}
+
+/// GET /sprites/{id}.{png|qoi}
+///
+/// Render the first frame of a sprite archive
+async fn get_sprite(
+    State(state): State<AssetState>,
+    Path(file): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let (id, extension) = split_file_name(&file)?;
+    let id = parse_sprite_id(id)?;
+    let format = ImageFormat::from_str(extension)
+        .ok_or_else(|| ApiError::InvalidInput(format!("Unsupported format: {}", extension)))?;
+
+    let tag = format!("sprite-{}-{}", id, format.extension());
+    let known_crc = archive_crc(&state.cache, SPRITE_INDEX, id);
+    if let Some(crc) = known_crc {
+        let etag = make_etag(&tag, crc);
+        if etag_matches(&headers, &etag) {
+            return Ok(not_modified(&etag));
+        }
+    }
+
+    let cache = state.cache.clone();
+    let data = tokio::task::spawn_blocking(move || render_sprite(&cache, id, format))
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Sprite render task failed: {}", e)))??;
+
+    let crc = known_crc.unwrap_or_else(|| crc32fast::hash(&data));
+    let etag = make_etag(&tag, crc);
+    if etag_matches(&headers, &etag) {
+        return Ok(not_modified(&etag));
+    }
+
+    Ok(asset_response(data, image_content_type(format), &etag))
+}
+
+/// GET /sprites/{id}/atlas.{json|png|qoi}
+///
+/// Render all frames of a sprite archive into an atlas, returning either the
+/// atlas metadata or the atlas image
+async fn get_sprite_atlas(
+    State(state): State<AssetState>,
+    Path((id, file)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let id = parse_sprite_id(&id)?;
+    let (name, extension) = split_file_name(&file)?;
+    if name != "atlas" {
+        return Err(ApiError::NotFound("Sprite asset".to_string()));
+    }
+
+    let (format, json) = match extension {
+        "json" => (ImageFormat::Png, true),
+        other => (
+            ImageFormat::from_str(other)
+                .ok_or_else(|| ApiError::InvalidInput(format!("Unsupported format: {}", other)))?,
+            false,
+        ),
+    };
+
+    let tag = format!("atlas-{}-{}", id, extension);
+    let known_crc = archive_crc(&state.cache, SPRITE_INDEX, id);
+    if let Some(crc) = known_crc {
+        let etag = make_etag(&tag, crc);
+        if etag_matches(&headers, &etag) {
+            return Ok(not_modified(&etag));
+        }
+    }
+
+    let cache = state.cache.clone();
+    let (image, atlas) = tokio::task::spawn_blocking(move || render_atlas(&cache, id, format))
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Atlas render task failed: {}", e)))??;
+
+    let (data, content_type) = if json {
+        let data = serde_json::to_vec(&atlas)
+            .map_err(|e| ApiError::InternalError(format!("Failed to encode atlas: {}", e)))?;
+        (data, "application/json")
+    } else {
+        (image, image_content_type(format))
+    };
+
+    let crc = known_crc.unwrap_or_else(|| crc32fast::hash(&data));
+    let etag = make_etag(&tag, crc);
+    if etag_matches(&headers, &etag) {
+        return Ok(not_modified(&etag));
+    }
+
+    Ok(asset_response(data, content_type, &etag))
+}
+
+/// Decode a sprite archive and encode its first frame
+fn render_sprite(cache: &CacheStore, id: u32, format: ImageFormat) -> Result<Vec<u8>, ApiError> {
+    let sprites = decode_sprites(cache, id)?;
+    let sprite = sprites
+        .iter()
+        .find(|s| s.is_valid())
+        .ok_or_else(|| ApiError::NotFound("Sprite".to_string()))?;
+
+    match format {
+        ImageFormat::Png => sprite.encode_png(),
+        ImageFormat::Qoi => sprite.encode_qoi(),
+    }
+    .map_err(|e| ApiError::InternalError(format!("Failed to encode sprite {}: {}", id, e)))
+}
+
+/// Decode a sprite archive and pack all of its frames into a single atlas
+fn render_atlas(
+    cache: &CacheStore,
+    id: u32,
+    format: ImageFormat,
+) -> Result<(Vec<u8>, crate::cache::sprites::SpriteSheetAtlas), ApiError> {
+    let sprites = decode_sprites(cache, id)?;
+
+    let generator = SpriteSheetGenerator::with_config(SpriteSheetConfig {
+        format,
+        ..Default::default()
+    });
+
+    generator
+        .generate(&sprites, &format!("sprite_{}", id))
+        .into_iter()
+        .next()
+        .ok_or_else(|| ApiError::NotFound("Sprite".to_string()))
+}
+
+/// Decode all frames of a sprite archive
+fn decode_sprites(
+    cache: &CacheStore,
+    id: u32,
+) -> Result<Vec<crate::cache::sprites::Sprite>, ApiError> {
+    let data = cache
+        .get_decompressed_file(SPRITE_INDEX, id)
+        .map_err(|e| ApiError::InternalError(format!("Failed to read sprite {}: {}", id, e)))?;
+
+    if data.is_empty() {
+        return Err(ApiError::NotFound("Sprite".to_string()));
+    }
+
+    SpriteDecoder::decode(id, &data).map_err(|e| {
+        debug!("Failed to decode sprite {}: {}", id, e);
+        ApiError::NotFound("Sprite".to_string())
+    })
+}
+
+/// Look up the CRC of an archive from the parsed reference tables
+///
+/// Reference tables themselves (index 255) use the CRC of the table data.
+fn archive_crc(cache: &CacheStore, index: u8, archive: u32) -> Option<u32> {
+    if index == META_INDEX {
+        if archive >= META_INDEX as u32 {
+            return None;
+        }
+        return cache.reference_table_crc(archive as u8);
+    }
+
+    cache.archive_crc(index, archive)
+}
+
+/// Split a file name into its stem and extension
+fn split_file_name(file: &str) -> Result<(&str, &str), ApiError> {
+    file.rsplit_once('.')
+        .ok_or_else(|| ApiError::InvalidInput(format!("Missing file extension: {}", file)))
+}
+
+/// Parse a sprite archive ID from a path segment
+fn parse_sprite_id(id: &str) -> Result<u32, ApiError> {
+    id.parse()
+        .map_err(|_| ApiError::InvalidInput(format!("Invalid sprite ID: {}", id)))
+}
+
+/// Content type for an encoded image
+fn image_content_type(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "image/png",
+        ImageFormat::Qoi => "image/qoi",
+    }
+}
+
+/// Build a strong ETag from an asset tag and CRC
+fn make_etag(tag: &str, crc: u32) -> String {
+    format!("\"{}-{:08x}\"", tag, crc)
+}
+
+/// Check whether the request's `If-None-Match` header matches an ETag
+///
+/// Handles comma-separated lists, the `*` wildcard, and weak validators.
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|candidate| candidate.trim())
+        .any(|candidate| {
+            candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+        })
+}
+
+/// Build a `304 Not Modified` response
+fn not_modified(etag: &str) -> Response {
+    let mut response = StatusCode::NOT_MODIFIED.into_response();
+    set_cache_headers(&mut response, etag);
+    response
+}
+
+/// Build a `200 OK` asset response
+fn asset_response(data: Vec<u8>, content_type: &'static str, etag: &str) -> Response {
+    let mut response = (
+        [(header::CONTENT_TYPE, HeaderValue::from_static(content_type))],
+        data,
+    )
+        .into_response();
+    set_cache_headers(&mut response, etag);
+    response
+}
+
+/// Attach the ETag and Cache-Control headers to a response
+fn set_cache_headers(response: &mut Response, etag: &str) {
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(etag) {
+        headers.insert(header::ETAG, value);
+    }
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(CACHE_CONTROL),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use std::env::temp_dir;
+    use tower::ServiceExt;
+
+    fn test_router() -> Router {
+        let path = temp_dir().join("rustscape_assets_test");
+        create_router(Arc::new(CacheStore::new(&path).unwrap()))
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        let etag = make_etag("2-10", 0xdeadbeef);
+        assert_eq!(etag, "\"2-10-deadbeef\"");
+
+        let mut headers = HeaderMap::new();
+        assert!(!etag_matches(&headers, &etag));
+
+        headers.insert(header::IF_NONE_MATCH, etag.parse().unwrap());
+        assert!(etag_matches(&headers, &etag));
+
+        headers.insert(
+            header::IF_NONE_MATCH,
+            format!("\"other\", W/{}", etag).parse().unwrap(),
+        );
+        assert!(etag_matches(&headers, &etag));
+
+        headers.insert(header::IF_NONE_MATCH, "\"other\"".parse().unwrap());
+        assert!(!etag_matches(&headers, &etag));
+
+        headers.insert(header::IF_NONE_MATCH, "*".parse().unwrap());
+        assert!(etag_matches(&headers, &etag));
+    }
+
+    #[test]
+    fn test_split_file_name() {
+        assert_eq!(split_file_name("123.png").unwrap(), ("123", "png"));
+        assert_eq!(split_file_name("atlas.json").unwrap(), ("atlas", "json"));
+        assert!(split_file_name("123").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cache_file_etag_and_not_modified() {
+        let router = test_router();
+
+        let response = router
+            .clone()
+            .oneshot(Request::get("/cache/255/255").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let etag = response.headers().get(header::ETAG).unwrap().clone();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(!body.is_empty());
+
+        let response = router
+            .oneshot(
+                Request::get("/cache/255/255")
+                    .header(header::IF_NONE_MATCH, etag)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn test_sprite_routes_reject_bad_requests() {
+        let router = test_router();
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::get("/sprites/abc.png")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = router
+            .clone()
+            .oneshot(Request::get("/sprites/1.gif").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // No cache on disk, so the sprite archive is empty
+        let response = router
+            .oneshot(
+                Request::get("/sprites/1/atlas.json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -7,6 +7,10 @@
 //! - GET /api/v1/auth/session - Validate current session
 //! - GET /api/v1/auth/check-username - Check username availability
 //! - POST /api/v1/auth/refresh - Refresh access token
+//! - POST /api/v1/auth/2fa/setup - Start two-factor enrollment
+//! - POST /api/v1/auth/2fa/verify - Confirm enrollment with a first code
+//! - POST /api/v1/auth/2fa/disable - Turn two-factor authentication off
+//! - POST /api/v1/auth/game-token - Get a one-time game login token
 
 use axum::{
     extract::{Query, State},
@@ -21,11 +25,13 @@
 use crate::api::error::ApiError;
 use crate::api::middleware::AuthenticatedUser;
 use crate::api::response::{
-    AuthResponse, RefreshResponse, SessionInfo, UserInfo, UsernameCheckResponse,
+    AuthResponse, GameTokenResponse, RecoveryCodesResponse, RefreshResponse, SessionInfo,
+    TwoFactorSetupResponse, UserInfo, UsernameCheckResponse,
 };
 use crate::api::ApiState;
+use crate::auth::password::verify_password;
 
-use super::password::{hash_password, verify_password, PasswordRequirements};
+use super::password::PasswordRequirements;
 use super::queries;
 
 /// Registration request body
@@ -57,6 +63,15 @@ pub struct LoginRequest {
     pub password: String,
     #[serde(default)]
     pub remember: bool,
+    /// Authenticator or recovery code, for accounts with 2FA enabled
+    #[serde(default)]
+    pub totp_code: Option<String>,
+}
+
+/// Two-factor code request body
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    pub code: String,
 }
 
 /// Refresh token request body
@@ -150,7 +165,7 @@ pub async fn register(
     }
 
     // Hash the password
-    let password_hash = hash_password(&payload.password, state.auth.bcrypt_cost)?;
+    let password_hash = state.auth.password_policy.hash(&payload.password)?;
 
     // Create the user
     let user = queries::create_user(
@@ -265,9 +280,7 @@ pub async fn login(
     }
 
     // Verify password
-    let password_valid = verify_password(&payload.password, &user.password_hash)?;
-
-    if !password_valid {
+    if !verify_password(&payload.password, &user.password_hash) {
         warn!(
             user_id = %user.id,
             username = %user.username,
@@ -297,6 +310,55 @@ pub async fn login(
         return Err(ApiError::InvalidCredentials);
     }
 
+    // Accounts with two-factor authentication also need a current code
+    if state.auth.two_factor.is_enabled(user.id).await? {
+        let Some(code) = payload.totp_code.as_deref() else {
+            return Ok(Json(AuthResponse::two_factor_required()));
+        };
+
+        let now = Utc::now().timestamp() as u64;
+        if !state.auth.two_factor.verify(user.id, code, now).await? {
+            warn!(
+                user_id = %user.id,
+                username = %user.username,
+                "Failed login attempt - invalid two-factor code"
+            );
+
+            queries::increment_failed_login_attempts(
+                &state.auth.db,
+                user.id,
+                state.auth.max_login_attempts,
+                state.auth.lockout_duration,
+            )
+            .await?;
+
+            queries::record_login_attempt(
+                &state.auth.db,
+                user.id,
+                client_ip.as_deref(),
+                user_agent.as_deref(),
+                false,
+                Some("invalid_2fa"),
+            )
+            .await?;
+
+            return Err(ApiError::InvalidTwoFactorCode);
+        }
+    }
+
+    // Replace bcrypt and weaker Argon2 hashes now that we have the password
+    state
+        .auth
+        .password_policy
+        .upgrade(
+            &state.auth.db,
+            user.id,
+            &payload.password,
+            &user.password_hash,
+            client_ip.as_deref(),
+        )
+        .await;
+
     // Success! Generate tokens
     let access_token = state
         .auth
@@ -511,6 +573,123 @@ pub async fn refresh_token(
     )))
 }
 
+/// POST /api/v1/auth/2fa/setup
+///
+/// Start two-factor enrollment with a new secret for an authenticator app
+pub async fn two_factor_setup(
+    State(state): State<ApiState>,
+    user: AuthenticatedUser,
+) -> Result<Json<TwoFactorSetupResponse>, ApiError> {
+    let setup = state
+        .auth
+        .two_factor
+        .begin_setup(user.id, &user.username)
+        .await?;
+
+    info!(user_id = %user.id, "Two-factor setup started");
+
+    Ok(Json(TwoFactorSetupResponse {
+        secret: setup.secret,
+        otpauth_uri: setup.uri,
+    }))
+}
+
+/// POST /api/v1/auth/2fa/verify
+///
+/// Confirm two-factor enrollment with a first code, enabling it and
+/// returning the recovery codes
+pub async fn two_factor_verify(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    user: AuthenticatedUser,
+    Json(payload): Json<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, ApiError> {
+    let now = Utc::now().timestamp() as u64;
+    let recovery_codes = state
+        .auth
+        .two_factor
+        .confirm(user.id, &payload.code, now)
+        .await?;
+
+    let client_ip = extract_client_ip(&headers);
+    queries::log_audit(
+        &state.auth.db,
+        Some(user.id),
+        "2fa_enabled",
+        Some("user"),
+        Some(user.id),
+        None,
+        None,
+        client_ip.as_deref(),
+    )
+    .await?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// POST /api/v1/auth/2fa/disable
+///
+/// Turn two-factor authentication off, given a current or recovery code
+pub async fn two_factor_disable(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    user: AuthenticatedUser,
+    Json(payload): Json<TwoFactorCodeRequest>,
+) -> Result<StatusCode, ApiError> {
+    let now = Utc::now().timestamp() as u64;
+    state
+        .auth
+        .two_factor
+        .disable(user.id, &payload.code, now)
+        .await?;
+
+    let client_ip = extract_client_ip(&headers);
+    queries::log_audit(
+        &state.auth.db,
+        Some(user.id),
+        "2fa_disabled",
+        Some("user"),
+        Some(user.id),
+        None,
+        None,
+        client_ip.as_deref(),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/v1/auth/game-token
+///
+/// Issue a one-time token the game client sends in place of the password
+pub async fn game_token(
+    State(state): State<ApiState>,
+    user: AuthenticatedUser,
+) -> Result<Json<GameTokenResponse>, ApiError> {
+    // The account may have been banned since the access token was issued
+    let user_record = queries::find_user_by_id(&state.auth.db, user.id)
+        .await?
+        .ok_or(ApiError::NotFound("User".to_string()))?;
+
+    if user_record.is_currently_banned() {
+        return Err(ApiError::AccountDisabled);
+    }
+
+    let token = state
+        .auth
+        .game_tokens
+        .issue(user_record.id, &user_record.username)
+        .await?;
+
+    info!(user_id = %user.id, "Game token issued");
+
+    Ok(Json(GameTokenResponse {
+        token,
+        username: user_record.username,
+        expires_in: state.auth.game_tokens.ttl_secs() as i64,
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -7,6 +7,10 @@
 //! - GET /api/v1/auth/session - Validate current session
 //! - GET /api/v1/auth/check-username - Check username availability
 //! - POST /api/v1/auth/refresh - Refresh access token
+//! - POST /api/v1/auth/2fa/setup - Start two-factor enrollment
+//! - POST /api/v1/auth/2fa/verify - Confirm enrollment with a first code
+//! - POST /api/v1/auth/2fa/disable - Turn two-factor authentication off
+//! - POST /api/v1/auth/game-token - Get a one-time game login token
 
 pub mod handlers;
 mod jwt;
@@ -21,6 +25,9 @@
 
 use crate::api::error::ApiError;
 use crate::api::middleware::Claims;
+use crate::auth::game_token::GameTokens;
+use crate::auth::password::PasswordPolicy;
+use crate::auth::two_factor::TwoFactor;
 use crate::config::ServerConfig;
 
 /// JWT configuration
@@ -69,8 +76,12 @@ pub struct AuthState {
     pub max_login_attempts: i32,
     /// Lockout duration in seconds
     pub lockout_duration: i64,
-    /// Bcrypt cost factor
-    pub bcrypt_cost: u32,
+    /// Password hashing policy
+    pub password_policy: PasswordPolicy,
+    /// Two-factor authentication
+    pub two_factor: TwoFactor,
+    /// One-time game login tokens
+    pub game_tokens: GameTokens,
 }
 
 impl AuthState {
@@ -101,11 +112,7 @@ pub async fn new(config: &ServerConfig) -> Result<Self, ApiError> {
         );
 
         // Create Redis connection pool
-        let redis_url = format!(
-            "redis://{}:{}",
-            std::env::var("RUSTSCAPE_REDIS_HOST").unwrap_or_else(|_| "localhost".to_string()),
-            std::env::var("RUSTSCAPE_REDIS_PORT").unwrap_or_else(|_| "6379".to_string())
-        );
+        let redis_url = config.redis.url();
 
         let redis_config = RedisConfig::from_url(&redis_url);
         let redis = redis_config
@@ -115,7 +122,10 @@ pub async fn new(config: &ServerConfig) -> Result<Self, ApiError> {
                 ApiError::RedisError(format!("Failed to create Redis pool: {}", e))
             })?;
 
-        info!("Redis pool created for {}", redis_url);
+        info!(
+            "Redis pool created for {}:{}",
+            config.redis.host, config.redis.port
+        );
 
         // Create JWT config
         let jwt_config = JwtConfig::default();
@@ -138,10 +148,10 @@ pub async fn new(config: &ServerConfig) -> Result<Self, ApiError> {
             .ok()
             .and_then(|v| v.parse().ok())
             .unwrap_or(900); // 15 minutes
-        let bcrypt_cost = std::env::var("RUSTSCAPE_AUTH_BCRYPT_COST")
-            .ok()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or(12);
+        let password_policy = PasswordPolicy::new(&config.password_hash)?;
+
+        let two_factor = TwoFactor::new(&config.two_factor, Some(db.clone()))?;
+        let game_tokens = GameTokens::new(config, Some(redis.clone()));
 
         Ok(Self {
             db,
@@ -153,7 +163,9 @@ pub async fn new(config: &ServerConfig) -> Result<Self, ApiError> {
             min_password_length,
             max_login_attempts,
             lockout_duration,
-            bcrypt_cost,
+            password_policy,
+            two_factor,
+            game_tokens,
         })
     }
 
@@ -1,27 +1,6 @@
-//! Password hashing and verification
+//! Password strength validation
 //!
-//! This module provides secure password hashing using bcrypt,
-//! as well as password strength validation.
-
-use bcrypt::{hash, verify};
-
-use crate::api::error::ApiError;
-
-/// Hash a password using bcrypt
-pub fn hash_password(password: &str, cost: u32) -> Result<String, ApiError> {
-    hash(password, cost).map_err(|e| {
-        tracing::error!("Failed to hash password: {}", e);
-        ApiError::InternalError("Failed to hash password".to_string())
-    })
-}
-
-/// Verify a password against a bcrypt hash
-pub fn verify_password(password: &str, hash: &str) -> Result<bool, ApiError> {
-    verify(password, hash).map_err(|e| {
-        tracing::error!("Failed to verify password: {}", e);
-        ApiError::InternalError("Failed to verify password".to_string())
-    })
-}
+//! Hashing lives in `crate::auth::password`, shared with game logins.
 
 /// Password strength requirements
 #[derive(Debug, Clone)]
@@ -172,29 +151,6 @@ pub struct PasswordStrength {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_hash_and_verify_password() {
-        let password = "test_password_123";
-        let hashed = hash_password(password, 4).unwrap(); // Use low cost for tests
-
-        assert!(verify_password(password, &hashed).unwrap());
-        assert!(!verify_password("wrong_password", &hashed).unwrap());
-    }
-
-    #[test]
-    fn test_hash_produces_different_results() {
-        let password = "same_password";
-        let hash1 = hash_password(password, 4).unwrap();
-        let hash2 = hash_password(password, 4).unwrap();
-
-        // Hashes should be different (different salts)
-        assert_ne!(hash1, hash2);
-
-        // But both should verify correctly
-        assert!(verify_password(password, &hash1).unwrap());
-        assert!(verify_password(password, &hash2).unwrap());
-    }
-
     #[test]
     fn test_password_requirements_default() {
         let req = PasswordRequirements::default();
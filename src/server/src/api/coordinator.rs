@@ -0,0 +1,117 @@
+//! Login coordinator endpoints
+//!
+//! Served by the server with `coordinator.enabled`, for the worlds that take
+//! their login leases from it:
+//! - POST /api/v1/coordinator/leases - Take the lease for an account (409 if
+//!   it is logged in to any world)
+//! - POST /api/v1/coordinator/leases/renew - Heartbeat a world's leases
+//! - POST /api/v1/coordinator/leases/release - Release a lease on logout
+//! - GET /api/v1/coordinator/leases/:username - Look up an account's lease
+//!
+//! Every request must carry the shared secret as a bearer token.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::api::error::ApiError;
+use crate::auth::coordinator::{LoginCoordinator, LoginLease};
+use crate::net::leases::{AcquireRequest, RenewRequest, RenewResponse};
+
+/// Coordinator route state
+#[derive(Clone)]
+struct CoordinatorState {
+    /// Lease store
+    coordinator: Arc<LoginCoordinator>,
+    /// Secret worlds must present
+    secret: Arc<str>,
+}
+
+/// Response to a release
+#[derive(Debug, Serialize, Deserialize)]
+struct ReleaseResponse {
+    /// Whether the lease was still held
+    released: bool,
+}
+
+/// Create the coordinator router
+pub fn create_router(coordinator: Arc<LoginCoordinator>, secret: &str) -> Router {
+    Router::new()
+        .route("/api/v1/coordinator/leases", post(acquire))
+        .route("/api/v1/coordinator/leases/renew", post(renew))
+        .route("/api/v1/coordinator/leases/release", post(release))
+        .route("/api/v1/coordinator/leases/:username", get(holder))
+        .with_state(CoordinatorState {
+            coordinator,
+            secret: Arc::from(secret),
+        })
+}
+
+/// Check the request carries the shared secret
+fn authorize(state: &CoordinatorState, headers: &HeaderMap) -> Result<(), ApiError> {
+    let token = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) if token == &*state.secret => Ok(()),
+        _ => Err(ApiError::Unauthorized),
+    }
+}
+
+/// POST /api/v1/coordinator/leases
+async fn acquire(
+    State(state): State<CoordinatorState>,
+    headers: HeaderMap,
+    Json(request): Json<AcquireRequest>,
+) -> Result<Json<LoginLease>, ApiError> {
+    authorize(&state, &headers)?;
+    let lease = state
+        .coordinator
+        .acquire(&request.username, request.world_id)
+        .await?;
+    Ok(Json(lease))
+}
+
+/// POST /api/v1/coordinator/leases/renew
+async fn renew(
+    State(state): State<CoordinatorState>,
+    headers: HeaderMap,
+    Json(request): Json<RenewRequest>,
+) -> Result<Json<RenewResponse>, ApiError> {
+    authorize(&state, &headers)?;
+    let lost = state.coordinator.renew(&request.leases).await?;
+    Ok(Json(RenewResponse { lost }))
+}
+
+/// POST /api/v1/coordinator/leases/release
+async fn release(
+    State(state): State<CoordinatorState>,
+    headers: HeaderMap,
+    Json(lease): Json<LoginLease>,
+) -> Result<Json<ReleaseResponse>, ApiError> {
+    authorize(&state, &headers)?;
+    let released = state.coordinator.release(&lease).await?;
+    Ok(Json(ReleaseResponse { released }))
+}
+
+/// GET /api/v1/coordinator/leases/:username
+async fn holder(
+    State(state): State<CoordinatorState>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> Result<Json<LoginLease>, ApiError> {
+    authorize(&state, &headers)?;
+    state
+        .coordinator
+        .holder(&username)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound("Lease".to_string()))
+}
@@ -11,6 +11,8 @@
 use serde::Serialize;
 use std::fmt;
 
+use crate::error::{AuthError, RustscapeError};
+
 /// API error response body
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
@@ -39,6 +41,8 @@ pub enum ApiError {
     AccountLocked,
     TooManyAttempts,
     Unauthorized,
+    InvalidTwoFactorCode,
+    AlreadyLoggedIn,
 
     // Validation errors
     ValidationError(std::collections::HashMap<String, String>),
@@ -69,6 +73,8 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 write!(f, "Too many login attempts. Please try again later")
             }
             ApiError::Unauthorized => write!(f, "Authentication required"),
+            ApiError::InvalidTwoFactorCode => write!(f, "Invalid two-factor code"),
+            ApiError::AlreadyLoggedIn => write!(f, "Account is already logged in"),
             ApiError::ValidationError(_) => write!(f, "Validation failed"),
             ApiError::InvalidInput(msg) => write!(f, "{}", msg),
             ApiError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
@@ -94,6 +100,8 @@ pub fn status_code(&self) -> StatusCode {
             ApiError::AccountLocked => StatusCode::TOO_MANY_REQUESTS,
             ApiError::TooManyAttempts => StatusCode::TOO_MANY_REQUESTS,
             ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::InvalidTwoFactorCode => StatusCode::UNAUTHORIZED,
+            ApiError::AlreadyLoggedIn => StatusCode::CONFLICT,
             ApiError::ValidationError(_) => StatusCode::BAD_REQUEST,
             ApiError::InvalidInput(_) => StatusCode::BAD_REQUEST,
             ApiError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
@@ -115,6 +123,8 @@ pub fn error_code(&self) -> &'static str {
             ApiError::AccountLocked => "ACCOUNT_LOCKED",
             ApiError::TooManyAttempts => "TOO_MANY_ATTEMPTS",
             ApiError::Unauthorized => "UNAUTHORIZED",
+            ApiError::InvalidTwoFactorCode => "INVALID_2FA_CODE",
+            ApiError::AlreadyLoggedIn => "ALREADY_LOGGED_IN",
             ApiError::ValidationError(_) => "VALIDATION_ERROR",
             ApiError::InvalidInput(_) => "INVALID_INPUT",
             ApiError::DatabaseError(_) => "DATABASE_ERROR",
@@ -176,6 +186,29 @@ fn from(err: sqlx::Error) -> Self {
     }
 }
 
+impl From<RustscapeError> for ApiError {
+    fn from(err: RustscapeError) -> Self {
+        match err {
+            RustscapeError::Auth(AuthError::InvalidTwoFactorCode) => ApiError::InvalidTwoFactorCode,
+            RustscapeError::Auth(AuthError::TwoFactorAlreadyEnabled) => {
+                ApiError::InvalidInput("Two-factor authentication is already enabled".to_string())
+            }
+            RustscapeError::Auth(AuthError::TwoFactorNotEnabled) => {
+                ApiError::InvalidInput("Two-factor authentication is not enabled".to_string())
+            }
+            RustscapeError::Auth(AuthError::AlreadyLoggedIn) => ApiError::AlreadyLoggedIn,
+            RustscapeError::Auth(AuthError::LoginServerOffline) => {
+                ApiError::RedisError("Login server offline".to_string())
+            }
+            RustscapeError::Database(e) => e.into(),
+            _ => {
+                tracing::error!("Server error: {:?}", err);
+                ApiError::InternalError(err.to_string())
+            }
+        }
+    }
+}
+
 impl From<redis::RedisError> for ApiError {
     fn from(err: redis::RedisError) -> Self {
         tracing::error!("Redis error: {:?}", err);
@@ -234,6 +267,10 @@ fn test_error_status_codes() {
             ApiError::TooManyAttempts.status_code(),
             StatusCode::TOO_MANY_REQUESTS
         );
+        assert_eq!(
+            ApiError::InvalidTwoFactorCode.status_code(),
+            StatusCode::UNAUTHORIZED
+        );
         assert_eq!(
             ApiError::InternalError("test".to_string()).status_code(),
             StatusCode::INTERNAL_SERVER_ERROR
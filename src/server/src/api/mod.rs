@@ -1,17 +1,25 @@
 //! REST API module for the Rustscape game server
 //!
 //! This module provides HTTP endpoints for:
-//! - User authentication (login, register, logout)
+//! - User authentication (login, register, logout, two-factor)
 //! - Session management
 //! - Account management
+//! - Cache and sprite assets for the web client
+//! - Server statistics for monitoring and load tests
+//! - The world list
+//! - Login leases, when this server is the login coordinator
 //!
 //! The API is built with Axum and integrates with PostgreSQL for persistence
 //! and Redis for session caching.
 
+pub mod assets;
 pub mod auth;
+pub mod coordinator;
 pub mod error;
 pub mod middleware;
 pub mod response;
+pub mod stats;
+pub mod worlds;
 
 use std::sync::Arc;
 
@@ -54,7 +62,11 @@ pub fn create_router(state: ApiState) -> Router {
         .route("/logout", post(auth::handlers::logout))
         .route("/session", get(auth::handlers::get_session))
         .route("/check-username", get(auth::handlers::check_username))
-        .route("/refresh", post(auth::handlers::refresh_token));
+        .route("/refresh", post(auth::handlers::refresh_token))
+        .route("/2fa/setup", post(auth::handlers::two_factor_setup))
+        .route("/2fa/verify", post(auth::handlers::two_factor_verify))
+        .route("/2fa/disable", post(auth::handlers::two_factor_disable))
+        .route("/game-token", post(auth::handlers::game_token));
 
     // Health check route
     let health_routes = Router::new()
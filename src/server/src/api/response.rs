@@ -56,6 +56,9 @@ pub struct AuthResponse {
     /// Token expiration time in seconds
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expires_in: Option<i64>,
+    /// Further step needed to finish logging in (`2fa_required`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
 }
 
 impl AuthResponse {
@@ -68,6 +71,7 @@ pub fn success(token: String, user: UserInfo, expires_in: i64) -> Self {
             refresh_token: None,
             user: Some(user),
             expires_in: Some(expires_in),
+            state: None,
         }
     }
 
@@ -85,6 +89,7 @@ pub fn success_with_refresh(
             refresh_token: Some(refresh_token),
             user: Some(user),
             expires_in: Some(expires_in),
+            state: None,
         }
     }
 
@@ -97,6 +102,20 @@ pub fn registered(token: String, user: UserInfo, expires_in: i64) -> Self {
             refresh_token: None,
             user: Some(user),
             expires_in: Some(expires_in),
+            state: None,
+        }
+    }
+
+    /// Create a response asking for a two-factor code to finish logging in
+    pub fn two_factor_required() -> Self {
+        Self {
+            success: false,
+            message: "Two-factor code required".to_string(),
+            token: None,
+            refresh_token: None,
+            user: None,
+            expires_in: None,
+            state: Some("2fa_required".to_string()),
         }
     }
 }
@@ -184,6 +203,36 @@ pub fn unavailable(reason: impl Into<String>) -> Self {
     }
 }
 
+/// Two-factor setup response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorSetupResponse {
+    /// Secret in base32, for manual entry into an authenticator app
+    pub secret: String,
+    /// `otpauth://` URI, for QR codes
+    pub otpauth_uri: String,
+}
+
+/// Two-factor confirmation response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodesResponse {
+    /// Single-use recovery codes, only shown once
+    pub recovery_codes: Vec<String>,
+}
+
+/// Game login token response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameTokenResponse {
+    /// One-time token to send in place of the password
+    pub token: String,
+    /// Username to log in with
+    pub username: String,
+    /// Seconds until the token expires
+    pub expires_in: i64,
+}
+
 /// Token refresh response
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -274,5 +323,16 @@ fn test_auth_response_serialization() {
         assert!(json.contains("\"success\":true"));
         assert!(json.contains("\"token\":\"jwt.token.here\""));
         assert!(json.contains("\"isMember\":false")); // camelCase
+        assert!(!json.contains("state"));
+    }
+
+    #[test]
+    fn test_two_factor_required_serialization() {
+        let response = AuthResponse::two_factor_required();
+
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(json.contains("\"success\":false"));
+        assert!(json.contains("\"state\":\"2fa_required\""));
+        assert!(!json.contains("token"));
     }
 }
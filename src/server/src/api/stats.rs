@@ -0,0 +1,85 @@
+//! Server statistics endpoint
+//!
+//! - GET /stats - Tick, player counts, error, login, compression, JS5
+//!   scheduler and JS5 response cache counters as JSON
+//!
+//! Counters are totals since startup; tools such as the load tester sample
+//! the endpoint before and after a run and report the difference.
+
+use std::sync::Arc;
+
+use axum::{extract::State, routing::get, Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::net::admission::AdmissionStats;
+use crate::net::deflate::CompressionStats;
+use crate::net::flood::FloodStats;
+use crate::net::js5_scheduler::Js5SchedulerStats;
+use crate::net::metrics::ConnectionStats;
+use crate::protocol::js5_cache::Js5ResponseCacheStats;
+use crate::state::AppState;
+
+/// Snapshot of the server's counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ServerStats {
+    /// Current game tick
+    pub tick: u64,
+    /// Players in the world
+    pub players_online: u64,
+    /// Open connections (any state)
+    pub connections: u64,
+    /// Ticks that took longer than the tick rate
+    pub slow_ticks: u64,
+    /// Inbound game packets that failed to process
+    pub packet_errors: u64,
+    /// Connection errors
+    pub connection: ConnectionStats,
+    /// Flood protection counters
+    pub flood: FloodStats,
+    /// WebSocket compression counters
+    #[serde(default)]
+    pub compression: CompressionStats,
+    /// Login admission counters
+    #[serde(default)]
+    pub login: AdmissionStats,
+    /// JS5 scheduler queue depths and throughput
+    #[serde(default)]
+    pub js5_scheduler: Js5SchedulerStats,
+    /// Encoded JS5 response cache sizes and hit rate
+    #[serde(default)]
+    pub js5_cache: Js5ResponseCacheStats,
+}
+
+impl ServerStats {
+    /// Collect the current counters
+    pub fn collect(state: &AppState) -> Self {
+        Self {
+            tick: state.world.tick(),
+            players_online: state.world.player_count(),
+            connections: state.session_manager.count() as u64,
+            slow_ticks: state.world.slow_ticks(),
+            packet_errors: state.world.packet_errors(),
+            connection: state.connection_metrics.stats(),
+            flood: state.flood_metrics.stats(),
+            compression: state.compression_metrics.stats(),
+            login: state.admission.stats(),
+            js5_scheduler: state.js5_scheduler.stats(),
+            js5_cache: state.js5_responses.stats(),
+        }
+    }
+}
+
+/// Create the statistics router
+///
+/// Like the asset router, it has its state applied and is served whether or
+/// not the REST API is available.
+pub fn create_router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/stats", get(get_stats))
+        .with_state(state)
+}
+
+/// GET /stats
+async fn get_stats(State(state): State<Arc<AppState>>) -> Json<ServerStats> {
+    Json(ServerStats::collect(&state))
+}
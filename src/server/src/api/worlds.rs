@@ -0,0 +1,25 @@
+//! World list endpoint
+//!
+//! - GET /api/worlds - Online worlds as JSON, the same list the world list
+//!   handshake sends
+//!
+//! Served whether or not the REST API is available.
+
+use std::sync::Arc;
+
+use axum::{extract::State, routing::get, Json, Router};
+
+use crate::game::world_list::WorldEntry;
+use crate::state::AppState;
+
+/// Create the world list router
+pub fn create_router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/api/worlds", get(get_worlds))
+        .with_state(state)
+}
+
+/// GET /api/worlds
+async fn get_worlds(State(state): State<Arc<AppState>>) -> Json<Vec<WorldEntry>> {
+    Json(state.worlds.worlds(state.world.player_count() as u32))
+}
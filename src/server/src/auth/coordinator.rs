@@ -0,0 +1,416 @@
+//! Login coordinator
+//!
+//! Each world process authenticates its own logins, so on their own they
+//! can't tell whether an account is already playing on another world. The
+//! coordinator hands out world-scoped login leases: a world takes a lease
+//! before letting an account in, renews the leases of its online players with
+//! a heartbeat and releases each one on logout. A lease held by another world
+//! refuses the login; a lease whose world stops heartbeating (crashed or cut
+//! off) expires after `lease_ttl_secs` and the account can log in again.
+//!
+//! Leases live in Redis, so several coordinator processes can share them, or
+//! in memory for a single coordinator and for tests.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use deadpool_redis::{Config as RedisPoolConfig, Pool as RedisPool, Runtime};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::auth::normalize_username;
+use crate::config::ServerConfig;
+use crate::error::{login_backend_offline, AuthError, Result, RustscapeError};
+
+/// Redis key prefix for leases
+const KEY_PREFIX: &str = "rustscape:login_lease:";
+
+/// Logged when Redis can't be reached
+const REDIS_FAILED: &str = "Redis error in login coordinator";
+
+/// Store a lease unless the account already has one, returning the holder
+const ACQUIRE_SCRIPT: &str = r#"
+local held = redis.call('GET', KEYS[1])
+if held then
+    return held
+end
+redis.call('SET', KEYS[1], ARGV[1], 'PX', ARGV[2])
+return false
+"#;
+
+/// Extend a lease's expiry if it is still held under the same ID
+const RENEW_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+/// Delete a lease if it is still held under the same ID
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+/// An account's right to be logged in to one world
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginLease {
+    /// Unique lease ID, so a stale release can't drop a newer lease
+    pub lease_id: String,
+    /// Username (normalized)
+    pub username: String,
+    /// World holding the lease
+    pub world_id: u16,
+}
+
+impl LoginLease {
+    /// Create a new lease for an account on a world
+    pub fn new(username: &str, world_id: u16) -> Self {
+        Self {
+            lease_id: Uuid::new_v4().simple().to_string(),
+            username: normalize_username(username),
+            world_id,
+        }
+    }
+
+    /// Redis key for the account
+    fn key(username: &str) -> String {
+        format!("{}{}", KEY_PREFIX, normalize_username(username))
+    }
+
+    /// Value stored under the account's key
+    fn value(&self) -> String {
+        format!("{}:{}", self.world_id, self.lease_id)
+    }
+
+    /// Parse a stored value back into a lease
+    fn parse(username: &str, value: &str) -> Option<Self> {
+        let (world_id, lease_id) = value.split_once(':')?;
+        Some(Self {
+            lease_id: lease_id.to_string(),
+            username: normalize_username(username),
+            world_id: world_id.parse().ok()?,
+        })
+    }
+}
+
+/// Where leases are kept
+enum LeaseStore {
+    /// Shared Redis
+    Redis(RedisPool),
+    /// This process only, with each lease's expiry
+    Memory(Mutex<HashMap<String, (LoginLease, Instant)>>),
+}
+
+/// Issues, renews and releases login leases
+pub struct LoginCoordinator {
+    /// Lease storage
+    store: LeaseStore,
+    /// Time a lease survives without being renewed
+    ttl: Duration,
+}
+
+impl LoginCoordinator {
+    /// Create a coordinator keeping leases in Redis
+    pub fn new(redis: RedisPool, ttl: Duration) -> Self {
+        Self {
+            store: LeaseStore::Redis(redis),
+            ttl,
+        }
+    }
+
+    /// Create a coordinator keeping leases in memory
+    pub fn in_memory(ttl: Duration) -> Self {
+        Self {
+            store: LeaseStore::Memory(Mutex::new(HashMap::new())),
+            ttl,
+        }
+    }
+
+    /// Create the coordinator described by the config
+    ///
+    /// Redis connections are made on first use, so this only fails on a
+    /// malformed URL.
+    pub fn connect(config: &ServerConfig) -> Result<Self> {
+        let ttl = Duration::from_secs(config.coordinator.lease_ttl_secs);
+        if config.coordinator.in_memory {
+            return Ok(Self::in_memory(ttl));
+        }
+
+        let redis = RedisPoolConfig::from_url(config.redis.url())
+            .create_pool(Some(Runtime::Tokio1))
+            .map_err(|e| {
+                RustscapeError::Config(format!("Failed to create coordinator Redis pool: {}", e))
+            })?;
+        Ok(Self::new(redis, ttl))
+    }
+
+    /// Time a lease survives without being renewed
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Take a lease for an account on a world
+    ///
+    /// Fails with `AlreadyLoggedIn` while any world, including the asking
+    /// one, holds a live lease for the account.
+    pub async fn acquire(&self, username: &str, world_id: u16) -> Result<LoginLease> {
+        let lease = LoginLease::new(username, world_id);
+
+        // World holding the account's lease, if any
+        let holder = match &self.store {
+            LeaseStore::Memory(leases) => {
+                let now = Instant::now();
+                let mut leases = leases.lock();
+                match leases.get(&lease.username) {
+                    Some((held, expires)) if *expires > now => Some(held.world_id),
+                    _ => {
+                        leases.insert(lease.username.clone(), (lease.clone(), now + self.ttl));
+                        None
+                    }
+                }
+            }
+            LeaseStore::Redis(redis) => {
+                let mut conn = redis
+                    .get()
+                    .await
+                    .map_err(|e| login_backend_offline(REDIS_FAILED, e))?;
+                let held: Option<String> = redis::Script::new(ACQUIRE_SCRIPT)
+                    .key(LoginLease::key(username))
+                    .arg(lease.value())
+                    .arg(self.ttl.as_millis() as u64)
+                    .invoke_async(&mut conn)
+                    .await
+                    .map_err(|e| login_backend_offline(REDIS_FAILED, e))?;
+                held.map(|value| {
+                    LoginLease::parse(username, &value).map_or(0, |held| held.world_id)
+                })
+            }
+        };
+
+        match holder {
+            None => {
+                debug!(
+                    username = %lease.username,
+                    world_id = world_id,
+                    "Login lease granted"
+                );
+                Ok(lease)
+            }
+            Some(held_by) => {
+                debug!(
+                    username = %lease.username,
+                    world_id = world_id,
+                    held_by = held_by,
+                    "Login lease refused, account is logged in elsewhere"
+                );
+                Err(RustscapeError::Auth(AuthError::AlreadyLoggedIn))
+            }
+        }
+    }
+
+    /// Extend leases for another TTL
+    ///
+    /// Returns the leases that were no longer held (expired, or released and
+    /// taken by another login), so their worlds can log those players out.
+    pub async fn renew(&self, leases: &[LoginLease]) -> Result<Vec<LoginLease>> {
+        let mut lost = Vec::new();
+
+        match &self.store {
+            LeaseStore::Memory(held) => {
+                let now = Instant::now();
+                let mut held = held.lock();
+                for lease in leases {
+                    match held.get_mut(&lease.username) {
+                        Some((current, expires)) if current == lease && *expires > now => {
+                            *expires = now + self.ttl;
+                        }
+                        _ => lost.push(lease.clone()),
+                    }
+                }
+            }
+            LeaseStore::Redis(redis) => {
+                let mut conn = redis
+                    .get()
+                    .await
+                    .map_err(|e| login_backend_offline(REDIS_FAILED, e))?;
+                let script = redis::Script::new(RENEW_SCRIPT);
+                for lease in leases {
+                    let renewed: i64 = script
+                        .key(LoginLease::key(&lease.username))
+                        .arg(lease.value())
+                        .arg(self.ttl.as_millis() as u64)
+                        .invoke_async(&mut conn)
+                        .await
+                        .map_err(|e| login_backend_offline(REDIS_FAILED, e))?;
+                    if renewed == 0 {
+                        lost.push(lease.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(lost)
+    }
+
+    /// Release a lease
+    ///
+    /// Returns false if the lease was no longer held.
+    pub async fn release(&self, lease: &LoginLease) -> Result<bool> {
+        match &self.store {
+            LeaseStore::Memory(held) => {
+                let mut held = held.lock();
+                match held.get(&lease.username) {
+                    Some((current, _)) if current == lease => {
+                        held.remove(&lease.username);
+                        Ok(true)
+                    }
+                    _ => Ok(false),
+                }
+            }
+            LeaseStore::Redis(redis) => {
+                let mut conn = redis
+                    .get()
+                    .await
+                    .map_err(|e| login_backend_offline(REDIS_FAILED, e))?;
+                let released: i64 = redis::Script::new(RELEASE_SCRIPT)
+                    .key(LoginLease::key(&lease.username))
+                    .arg(lease.value())
+                    .invoke_async(&mut conn)
+                    .await
+                    .map_err(|e| login_backend_offline(REDIS_FAILED, e))?;
+                Ok(released > 0)
+            }
+        }
+    }
+
+    /// Get the live lease for an account, if any
+    pub async fn holder(&self, username: &str) -> Result<Option<LoginLease>> {
+        match &self.store {
+            LeaseStore::Memory(held) => {
+                let now = Instant::now();
+                Ok(held
+                    .lock()
+                    .get(&normalize_username(username))
+                    .filter(|(_, expires)| *expires > now)
+                    .map(|(lease, _)| lease.clone()))
+            }
+            LeaseStore::Redis(redis) => {
+                let mut conn = redis
+                    .get()
+                    .await
+                    .map_err(|e| login_backend_offline(REDIS_FAILED, e))?;
+                let value: Option<String> = redis::cmd("GET")
+                    .arg(LoginLease::key(username))
+                    .query_async(&mut conn)
+                    .await
+                    .map_err(|e| login_backend_offline(REDIS_FAILED, e))?;
+                Ok(value.and_then(|value| LoginLease::parse(username, &value)))
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for LoginCoordinator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let store = match self.store {
+            LeaseStore::Redis(_) => "redis",
+            LeaseStore::Memory(_) => "memory",
+        };
+        f.debug_struct("LoginCoordinator")
+            .field("store", &store)
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn already_logged_in(result: Result<LoginLease>) -> bool {
+        matches!(
+            result,
+            Err(RustscapeError::Auth(AuthError::AlreadyLoggedIn))
+        )
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_login_across_worlds_refused() {
+        let coordinator = LoginCoordinator::in_memory(Duration::from_secs(30));
+
+        let lease = coordinator.acquire("Zezima", 1).await.unwrap();
+        assert_eq!(lease.username, "zezima");
+        assert_eq!(lease.world_id, 1);
+
+        assert!(already_logged_in(coordinator.acquire("zezima", 2).await));
+        assert!(already_logged_in(coordinator.acquire("ZEZIMA", 1).await));
+        assert_eq!(
+            coordinator.holder("zezima").await.unwrap(),
+            Some(lease.clone())
+        );
+
+        // Other accounts are unaffected
+        assert!(coordinator.acquire("durial321", 2).await.is_ok());
+
+        // Released on logout, then free for any world
+        assert!(coordinator.release(&lease).await.unwrap());
+        assert_eq!(coordinator.holder("zezima").await.unwrap(), None);
+        assert_eq!(coordinator.acquire("zezima", 2).await.unwrap().world_id, 2);
+    }
+
+    #[tokio::test]
+    async fn test_stale_release_keeps_newer_lease() {
+        let coordinator = LoginCoordinator::in_memory(Duration::from_millis(20));
+
+        let old = coordinator.acquire("zezima", 1).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        let new = coordinator.acquire("zezima", 2).await.unwrap();
+
+        // World 1 logging its player out late must not free world 2's lease
+        assert!(!coordinator.release(&old).await.unwrap());
+        assert_eq!(coordinator.holder("zezima").await.unwrap(), Some(new));
+    }
+
+    #[tokio::test]
+    async fn test_leases_expire_without_heartbeat() {
+        let coordinator = LoginCoordinator::in_memory(Duration::from_millis(60));
+
+        let kept = coordinator.acquire("zezima", 1).await.unwrap();
+        let dropped = coordinator.acquire("durial321", 1).await.unwrap();
+
+        // Only one of the leases is renewed
+        for _ in 0..3 {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            assert!(coordinator
+                .renew(std::slice::from_ref(&kept))
+                .await
+                .unwrap()
+                .is_empty());
+        }
+
+        assert!(already_logged_in(coordinator.acquire("zezima", 2).await));
+        let taken = coordinator.acquire("durial321", 2).await.unwrap();
+        assert_eq!(taken.world_id, 2);
+
+        // Renewing a lost lease reports it
+        let lost = coordinator
+            .renew(&[kept.clone(), dropped.clone()])
+            .await
+            .unwrap();
+        assert_eq!(lost, vec![dropped]);
+    }
+
+    #[test]
+    fn test_stored_value_roundtrip() {
+        let lease = LoginLease::new("Zezima", 7);
+        assert_eq!(LoginLease::parse("zezima", &lease.value()), Some(lease));
+        assert_eq!(LoginLease::parse("zezima", "garbage"), None);
+        assert_eq!(LoginLease::key("Zezima"), "rustscape:login_lease:zezima");
+    }
+}
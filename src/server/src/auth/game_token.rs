@@ -0,0 +1,203 @@
+//! One-time game login tokens
+//!
+//! Web clients log in through the REST API and then ask it for a game token,
+//! which they send in the RSA login block in place of the password, so the
+//! raw password never crosses the game socket. Tokens live in Redis for a few
+//! seconds and are deleted by the login that redeems them (`GETDEL`), so a
+//! token can only ever be used once.
+
+use data_encoding::BASE64URL_NOPAD;
+use deadpool_redis::{Config as RedisPoolConfig, Pool as RedisPool, Runtime};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::auth::normalize_username;
+use crate::config::ServerConfig;
+use crate::error::{login_backend_offline, Result, RustscapeError};
+
+/// Prefix that tells a game token apart from a password
+pub const GAME_TOKEN_PREFIX: &str = "gt_";
+
+/// Random bytes in a token
+const TOKEN_BYTES: usize = 24;
+
+/// Length of an encoded token, prefix included
+pub const GAME_TOKEN_LEN: usize = GAME_TOKEN_PREFIX.len() + (TOKEN_BYTES * 4).div_ceil(3);
+
+/// Redis key prefix for outstanding tokens
+const KEY_PREFIX: &str = "rustscape:game_token:";
+
+/// Logged when Redis can't be reached
+const REDIS_FAILED: &str = "Redis error during game token lookup";
+
+/// Check whether a login block password is a game token
+///
+/// Passwords are at most 20 characters, so they can never be mistaken for
+/// a token.
+pub fn is_game_token(password: &str) -> bool {
+    password.len() == GAME_TOKEN_LEN
+        && password.starts_with(GAME_TOKEN_PREFIX)
+        && BASE64URL_NOPAD
+            .decode(&password.as_bytes()[GAME_TOKEN_PREFIX.len()..])
+            .is_ok()
+}
+
+/// Generate a new random token
+pub fn generate_token() -> String {
+    let bytes: [u8; TOKEN_BYTES] = rand::random();
+    format!("{}{}", GAME_TOKEN_PREFIX, BASE64URL_NOPAD.encode(&bytes))
+}
+
+/// Account a token was issued for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameTokenClaims {
+    /// Database user ID
+    pub user_id: Uuid,
+    /// Username the token may log in as
+    pub username: String,
+}
+
+impl GameTokenClaims {
+    /// Check the token is being used for the account it was issued for
+    pub fn matches(&self, username: &str) -> bool {
+        normalize_username(&self.username) == normalize_username(username)
+    }
+}
+
+/// Issues and redeems game tokens
+pub struct GameTokens {
+    /// Redis holding outstanding tokens (None disables token logins)
+    redis: Option<RedisPool>,
+    /// Seconds a token stays valid
+    ttl_secs: u64,
+}
+
+impl GameTokens {
+    /// Create the store; without Redis no token is ever accepted
+    pub fn new(config: &ServerConfig, redis: Option<RedisPool>) -> Self {
+        Self {
+            redis,
+            ttl_secs: config.login.game_token_ttl_secs,
+        }
+    }
+
+    /// Create the store with a Redis pool for the configured server
+    ///
+    /// Connections are made on first use, so this only fails on a malformed
+    /// URL.
+    pub fn connect(config: &ServerConfig) -> Self {
+        let redis = match RedisPoolConfig::from_url(config.redis.url())
+            .create_pool(Some(Runtime::Tokio1))
+        {
+            Ok(pool) => Some(pool),
+            Err(e) => {
+                warn!(error = %e, "Failed to create Redis pool, game token logins disabled");
+                None
+            }
+        };
+        Self::new(config, redis)
+    }
+
+    /// Seconds a token stays valid
+    pub fn ttl_secs(&self) -> u64 {
+        self.ttl_secs
+    }
+
+    /// Issue a token for an account
+    pub async fn issue(&self, user_id: Uuid, username: &str) -> Result<String> {
+        let redis = self
+            .redis
+            .as_ref()
+            .ok_or_else(|| RustscapeError::Internal("Game tokens require Redis".to_string()))?;
+        let claims = GameTokenClaims {
+            user_id,
+            username: username.to_string(),
+        };
+        let value = serde_json::to_string(&claims)
+            .map_err(|e| RustscapeError::Internal(format!("Failed to encode game token: {}", e)))?;
+
+        let token = generate_token();
+        let mut conn = redis
+            .get()
+            .await
+            .map_err(|e| login_backend_offline(REDIS_FAILED, e))?;
+        redis::cmd("SET")
+            .arg(format!("{}{}", KEY_PREFIX, token))
+            .arg(value)
+            .arg("EX")
+            .arg(self.ttl_secs)
+            .arg("NX")
+            .query_async::<_, ()>(&mut conn)
+            .await
+            .map_err(|e| login_backend_offline(REDIS_FAILED, e))?;
+
+        Ok(token)
+    }
+
+    /// Redeem a token, deleting it so it can't be used again
+    ///
+    /// Returns `None` for unknown, expired and already used tokens.
+    pub async fn redeem(&self, token: &str) -> Result<Option<GameTokenClaims>> {
+        let Some(redis) = &self.redis else {
+            return Ok(None);
+        };
+        if !is_game_token(token) {
+            return Ok(None);
+        }
+
+        let mut conn = redis
+            .get()
+            .await
+            .map_err(|e| login_backend_offline(REDIS_FAILED, e))?;
+        let value: Option<String> = redis::cmd("GETDEL")
+            .arg(format!("{}{}", KEY_PREFIX, token))
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| login_backend_offline(REDIS_FAILED, e))?;
+
+        Ok(value.and_then(|value| serde_json::from_str(&value).ok()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_tokens() {
+        let token = generate_token();
+        assert_eq!(token.len(), GAME_TOKEN_LEN);
+        assert!(is_game_token(&token));
+        assert_ne!(generate_token(), token);
+
+        // Passwords never look like tokens
+        assert!(!is_game_token("hunter2"));
+        assert!(!is_game_token(&token[1..]));
+        let mut wrong_prefix = token.clone();
+        wrong_prefix.replace_range(..GAME_TOKEN_PREFIX.len(), "xx_");
+        assert!(!is_game_token(&wrong_prefix));
+        let mut bad_chars = token.clone();
+        bad_chars.replace_range(GAME_TOKEN_PREFIX.len().., &"!".repeat(32));
+        assert!(!is_game_token(&bad_chars));
+    }
+
+    #[test]
+    fn test_claims_match_username() {
+        let claims = GameTokenClaims {
+            user_id: Uuid::new_v4(),
+            username: "Zezima".to_string(),
+        };
+        assert!(claims.matches("zezima"));
+        assert!(claims.matches("ZEZIMA"));
+        assert!(!claims.matches("durial321"));
+    }
+
+    #[tokio::test]
+    async fn test_without_redis() {
+        let tokens = GameTokens::new(&ServerConfig::default(), None);
+        assert_eq!(tokens.ttl_secs(), 30);
+        assert!(tokens.issue(Uuid::new_v4(), "zezima").await.is_err());
+        assert_eq!(tokens.redeem(&generate_token()).await.unwrap(), None);
+    }
+}
@@ -2,21 +2,29 @@
 //!
 //! Provides authentication and account management for the game server.
 //! Supports both development mode (accepts all logins) and production mode
-//! (validates against stored credentials or database).
+//! (validates against stored credentials or database). Accounts can also
+//! enroll TOTP two-factor authentication (see `two_factor`), and web clients
+//! log in with one-time tokens from the REST API (see `game_token`).
+//! Passwords are hashed according to the policy in `password`, and
+//! `coordinator` keeps an account from being logged in to two worlds at once.
+
+pub mod coordinator;
+pub mod game_token;
+pub mod password;
+pub mod two_factor;
 
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::sync::RwLock;
 
-use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2,
-};
 use sqlx::PgPool;
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
 use crate::error::{AuthError, Result, RustscapeError};
 
+use self::password::{verify_password, PasswordPolicy};
+
 /// Player account information
 #[derive(Debug, Clone)]
 pub struct Account {
@@ -26,7 +34,7 @@ pub struct Account {
     pub user_id: Option<Uuid>,
     /// Username (normalized)
     pub username: String,
-    /// Password hash (Argon2)
+    /// Password hash (Argon2, or bcrypt for older database accounts)
     pub password_hash: String,
     /// Player rights (0=normal, 1=mod, 2=admin)
     pub rights: u8,
@@ -40,8 +48,8 @@ pub struct Account {
 
 impl Account {
     /// Create a new account with the given credentials
-    pub fn new(id: u64, username: &str, password: &str) -> Result<Self> {
-        let password_hash = hash_password(password)?;
+    pub fn new(id: u64, username: &str, password: &str, policy: &PasswordPolicy) -> Result<Self> {
+        let password_hash = policy.hash(password)?;
         Ok(Self {
             id,
             user_id: None,
@@ -108,6 +116,28 @@ pub fn user_id(&self) -> Option<Uuid> {
     }
 }
 
+/// Account activity shown to a player in the lobby
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoginActivity {
+    /// Time of the previous login (None if this is the first)
+    pub last_login: Option<chrono::DateTime<chrono::Utc>>,
+    /// Address of the previous successful login
+    pub last_ip: Option<IpAddr>,
+    /// Unread messages in the player's inbox
+    pub unread_messages: u16,
+    /// When the account's membership runs out
+    pub membership_expires: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Previous login details (from users, login_history and player_messages)
+#[derive(Debug, sqlx::FromRow)]
+struct DbActivityRecord {
+    last_login_at: Option<chrono::DateTime<chrono::Utc>>,
+    membership_expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    last_ip: Option<String>,
+    unread_messages: i64,
+}
+
 /// Database user record (from users table)
 #[derive(Debug, Clone, sqlx::FromRow)]
 struct DbUserRecord {
@@ -128,6 +158,17 @@ fn is_locked(&self) -> bool {
             false
         }
     }
+
+    /// Refuse banned and locked accounts
+    fn check_status(&self) -> Result<()> {
+        if self.is_banned {
+            return Err(RustscapeError::Auth(AuthError::AccountDisabled));
+        }
+        if self.is_locked() {
+            return Err(RustscapeError::Auth(AuthError::AccountLocked));
+        }
+        Ok(())
+    }
 }
 
 /// Authentication service for managing player accounts and logins
@@ -144,6 +185,8 @@ pub struct AuthService {
     max_players: u16,
     /// Optional database pool for production auth
     db_pool: Option<PgPool>,
+    /// Password hashing policy
+    password_policy: PasswordPolicy,
 }
 
 impl AuthService {
@@ -157,6 +200,7 @@ pub fn new(dev_mode: bool) -> Self {
             active_indices: RwLock::new(vec![false; max_players as usize + 1]),
             max_players,
             db_pool: None,
+            password_policy: PasswordPolicy::default(),
         }
     }
 
@@ -169,12 +213,12 @@ pub fn with_max_players(dev_mode: bool, max_players: u16) -> Self {
             active_indices: RwLock::new(vec![false; max_players as usize + 1]),
             max_players,
             db_pool: None,
+            password_policy: PasswordPolicy::default(),
         }
     }
 
     /// Create with database pool for production auth
-    pub fn with_database(dev_mode: bool, db_pool: PgPool) -> Self {
-        let max_players = 2000;
+    pub fn with_database(dev_mode: bool, db_pool: PgPool, max_players: u16) -> Self {
         Self {
             dev_mode,
             accounts: RwLock::new(HashMap::new()),
@@ -182,9 +226,16 @@ pub fn with_database(dev_mode: bool, db_pool: PgPool) -> Self {
             active_indices: RwLock::new(vec![false; max_players as usize + 1]),
             max_players,
             db_pool: Some(db_pool),
+            password_policy: PasswordPolicy::default(),
         }
     }
 
+    /// Use a password hashing policy other than the default
+    pub fn with_password_policy(mut self, policy: PasswordPolicy) -> Self {
+        self.password_policy = policy;
+        self
+    }
+
     /// Check if database authentication is available
     pub fn has_database(&self) -> bool {
         self.db_pool.is_some()
@@ -192,6 +243,17 @@ pub fn has_database(&self) -> bool {
 
     /// Authenticate a user with username and password
     pub fn authenticate(&self, username: &str, password: &str) -> Result<AuthResult> {
+        let account = self.verify(username, password)?;
+        let player_index = self.allocate_player_index()?;
+
+        Ok(AuthResult {
+            account,
+            player_index,
+        })
+    }
+
+    /// Verify a username and password without allocating a player index
+    pub fn verify(&self, username: &str, password: &str) -> Result<Account> {
         let username_normalized = normalize_username(username);
 
         if self.dev_mode {
@@ -201,13 +263,7 @@ pub fn authenticate(&self, username: &str, password: &str) -> Result<AuthResult>
                 "Dev mode authentication - auto-accepting"
             );
 
-            let account = self.get_or_create_dev_account(&username_normalized);
-            let player_index = self.allocate_player_index()?;
-
-            return Ok(AuthResult {
-                account,
-                player_index,
-            });
+            return Ok(self.get_or_create_dev_account(&username_normalized));
         }
 
         // Production mode - verify against stored accounts
@@ -232,17 +288,21 @@ pub fn authenticate(&self, username: &str, password: &str) -> Result<AuthResult>
             return Err(RustscapeError::Auth(AuthError::InvalidCredentials));
         }
 
-        let account = account.clone();
-        drop(accounts); // Release read lock before allocating index
+        info!(username = %username_normalized, "Authentication successful");
 
-        // Allocate player index
-        let player_index = self.allocate_player_index()?;
+        Ok(account.clone())
+    }
 
-        info!(
-            username = %username_normalized,
-            player_index = player_index,
-            "Authentication successful"
-        );
+    /// Authenticate a user against the database
+    /// This is the preferred method when database is available
+    pub async fn authenticate_db(
+        &self,
+        username: &str,
+        password: &str,
+        ip_address: Option<IpAddr>,
+    ) -> Result<AuthResult> {
+        let account = self.verify_db(username, password, ip_address).await?;
+        let player_index = self.allocate_player_index()?;
 
         Ok(AuthResult {
             account,
@@ -250,14 +310,21 @@ pub fn authenticate(&self, username: &str, password: &str) -> Result<AuthResult>
         })
     }
 
-    /// Authenticate a user against the database
-    /// This is the preferred method when database is available
-    pub async fn authenticate_db(&self, username: &str, password: &str) -> Result<AuthResult> {
+    /// Verify a username and password against the database without
+    /// allocating a player index
+    ///
+    /// `ip_address` is the client's, recorded if the stored hash is upgraded.
+    pub async fn verify_db(
+        &self,
+        username: &str,
+        password: &str,
+        ip_address: Option<IpAddr>,
+    ) -> Result<Account> {
         let username_normalized = normalize_username(username);
 
         // If in dev mode, use the sync method
         if self.dev_mode {
-            return self.authenticate(username, password);
+            return self.verify(username, password);
         }
 
         // Check if we have a database connection
@@ -265,7 +332,7 @@ pub async fn authenticate_db(&self, username: &str, password: &str) -> Result<Au
             Some(pool) => pool,
             None => {
                 // Fall back to in-memory authentication
-                return self.authenticate(username, password);
+                return self.verify(username, password);
             }
         };
 
@@ -285,30 +352,96 @@ pub async fn authenticate_db(&self, username: &str, password: &str) -> Result<Au
             RustscapeError::Auth(AuthError::InvalidCredentials)
         })?;
 
-        let user = user.ok_or(RustscapeError::Auth(AuthError::InvalidCredentials))?;
+        let mut user = user.ok_or(RustscapeError::Auth(AuthError::InvalidCredentials))?;
 
-        // Check if account is banned
-        if user.is_banned {
-            return Err(RustscapeError::Auth(AuthError::AccountDisabled));
+        user.check_status()?;
+
+        // Stored hashes may be bcrypt (pgcrypto's crypt) or Argon2
+        if !verify_password(password, &user.password_hash) {
+            warn!(username = %username_normalized, "Failed login attempt - invalid password");
+            return Err(RustscapeError::Auth(AuthError::InvalidCredentials));
         }
 
-        // Check if account is locked
-        if user.is_locked() {
-            return Err(RustscapeError::Auth(AuthError::AccountLocked));
+        let ip_address = ip_address.map(|ip| ip.to_string());
+        if let Some(hash) = self
+            .password_policy
+            .upgrade(
+                pool,
+                user.id,
+                password,
+                &user.password_hash,
+                ip_address.as_deref(),
+            )
+            .await
+        {
+            user.password_hash = hash;
         }
 
-        // Verify password using bcrypt (database uses pgcrypto's crypt)
-        // First try bcrypt verification
-        let password_valid = verify_password_bcrypt(password, &user.password_hash)
-            .or_else(|| verify_password_argon2(password, &user.password_hash))
-            .unwrap_or(false);
+        let user_id = user.id;
+        let account = self.cache_db_account(user);
 
-        if !password_valid {
-            warn!(username = %username_normalized, "Failed login attempt - invalid password");
-            return Err(RustscapeError::Auth(AuthError::InvalidCredentials));
-        }
+        info!(
+            username = %username_normalized,
+            user_id = %user_id,
+            "Database authentication successful"
+        );
+
+        Ok(account)
+    }
+
+    /// Authenticate the user a game token was issued for
+    pub async fn authenticate_user_db(&self, user_id: Uuid) -> Result<AuthResult> {
+        let account = self.verify_user_db(user_id).await?;
+        let player_index = self.allocate_player_index()?;
 
-        // Create account from database record
+        Ok(AuthResult {
+            account,
+            player_index,
+        })
+    }
+
+    /// Look up the user a game token was issued for without allocating a
+    /// player index
+    ///
+    /// The token stands in for the password, so only the account's status
+    /// is checked.
+    pub async fn verify_user_db(&self, user_id: Uuid) -> Result<Account> {
+        let pool = self
+            .db_pool
+            .as_ref()
+            .ok_or(RustscapeError::Auth(AuthError::InvalidCredentials))?;
+
+        let user: Option<DbUserRecord> = sqlx::query_as(
+            r#"
+            SELECT id, username, password_hash, rights, is_member, is_banned, locked_until
+            FROM users
+            WHERE id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| {
+            warn!(error = %e, "Database query failed during token authentication");
+            RustscapeError::Auth(AuthError::InvalidCredentials)
+        })?;
+
+        let user = user.ok_or(RustscapeError::Auth(AuthError::InvalidCredentials))?;
+        user.check_status()?;
+
+        let account = self.cache_db_account(user);
+
+        info!(
+            username = %account.username,
+            user_id = %user_id,
+            "Game token authentication successful"
+        );
+
+        Ok(account)
+    }
+
+    /// Create an account from a database record and cache it
+    fn cache_db_account(&self, user: DbUserRecord) -> Account {
         let id = {
             let mut next_id = self.next_id.write().unwrap();
             let id = *next_id;
@@ -330,23 +463,70 @@ pub async fn authenticate_db(&self, username: &str, password: &str) -> Result<Au
         // Also store in the in-memory cache for subsequent lookups
         {
             let mut accounts = self.accounts.write().unwrap();
-            accounts.insert(username_normalized.clone(), account.clone());
+            accounts.insert(normalize_username(&account.username), account.clone());
         }
 
-        // Allocate player index
-        let player_index = self.allocate_player_index()?;
+        account
+    }
 
-        info!(
-            username = %username_normalized,
-            user_id = %user.id,
-            player_index = player_index,
-            "Database authentication successful"
-        );
+    /// Get the activity shown in the lobby and record this login
+    ///
+    /// Returns the previous login, so it has to be read before the new one
+    /// is recorded. Accounts without a database row have no history.
+    pub async fn record_lobby_login(&self, account: &Account, ip: IpAddr) -> LoginActivity {
+        let (Some(pool), Some(user_id)) = (&self.db_pool, account.user_id) else {
+            return LoginActivity::default();
+        };
 
-        Ok(AuthResult {
-            account,
-            player_index,
-        })
+        let record = sqlx::query_as::<_, DbActivityRecord>(
+            r#"
+            SELECT u.last_login_at, u.membership_expires_at,
+                   (SELECT host(h.ip_address) FROM login_history h
+                    WHERE h.user_id = u.id AND h.success
+                    ORDER BY h.created_at DESC LIMIT 1) AS last_ip,
+                   (SELECT COUNT(*) FROM player_messages m
+                    WHERE m.recipient_id = u.id AND m.read_at IS NULL) AS unread_messages
+            FROM users u
+            WHERE u.id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await;
+
+        let activity = match record {
+            Ok(Some(record)) => LoginActivity {
+                last_login: record.last_login_at,
+                last_ip: record.last_ip.and_then(|ip| ip.parse().ok()),
+                unread_messages: u16::try_from(record.unread_messages).unwrap_or(u16::MAX),
+                membership_expires: record.membership_expires_at,
+            },
+            Ok(None) => LoginActivity::default(),
+            Err(e) => {
+                warn!(error = %e, user_id = %user_id, "Failed to load login activity");
+                LoginActivity::default()
+            }
+        };
+
+        let recorded = async {
+            sqlx::query("UPDATE users SET last_login_at = NOW() WHERE id = $1")
+                .bind(user_id)
+                .execute(pool)
+                .await?;
+            sqlx::query(
+                "INSERT INTO login_history (user_id, ip_address, success) VALUES ($1, $2::inet, TRUE)",
+            )
+            .bind(user_id)
+            .bind(ip.to_string())
+            .execute(pool)
+            .await
+        }
+        .await;
+        if let Err(e) = recorded {
+            warn!(error = %e, user_id = %user_id, "Failed to record lobby login");
+        }
+
+        activity
     }
 
     /// Register a new account
@@ -381,7 +561,7 @@ pub fn register(&self, username: &str, password: &str) -> Result<Account> {
         };
 
         // Create account
-        let account = Account::new(id, &username_normalized, password)?;
+        let account = Account::new(id, &username_normalized, password, &self.password_policy)?;
         accounts.insert(username_normalized.clone(), account.clone());
 
         info!(
@@ -513,44 +693,6 @@ pub fn normalize_username(username: &str) -> String {
     username.trim().to_lowercase().replace(' ', "_")
 }
 
-/// Hash a password using Argon2
-fn hash_password(password: &str) -> Result<String> {
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-
-    let password_hash = argon2
-        .hash_password(password.as_bytes(), &salt)
-        .map_err(|e| RustscapeError::Internal(format!("Failed to hash password: {}", e)))?
-        .to_string();
-
-    Ok(password_hash)
-}
-
-/// Verify a password against an Argon2 hash
-fn verify_password(password: &str, hash: &str) -> bool {
-    verify_password_argon2(password, hash).unwrap_or(false)
-}
-
-/// Verify a password against an Argon2 hash (returns Option for chaining)
-fn verify_password_argon2(password: &str, hash: &str) -> Option<bool> {
-    let parsed_hash = PasswordHash::new(hash).ok()?;
-    Some(
-        Argon2::default()
-            .verify_password(password.as_bytes(), &parsed_hash)
-            .is_ok(),
-    )
-}
-
-/// Verify a password against a bcrypt hash (used by pgcrypto)
-fn verify_password_bcrypt(password: &str, hash: &str) -> Option<bool> {
-    // bcrypt hashes start with $2a$, $2b$, or $2y$
-    if !hash.starts_with("$2") {
-        return None;
-    }
-
-    Some(bcrypt::verify(password, hash).unwrap_or(false))
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -668,15 +810,6 @@ fn test_set_rights() {
         assert_eq!(account.rights, 2);
     }
 
-    #[test]
-    fn test_password_hashing() {
-        let password = "test_password_123";
-        let hash = hash_password(password).unwrap();
-
-        assert!(verify_password(password, &hash));
-        assert!(!verify_password("wrong_password", &hash));
-    }
-
     #[test]
     fn test_invalid_username() {
         let auth = AuthService::new(false);
@@ -0,0 +1,279 @@
+//! Password hashing policy
+//!
+//! New passwords are hashed with Argon2id using the configured parameters.
+//! Older hashes still verify: bcrypt (written by earlier versions of the web
+//! API and by pgcrypto's `crypt`) and Argon2 with weaker parameters. After a
+//! successful login `upgrade` replaces them with a hash under the current
+//! policy.
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::PasswordHashConfig;
+use crate::error::{Result, RustscapeError};
+
+/// Hashes new passwords and decides which stored hashes are outdated
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    /// Argon2id parameters for new hashes
+    params: Params,
+}
+
+impl PasswordPolicy {
+    /// Create a policy from configuration
+    pub fn new(config: &PasswordHashConfig) -> Result<Self> {
+        let params = Params::new(
+            config.memory_kib,
+            config.iterations,
+            config.parallelism,
+            None,
+        )
+        .map_err(|e| RustscapeError::Config(format!("Invalid Argon2 parameters: {}", e)))?;
+
+        Ok(Self { params })
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+
+    /// Hash a password with Argon2id
+    pub fn hash(&self, password: &str) -> Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+
+        let password_hash = self
+            .argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| RustscapeError::Internal(format!("Failed to hash password: {}", e)))?
+            .to_string();
+
+        Ok(password_hash)
+    }
+
+    /// Check whether a stored hash should be replaced
+    ///
+    /// Anything other than Argon2id (version 0x13) with at least the
+    /// configured memory, passes and lanes is outdated.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        if is_bcrypt(hash) {
+            return true;
+        }
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return true;
+        };
+        if parsed.algorithm != Algorithm::Argon2id.ident()
+            || parsed.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+        let Ok(params) = Params::try_from(&parsed) else {
+            return true;
+        };
+
+        params.m_cost() < self.params.m_cost()
+            || params.t_cost() < self.params.t_cost()
+            || params.p_cost() < self.params.p_cost()
+    }
+
+    /// Re-hash a user's password under the current policy after a
+    /// successful login
+    ///
+    /// Returns the stored hash if one was written. The login has already
+    /// succeeded, so failures are only logged and the upgrade is tried again
+    /// next time.
+    pub async fn upgrade(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        password: &str,
+        old_hash: &str,
+        ip_address: Option<&str>,
+    ) -> Option<String> {
+        if !self.needs_rehash(old_hash) {
+            return None;
+        }
+
+        let new_hash = match self.hash(password) {
+            Ok(hash) => hash,
+            Err(e) => {
+                warn!(user_id = %user_id, error = %e, "Failed to re-hash password");
+                return None;
+            }
+        };
+
+        if let Err(e) = store_hash(pool, user_id, &new_hash).await {
+            warn!(user_id = %user_id, error = %e, "Failed to store upgraded password hash");
+            return None;
+        }
+
+        let old_scheme = hash_scheme(old_hash);
+        if let Err(e) = audit_rehash(
+            pool,
+            user_id,
+            json!({ "scheme": old_scheme }),
+            json!({
+                "scheme": "argon2id",
+                "memory_kib": self.params.m_cost(),
+                "iterations": self.params.t_cost(),
+                "parallelism": self.params.p_cost(),
+            }),
+            ip_address,
+        )
+        .await
+        {
+            warn!(user_id = %user_id, error = %e, "Failed to audit password hash upgrade");
+        }
+
+        info!(user_id = %user_id, from = old_scheme, "Upgraded password hash");
+        Some(new_hash)
+    }
+}
+
+/// Replace a user's stored password hash
+async fn store_hash(pool: &PgPool, user_id: Uuid, password_hash: &str) -> Result<()> {
+    sqlx::query("UPDATE users SET password_hash = $2 WHERE id = $1")
+        .bind(user_id)
+        .bind(password_hash)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Record a hash upgrade in the audit log
+async fn audit_rehash(
+    pool: &PgPool,
+    user_id: Uuid,
+    old_value: serde_json::Value,
+    new_value: serde_json::Value,
+    ip_address: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log (user_id, action, entity_type, entity_id, old_value, new_value, ip_address)
+        VALUES ($1, 'password_rehashed', 'user', $1, $2, $3, $4::inet)
+        "#,
+    )
+    .bind(user_id)
+    .bind(old_value)
+    .bind(new_value)
+    .bind(ip_address)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self::new(&PasswordHashConfig::default()).expect("default Argon2 parameters are valid")
+    }
+}
+
+/// Verify a password against a stored Argon2 or bcrypt hash
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    if is_bcrypt(hash) {
+        return bcrypt::verify(password, hash).unwrap_or(false);
+    }
+
+    // Argon2 reads the variant and parameters from the hash itself
+    match PasswordHash::new(hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// bcrypt hashes start with $2a$, $2b$ or $2y$
+fn is_bcrypt(hash: &str) -> bool {
+    hash.starts_with("$2")
+}
+
+/// Name of the scheme a stored hash uses, for audit logs
+fn hash_scheme(hash: &str) -> &'static str {
+    if is_bcrypt(hash) {
+        return "bcrypt";
+    }
+    match PasswordHash::new(hash).map(|parsed| parsed.algorithm.as_str()) {
+        Ok("argon2id") => "argon2id",
+        Ok("argon2i") => "argon2i",
+        Ok("argon2d") => "argon2d",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Small parameters so tests run quickly
+    fn policy(memory_kib: u32, iterations: u32) -> PasswordPolicy {
+        PasswordPolicy::new(&PasswordHashConfig {
+            memory_kib,
+            iterations,
+            parallelism: 1,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_hash_and_verify() {
+        let policy = policy(1024, 1);
+        let hash = policy.hash("test_password_123").unwrap();
+
+        assert!(hash.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+        assert!(verify_password("test_password_123", &hash));
+        assert!(!verify_password("wrong_password", &hash));
+        assert!(!policy.needs_rehash(&hash));
+
+        // Salted, so the same password hashes differently
+        assert_ne!(policy.hash("test_password_123").unwrap(), hash);
+    }
+
+    #[test]
+    fn test_bcrypt_verifies_but_needs_rehash() {
+        let hash = bcrypt::hash("hunter2", 4).unwrap();
+
+        assert!(verify_password("hunter2", &hash));
+        assert!(!verify_password("hunter3", &hash));
+        assert!(policy(1024, 1).needs_rehash(&hash));
+        assert_eq!(hash_scheme(&hash), "bcrypt");
+    }
+
+    #[test]
+    fn test_weaker_argon2_needs_rehash() {
+        let weak = policy(1024, 1).hash("hunter2").unwrap();
+        let strong = policy(2048, 2).hash("hunter2").unwrap();
+
+        // Hashes verify whatever parameters they were made with
+        assert!(verify_password("hunter2", &weak));
+        assert!(verify_password("hunter2", &strong));
+
+        let current = policy(2048, 1);
+        assert!(current.needs_rehash(&weak));
+        assert!(!current.needs_rehash(&strong));
+
+        // Other Argon2 variants are replaced with Argon2id
+        let salt = SaltString::generate(&mut OsRng);
+        let argon2i = Argon2::new(Algorithm::Argon2i, Version::V0x13, Params::default())
+            .hash_password(b"hunter2", &salt)
+            .unwrap()
+            .to_string();
+        assert!(verify_password("hunter2", &argon2i));
+        assert!(current.needs_rehash(&argon2i));
+        assert_eq!(hash_scheme(&argon2i), "argon2i");
+    }
+
+    #[test]
+    fn test_unknown_hashes_never_verify() {
+        assert!(!verify_password("", ""));
+        assert!(!verify_password("hunter2", "hunter2"));
+        assert!(policy(1024, 1).needs_rehash("not a hash"));
+        assert_eq!(hash_scheme("not a hash"), "unknown");
+    }
+}
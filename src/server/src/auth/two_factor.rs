@@ -0,0 +1,458 @@
+//! Two-factor authentication
+//!
+//! Accounts can enroll a TOTP authenticator (see `crypto::totp`). Setup
+//! stores a new secret, encrypted with AES-256-GCM, as pending until the
+//! first code from the authenticator app is confirmed; confirming enables it
+//! and issues single-use recovery codes. From then on web logins and game
+//! logins (through the authenticator field of the login block) must present
+//! a current code or an unused recovery code.
+//!
+//! Times are passed in as Unix seconds so checks can be tested with fixed
+//! clocks. A time step is never accepted twice for the same account.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use data_encoding::{HEXLOWER, HEXLOWER_PERMISSIVE};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::config::TwoFactorConfig;
+use crate::crypto::totp::Totp;
+use crate::error::{AuthError, Result, RustscapeError};
+
+/// AES-GCM nonce length in bytes
+pub const NONCE_LEN: usize = 12;
+
+/// Characters used in recovery codes (no 0/o, 1/l/i lookalikes)
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// Characters in each half of a recovery code
+const RECOVERY_CODE_HALF_LEN: usize = 5;
+
+/// Encrypts TOTP secrets for storage
+pub struct SecretCipher {
+    cipher: Aes256Gcm,
+}
+
+impl SecretCipher {
+    /// Create from a 32-byte key in hex
+    pub fn from_hex(key: &str) -> Result<Self> {
+        let key = HEXLOWER_PERMISSIVE
+            .decode(key.as_bytes())
+            .map_err(|e| RustscapeError::Config(format!("Invalid 2FA encryption key: {}", e)))?;
+        let cipher = Aes256Gcm::new_from_slice(&key).map_err(|_| {
+            RustscapeError::Config("2FA encryption key must be 32 bytes".to_string())
+        })?;
+        Ok(Self { cipher })
+    }
+
+    /// Encrypt under a fresh random nonce, returning nonce || ciphertext
+    pub fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .expect("AES-GCM encryption of an in-memory buffer cannot fail");
+
+        let mut data = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        data
+    }
+
+    /// Decrypt nonce || ciphertext, returning `None` if it was tampered with
+    /// or encrypted under another key
+    pub fn decrypt(&self, data: &[u8]) -> Option<Vec<u8>> {
+        if data.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .ok()
+    }
+}
+
+/// A pending enrollment, shown to the user once
+#[derive(Debug, Clone)]
+pub struct TwoFactorSetup {
+    /// Secret in base32, for manual entry
+    pub secret: String,
+    /// `otpauth://` URI, for QR codes
+    pub uri: String,
+}
+
+/// Check a TOTP code, refusing time steps at or before the last one used
+///
+/// Returns the matched time step.
+pub fn check_code(
+    totp: &Totp,
+    last_used_step: Option<u64>,
+    code: &str,
+    unix_secs: u64,
+    skew: u64,
+) -> Option<u64> {
+    totp.verify(code, unix_secs, skew)
+        .filter(|&step| last_used_step.is_none_or(|last| step > last))
+}
+
+/// Generate recovery codes of the form `xxxxx-xxxxx`
+pub fn generate_recovery_codes(count: usize) -> Vec<String> {
+    let random_half = || -> String {
+        (0..RECOVERY_CODE_HALF_LEN)
+            .map(|_| {
+                let index = rand::random::<usize>() % RECOVERY_CODE_ALPHABET.len();
+                RECOVERY_CODE_ALPHABET[index] as char
+            })
+            .collect()
+    };
+    (0..count)
+        .map(|_| format!("{}-{}", random_half(), random_half()))
+        .collect()
+}
+
+/// Hash a recovery code for storage, ignoring case, spaces and dashes
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    HEXLOWER.encode(&Sha256::digest(normalized.as_bytes()))
+}
+
+/// Stored two-factor state for an account
+#[derive(sqlx::FromRow)]
+struct TwoFactorRecord {
+    secret_encrypted: Vec<u8>,
+    enabled: bool,
+    last_used_step: Option<i64>,
+}
+
+/// Two-factor enrollment and verification
+pub struct TwoFactor {
+    /// Cipher for stored secrets
+    cipher: SecretCipher,
+    /// Issuer shown by authenticator apps
+    issuer: String,
+    /// Time steps accepted either side of now
+    skew_steps: u64,
+    /// Recovery codes issued on enrollment
+    recovery_codes: usize,
+    /// Database holding the two-factor tables (None disables 2FA)
+    pool: Option<PgPool>,
+}
+
+impl TwoFactor {
+    /// Create the service; without a database no account has 2FA enabled
+    pub fn new(config: &TwoFactorConfig, pool: Option<PgPool>) -> Result<Self> {
+        Ok(Self {
+            cipher: SecretCipher::from_hex(&config.encryption_key)?,
+            issuer: config.issuer.clone(),
+            skew_steps: config.skew_steps,
+            recovery_codes: config.recovery_codes,
+            pool,
+        })
+    }
+
+    /// Whether an account has confirmed two-factor authentication
+    pub async fn is_enabled(&self, user_id: Uuid) -> Result<bool> {
+        let Some(pool) = &self.pool else {
+            return Ok(false);
+        };
+        Ok(self
+            .load(pool, user_id)
+            .await?
+            .is_some_and(|record| record.enabled))
+    }
+
+    /// Start enrollment with a new secret, replacing any earlier pending one
+    pub async fn begin_setup(&self, user_id: Uuid, account_name: &str) -> Result<TwoFactorSetup> {
+        let pool = self.require_pool()?;
+        if self.is_enabled(user_id).await? {
+            return Err(RustscapeError::Auth(AuthError::TwoFactorAlreadyEnabled));
+        }
+
+        let totp = Totp::generate();
+        sqlx::query(
+            r#"
+            INSERT INTO user_two_factor (user_id, secret_encrypted, enabled)
+            VALUES ($1, $2, FALSE)
+            ON CONFLICT (user_id) DO UPDATE SET
+                secret_encrypted = $2,
+                last_used_step = NULL,
+                created_at = NOW()
+            WHERE NOT user_two_factor.enabled
+            "#,
+        )
+        .bind(user_id)
+        .bind(self.cipher.encrypt(totp.secret()))
+        .execute(pool)
+        .await?;
+
+        Ok(TwoFactorSetup {
+            secret: totp.to_base32(),
+            uri: totp.provisioning_uri(&self.issuer, account_name),
+        })
+    }
+
+    /// Confirm a pending enrollment with a code from the authenticator app,
+    /// returning the recovery codes (only their hashes are stored)
+    pub async fn confirm(&self, user_id: Uuid, code: &str, unix_secs: u64) -> Result<Vec<String>> {
+        let pool = self.require_pool()?;
+        let record = self
+            .load(pool, user_id)
+            .await?
+            .ok_or(RustscapeError::Auth(AuthError::TwoFactorNotEnabled))?;
+        if record.enabled {
+            return Err(RustscapeError::Auth(AuthError::TwoFactorAlreadyEnabled));
+        }
+
+        let totp = self.totp(&record)?;
+        let step = check_code(&totp, None, code, unix_secs, self.skew_steps)
+            .ok_or(RustscapeError::Auth(AuthError::InvalidTwoFactorCode))?;
+
+        let codes = generate_recovery_codes(self.recovery_codes);
+        let mut tx = pool.begin().await?;
+        sqlx::query(
+            r#"
+            UPDATE user_two_factor
+            SET enabled = TRUE, enabled_at = NOW(), last_used_step = $2
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .bind(step as i64)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        for code in &codes {
+            sqlx::query("INSERT INTO user_recovery_codes (user_id, code_hash) VALUES ($1, $2)")
+                .bind(user_id)
+                .bind(hash_recovery_code(code))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        info!(user_id = %user_id, "Two-factor authentication enabled");
+        Ok(codes)
+    }
+
+    /// Turn off two-factor authentication, given a current or recovery code
+    pub async fn disable(&self, user_id: Uuid, code: &str, unix_secs: u64) -> Result<()> {
+        let pool = self.require_pool()?;
+        if !self.verify(user_id, code, unix_secs).await? {
+            return Err(RustscapeError::Auth(AuthError::InvalidTwoFactorCode));
+        }
+
+        let mut tx = pool.begin().await?;
+        sqlx::query("DELETE FROM user_two_factor WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        info!(user_id = %user_id, "Two-factor authentication disabled");
+        Ok(())
+    }
+
+    /// Check a current TOTP code or an unused recovery code
+    ///
+    /// An accepted TOTP code uses up its time step and an accepted recovery
+    /// code is used up, so neither can be replayed.
+    pub async fn verify(&self, user_id: Uuid, code: &str, unix_secs: u64) -> Result<bool> {
+        let pool = self.require_pool()?;
+        let record = self
+            .load(pool, user_id)
+            .await?
+            .filter(|record| record.enabled)
+            .ok_or(RustscapeError::Auth(AuthError::TwoFactorNotEnabled))?;
+
+        let totp = self.totp(&record)?;
+        let last_used_step = record.last_used_step.map(|step| step as u64);
+        if let Some(step) = check_code(&totp, last_used_step, code, unix_secs, self.skew_steps) {
+            // Conditional so two logins racing with the same code can't both win
+            let updated = sqlx::query(
+                r#"
+                UPDATE user_two_factor SET last_used_step = $2
+                WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+                "#,
+            )
+            .bind(user_id)
+            .bind(step as i64)
+            .execute(pool)
+            .await?
+            .rows_affected();
+            return Ok(updated == 1);
+        }
+
+        let used = sqlx::query(
+            r#"
+            UPDATE user_recovery_codes SET used_at = NOW()
+            WHERE id = (
+                SELECT id FROM user_recovery_codes
+                WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+                LIMIT 1
+            ) AND used_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(hash_recovery_code(code))
+        .execute(pool)
+        .await?
+        .rows_affected();
+        if used == 1 {
+            info!(user_id = %user_id, "Two-factor recovery code used");
+        }
+        Ok(used == 1)
+    }
+
+    /// Enforce two-factor authentication for a login that passed its
+    /// password check
+    ///
+    /// Accounts without a database user (development accounts) and accounts
+    /// without 2FA enabled pass without a code.
+    pub async fn check_login(
+        &self,
+        user_id: Option<Uuid>,
+        code: Option<&str>,
+        unix_secs: u64,
+    ) -> Result<()> {
+        let Some(user_id) = user_id else {
+            return Ok(());
+        };
+        if !self.is_enabled(user_id).await? {
+            return Ok(());
+        }
+
+        let code = code.ok_or(RustscapeError::Auth(AuthError::TwoFactorRequired))?;
+        if self.verify(user_id, code, unix_secs).await? {
+            Ok(())
+        } else {
+            Err(RustscapeError::Auth(AuthError::InvalidTwoFactorCode))
+        }
+    }
+
+    /// Load the stored state for an account
+    async fn load(&self, pool: &PgPool, user_id: Uuid) -> Result<Option<TwoFactorRecord>> {
+        Ok(sqlx::query_as::<_, TwoFactorRecord>(
+            "SELECT secret_encrypted, enabled, last_used_step FROM user_two_factor WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?)
+    }
+
+    /// Decrypt a stored secret
+    fn totp(&self, record: &TwoFactorRecord) -> Result<Totp> {
+        self.cipher
+            .decrypt(&record.secret_encrypted)
+            .map(Totp::new)
+            .ok_or_else(|| RustscapeError::Internal("Failed to decrypt 2FA secret".to_string()))
+    }
+
+    /// Enrollment needs somewhere to store the secret
+    fn require_pool(&self) -> Result<&PgPool> {
+        self.pool.as_ref().ok_or_else(|| {
+            RustscapeError::Internal("Two-factor authentication requires a database".to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238 appendix B SHA1 secret
+    fn rfc_secret() -> Totp {
+        Totp::new(b"12345678901234567890".to_vec())
+    }
+
+    #[test]
+    fn test_secret_cipher_roundtrip() {
+        let config = TwoFactorConfig::default();
+        let cipher = SecretCipher::from_hex(&config.encryption_key).unwrap();
+        let secret = rfc_secret();
+
+        let encrypted = cipher.encrypt(secret.secret());
+        assert_ne!(&encrypted[NONCE_LEN..], secret.secret());
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), secret.secret());
+
+        // Fresh nonce every time
+        assert_ne!(cipher.encrypt(secret.secret()), encrypted);
+
+        // Tampered data and other keys are refused
+        let mut tampered = encrypted.clone();
+        tampered[NONCE_LEN] ^= 1;
+        assert!(cipher.decrypt(&tampered).is_none());
+        assert!(cipher.decrypt(&encrypted[..4]).is_none());
+        let other = SecretCipher::from_hex(&"ab".repeat(32)).unwrap();
+        assert!(other.decrypt(&encrypted).is_none());
+
+        assert!(SecretCipher::from_hex("abcd").is_err());
+        assert!(SecretCipher::from_hex("not hex").is_err());
+    }
+
+    #[test]
+    fn test_check_code_refuses_replay() {
+        let totp = rfc_secret();
+        let now = 1111111111;
+        let step = now / 30;
+
+        assert_eq!(check_code(&totp, None, "050471", now, 1), Some(step));
+        // The same step can't be used twice, even within the skew window
+        assert_eq!(check_code(&totp, Some(step), "050471", now, 1), None);
+        assert_eq!(check_code(&totp, Some(step), "050471", now + 30, 1), None);
+        // A later step is fine
+        let next = totp.code_at(now + 30);
+        assert_eq!(
+            check_code(&totp, Some(step), &next, now + 30, 1),
+            Some(step + 1)
+        );
+        assert_eq!(check_code(&totp, None, "000000", now, 1), None);
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = generate_recovery_codes(10);
+        assert_eq!(codes.len(), 10);
+        for code in &codes {
+            assert_eq!(code.len(), 2 * RECOVERY_CODE_HALF_LEN + 1);
+            assert_eq!(code.as_bytes()[RECOVERY_CODE_HALF_LEN], b'-');
+            assert!(code
+                .bytes()
+                .all(|b| b == b'-' || RECOVERY_CODE_ALPHABET.contains(&b)));
+        }
+
+        // Hashes ignore formatting but not content
+        let hash = hash_recovery_code("abcde-fghjk");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash_recovery_code(" ABCDE FGHJK "), hash);
+        assert_eq!(hash_recovery_code("abcdefghjk"), hash);
+        assert_ne!(hash_recovery_code("abcde-fghjm"), hash);
+    }
+
+    #[tokio::test]
+    async fn test_without_database() {
+        let two_factor = TwoFactor::new(&TwoFactorConfig::default(), None).unwrap();
+        let user_id = Uuid::new_v4();
+
+        assert!(!two_factor.is_enabled(user_id).await.unwrap());
+        assert!(two_factor
+            .check_login(Some(user_id), None, 1111111111)
+            .await
+            .is_ok());
+        assert!(two_factor.check_login(None, None, 1111111111).await.is_ok());
+        assert!(two_factor.begin_setup(user_id, "zezima").await.is_err());
+    }
+}
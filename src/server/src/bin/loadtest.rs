@@ -0,0 +1,724 @@
+//! Load Test CLI Tool
+//!
+//! Spawns simulated players that speak the real login and game protocol over
+//! TCP or WebSocket, log in (the server must run in dev_mode, which accepts
+//! any credentials), then walk, chat, bank and drop items until the run ends.
+//!
+//! Reports:
+//! - Login latency (connect to login init sequence received, including any
+//!   time spent in the server's login queue)
+//! - Tick jitter, from the spacing of the player updates each bot receives
+//! - Bytes received per bot per tick
+//! - Client-side failures, and server-side error counters from the `/stats`
+//!   endpoint when `--stats` is given
+//!
+//! Usage:
+//!   loadtest [--tcp <addr> | --ws <url>] [OPTIONS]
+//!
+//! Examples:
+//!   loadtest --bots 200 --duration 120
+//!   loadtest --ws ws://127.0.0.1:43596 --bots 50 --stats 127.0.0.1:5555
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{interval_at, sleep_until, MissedTickBehavior};
+
+use rustscape_server::api::stats::ServerStats;
+use rustscape_server::client::{ClientOptions, ClientPacket, GameClient, ServerPacket};
+use rustscape_server::crypto::RsaKeyPair;
+use rustscape_server::error::{AuthError, RustscapeError};
+use rustscape_server::protocol::game::CONTAINER_INVENTORY;
+use rustscape_server::protocol::login_init::{DEFAULT_SPAWN_X, DEFAULT_SPAWN_Y};
+
+/// How long a queued bot waits before retrying its login, as the client does
+const LOGIN_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// How far from spawn the bots wander, in tiles
+const WANDER_RADIUS: i32 = 10;
+
+/// Item given to bots to drop and bank (logs)
+const DROP_ITEM: u16 = 1511;
+
+/// Logs given per restock
+const RESTOCK_AMOUNT: u32 = 5;
+
+/// Lines the bots say
+const CHAT_LINES: [&str; 6] = [
+    "hello",
+    "selling lobbies",
+    "anyone want to duel",
+    "buying gf",
+    "lol",
+    "where is the bank",
+];
+
+/// How the bots connect
+#[derive(Debug, Clone)]
+enum Target {
+    /// Game port address
+    Tcp(String),
+    /// WebSocket URL
+    WebSocket(String),
+}
+
+/// CLI arguments
+struct Args {
+    /// Server to connect to
+    target: Target,
+    /// Number of bots
+    bots: usize,
+    /// Seconds over which bot logins are spread
+    ramp: Duration,
+    /// Seconds to run after the last bot has started
+    duration: Duration,
+    /// Time between bot actions
+    action_interval: Duration,
+    /// Server tick rate the jitter is measured against
+    tick: Duration,
+    /// Username prefix (bots are named prefix + number)
+    prefix: String,
+    /// Management address to read `/stats` from
+    stats: Option<String>,
+    /// RSA modulus (hex) to encrypt the login block with
+    rsa_modulus: Option<String>,
+    /// RSA public exponent
+    rsa_exponent: u64,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut target = Target::Tcp("127.0.0.1:43594".to_string());
+    let mut bots: usize = 10;
+    let mut ramp_secs: u64 = 10;
+    let mut duration_secs: u64 = 60;
+    let mut action_ms: u64 = 1200;
+    let mut tick_ms: u64 = 600;
+    let mut prefix = "bot".to_string();
+    let mut stats: Option<String> = None;
+    let mut rsa_modulus: Option<String> = None;
+    let mut rsa_exponent: u64 = 65537;
+
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        if matches!(flag, "--help" | "-h") {
+            print_help();
+            std::process::exit(0);
+        }
+
+        i += 1;
+        let value = args
+            .get(i)
+            .ok_or_else(|| format!("Missing value for {}", flag))?;
+        let number = || {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid value for {}: {}", flag, value))
+        };
+
+        match flag {
+            "--tcp" => target = Target::Tcp(value.clone()),
+            "--ws" => target = Target::WebSocket(value.clone()),
+            "--bots" | "-n" => bots = number()? as usize,
+            "--ramp" => ramp_secs = number()?,
+            "--duration" | "-d" => duration_secs = number()?,
+            "--action-ms" => action_ms = number()?.max(1),
+            "--tick-ms" => tick_ms = number()?.max(1),
+            "--prefix" => prefix = value.clone(),
+            "--stats" => stats = Some(value.clone()),
+            "--rsa-modulus" => rsa_modulus = Some(value.clone()),
+            "--rsa-exponent" => rsa_exponent = number()?,
+            _ => return Err(format!("Unknown argument: {}", flag)),
+        }
+        i += 1;
+    }
+
+    if bots == 0 {
+        return Err("--bots must be at least 1".to_string());
+    }
+
+    Ok(Args {
+        target,
+        bots,
+        ramp: Duration::from_secs(ramp_secs),
+        duration: Duration::from_secs(duration_secs),
+        action_interval: Duration::from_millis(action_ms),
+        tick: Duration::from_millis(tick_ms),
+        prefix,
+        stats,
+        rsa_modulus,
+        rsa_exponent,
+    })
+}
+
+fn print_help() {
+    println!("Load Test Tool");
+    println!();
+    println!("Usage: loadtest [OPTIONS]");
+    println!();
+    println!("Options:");
+    println!("  --tcp <addr>           Connect over TCP (default: 127.0.0.1:43594)");
+    println!("  --ws <url>             Connect over WebSocket (ws:// or wss://)");
+    println!("  -n, --bots <n>         Number of bots (default: 10)");
+    println!("  --ramp <secs>          Spread logins over this many seconds (default: 10)");
+    println!("  -d, --duration <secs>  Run time after the ramp (default: 60)");
+    println!("  --action-ms <ms>       Time between bot actions (default: 1200)");
+    println!("  --tick-ms <ms>         Server tick rate for jitter (default: 600)");
+    println!("  --prefix <name>        Bot username prefix (default: bot)");
+    println!("  --stats <addr>         Management address to read /stats from");
+    println!("  --rsa-modulus <hex>    Encrypt the login block with this RSA key");
+    println!("  --rsa-exponent <n>     RSA public exponent (default: 65537)");
+    println!("  -h, --help             Print this help message");
+}
+
+#[tokio::main]
+async fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("Use --help for usage information");
+            std::process::exit(1);
+        }
+    };
+
+    match run(args).await {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(2),
+        Err(e) => {
+            eprintln!("Load test failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Run the load test; returns false if any bot failed
+async fn run(args: Args) -> Result<bool, Box<dyn std::error::Error>> {
+    let rsa_key = match &args.rsa_modulus {
+        Some(modulus) => Some(RsaKeyPair::from_public_hex(modulus, args.rsa_exponent)?),
+        None => None,
+    };
+    let options = ClientOptions {
+        rsa_key,
+        ..Default::default()
+    };
+
+    let stats_before = match &args.stats {
+        Some(addr) => Some(fetch_stats(addr).await?),
+        None => None,
+    };
+
+    println!(
+        "Starting {} bots against {:?} (ramp {}s, duration {}s)",
+        args.bots,
+        args.target,
+        args.ramp.as_secs(),
+        args.duration.as_secs()
+    );
+
+    let start = Instant::now();
+    let deadline = start + args.ramp + args.duration;
+    let args = Arc::new(args);
+
+    let mut handles = Vec::with_capacity(args.bots);
+    for id in 0..args.bots {
+        let args = args.clone();
+        let options = options.clone();
+        let start_at = start + args.ramp.mul_f64(id as f64 / args.bots as f64);
+        handles.push(tokio::spawn(async move {
+            let mut bot = Bot::new(id, &args);
+            sleep_until(start_at.into()).await;
+            bot.run(&args, options, deadline).await;
+            bot.report
+        }));
+    }
+
+    let mut report = BotReport::default();
+    for handle in handles {
+        report.merge(handle.await?);
+    }
+    let elapsed = start.elapsed();
+
+    let stats_after = match &args.stats {
+        Some(addr) => Some(fetch_stats(addr).await?),
+        None => None,
+    };
+
+    print_report(&args, &report, elapsed);
+    if let (Some(before), Some(after)) = (stats_before, stats_after) {
+        print_server_stats(&before, &after);
+    }
+
+    Ok(report.failures.is_empty())
+}
+
+/// What a bot measured
+#[derive(Debug, Default)]
+struct BotReport {
+    /// Successful logins
+    logins: u64,
+    /// Logins answered with a queue position and retried
+    login_retries: u64,
+    /// Login latencies
+    login_latency: Vec<Duration>,
+    /// Deviation of each player update interval from the tick rate
+    tick_jitter: Vec<Duration>,
+    /// Player updates received
+    ticks: u64,
+    /// Bytes received after login
+    bytes_read: u64,
+    /// Bytes sent after login
+    bytes_written: u64,
+    /// Actions performed, by name
+    actions: BTreeMap<&'static str, u64>,
+    /// Failures, by description
+    failures: BTreeMap<String, u64>,
+}
+
+impl BotReport {
+    fn merge(&mut self, other: BotReport) {
+        self.logins += other.logins;
+        self.login_retries += other.login_retries;
+        self.login_latency.extend(other.login_latency);
+        self.tick_jitter.extend(other.tick_jitter);
+        self.ticks += other.ticks;
+        self.bytes_read += other.bytes_read;
+        self.bytes_written += other.bytes_written;
+        for (action, count) in other.actions {
+            *self.actions.entry(action).or_default() += count;
+        }
+        for (failure, count) in other.failures {
+            *self.failures.entry(failure).or_default() += count;
+        }
+    }
+
+    fn fail(&mut self, failure: String) {
+        *self.failures.entry(failure).or_default() += 1;
+    }
+}
+
+/// Where a bot is in its bank visit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BankStep {
+    /// Bank closed
+    Closed,
+    /// Bank opened; next action deposits or withdraws
+    Open,
+    /// Item moved; next action closes the bank
+    Done,
+}
+
+/// A simulated player
+struct Bot {
+    /// Username
+    username: String,
+    /// Inventory contents by slot, as last sent by the server
+    inventory: HashMap<u16, (u16, u32)>,
+    /// Tab 0 bank contents by slot, as last sent by the server
+    bank: HashMap<u16, (u16, u32)>,
+    /// Bank visit progress
+    bank_step: BankStep,
+    /// Arrival of the previous player update
+    last_update: Option<Instant>,
+    /// Measurements
+    report: BotReport,
+}
+
+impl Bot {
+    fn new(id: usize, args: &Args) -> Self {
+        Self {
+            username: format!("{}{}", args.prefix, id),
+            inventory: HashMap::new(),
+            bank: HashMap::new(),
+            bank_step: BankStep::Closed,
+            last_update: None,
+            report: BotReport::default(),
+        }
+    }
+
+    async fn run(&mut self, args: &Args, options: ClientOptions, deadline: Instant) {
+        let login_start = Instant::now();
+        let mut client = loop {
+            let connected = match &args.target {
+                Target::Tcp(addr) => GameClient::connect_tcp(addr.as_str(), options.clone()).await,
+                Target::WebSocket(url) => GameClient::connect_websocket(url, options.clone()).await,
+            };
+            let mut client = match connected {
+                Ok(client) => client,
+                Err(e) => return self.report.fail(format!("connect: {}", e)),
+            };
+
+            match client.login(&self.username, "loadtest").await {
+                Ok(_) => {
+                    self.report.logins += 1;
+                    self.report.login_latency.push(login_start.elapsed());
+                    break client;
+                }
+                // Retry like the real client until the server lets us in
+                Err(RustscapeError::Auth(AuthError::LoginQueued(_))) => {
+                    self.report.login_retries += 1;
+                    if Instant::now() + LOGIN_RETRY_DELAY >= deadline {
+                        return self
+                            .report
+                            .fail("login still queued at deadline".to_string());
+                    }
+                    tokio::time::sleep(LOGIN_RETRY_DELAY).await;
+                }
+                Err(RustscapeError::Auth(AuthError::LoginRejected(code))) => {
+                    return self.report.fail(format!("login rejected ({})", code));
+                }
+                Err(e) => return self.report.fail(format!("login: {}", e)),
+            }
+        };
+
+        let (read_at_login, written_at_login) = (client.bytes_read(), client.bytes_written());
+        if let Err(e) = self.play(&mut client, args, deadline).await {
+            self.report.fail(format!("in game: {}", e));
+        }
+        self.report.bytes_read += client.bytes_read() - read_at_login;
+        self.report.bytes_written += client.bytes_written() - written_at_login;
+
+        let _ = client.close().await;
+    }
+
+    /// Act and read packets until the deadline
+    async fn play(
+        &mut self,
+        client: &mut GameClient,
+        args: &Args,
+        deadline: Instant,
+    ) -> rustscape_server::error::Result<()> {
+        client.send(&ClientPacket::MapLoaded).await?;
+        client.send(&restock()).await?;
+
+        // Stagger bots so they don't all act in the same tick
+        let first_action = Instant::now()
+            + args
+                .action_interval
+                .mul_f64(rand::thread_rng().gen_range(0.0..1.0));
+        let mut actions = interval_at(first_action.into(), args.action_interval);
+        actions.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let tick = args.tick;
+        let end = sleep_until(deadline.into());
+        tokio::pin!(end);
+
+        loop {
+            tokio::select! {
+                _ = &mut end => return Ok(()),
+                _ = actions.tick() => self.act(client).await?,
+                packet = client.recv() => self.handle(packet?, tick),
+            }
+        }
+    }
+
+    /// Track the state the bot's actions depend on, and time player updates
+    fn handle(&mut self, packet: ServerPacket, tick: Duration) {
+        match packet {
+            ServerPacket::PlayerUpdate(_) => {
+                let now = Instant::now();
+                if let Some(last) = self.last_update.replace(now) {
+                    let interval = now - last;
+                    self.report.tick_jitter.push(interval.abs_diff(tick));
+                }
+                self.report.ticks += 1;
+            }
+            ServerPacket::Inventory { container, items } if container == CONTAINER_INVENTORY => {
+                self.inventory = items
+                    .into_iter()
+                    .filter(|&(_, item_id, _)| item_id != 0)
+                    .map(|(slot, item_id, amount)| (slot, (item_id, amount)))
+                    .collect();
+            }
+            ServerPacket::InventorySlot {
+                container,
+                slot,
+                item_id,
+                amount,
+            } if container == CONTAINER_INVENTORY => {
+                if item_id == 0 {
+                    self.inventory.remove(&slot);
+                } else {
+                    self.inventory.insert(slot, (item_id, amount));
+                }
+            }
+            ServerPacket::BankContents(entries) => {
+                self.bank = entries
+                    .into_iter()
+                    .filter(|entry| entry.tab == 0 && entry.item_id != 0)
+                    .map(|entry| (entry.slot, (entry.item_id, entry.amount)))
+                    .collect();
+            }
+            ServerPacket::BankSlot(entry) if entry.tab == 0 => {
+                if entry.item_id == 0 {
+                    self.bank.remove(&entry.slot);
+                } else {
+                    self.bank.insert(entry.slot, (entry.item_id, entry.amount));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Perform one random action (or the next step of a bank visit)
+    async fn act(&mut self, client: &mut GameClient) -> rustscape_server::error::Result<()> {
+        if self.bank_step != BankStep::Closed {
+            return self.continue_banking(client).await;
+        }
+
+        let roll = rand::thread_rng().gen_range(0..100);
+        let (action, packet) = match roll {
+            0..=49 => {
+                let mut rng = rand::thread_rng();
+                let x = DEFAULT_SPAWN_X as i32 + rng.gen_range(-WANDER_RADIUS..=WANDER_RADIUS);
+                let y = DEFAULT_SPAWN_Y as i32 + rng.gen_range(-WANDER_RADIUS..=WANDER_RADIUS);
+                let walk = ClientPacket::Walk {
+                    x: x as u16,
+                    y: y as u16,
+                    running: rng.gen_bool(0.5),
+                };
+                ("walk", walk)
+            }
+            50..=69 => {
+                let line = CHAT_LINES.choose(&mut rand::thread_rng()).unwrap_or(&"hi");
+                let chat = ClientPacket::Chat {
+                    effects: 0,
+                    message: line.to_string(),
+                };
+                ("chat", chat)
+            }
+            70..=84 => {
+                self.bank_step = BankStep::Open;
+                ("bank", ClientPacket::Command("bank".to_string()))
+            }
+            _ => match self.random_item(|item_id| item_id == DROP_ITEM) {
+                Some((slot, item_id, _)) => ("drop", ClientPacket::ItemDrop { slot, item_id }),
+                None => ("restock", restock()),
+            },
+        };
+
+        *self.report.actions.entry(action).or_default() += 1;
+        client.send(&packet).await
+    }
+
+    /// Deposit or withdraw one item, then close the bank
+    async fn continue_banking(
+        &mut self,
+        client: &mut GameClient,
+    ) -> rustscape_server::error::Result<()> {
+        if self.bank_step == BankStep::Done {
+            self.bank_step = BankStep::Closed;
+            return client.send(&ClientPacket::BankClose).await;
+        }
+        self.bank_step = BankStep::Done;
+
+        let withdraw = self
+            .bank
+            .iter()
+            .next()
+            .map(|(&slot, &(item_id, _))| (slot, item_id));
+        let packet = match (self.random_item(|_| true), withdraw) {
+            (Some((slot, item_id, amount)), withdraw)
+                if withdraw.is_none() || rand::thread_rng().gen_bool(0.5) =>
+            {
+                ClientPacket::BankDeposit {
+                    slot,
+                    item_id,
+                    amount: amount.min(100),
+                }
+            }
+            (_, Some((slot, item_id))) => ClientPacket::BankWithdraw {
+                slot,
+                item_id,
+                amount: 100,
+                as_note: false,
+            },
+            _ => return Ok(()),
+        };
+
+        *self.report.actions.entry("bank item").or_default() += 1;
+        client.send(&packet).await
+    }
+
+    /// Pick a random inventory item matching `filter`
+    fn random_item(&self, filter: impl Fn(u16) -> bool) -> Option<(u16, u16, u32)> {
+        let items: Vec<_> = self
+            .inventory
+            .iter()
+            .filter(|(_, &(item_id, _))| filter(item_id))
+            .map(|(&slot, &(item_id, amount))| (slot, item_id, amount))
+            .collect();
+        items.choose(&mut rand::thread_rng()).copied()
+    }
+}
+
+/// Command giving the bot a fresh stack of items
+fn restock() -> ClientPacket {
+    ClientPacket::Command(format!("item {} {}", DROP_ITEM, RESTOCK_AMOUNT))
+}
+
+/// Read the server's counters from its `/stats` endpoint
+async fn fetch_stats(addr: &str) -> Result<ServerStats, Box<dyn std::error::Error>> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let request = format!(
+        "GET /stats HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\n\r\n",
+        addr
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let response = String::from_utf8_lossy(&response);
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or("malformed HTTP response from /stats")?;
+    let status = head.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(format!("/stats returned: {}", status).into());
+    }
+
+    Ok(serde_json::from_str(body)?)
+}
+
+fn print_report(args: &Args, report: &BotReport, elapsed: Duration) {
+    println!();
+    println!(
+        "Finished in {:.1}s: {}/{} bots logged in",
+        elapsed.as_secs_f64(),
+        report.logins,
+        args.bots
+    );
+
+    println!();
+    println!(
+        "Login latency ({} retries after being queued):",
+        report.login_retries
+    );
+    print_percentiles(&report.login_latency);
+
+    println!();
+    println!(
+        "Tick jitter (player update spacing vs {}ms tick, {} intervals):",
+        args.tick.as_millis(),
+        report.tick_jitter.len()
+    );
+    print_percentiles(&report.tick_jitter);
+
+    println!();
+    println!("Traffic:");
+    let per_tick = |bytes: u64| bytes.checked_div(report.ticks);
+    match (per_tick(report.bytes_read), per_tick(report.bytes_written)) {
+        (Some(read), Some(written)) => {
+            println!(
+                "  received: {} bytes/bot/tick ({} total)",
+                read, report.bytes_read
+            );
+            println!(
+                "  sent:     {} bytes/bot/tick ({} total)",
+                written, report.bytes_written
+            );
+        }
+        _ => println!("  no player updates received"),
+    }
+
+    println!();
+    println!("Actions:");
+    for (action, count) in &report.actions {
+        println!("  {:<10} {}", action, count);
+    }
+
+    println!();
+    if report.failures.is_empty() {
+        println!("Client errors: none");
+    } else {
+        println!("Client errors:");
+        for (failure, count) in &report.failures {
+            println!("  {:>5}x {}", count, failure);
+        }
+    }
+}
+
+fn print_percentiles(samples: &[Duration]) {
+    if samples.is_empty() {
+        println!("  no samples");
+        return;
+    }
+
+    let mut sorted = samples.to_vec();
+    sorted.sort();
+    let at = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+    let mean = sorted.iter().sum::<Duration>() / sorted.len() as u32;
+
+    println!(
+        "  mean {:.1}ms  p50 {:.1}ms  p95 {:.1}ms  p99 {:.1}ms  max {:.1}ms",
+        mean.as_secs_f64() * 1000.0,
+        at(0.50).as_secs_f64() * 1000.0,
+        at(0.95).as_secs_f64() * 1000.0,
+        at(0.99).as_secs_f64() * 1000.0,
+        at(1.0).as_secs_f64() * 1000.0
+    );
+}
+
+fn print_server_stats(before: &ServerStats, after: &ServerStats) {
+    let delta = |before: u64, after: u64| after.saturating_sub(before);
+
+    println!();
+    println!(
+        "Server ({} ticks, {} players online at end):",
+        delta(before.tick, after.tick),
+        after.players_online
+    );
+    println!(
+        "  slow ticks:         {}",
+        delta(before.slow_ticks, after.slow_ticks)
+    );
+    println!(
+        "  packet errors:      {}",
+        delta(before.packet_errors, after.packet_errors)
+    );
+    println!(
+        "  login failures:     {}",
+        delta(
+            before.connection.login_failures,
+            after.connection.login_failures
+        )
+    );
+    println!(
+        "  connection errors:  {}",
+        delta(
+            before.connection.connection_errors,
+            after.connection.connection_errors
+        )
+    );
+    println!(
+        "  login queued:       {}",
+        delta(before.login.queued, after.login.queued)
+    );
+    println!(
+        "  flood drops:        {}",
+        delta(before.flood.dropped_packets, after.flood.dropped_packets)
+    );
+    println!(
+        "  flood disconnects:  {}",
+        delta(before.flood.disconnects, after.flood.disconnects)
+    );
+
+    let compressed_in = delta(before.compression.bytes_in, after.compression.bytes_in);
+    if compressed_in > 0 {
+        let compressed_out = delta(before.compression.bytes_out, after.compression.bytes_out);
+        println!(
+            "  ws compression:     {:.2} ({} -> {} bytes)",
+            compressed_out as f64 / compressed_in as f64,
+            compressed_in,
+            compressed_out
+        );
+    }
+}
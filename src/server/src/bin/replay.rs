@@ -0,0 +1,183 @@
+//! Packet Capture Replay CLI Tool
+//!
+//! Feeds the inbound packets of a capture file back into a headless game
+//! packet handler, starting from the player state recorded in the capture,
+//! and diffs the responses against what the server originally sent.
+//!
+//! Usage:
+//!   replay <capture> [--limit <n>] [--dump]
+//!
+//! Examples:
+//!   replay data/captures/zezima-1760000000-42.rscap
+//!   replay data/captures/zezima-1760000000-42.rscap --limit 5
+//!   replay data/captures/zezima-1760000000-42.rscap --dump
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use rustscape_server::net::capture::{self, CaptureReader, CaptureRecord, Direction};
+
+/// CLI arguments
+struct Args {
+    /// Path to the capture file
+    capture_path: PathBuf,
+    /// Maximum number of mismatches to print
+    limit: usize,
+    /// Print every record in the capture instead of replaying it
+    dump: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut capture_path: Option<PathBuf> = None;
+    let mut limit: usize = 20;
+    let mut dump = false;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--limit" | "-l" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for --limit".to_string());
+                }
+                limit = args[i]
+                    .parse()
+                    .map_err(|_| format!("Invalid limit: {}", args[i]))?;
+            }
+            "--dump" | "-d" => {
+                dump = true;
+            }
+            "--help" | "-h" => {
+                print_help();
+                std::process::exit(0);
+            }
+            arg if !arg.starts_with('-') && capture_path.is_none() => {
+                capture_path = Some(PathBuf::from(arg));
+            }
+            arg => {
+                return Err(format!("Unknown argument: {}", arg));
+            }
+        }
+        i += 1;
+    }
+
+    let capture_path = capture_path.ok_or("Missing capture file")?;
+
+    Ok(Args {
+        capture_path,
+        limit,
+        dump,
+    })
+}
+
+fn print_help() {
+    println!("Packet Capture Replay Tool");
+    println!();
+    println!("Usage: replay <capture> [OPTIONS]");
+    println!();
+    println!("Options:");
+    println!("  -l, --limit <n>   Maximum number of mismatches to print (default: 20)");
+    println!("  -d, --dump        Print the capture's records instead of replaying them");
+    println!("  -h, --help        Print this help message");
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("Use --help for usage information");
+            std::process::exit(1);
+        }
+    };
+
+    match run(&args) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(2),
+        Err(e) => {
+            eprintln!("Replay failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Replay or dump the capture; returns false if the replay diverged
+fn run(args: &Args) -> Result<bool, Box<dyn std::error::Error>> {
+    let file = File::open(&args.capture_path)?;
+    let reader = CaptureReader::new(BufReader::new(file))?;
+
+    let header = reader.header();
+    println!(
+        "Capture: {} (revision {}, player index {}, rights {}, member {})",
+        header.username, header.revision, header.player_index, header.rights, header.member
+    );
+
+    if args.dump {
+        for record in reader {
+            println!("{}", format_record(&record?));
+        }
+        return Ok(true);
+    }
+
+    let report = capture::replay(reader)?;
+
+    println!(
+        "Replayed {} inbound packets ({} rejected): {} responses recorded, {} replayed",
+        report.inbound, report.errors, report.recorded, report.replayed
+    );
+
+    if report.is_match() {
+        println!("Replay matches the capture");
+        return Ok(true);
+    }
+
+    println!("{} mismatched responses:", report.mismatches.len());
+    for mismatch in report.mismatches.iter().take(args.limit) {
+        println!();
+        println!("  #{}", mismatch.position);
+        match &mismatch.recorded {
+            Some(record) => println!("    recorded: {}", format_record(record)),
+            None => println!("    recorded: <none>"),
+        }
+        match &mismatch.replayed {
+            Some((inbound_opcode, record)) => println!(
+                "    replayed: {} (from inbound opcode {})",
+                format_record(record),
+                inbound_opcode
+            ),
+            None => println!("    replayed: <none>"),
+        }
+    }
+
+    if report.mismatches.len() > args.limit {
+        println!();
+        println!("  ... {} more", report.mismatches.len() - args.limit);
+    }
+
+    Ok(false)
+}
+
+fn format_record(record: &CaptureRecord) -> String {
+    let direction = match record.direction {
+        Direction::Inbound => "in ",
+        Direction::Outbound => "out",
+        Direction::OutboundFrame => "frm",
+    };
+    let payload: Vec<String> = record
+        .payload
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    format!(
+        "[tick {}] {} opcode {:3} size {:5} {}",
+        record.tick,
+        direction,
+        record.opcode,
+        record.payload.len(),
+        payload.join(" ")
+    )
+}
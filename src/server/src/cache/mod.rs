@@ -31,6 +31,7 @@
 //! - Byte 7: Index ID
 //! - Bytes 8-519: Data (512 bytes)
 
+pub mod objects;
 pub mod sprites;
 
 use std::collections::HashMap;
@@ -786,6 +787,67 @@ pub fn get_parsed_reference_table(&self, index: u8) -> Option<ReferenceTable> {
         self.reference_tables.read().unwrap().get(&index).cloned()
     }
 
+    /// Get the CRC of an index's reference table without copying the table
+    pub fn reference_table_crc(&self, index: u8) -> Option<u32> {
+        self.reference_tables
+            .read()
+            .unwrap()
+            .get(&index)
+            .map(|table| table.crc)
+    }
+
+    /// Get the CRC of an archive from its index's reference table
+    pub fn archive_crc(&self, index: u8, archive: u32) -> Option<u32> {
+        let tables = self.reference_tables.read().unwrap();
+        let archives = &tables.get(&index)?.archives;
+        // Archive IDs are delta-encoded, so the list is sorted
+        archives
+            .binary_search_by_key(&archive, |info| info.id)
+            .ok()
+            .map(|position| archives[position].crc)
+    }
+
+    /// Get the files of an archive, by file ID
+    ///
+    /// Archives holding several files (such as definition archives) are
+    /// split using the file IDs in the index's reference table. Returns
+    /// nothing if the archive can't be read or split.
+    pub fn get_archive_files(&self, index: u8, archive: u32) -> Vec<(u32, Vec<u8>)> {
+        let file_ids = {
+            let tables = self.reference_tables.read().unwrap();
+            let Some(archives) = tables.get(&index).map(|table| &table.archives) else {
+                return Vec::new();
+            };
+            match archives.binary_search_by_key(&archive, |info| info.id) {
+                Ok(position) => archives[position].file_ids.clone(),
+                Err(_) => return Vec::new(),
+            }
+        };
+
+        let data = match self.get_decompressed_file(index, archive) {
+            Ok(data) if !data.is_empty() => data,
+            _ => return Vec::new(),
+        };
+        split_archive(&data, &file_ids).unwrap_or_else(|| {
+            trace!("Failed to split archive {}/{}", index, archive);
+            Vec::new()
+        })
+    }
+
+    /// Find an archive by name in a named index
+    pub fn find_archive(&self, index: u8, name: &str) -> Option<u32> {
+        let hash = name_hash(name);
+        self.reference_tables
+            .read()
+            .unwrap()
+            .get(&index)
+            .filter(|table| table.named)?
+            .archives
+            .iter()
+            .find(|archive| archive.name_hash == hash)
+            .map(|archive| archive.id)
+    }
+
     /// Generate a stub checksum table
     fn generate_stub_checksum_table(&self) -> Vec<u8> {
         let mut data = Vec::with_capacity(INDEX_COUNT * 8);
@@ -861,6 +923,46 @@ pub fn index_count(&self) -> usize {
     }
 }
 
+/// Split a decompressed archive into its files
+///
+/// An archive with one file is that file. Otherwise the archive ends with a
+/// chunk count, preceded by each chunk's file sizes (delta-encoded ints);
+/// each file is the concatenation of its pieces across the chunks.
+pub fn split_archive(data: &[u8], file_ids: &[u32]) -> Option<Vec<(u32, Vec<u8>)>> {
+    if file_ids.len() <= 1 {
+        let id = file_ids.first().copied().unwrap_or(0);
+        return Some(vec![(id, data.to_vec())]);
+    }
+
+    let chunks = *data.last()? as usize;
+    let table_len = chunks * file_ids.len() * 4;
+    let table_start = data.len().checked_sub(1 + table_len)?;
+
+    let mut files = vec![Vec::new(); file_ids.len()];
+    let mut table = &data[table_start..data.len() - 1];
+    let mut pos = 0;
+    for _ in 0..chunks {
+        let mut size = 0i32;
+        for file in &mut files {
+            let (delta, rest) = table.split_at(4);
+            table = rest;
+            size = size.wrapping_add(i32::from_be_bytes(delta.try_into().ok()?));
+            let end = pos + usize::try_from(size).ok()?;
+            file.extend_from_slice(data[..table_start].get(pos..end)?);
+            pos = end;
+        }
+    }
+
+    Some(file_ids.iter().copied().zip(files).collect())
+}
+
+/// Hash an archive name the way the client does
+pub fn name_hash(name: &str) -> i32 {
+    name.to_lowercase().bytes().fold(0i32, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(byte as i32)
+    })
+}
+
 impl std::fmt::Debug for CacheStore {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("CacheStore")
@@ -925,6 +1027,70 @@ fn test_index_count() {
         assert_eq!(store.index_count(), INDEX_COUNT);
     }
 
+    #[test]
+    fn test_name_hash() {
+        assert_eq!(name_hash(""), 0);
+        assert_eq!(name_hash("ab"), 97 * 31 + 98);
+        assert_eq!(name_hash("M50_50"), name_hash("m50_50"));
+        assert_ne!(name_hash("m50_50"), name_hash("l50_50"));
+
+        // No reference tables without a cache
+        let store = CacheStore::new(temp_dir().join("rustscape_cache_test6")).unwrap();
+        assert_eq!(store.find_archive(5, "m50_50"), None);
+    }
+
+    #[test]
+    fn test_archive_crc_lookup() {
+        let store = CacheStore::new(temp_dir().join("rustscape_cache_test7")).unwrap();
+        assert_eq!(store.reference_table_crc(2), None);
+
+        store.reference_tables.write().unwrap().insert(
+            2,
+            ReferenceTable {
+                protocol: 5,
+                revision: 0,
+                named: false,
+                whirlpool: false,
+                archives: [1, 4, 9]
+                    .into_iter()
+                    .map(|id| ArchiveInfo {
+                        id,
+                        crc: id * 100,
+                        ..Default::default()
+                    })
+                    .collect(),
+                crc: 77,
+            },
+        );
+
+        assert_eq!(store.reference_table_crc(2), Some(77));
+        assert_eq!(store.archive_crc(2, 4), Some(400));
+        assert_eq!(store.archive_crc(2, 5), None);
+        assert_eq!(store.archive_crc(3, 4), None);
+    }
+
+    #[test]
+    fn test_split_archive() {
+        assert_eq!(
+            split_archive(&[1, 2, 3], &[7]),
+            Some(vec![(7, vec![1, 2, 3])])
+        );
+
+        // Two chunks: file 0 gets [1] then [4], file 2 gets [2, 3] then []
+        let mut data = vec![1, 2, 3, 4];
+        for size in [1i32, 1, 1, -1] {
+            data.extend_from_slice(&size.to_be_bytes());
+        }
+        data.push(2);
+        assert_eq!(
+            split_archive(&data, &[0, 2]),
+            Some(vec![(0, vec![1, 4]), (2, vec![2, 3])])
+        );
+
+        // A size table longer than the archive
+        assert_eq!(split_archive(&[0, 9], &[0, 1]), None);
+    }
+
     #[test]
     fn test_compression_type() {
         assert_eq!(CompressionType::from_u8(0), Some(CompressionType::None));
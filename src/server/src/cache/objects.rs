@@ -0,0 +1,236 @@
+//! Object (location) definitions
+//!
+//! Only the fields the server needs are kept: an object's size and how it
+//! clips the tiles it stands on. Definitions live in the object config index,
+//! 256 to an archive, and are decoded an archive at a time as objects are
+//! looked up.
+//!
+//! A definition is a run of opcodes ended by 0. Opcodes are written in
+//! ascending order and the size and clipping ones come first, so decoding
+//! stops at an opcode it doesn't know and keeps what it has read.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use tracing::trace;
+
+use crate::cache::CacheStore;
+use crate::net::buffer::PacketBuffer;
+
+/// Cache index holding object definitions
+pub const OBJECT_INDEX: u8 = 16;
+
+/// The parts of an object definition used for collision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectDefinition {
+    /// Tiles covered along X (before rotation)
+    pub width: u8,
+    /// Tiles covered along Y (before rotation)
+    pub length: u8,
+    /// How the object clips its tiles: 0 not at all, otherwise it blocks
+    /// walking; only 1 makes a floor decoration block
+    pub clip_type: u8,
+    /// Whether the object stops projectiles
+    pub blocks_projectiles: bool,
+}
+
+impl Default for ObjectDefinition {
+    fn default() -> Self {
+        Self {
+            width: 1,
+            length: 1,
+            clip_type: 2,
+            blocks_projectiles: true,
+        }
+    }
+}
+
+impl ObjectDefinition {
+    /// Decode a definition from its config file
+    pub fn decode(data: &[u8]) -> Self {
+        let mut definition = Self::default();
+        let mut buffer = PacketBuffer::from_bytes(data);
+
+        while buffer.has_remaining() {
+            let opcode = buffer.read_ubyte();
+            match opcode {
+                0 => break,
+                14 => definition.width = buffer.read_ubyte(),
+                15 => definition.length = buffer.read_ubyte(),
+                17 => {
+                    definition.clip_type = 0;
+                    definition.blocks_projectiles = false;
+                }
+                18 => definition.blocks_projectiles = false,
+                27 => definition.clip_type = 1,
+                _ if skip_opcode(&mut buffer, opcode) => {}
+                _ => {
+                    trace!(
+                        opcode = opcode,
+                        "Stopped at unknown object definition opcode"
+                    );
+                    break;
+                }
+            }
+        }
+        definition
+    }
+}
+
+/// Skip the data of an opcode that doesn't affect collision
+///
+/// Returns false for opcodes whose length isn't known.
+fn skip_opcode(buffer: &mut PacketBuffer, opcode: u8) -> bool {
+    match opcode {
+        // Models with their shapes
+        1 => {
+            let count = buffer.read_ubyte() as usize;
+            buffer.skip(count * 3);
+        }
+        // Models
+        5 => {
+            let count = buffer.read_ubyte() as usize;
+            buffer.skip(count * 2);
+        }
+        // Name and menu options
+        2 | 30..=34 | 150..=154 => {
+            buffer.read_string();
+        }
+        // Flags with no data
+        21..=23 | 62 | 64 | 73 | 74 | 82 | 88..=91 | 94 | 96..=98 | 103 | 105 => {}
+        19 | 28 | 29 | 39 | 69 | 75 | 81 | 101 | 104 => buffer.skip(1),
+        24 | 60 | 65..=68 | 70..=72 | 93 | 95 | 102 | 107 => buffer.skip(2),
+        78 | 99 | 100 => buffer.skip(3),
+        // Recolours and retextures
+        40 | 41 => {
+            let count = buffer.read_ubyte() as usize;
+            buffer.skip(count * 4);
+        }
+        42 => {
+            let count = buffer.read_ubyte() as usize;
+            buffer.skip(count);
+        }
+        // Variable-dependent forms: varbit, varp, (a default form), then the
+        // forms themselves
+        77 | 92 => {
+            buffer.skip(if opcode == 92 { 6 } else { 4 });
+            let count = buffer.read_ubyte() as usize;
+            buffer.skip((count + 1) * 2);
+        }
+        // Ambient sounds
+        79 => {
+            buffer.skip(5);
+            let count = buffer.read_ubyte() as usize;
+            buffer.skip(count * 2);
+        }
+        106 => {
+            let count = buffer.read_ubyte() as usize;
+            buffer.skip(count * 3);
+        }
+        160 => {
+            let count = buffer.read_ubyte() as usize;
+            buffer.skip(count * 2);
+        }
+        // Parameters: string or int values keyed by a medium
+        249 => {
+            let count = buffer.read_ubyte();
+            for _ in 0..count {
+                let is_string = buffer.read_ubyte() == 1;
+                buffer.skip(3);
+                if is_string {
+                    buffer.read_string();
+                } else {
+                    buffer.skip(4);
+                }
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Object definitions, decoded from the cache as they are used
+pub struct ObjectDefinitions {
+    /// Cache to read definitions from
+    cache: Arc<CacheStore>,
+    /// Decoded definition archives, by archive ID
+    archives: RwLock<HashMap<u32, Arc<HashMap<u32, ObjectDefinition>>>>,
+}
+
+impl ObjectDefinitions {
+    /// Create a store reading definitions from the cache
+    pub fn new(cache: Arc<CacheStore>) -> Self {
+        Self {
+            cache,
+            archives: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Get an object's definition, if the cache has it
+    pub fn get(&self, id: u32) -> Option<ObjectDefinition> {
+        let archive_id = id >> 8;
+        let archive = self.archives.read().get(&archive_id).cloned();
+        let archive = archive.unwrap_or_else(|| {
+            let decoded: HashMap<u32, ObjectDefinition> = self
+                .cache
+                .get_archive_files(OBJECT_INDEX, archive_id)
+                .into_iter()
+                .map(|(file, data)| (file, ObjectDefinition::decode(&data)))
+                .collect();
+            trace!(
+                archive = archive_id,
+                definitions = decoded.len(),
+                "Loaded object definitions"
+            );
+            self.archives
+                .write()
+                .entry(archive_id)
+                .or_insert_with(|| Arc::new(decoded))
+                .clone()
+        });
+        archive.get(&(id & 0xff)).copied()
+    }
+}
+
+impl std::fmt::Debug for ObjectDefinitions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectDefinitions")
+            .field("archives", &self.archives.read().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_size_and_clipping() {
+        let mut data = vec![1, 1, 0x12, 0x34, 10, 2];
+        data.extend(b"Door\0");
+        data.extend([14, 2, 15, 3, 27, 19, 1, 30]);
+        data.extend(b"Open\0");
+        data.push(0);
+
+        let definition = ObjectDefinition::decode(&data);
+        assert_eq!(definition.width, 2);
+        assert_eq!(definition.length, 3);
+        assert_eq!(definition.clip_type, 1);
+        assert!(definition.blocks_projectiles);
+
+        let definition = ObjectDefinition::decode(&[17, 0]);
+        assert_eq!(definition.clip_type, 0);
+        assert!(!definition.blocks_projectiles);
+
+        assert_eq!(ObjectDefinition::decode(&[]), ObjectDefinition::default());
+    }
+
+    #[test]
+    fn test_decode_stops_at_unknown_opcode() {
+        // 200 is unknown, so the length after it is lost
+        let definition = ObjectDefinition::decode(&[14, 4, 200, 15, 4, 0]);
+        assert_eq!(definition.width, 4);
+        assert_eq!(definition.length, 1);
+    }
+}
@@ -0,0 +1,1457 @@
+//! Headless protocol client
+//!
+//! Speaks the same login, game and JS5 protocol as the desktop and browser
+//! clients, without any rendering, so bots, load tests and integration tests
+//! can drive a real server end to end:
+//! - Login handshake, with the credentials block RSA-encrypted using the
+//!   public half of the server's key (or sent in plaintext to a dev_mode
+//!   server)
+//! - Lobby login and world list requests
+//! - ISAAC ciphers set up with `IsaacPair::for_client`
+//! - Typed game packets in both directions, framed with the server's
+//!   incoming packet registry and `server_framing`
+//! - JS5 file requests
+//! - TCP or WebSocket (ws:// and wss://) transports
+
+use bytes::{Buf, BytesMut};
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::{debug, trace};
+
+use crate::crypto::{IsaacPair, RsaEncryptor, RsaKeyPair};
+use crate::error::{
+    AuthError, Js5Response, LoginResponse, NetworkError, ProtocolError, Result, RustscapeError,
+};
+use crate::game::bank::BANK_TAB_COUNT;
+use crate::net::buffer::PacketBuffer;
+use crate::protocol::game::{
+    incoming_packets, IncomingGamePacket, OutgoingGamePacket, OutgoingOpcode,
+};
+use crate::protocol::handshake::HandshakeOpcode;
+use crate::protocol::js5::{Js5FileResponse, JS5_BLOCK_MARKER, JS5_BLOCK_SIZE, JS5_HEADER_SIZE};
+use crate::protocol::lobby::LobbyResponse;
+use crate::protocol::login::LoginType;
+use crate::protocol::login_init::{opcodes, InitialPlayerState, LoginInitializer};
+use crate::protocol::packets::PacketSize;
+
+/// Magic byte at the start of the RSA block
+const RSA_BLOCK_MAGIC: u8 = 10;
+
+/// Read chunk size for TCP connections
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// How a server packet is framed on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    /// Fixed payload size, no length prefix
+    Fixed(usize),
+    /// One byte length prefix
+    Byte,
+    /// Two byte length prefix
+    Short,
+}
+
+/// Work out how the server framed the packet at the front of `buffer`
+///
+/// The server doesn't keep one framing per opcode: variable packets get a
+/// byte length below 256 bytes and a short length from 256 (player updates
+/// always a short), and container slot updates are fixed packets on the same
+/// opcodes as the variable full updates. Those packets are told apart by
+/// their first bytes. `contents_next` is set when the previous packet was the
+/// bank tab info or a full inventory, which the full bank contents follow.
+///
+/// Returns `None` until enough of the packet has arrived to tell.
+fn server_framing(opcode: u8, buffer: &[u8], contents_next: bool) -> Result<Option<Framing>> {
+    let framing = match opcode {
+        opcodes::RESET_ANIMS => Framing::Fixed(0),
+        opcodes::RUN_ENERGY => Framing::Fixed(1),
+        opcodes::WEIGHT => Framing::Fixed(2),
+        opcodes::SKILL_UPDATE => Framing::Fixed(6),
+        opcodes::MAP_REGION => Framing::Fixed(4),
+        opcodes::PLAYER_UPDATE => Framing::Short,
+        // Option text and tokens never come near 256 bytes
+        opcodes::PLAYER_OPTION => Framing::Byte,
+        op if op == OutgoingOpcode::ResumeToken.as_u8() => Framing::Byte,
+        // A byte length points at the message's string terminator
+        opcodes::SYSTEM_MESSAGE => match buffer.first().and_then(|&len| buffer.get(len as usize)) {
+            Some(0) => Framing::Byte,
+            Some(_) => Framing::Short,
+            None => return Ok(None),
+        },
+        // Slot updates start with the high byte of a container id below 256;
+        // full updates hold at most 28 items, so have a non-zero byte length
+        op if op == OutgoingOpcode::InventoryUpdate.as_u8() => match buffer.first() {
+            Some(0) => Framing::Fixed(10),
+            Some(_) => Framing::Byte,
+            None => return Ok(None),
+        },
+        op if op == OutgoingOpcode::BankUpdate.as_u8() => {
+            return Ok(bank_update_framing(buffer, contents_next))
+        }
+        op if op == OutgoingOpcode::GroundItemSpawn.as_u8() => Framing::Fixed(9),
+        op if op == OutgoingOpcode::GroundItemRemove.as_u8() => Framing::Fixed(5),
+        op if op == OutgoingOpcode::Logout.as_u8() => Framing::Fixed(0),
+        op if op == OutgoingOpcode::BankOpen.as_u8() => Framing::Fixed(2),
+        op if op == OutgoingOpcode::BankSettings.as_u8() => Framing::Fixed(6),
+        op if op == OutgoingOpcode::BankTabInfo.as_u8() => Framing::Fixed(1 + 2 * BANK_TAB_COUNT),
+        _ => {
+            return Err(RustscapeError::Protocol(ProtocolError::InvalidOpcode(
+                opcode,
+            )))
+        }
+    };
+    Ok(Some(framing))
+}
+
+/// Work out the framing of a bank update
+///
+/// The full contents are an item count and 10 bytes per item, so only the
+/// right length width gives a length that matches the count; a slot update
+/// matches neither. The exception is a slot update for tab 2, slot 0, which
+/// starts like an empty bank (or one of 51 items), so the packet before
+/// decides.
+fn bank_update_framing(buffer: &[u8], contents_next: bool) -> Option<Framing> {
+    let &[b0, b1, b2, b3, ref rest @ ..] = buffer else {
+        return None;
+    };
+    let matches_count = |len: usize, count: u16| len == 2 + 10 * count as usize;
+    let short_len = u16::from_be_bytes([b0, b1]) as usize;
+    let short = short_len >= 256 && matches_count(short_len, u16::from_be_bytes([b2, b3]));
+
+    if [b0, b1, b2] != [2, 0, 0] {
+        return Some(if short {
+            Framing::Short
+        } else if matches_count(b0 as usize, u16::from_be_bytes([b1, b2])) {
+            Framing::Byte
+        } else {
+            Framing::Fixed(10)
+        });
+    }
+
+    if !contents_next {
+        return Some(Framing::Fixed(10));
+    }
+
+    // After an empty bank comes the next packet, whose first byte is unlikely
+    // to also pass for the first item's tab
+    let first_tab = *rest.first()?;
+    Some(if short && (first_tab as usize) < BANK_TAB_COUNT {
+        Framing::Short
+    } else {
+        Framing::Byte
+    })
+}
+
+/// Client connection options
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    /// Revision sent in the handshake and login block
+    pub revision: u32,
+    /// Server RSA key; only the public half is used. `None` sends the login
+    /// block in plaintext, which only a dev_mode server accepts.
+    pub rsa_key: Option<RsaKeyPair>,
+    /// Low memory flag
+    pub low_memory: bool,
+    /// Unique client identifier
+    pub uid: u32,
+    /// Display mode
+    pub display_mode: u8,
+    /// Screen width
+    pub screen_width: u16,
+    /// Screen height
+    pub screen_height: u16,
+    /// Machine info string
+    pub machine_info: String,
+    /// Authenticator code, for accounts with two-factor authentication
+    pub authenticator: Option<u32>,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            revision: crate::REVISION,
+            rsa_key: None,
+            low_memory: false,
+            uid: 0,
+            display_mode: 1,
+            screen_width: 765,
+            screen_height: 503,
+            machine_info: "rustscape-headless".to_string(),
+            authenticator: None,
+        }
+    }
+}
+
+/// Details of a successful login
+#[derive(Debug, Clone)]
+pub struct LoginSession {
+    /// Player rights
+    pub rights: u8,
+    /// Flagged status
+    pub flagged: bool,
+    /// Player index in the game world
+    pub player_index: u16,
+    /// Whether the player is a member
+    pub member: bool,
+    /// Login init sequence (map region, skills, etc.), sent before ISAAC starts
+    pub init_packets: Vec<ServerPacket>,
+}
+
+/// Details of a successful lobby login
+#[derive(Debug, Clone)]
+pub struct LobbySession {
+    /// Lobby response block (last login, membership, etc.)
+    pub response: LobbyResponse,
+    /// World list sent after the lobby response
+    pub worlds: Vec<ListedWorld>,
+}
+
+/// A world as sent in the world list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListedWorld {
+    /// World ID
+    pub id: u16,
+    /// World flags
+    pub flags: i32,
+    /// Display name
+    pub name: String,
+    /// Address to connect to
+    pub address: String,
+    /// Players online
+    pub players: u16,
+}
+
+/// Game packet sent by the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientPacket {
+    /// Keep-alive
+    KeepAlive,
+    /// Map region loaded
+    MapLoaded,
+    /// Walk to a tile (minimap click)
+    Walk { x: u16, y: u16, running: bool },
+    /// Public chat; the server doesn't decode the huffman text yet, so the
+    /// message is sent as plain bytes
+    Chat { effects: u16, message: String },
+    /// Command (without the leading `::`)
+    Command(String),
+    /// Drop an inventory item
+    ItemDrop { slot: u16, item_id: u16 },
+    /// Swap two inventory slots
+    InventorySwap { from_slot: u16, to_slot: u16 },
+    /// Deposit an inventory item into the bank
+    BankDeposit {
+        slot: u16,
+        item_id: u16,
+        amount: u32,
+    },
+    /// Withdraw a bank item
+    BankWithdraw {
+        slot: u16,
+        item_id: u16,
+        amount: u32,
+        as_note: bool,
+    },
+    /// Deposit the whole inventory
+    BankDepositAll,
+    /// Close the bank
+    BankClose,
+}
+
+impl ClientPacket {
+    /// Get the packet opcode
+    pub fn opcode(&self) -> u8 {
+        match self {
+            ClientPacket::KeepAlive => 0,
+            ClientPacket::MapLoaded => 77,
+            ClientPacket::Walk { .. } => 14,
+            ClientPacket::Chat { .. } => 4,
+            ClientPacket::Command(_) => 52,
+            ClientPacket::ItemDrop { .. } => 145,
+            ClientPacket::InventorySwap { .. } => 243,
+            ClientPacket::BankDeposit { .. } => 233,
+            ClientPacket::BankWithdraw { .. } => 43,
+            ClientPacket::BankDepositAll => 129,
+            ClientPacket::BankClose => 185,
+        }
+    }
+
+    /// Encode the packet payload, padded to the opcode's fixed size
+    pub fn encode(&self) -> IncomingGamePacket {
+        let mut buffer = PacketBuffer::with_capacity(16);
+
+        match self {
+            ClientPacket::KeepAlive
+            | ClientPacket::MapLoaded
+            | ClientPacket::BankDepositAll
+            | ClientPacket::BankClose => {}
+            ClientPacket::Walk { x, y, running } => {
+                buffer.write_ushort_le(*x);
+                buffer.write_short_a(*y);
+                buffer.write_byte_s(if *running { 1 } else { 0 });
+            }
+            ClientPacket::Chat { effects, message } => {
+                buffer.write_ushort(*effects);
+                buffer.write_bytes(message.as_bytes());
+            }
+            ClientPacket::Command(command) => buffer.write_string(command),
+            ClientPacket::ItemDrop { slot, item_id } => {
+                buffer.write_ushort_le(*slot);
+                buffer.write_ushort_le(*item_id);
+            }
+            ClientPacket::InventorySwap { from_slot, to_slot } => {
+                buffer.write_ushort_le(*from_slot);
+                buffer.write_ushort_le(*to_slot);
+            }
+            ClientPacket::BankDeposit {
+                slot,
+                item_id,
+                amount,
+            } => {
+                buffer.write_ushort_le(*slot);
+                buffer.write_ushort_le(*item_id);
+                buffer.write_uint(*amount);
+            }
+            ClientPacket::BankWithdraw {
+                slot,
+                item_id,
+                amount,
+                as_note,
+            } => {
+                buffer.write_ushort_le(*slot);
+                buffer.write_ushort_le(*item_id);
+                buffer.write_uint(*amount);
+                buffer.write_ubyte(if *as_note { 1 } else { 0 });
+            }
+        }
+
+        let opcode = self.opcode();
+        let mut data = buffer.as_bytes().to_vec();
+        if let PacketSize::Fixed(size) = incoming_packets().size(opcode) {
+            data.resize(size, 0);
+        }
+
+        IncomingGamePacket::new(opcode, data)
+    }
+}
+
+/// An item entry in a bank update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BankEntry {
+    /// Bank tab
+    pub tab: u8,
+    /// Slot within the tab
+    pub slot: u16,
+    /// Item ID (0 = empty)
+    pub item_id: u16,
+    /// Item amount
+    pub amount: u32,
+    /// Whether the slot holds a placeholder
+    pub placeholder: bool,
+}
+
+/// Game packet received from the server
+#[derive(Debug, Clone)]
+pub enum ServerPacket {
+    /// Map region (the login init variant also carries map keys, which are dropped)
+    MapRegion { region_x: u16, region_y: u16 },
+    /// Reset animations
+    ResetAnimations,
+    /// Run energy
+    RunEnergy(u8),
+    /// Carried weight
+    Weight(i16),
+    /// Skill level and experience
+    SkillUpdate { skill: u8, level: u8, xp: i32 },
+    /// System message
+    SystemMessage(String),
+    /// Session resume token
+    ResumeToken(String),
+    /// Player update (bit-packed, left undecoded)
+    PlayerUpdate(Vec<u8>),
+    /// Single container slot
+    InventorySlot {
+        container: u16,
+        slot: u16,
+        item_id: u16,
+        amount: u32,
+    },
+    /// Full container contents as (slot, item_id, amount)
+    Inventory {
+        container: u16,
+        items: Vec<(u16, u16, u32)>,
+    },
+    /// Bank opened
+    BankOpen { capacity: u16 },
+    /// Single bank slot
+    BankSlot(BankEntry),
+    /// Full bank contents
+    BankContents(Vec<BankEntry>),
+    /// Ground item appeared
+    GroundItemSpawn {
+        item_id: u16,
+        amount: u32,
+        local_x: u8,
+        local_y: u8,
+        z: u8,
+    },
+    /// Ground item removed
+    GroundItemRemove {
+        item_id: u16,
+        local_x: u8,
+        local_y: u8,
+        z: u8,
+    },
+    /// Logged out by the server
+    Logout,
+    /// Any other packet
+    Other(OutgoingGamePacket),
+}
+
+impl ServerPacket {
+    /// Decode a framed server packet
+    pub fn decode(packet: OutgoingGamePacket) -> Self {
+        let mut buffer = PacketBuffer::from_bytes(&packet.data);
+
+        match packet.opcode {
+            opcodes::MAP_REGION if packet.data.len() >= 4 => ServerPacket::MapRegion {
+                region_x: buffer.read_ushort(),
+                region_y: buffer.read_ushort(),
+            },
+            opcodes::RESET_ANIMS => ServerPacket::ResetAnimations,
+            opcodes::RUN_ENERGY => ServerPacket::RunEnergy(buffer.read_ubyte()),
+            opcodes::WEIGHT => ServerPacket::Weight(buffer.read_short()),
+            opcodes::SKILL_UPDATE => ServerPacket::SkillUpdate {
+                skill: buffer.read_ubyte(),
+                level: buffer.read_ubyte(),
+                xp: buffer.read_int(),
+            },
+            opcodes::SYSTEM_MESSAGE => ServerPacket::SystemMessage(buffer.read_string()),
+            opcodes::PLAYER_UPDATE => ServerPacket::PlayerUpdate(packet.data),
+            op if op == OutgoingOpcode::ResumeToken.as_u8() => {
+                ServerPacket::ResumeToken(buffer.read_string())
+            }
+            // A slot update is 10 bytes; a full update is 4 + 8 per item
+            op if op == OutgoingOpcode::InventoryUpdate.as_u8() && packet.data.len() == 10 => {
+                ServerPacket::InventorySlot {
+                    container: buffer.read_ushort(),
+                    slot: buffer.read_ushort(),
+                    item_id: buffer.read_ushort(),
+                    amount: buffer.read_uint(),
+                }
+            }
+            op if op == OutgoingOpcode::InventoryUpdate.as_u8() => {
+                let container = buffer.read_ushort();
+                let count = buffer.read_ushort() as usize;
+                let items = (0..count.min(buffer.remaining() / 8))
+                    .map(|_| {
+                        (
+                            buffer.read_ushort(),
+                            buffer.read_ushort(),
+                            buffer.read_uint(),
+                        )
+                    })
+                    .collect();
+                ServerPacket::Inventory { container, items }
+            }
+            op if op == OutgoingOpcode::BankOpen.as_u8() => ServerPacket::BankOpen {
+                capacity: buffer.read_ushort(),
+            },
+            // A slot update is 10 bytes; a full update is 2 + 10 per item
+            op if op == OutgoingOpcode::BankUpdate.as_u8() && packet.data.len() == 10 => {
+                ServerPacket::BankSlot(read_bank_entry(&mut buffer))
+            }
+            op if op == OutgoingOpcode::BankUpdate.as_u8() => {
+                let count = buffer.read_ushort() as usize;
+                let entries = (0..count.min(buffer.remaining() / 10))
+                    .map(|_| read_bank_entry(&mut buffer))
+                    .collect();
+                ServerPacket::BankContents(entries)
+            }
+            op if op == OutgoingOpcode::GroundItemSpawn.as_u8() => ServerPacket::GroundItemSpawn {
+                item_id: buffer.read_ushort(),
+                amount: buffer.read_uint(),
+                local_x: buffer.read_ubyte(),
+                local_y: buffer.read_ubyte(),
+                z: buffer.read_ubyte(),
+            },
+            op if op == OutgoingOpcode::GroundItemRemove.as_u8() => {
+                ServerPacket::GroundItemRemove {
+                    item_id: buffer.read_ushort(),
+                    local_x: buffer.read_ubyte(),
+                    local_y: buffer.read_ubyte(),
+                    z: buffer.read_ubyte(),
+                }
+            }
+            op if op == OutgoingOpcode::Logout.as_u8() => ServerPacket::Logout,
+            _ => ServerPacket::Other(packet),
+        }
+    }
+}
+
+fn read_bank_entry(buffer: &mut PacketBuffer) -> BankEntry {
+    BankEntry {
+        tab: buffer.read_ubyte(),
+        slot: buffer.read_ushort(),
+        item_id: buffer.read_ushort(),
+        amount: buffer.read_uint(),
+        placeholder: buffer.read_ubyte() == 1,
+    }
+}
+
+/// Connection to the server
+enum ClientTransport {
+    /// Plain TCP, as used by the desktop client
+    Tcp(TcpStream),
+    /// WebSocket, as used by the browser client
+    WebSocket(Box<WebSocketStream<MaybeTlsStream<TcpStream>>>),
+}
+
+impl ClientTransport {
+    /// Read whatever arrives next into `buf`, returning the number of bytes
+    async fn read_into(&mut self, buf: &mut BytesMut) -> Result<usize> {
+        match self {
+            ClientTransport::Tcp(stream) => {
+                buf.reserve(READ_CHUNK_SIZE);
+                let n = stream.read_buf(buf).await?;
+                if n == 0 {
+                    return Err(RustscapeError::Network(NetworkError::ConnectionClosed));
+                }
+                Ok(n)
+            }
+            ClientTransport::WebSocket(stream) => loop {
+                match stream.next().await {
+                    Some(Ok(Message::Binary(data))) => {
+                        buf.extend_from_slice(&data);
+                        return Ok(data.len());
+                    }
+                    Some(Ok(Message::Close(_))) | None => {
+                        return Err(RustscapeError::Network(NetworkError::ConnectionClosed));
+                    }
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => {
+                        return Err(RustscapeError::Network(NetworkError::WebSocket(
+                            e.to_string(),
+                        )));
+                    }
+                }
+            },
+        }
+    }
+
+    /// Write data (as one binary frame on WebSocket connections)
+    async fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        match self {
+            ClientTransport::Tcp(stream) => {
+                stream.write_all(data).await?;
+                stream.flush().await?;
+                Ok(())
+            }
+            ClientTransport::WebSocket(stream) => stream
+                .send(Message::Binary(data.to_vec()))
+                .await
+                .map_err(|e| RustscapeError::Network(NetworkError::WebSocket(e.to_string()))),
+        }
+    }
+
+    /// Close the connection
+    async fn close(&mut self) -> Result<()> {
+        match self {
+            ClientTransport::Tcp(stream) => {
+                stream.shutdown().await?;
+                Ok(())
+            }
+            ClientTransport::WebSocket(stream) => stream
+                .close()
+                .await
+                .map_err(|e| RustscapeError::Network(NetworkError::WebSocket(e.to_string()))),
+        }
+    }
+}
+
+/// Headless game client
+pub struct GameClient {
+    /// Connection to the server
+    transport: ClientTransport,
+    /// Received bytes not yet consumed
+    buffer: BytesMut,
+    /// ISAAC ciphers (set after login)
+    isaac: Option<IsaacPair>,
+    /// Decoded opcode of a packet whose payload hasn't fully arrived
+    pending_opcode: Option<u8>,
+    /// Whether a bank update now would be the full contents (see `server_framing`)
+    bank_contents_next: bool,
+    /// Connection options
+    options: ClientOptions,
+    /// Total bytes received
+    bytes_read: u64,
+    /// Total bytes sent
+    bytes_written: u64,
+}
+
+impl GameClient {
+    /// Connect to the game port over TCP
+    pub async fn connect_tcp(addr: impl ToSocketAddrs, options: ClientOptions) -> Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        Ok(Self::new(ClientTransport::Tcp(stream), options))
+    }
+
+    /// Connect to the WebSocket port (`ws://` or `wss://` URL)
+    pub async fn connect_websocket(url: &str, options: ClientOptions) -> Result<Self> {
+        let (stream, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .map_err(|e| RustscapeError::Network(NetworkError::WebSocket(e.to_string())))?;
+        Ok(Self::new(
+            ClientTransport::WebSocket(Box::new(stream)),
+            options,
+        ))
+    }
+
+    fn new(transport: ClientTransport, options: ClientOptions) -> Self {
+        Self {
+            transport,
+            buffer: BytesMut::with_capacity(READ_CHUNK_SIZE),
+            isaac: None,
+            pending_opcode: None,
+            bank_contents_next: false,
+            options,
+            bytes_read: 0,
+            bytes_written: 0,
+        }
+    }
+
+    /// Check if this client is connected over WebSocket
+    pub fn is_websocket(&self) -> bool {
+        matches!(self.transport, ClientTransport::WebSocket(_))
+    }
+
+    /// Check if the client has logged in (ISAAC ciphers are set up)
+    pub fn is_logged_in(&self) -> bool {
+        self.isaac.is_some()
+    }
+
+    /// Get the total number of bytes received
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Get the total number of bytes sent
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Log in with a username and password
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<LoginSession> {
+        self.login_as(LoginType::Normal, username, password).await
+    }
+
+    /// Reattach to a player parked after a dropped connection, presenting
+    /// the resume token from the previous session
+    pub async fn resume(&mut self, username: &str, token: &str) -> Result<LoginSession> {
+        self.login_as(LoginType::Reconnect, username, token).await
+    }
+
+    /// Log into the lobby, reading the lobby response and the world list
+    ///
+    /// The connection stays in the lobby, where `request_world_list` refreshes
+    /// the list; log into the chosen world on a new connection.
+    pub async fn lobby_login(&mut self, username: &str, password: &str) -> Result<LobbySession> {
+        self.send_login(LoginType::Lobby, username, password)
+            .await?;
+
+        let response = self.read_byte().await?;
+        if response != LoginResponse::Success.as_u8() {
+            return Err(RustscapeError::Auth(AuthError::LoginRejected(response)));
+        }
+        let size = self.read_byte().await? as usize;
+        let response = LobbyResponse::decode(&self.read_exact(size).await?);
+
+        debug!(username = %username, "Logged into lobby");
+
+        let worlds = self.read_world_list().await?;
+        Ok(LobbySession { response, worlds })
+    }
+
+    /// Request the world list, either before logging in or from the lobby
+    pub async fn request_world_list(&mut self) -> Result<Vec<ListedWorld>> {
+        let mut request = PacketBuffer::with_capacity(5);
+        request.write_ubyte(HandshakeOpcode::WorldList.as_u8());
+        request.write_int(0);
+        self.write(request.as_bytes()).await?;
+
+        self.read_world_list().await
+    }
+
+    /// Read a world list response
+    async fn read_world_list(&mut self) -> Result<Vec<ListedWorld>> {
+        let status = self.read_byte().await?;
+        if status != 1 {
+            return Err(RustscapeError::Protocol(ProtocolError::MalformedPacket(
+                format!("World list status {}", status),
+            )));
+        }
+
+        let count = self.read_byte().await? as usize;
+        let mut worlds = Vec::with_capacity(count);
+        for _ in 0..count {
+            let header = self.read_exact(6).await?;
+            let name = self.read_string().await?;
+            let address = self.read_string().await?;
+            let players = self.read_exact(2).await?;
+            worlds.push(ListedWorld {
+                id: u16::from_be_bytes([header[0], header[1]]),
+                flags: i32::from_be_bytes([header[2], header[3], header[4], header[5]]),
+                name,
+                address,
+                players: u16::from_be_bytes([players[0], players[1]]),
+            });
+        }
+        Ok(worlds)
+    }
+
+    /// Perform the login handshake and read the login init sequence
+    async fn login_as(
+        &mut self,
+        login_type: LoginType,
+        username: &str,
+        password: &str,
+    ) -> Result<LoginSession> {
+        let seeds = self.send_login(login_type, username, password).await?;
+
+        let response = self.read_byte().await?;
+        if response == LoginResponse::Delay.as_u8() {
+            // Queued; the server closes the connection and expects a retry
+            let position = self.read_exact(2).await?;
+            return Err(RustscapeError::Auth(AuthError::LoginQueued(
+                u16::from_be_bytes([position[0], position[1]]),
+            )));
+        }
+        if response != LoginResponse::Success.as_u8() {
+            return Err(RustscapeError::Auth(AuthError::LoginRejected(response)));
+        }
+
+        let details = self.read_exact(5).await?;
+        let rights = details[0];
+        let flagged = details[1] == 1;
+        let player_index = u16::from_be_bytes([details[2], details[3]]);
+        let member = details[4] == 1;
+
+        debug!(
+            username = %username,
+            player_index = player_index,
+            rights = rights,
+            "Logged in"
+        );
+
+        // The init sequence is sent before ISAAC starts
+        let mut initializer = LoginInitializer::new();
+        initializer.build_init_sequence(&InitialPlayerState::new(player_index, rights, member));
+
+        let mut init_packets = Vec::with_capacity(initializer.packet_count());
+        for _ in 0..initializer.packet_count() {
+            let opcode = self.read_byte().await?;
+            let packet = match opcode {
+                // Carries the map keys, unlike the in-game region change
+                opcodes::MAP_REGION => self.read_frame(opcode, Some(Framing::Byte)).await?,
+                _ => self.read_frame(opcode, None).await?,
+            };
+            init_packets.push(ServerPacket::decode(packet));
+        }
+
+        self.isaac = Some(IsaacPair::for_client(&seeds));
+
+        Ok(LoginSession {
+            rights,
+            flagged,
+            player_index,
+            member,
+            init_packets,
+        })
+    }
+
+    /// Perform the login handshake and send the login block, returning the
+    /// ISAAC seeds
+    async fn send_login(
+        &mut self,
+        login_type: LoginType,
+        username: &str,
+        password: &str,
+    ) -> Result<[u32; 4]> {
+        // Handshake: opcode and revision, answered with a status and server key
+        let mut handshake = PacketBuffer::with_capacity(5);
+        handshake.write_ubyte(HandshakeOpcode::Login.as_u8());
+        handshake.write_uint(self.options.revision);
+        self.write(handshake.as_bytes()).await?;
+
+        let status = self.read_byte().await?;
+        if status != LoginResponse::ExchangeKeys.as_u8() {
+            return Err(RustscapeError::Auth(AuthError::LoginRejected(status)));
+        }
+        let server_key = u64::from_be_bytes(self.read_exact(8).await?.try_into().unwrap());
+
+        // Seed ISAAC the way the desktop client does
+        let seeds = [
+            rand::random::<u32>(),
+            rand::random::<u32>(),
+            (server_key >> 32) as u32,
+            server_key as u32,
+        ];
+
+        let block = self.login_block(username, password, &seeds)?;
+        let mut packet = PacketBuffer::with_capacity(block.len() + 3);
+        packet.write_ubyte(login_type.as_u8());
+        packet.write_ushort(block.len() as u16);
+        packet.write_bytes(&block);
+        self.write(packet.as_bytes()).await?;
+
+        Ok(seeds)
+    }
+
+    /// Build the login block (revision, RSA block, username and client info)
+    fn login_block(&self, username: &str, password: &str, seeds: &[u32; 4]) -> Result<Vec<u8>> {
+        let mut secure = PacketBuffer::with_capacity(32 + password.len());
+        secure.write_ubyte(RSA_BLOCK_MAGIC);
+        for seed in seeds {
+            secure.write_uint(*seed);
+        }
+        secure.write_uint(self.options.uid);
+        secure.write_string(password);
+        if let Some(code) = self.options.authenticator {
+            secure.write_uint(code);
+        }
+
+        let secure = match &self.options.rsa_key {
+            Some(key) => RsaEncryptor::new(key.public_half())
+                .encrypt(secure.as_bytes())
+                .map_err(|e| RustscapeError::Internal(format!("RSA encryption failed: {}", e)))?,
+            None => secure.as_bytes().to_vec(),
+        };
+
+        let mut block = PacketBuffer::with_capacity(secure.len() + 64);
+        block.write_uint(self.options.revision);
+        block.write_ubyte(if self.options.low_memory { 1 } else { 0 });
+        block.write_ushort(secure.len() as u16);
+        block.write_bytes(&secure);
+        block.write_string(username);
+        block.write_ubyte(self.options.display_mode);
+        block.write_ushort(self.options.screen_width);
+        block.write_ushort(self.options.screen_height);
+        block.write_uint(0);
+        block.write_string(&self.options.machine_info);
+
+        Ok(block.as_bytes().to_vec())
+    }
+
+    /// Send a typed game packet
+    pub async fn send(&mut self, packet: &ClientPacket) -> Result<()> {
+        self.send_raw(&packet.encode()).await
+    }
+
+    /// Send a game packet, framed by the incoming packet registry
+    pub async fn send_raw(&mut self, packet: &IncomingGamePacket) -> Result<()> {
+        let size = incoming_packets().size(packet.opcode);
+        let isaac = self.isaac.as_mut().ok_or_else(not_logged_in)?;
+
+        let mut buffer = PacketBuffer::with_capacity(packet.data.len() + 3);
+        buffer.write_ubyte(isaac.encode_opcode(packet.opcode));
+        match size {
+            PacketSize::Fixed(size) => {
+                if packet.data.len() != size {
+                    return Err(RustscapeError::Protocol(ProtocolError::InvalidPacketSize {
+                        expected: size,
+                        actual: packet.data.len(),
+                    }));
+                }
+            }
+            PacketSize::VariableByte if packet.data.len() <= u8::MAX as usize => {
+                buffer.write_ubyte(packet.data.len() as u8)
+            }
+            PacketSize::VariableShort if packet.data.len() <= u16::MAX as usize => {
+                buffer.write_ushort(packet.data.len() as u16)
+            }
+            PacketSize::VariableByte | PacketSize::VariableShort => {
+                return Err(RustscapeError::Protocol(ProtocolError::PacketTooLarge {
+                    size: packet.data.len(),
+                    max: if size == PacketSize::VariableByte {
+                        u8::MAX as usize
+                    } else {
+                        u16::MAX as usize
+                    },
+                }));
+            }
+            PacketSize::Unknown => {
+                return Err(RustscapeError::Protocol(ProtocolError::InvalidOpcode(
+                    packet.opcode,
+                )));
+            }
+        }
+        buffer.write_bytes(&packet.data);
+
+        trace!(
+            opcode = packet.opcode,
+            size = packet.data.len(),
+            "Sending packet"
+        );
+        self.write(buffer.as_bytes()).await
+    }
+
+    /// Receive the next game packet, decoded
+    pub async fn recv(&mut self) -> Result<ServerPacket> {
+        Ok(ServerPacket::decode(self.recv_raw().await?))
+    }
+
+    /// Receive the next game packet, framed by `server_framing`
+    ///
+    /// Cancel safe: bytes already received stay buffered, so a call dropped
+    /// part way through a packet (e.g. in `tokio::select!`) loses nothing.
+    pub async fn recv_raw(&mut self) -> Result<OutgoingGamePacket> {
+        if self.isaac.is_none() {
+            return Err(not_logged_in());
+        }
+
+        loop {
+            if self.pending_opcode.is_none() && !self.buffer.is_empty() {
+                let encoded = self.buffer.get_u8();
+                let isaac = self.isaac.as_mut().ok_or_else(not_logged_in)?;
+                self.pending_opcode = Some(isaac.decode_opcode(encoded));
+            }
+
+            if let Some(opcode) = self.pending_opcode {
+                if let Some(packet) = self.take_frame(opcode, None)? {
+                    self.pending_opcode = None;
+                    return Ok(packet);
+                }
+            }
+
+            self.read_more().await?;
+        }
+    }
+
+    /// Read a packet's length prefix (if any) and payload
+    ///
+    /// `framing` overrides `server_framing`, for the login init packets that
+    /// differ from their in-game versions.
+    async fn read_frame(
+        &mut self,
+        opcode: u8,
+        framing: Option<Framing>,
+    ) -> Result<OutgoingGamePacket> {
+        loop {
+            if let Some(packet) = self.take_frame(opcode, framing)? {
+                return Ok(packet);
+            }
+            self.read_more().await?;
+        }
+    }
+
+    /// Take a packet's length prefix and payload from the buffer, if it has
+    /// all arrived
+    fn take_frame(
+        &mut self,
+        opcode: u8,
+        framing: Option<Framing>,
+    ) -> Result<Option<OutgoingGamePacket>> {
+        let framing = match framing {
+            Some(framing) => framing,
+            None => match server_framing(opcode, &self.buffer, self.bank_contents_next)? {
+                Some(framing) => framing,
+                None => return Ok(None),
+            },
+        };
+
+        let (header, len) = match framing {
+            Framing::Fixed(size) => (0, size),
+            Framing::Byte if !self.buffer.is_empty() => (1, self.buffer[0] as usize),
+            Framing::Short if self.buffer.len() >= 2 => (
+                2,
+                u16::from_be_bytes([self.buffer[0], self.buffer[1]]) as usize,
+            ),
+            Framing::Byte | Framing::Short => return Ok(None),
+        };
+
+        if self.buffer.len() < header + len {
+            return Ok(None);
+        }
+
+        self.buffer.advance(header);
+        let data = self.buffer.split_to(len).to_vec();
+        let packet = match framing {
+            Framing::Fixed(_) => OutgoingGamePacket::fixed(opcode, data),
+            Framing::Byte => OutgoingGamePacket::variable(opcode, data),
+            Framing::Short => OutgoingGamePacket::variable_short(opcode, data),
+        };
+
+        self.bank_contents_next = opcode == OutgoingOpcode::BankTabInfo.as_u8()
+            || (opcode == OutgoingOpcode::InventoryUpdate.as_u8() && packet.variable_length);
+
+        trace!(opcode = opcode, size = len, "Received packet");
+        Ok(Some(packet))
+    }
+
+    /// Perform the JS5 handshake
+    pub async fn js5_handshake(&mut self) -> Result<()> {
+        let mut handshake = PacketBuffer::with_capacity(5);
+        handshake.write_ubyte(HandshakeOpcode::Js5.as_u8());
+        handshake.write_uint(self.options.revision);
+        self.write(handshake.as_bytes()).await?;
+
+        let status = self.read_byte().await?;
+        if status != Js5Response::Ok.as_u8() {
+            debug!(status = status, "JS5 handshake rejected");
+            return Err(RustscapeError::Protocol(ProtocolError::InvalidHandshake));
+        }
+        Ok(())
+    }
+
+    /// Request a file over JS5 and wait for the response
+    ///
+    /// Returns the container data with the block markers removed.
+    pub async fn request_file(
+        &mut self,
+        index: u8,
+        archive: u16,
+        priority: bool,
+    ) -> Result<Js5FileResponse> {
+        let mut request = PacketBuffer::with_capacity(4);
+        request.write_ubyte(if priority { 1 } else { 0 });
+        request.write_ubyte(index);
+        request.write_ushort(archive);
+        self.write(request.as_bytes()).await?;
+
+        let header = self.read_exact(JS5_HEADER_SIZE).await?;
+        let response_index = header[0];
+        let response_archive = u16::from_be_bytes([header[1], header[2]]);
+        let compression = header[3] & 0x7F;
+        let length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+
+        // Compressed containers also carry their decompressed length
+        let data_len = length as usize + if compression != 0 { 4 } else { 0 };
+
+        let mut data = Vec::with_capacity(data_len);
+        let mut block_offset = JS5_HEADER_SIZE;
+        while data.len() < data_len {
+            if block_offset == JS5_BLOCK_SIZE {
+                if self.read_byte().await? != JS5_BLOCK_MARKER {
+                    return Err(RustscapeError::Protocol(ProtocolError::MalformedPacket(
+                        "missing JS5 block marker".to_string(),
+                    )));
+                }
+                block_offset = 1;
+            }
+            let chunk = (JS5_BLOCK_SIZE - block_offset).min(data_len - data.len());
+            data.extend(self.read_exact(chunk).await?);
+            block_offset += chunk;
+        }
+
+        Ok(Js5FileResponse::new(
+            response_index,
+            response_archive,
+            compression,
+            length,
+            data,
+            priority,
+        ))
+    }
+
+    /// Close the connection
+    pub async fn close(&mut self) -> Result<()> {
+        self.transport.close().await
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.transport.write_all(data).await?;
+        self.bytes_written += data.len() as u64;
+        Ok(())
+    }
+
+    async fn read_byte(&mut self) -> Result<u8> {
+        self.fill(1).await?;
+        Ok(self.buffer.get_u8())
+    }
+
+    async fn read_exact(&mut self, n: usize) -> Result<Vec<u8>> {
+        self.fill(n).await?;
+        Ok(self.buffer.split_to(n).to_vec())
+    }
+
+    async fn read_string(&mut self) -> Result<String> {
+        let mut bytes = Vec::new();
+        loop {
+            match self.read_byte().await? {
+                0 => return Ok(String::from_utf8_lossy(&bytes).into_owned()),
+                byte => bytes.push(byte),
+            }
+        }
+    }
+
+    /// Read until at least `n` bytes are buffered
+    async fn fill(&mut self, n: usize) -> Result<()> {
+        while self.buffer.len() < n {
+            self.read_more().await?;
+        }
+        Ok(())
+    }
+
+    /// Buffer whatever arrives next
+    async fn read_more(&mut self) -> Result<()> {
+        let read = self.transport.read_into(&mut self.buffer).await?;
+        self.bytes_read += read as u64;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for GameClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GameClient")
+            .field("is_websocket", &self.is_websocket())
+            .field("logged_in", &self.is_logged_in())
+            .field("bytes_read", &self.bytes_read)
+            .field("bytes_written", &self.bytes_written)
+            .finish()
+    }
+}
+
+fn not_logged_in() -> RustscapeError {
+    RustscapeError::Protocol(ProtocolError::IsaacError(
+        "game packets require a completed login".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use num_bigint::BigUint;
+    use rsa::traits::{PrivateKeyParts, PublicKeyParts};
+    use tokio::net::TcpListener;
+    use tokio::sync::broadcast;
+
+    use crate::config::{ServerConfig, SessionConfig};
+    use crate::crypto::RsaDecryptor;
+    use crate::game::bank::BankItemInfo;
+    use crate::net::handler::ConnectionHandler;
+    use crate::protocol::game::{
+        build_bank_full_update, build_bank_slot_update, build_inventory_full_update,
+        build_inventory_slot_update, build_system_message, GamePacketHandler, CONTAINER_INVENTORY,
+    };
+    use crate::state::AppState;
+
+    /// Start a dev_mode server with a running game tick and session resume,
+    /// returning its address
+    async fn spawn_server(websocket: bool) -> (SocketAddr, broadcast::Sender<()>) {
+        let config = ServerConfig {
+            dev_mode: true,
+            tick_rate_ms: 100,
+            session: SessionConfig {
+                resume_grace_secs: 30,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (shutdown_tx, _) = broadcast::channel(1);
+        let state = Arc::new(AppState::new(config, shutdown_tx.clone()).unwrap());
+
+        let world_state = state.clone();
+        let mut shutdown_rx = shutdown_tx.subscribe();
+        tokio::spawn(async move {
+            world_state
+                .world
+                .run_with_sync(&mut shutdown_rx, None, Some(&world_state.session_manager))
+                .await;
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                let handler = ConnectionHandler::new(state.clone(), websocket);
+                tokio::spawn(async move {
+                    let _ = if websocket {
+                        handler.handle_websocket(stream, peer).await
+                    } else {
+                        handler.handle_tcp(stream, peer).await
+                    };
+                });
+            }
+        });
+
+        (addr, shutdown_tx)
+    }
+
+    /// Receive packets until one matches, giving up after a few seconds
+    async fn recv_until<T>(
+        client: &mut GameClient,
+        mut matches: impl FnMut(ServerPacket) -> Option<T>,
+    ) -> T {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(found) = matches(client.recv().await.unwrap()) {
+                    return found;
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for packet")
+    }
+
+    #[test]
+    fn test_client_packets_match_server_sizes() {
+        let packets = [
+            ClientPacket::KeepAlive,
+            ClientPacket::MapLoaded,
+            ClientPacket::Walk {
+                x: 3200,
+                y: 3201,
+                running: true,
+            },
+            ClientPacket::ItemDrop {
+                slot: 1,
+                item_id: 995,
+            },
+            ClientPacket::InventorySwap {
+                from_slot: 0,
+                to_slot: 1,
+            },
+            ClientPacket::BankDeposit {
+                slot: 0,
+                item_id: 995,
+                amount: 10,
+            },
+            ClientPacket::BankWithdraw {
+                slot: 0,
+                item_id: 995,
+                amount: 10,
+                as_note: false,
+            },
+            ClientPacket::BankDepositAll,
+            ClientPacket::BankClose,
+        ];
+
+        for packet in packets {
+            let encoded = packet.encode();
+            let size = incoming_packets().size(encoded.opcode);
+            assert_eq!(size, PacketSize::Fixed(encoded.data.len()), "{:?}", packet);
+        }
+    }
+
+    #[test]
+    fn test_client_packets_decode_on_server() {
+        let handler = GamePacketHandler::new();
+
+        let walk = ClientPacket::Walk {
+            x: 3200,
+            y: 3201,
+            running: true,
+        };
+        let result = handler.process(&walk.encode()).unwrap();
+        let movement = result.movement.unwrap();
+        assert_eq!((movement.dest_x, movement.dest_y), (3200, 3201));
+        assert!(movement.running);
+
+        let command = ClientPacket::Command("pos".to_string());
+        let result = handler.process(&command.encode()).unwrap();
+        assert_eq!(result.command.as_deref(), Some("pos"));
+    }
+
+    #[test]
+    fn test_server_packet_decode() {
+        match ServerPacket::decode(build_system_message("Hello")) {
+            ServerPacket::SystemMessage(message) => assert_eq!(message, "Hello"),
+            other => panic!("unexpected packet: {:?}", other),
+        }
+
+        match ServerPacket::decode(build_inventory_slot_update(CONTAINER_INVENTORY, 3, 995, 7)) {
+            ServerPacket::InventorySlot {
+                container,
+                slot,
+                item_id,
+                amount,
+            } => assert_eq!((container, slot, item_id, amount), (93, 3, 995, 7)),
+            other => panic!("unexpected packet: {:?}", other),
+        }
+
+        let mut bank = crate::game::bank::Bank::new();
+        bank.deposit(995, 100, None).unwrap();
+        match ServerPacket::decode(build_bank_full_update(&bank.get_all_items())) {
+            ServerPacket::BankContents(entries) => {
+                assert_eq!(entries.len(), 1);
+                assert_eq!((entries[0].item_id, entries[0].amount), (995, 100));
+            }
+            other => panic!("unexpected packet: {:?}", other),
+        }
+    }
+
+    /// Frame an encoded packet (with another packet's bytes after it) the way
+    /// the client would
+    fn frame(packet: &OutgoingGamePacket, contents_next: bool) -> Option<Framing> {
+        let mut encoded = packet.encode_raw();
+        encoded.extend([0xAB; 16]);
+        server_framing(packet.opcode, &encoded[1..], contents_next).unwrap()
+    }
+
+    fn bank_items(count: u16) -> Vec<BankItemInfo> {
+        (0..count)
+            .map(|slot| BankItemInfo {
+                tab: 0,
+                slot,
+                item_id: 995,
+                amount: 1,
+                placeholder: false,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_framing_follows_server_encoding() {
+        let items: Vec<(u16, u16, u32)> = (0..28).map(|slot| (slot, 995, 1)).collect();
+        let cases = [
+            (build_system_message("Hello"), Framing::Byte),
+            (build_system_message(&"x".repeat(300)), Framing::Short),
+            (
+                build_inventory_slot_update(CONTAINER_INVENTORY, 5, 995, 1),
+                Framing::Fixed(10),
+            ),
+            (
+                build_inventory_full_update(CONTAINER_INVENTORY, &items),
+                Framing::Byte,
+            ),
+            (
+                build_bank_slot_update(0, 5, 995, 1, false),
+                Framing::Fixed(10),
+            ),
+            (
+                build_bank_slot_update(8, 700, 995, 1, true),
+                Framing::Fixed(10),
+            ),
+            (build_bank_full_update(&bank_items(3)), Framing::Byte),
+            (build_bank_full_update(&bank_items(200)), Framing::Short),
+        ];
+
+        for (packet, framing) in cases {
+            assert_eq!(frame(&packet, false), Some(framing), "{:?}", packet);
+            assert_eq!(frame(&packet, true), Some(framing), "{:?}", packet);
+        }
+
+        assert!(server_framing(200, &[0; 16], false).is_err());
+    }
+
+    #[test]
+    fn test_framing_uses_previous_packet_for_ambiguous_bank_updates() {
+        // Tab 2, slot 0 starts with the same bytes as an empty bank
+        let slot = build_bank_slot_update(2, 0, 995, 1, false);
+        let empty = build_bank_full_update(&[]);
+        let fifty_one = build_bank_full_update(&bank_items(51));
+        assert_eq!(slot.encode_raw()[1..4], empty.encode_raw()[1..4]);
+        assert_eq!(slot.encode_raw()[1..4], fifty_one.encode_raw()[1..4]);
+
+        assert_eq!(frame(&slot, false), Some(Framing::Fixed(10)));
+        assert_eq!(frame(&empty, true), Some(Framing::Byte));
+        assert_eq!(frame(&fifty_one, true), Some(Framing::Short));
+
+        // Not enough has arrived to tell
+        assert_eq!(
+            server_framing(OutgoingOpcode::BankUpdate.as_u8(), &[2, 0, 0], true).unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_login_block_encrypted_with_public_key() {
+        let private = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 512).unwrap();
+        let key_pair = RsaKeyPair::new(
+            BigUint::from_bytes_be(&private.n().to_bytes_be()),
+            BigUint::from_bytes_be(&private.d().to_bytes_be()),
+            BigUint::from_bytes_be(&private.e().to_bytes_be()),
+        );
+
+        // Only needs a transport to exist; nothing is sent
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let options = ClientOptions {
+            rsa_key: Some(key_pair.public_half()),
+            ..Default::default()
+        };
+        let client = GameClient::connect_tcp(listener.local_addr().unwrap(), options)
+            .await
+            .unwrap();
+
+        let seeds = [1, 2, 3, 4];
+        let block = client.login_block("zezima", "hunter2", &seeds).unwrap();
+
+        let mut buffer = PacketBuffer::from_bytes(&block);
+        assert_eq!(buffer.read_uint(), crate::REVISION);
+        assert_eq!(buffer.read_ubyte(), 0);
+        let rsa_size = buffer.read_ushort() as usize;
+        let encrypted = buffer.read_bytes(rsa_size);
+        assert_eq!(buffer.read_string(), "zezima");
+
+        let decrypted = RsaDecryptor::new(key_pair)
+            .decrypt_login_block(&encrypted)
+            .unwrap();
+        let mut secure = PacketBuffer::from_bytes(&decrypted);
+        assert_eq!(secure.read_ubyte(), RSA_BLOCK_MAGIC);
+        for seed in seeds {
+            assert_eq!(secure.read_uint(), seed);
+        }
+        assert_eq!(secure.read_uint(), 0);
+        assert_eq!(secure.read_string(), "hunter2");
+    }
+
+    #[tokio::test]
+    async fn test_login_and_command_over_tcp() {
+        let (addr, shutdown_tx) = spawn_server(false).await;
+
+        let mut client = GameClient::connect_tcp(addr, ClientOptions::default())
+            .await
+            .unwrap();
+        let session = client.login("zezima", "password").await.unwrap();
+        assert!(session.player_index > 0);
+        assert!(client.is_logged_in());
+        assert!(session
+            .init_packets
+            .iter()
+            .any(|p| matches!(p, ServerPacket::MapRegion { .. })));
+
+        client
+            .send(&ClientPacket::Command("pos".to_string()))
+            .await
+            .unwrap();
+        let message = recv_until(&mut client, |packet| match packet {
+            ServerPacket::SystemMessage(message) => Some(message),
+            _ => None,
+        })
+        .await;
+        assert!(message.starts_with("Position:"), "{}", message);
+
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn test_login_over_websocket() {
+        let (addr, shutdown_tx) = spawn_server(true).await;
+
+        let mut client =
+            GameClient::connect_websocket(&format!("ws://{}", addr), ClientOptions::default())
+                .await
+                .unwrap();
+        assert!(client.is_websocket());
+        client.login("zezima", "password").await.unwrap();
+
+        // The first tick after login delivers the resume token
+        let token = recv_until(&mut client, |packet| match packet {
+            ServerPacket::ResumeToken(token) => Some(token),
+            _ => None,
+        })
+        .await;
+        assert!(!token.is_empty());
+        assert!(client.bytes_read() > 0);
+
+        let _ = shutdown_tx.send(());
+    }
+}
@@ -8,6 +8,9 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
+use crate::game::player::MAX_PLAYER_INDEX;
+use crate::net::proxy::Cidr;
+
 /// Server configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
@@ -59,10 +62,62 @@ pub struct ServerConfig {
     #[serde(default)]
     pub database: DatabaseConfig,
 
+    /// Redis configuration
+    #[serde(default)]
+    pub redis: RedisConfig,
+
     /// RSA private key configuration
     #[serde(default)]
     pub rsa: RsaConfig,
 
+    /// JS5 bandwidth configuration
+    #[serde(default)]
+    pub js5: Js5Config,
+
+    /// Inbound game packet configuration
+    #[serde(default)]
+    pub packets: PacketConfig,
+
+    /// Reverse proxy configuration
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+
+    /// TLS configuration for the WebSocket listener
+    #[serde(default)]
+    pub tls: TlsConfig,
+
+    /// WebSocket configuration
+    #[serde(default)]
+    pub websocket: WebSocketConfig,
+
+    /// Session configuration
+    #[serde(default)]
+    pub session: SessionConfig,
+
+    /// Login admission configuration
+    #[serde(default)]
+    pub login: LoginConfig,
+
+    /// Login coordinator configuration
+    #[serde(default)]
+    pub coordinator: CoordinatorConfig,
+
+    /// World list configuration
+    #[serde(default)]
+    pub world_list: WorldListConfig,
+
+    /// Two-factor authentication configuration
+    #[serde(default)]
+    pub two_factor: TwoFactorConfig,
+
+    /// Password hashing configuration
+    #[serde(default)]
+    pub password_hash: PasswordHashConfig,
+
+    /// Packet capture configuration
+    #[serde(default)]
+    pub capture: CaptureConfig,
+
     /// Development mode flag
     #[serde(default)]
     pub dev_mode: bool,
@@ -108,6 +163,40 @@ pub struct DatabaseConfig {
     pub pool_size: u32,
 }
 
+/// Redis configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedisConfig {
+    /// Redis host
+    #[serde(default = "default_redis_host")]
+    pub host: String,
+
+    /// Redis port
+    #[serde(default = "default_redis_port")]
+    pub port: u16,
+
+    /// Redis password
+    #[serde(default)]
+    pub password: String,
+
+    /// Database index
+    #[serde(default)]
+    pub database: u8,
+}
+
+impl RedisConfig {
+    /// Get the Redis connection URL
+    pub fn url(&self) -> String {
+        if self.password.is_empty() {
+            format!("redis://{}:{}/{}", self.host, self.port, self.database)
+        } else {
+            format!(
+                "redis://:{}@{}:{}/{}",
+                self.password, self.host, self.port, self.database
+            )
+        }
+    }
+}
+
 /// RSA key configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RsaConfig {
@@ -124,6 +213,296 @@ pub struct RsaConfig {
     pub public_exponent: u64,
 }
 
+/// JS5 bandwidth configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Js5Config {
+    /// Total JS5 bytes per second across all sessions (0 = unlimited)
+    #[serde(default)]
+    pub max_bytes_per_sec: u64,
+
+    /// JS5 bytes per second for a single session (0 = unlimited)
+    #[serde(default)]
+    pub session_bytes_per_sec: u64,
+
+    /// Burst size of the per-session budget in bytes (0 = one second of rate)
+    #[serde(default = "default_js5_session_burst")]
+    pub session_burst_bytes: u64,
+
+    /// Size of the shared encoded response cache in bytes (reference tables are always kept)
+    #[serde(default = "default_js5_response_cache")]
+    pub response_cache_bytes: usize,
+}
+
+/// Inbound game packet configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PacketConfig {
+    /// Maximum packets applied per player each tick
+    #[serde(default = "default_packet_tick_budget")]
+    pub tick_budget: usize,
+
+    /// Maximum packets waiting in a player's queue (extra packets are dropped)
+    #[serde(default = "default_packet_queue_capacity")]
+    pub queue_capacity: usize,
+
+    /// Maximum packets a session may send per tick (0 = unlimited)
+    #[serde(default = "default_max_packets_per_tick")]
+    pub max_packets_per_tick: u32,
+
+    /// Maximum bytes a session may send per tick (0 = unlimited)
+    #[serde(default = "default_max_bytes_per_tick")]
+    pub max_bytes_per_tick: usize,
+
+    /// Per-opcode packet caps per tick
+    #[serde(default = "default_opcode_limits")]
+    pub opcode_limits: Vec<OpcodeLimit>,
+
+    /// Ticks with limit violations before the session is disconnected (0 = never)
+    #[serde(default = "default_max_violations")]
+    pub max_violations: u32,
+
+    /// Ticks after which an earlier violation is forgotten
+    #[serde(default = "default_violation_window_ticks")]
+    pub violation_window_ticks: u64,
+}
+
+/// Packet cap for a single opcode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpcodeLimit {
+    /// Incoming packet opcode
+    pub opcode: u8,
+
+    /// Maximum packets with this opcode per tick
+    pub max_per_tick: u32,
+}
+
+/// Reverse proxy configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// Expect a PROXY protocol (v1 or v2) header on game connections from trusted proxies
+    #[serde(default)]
+    pub game_proxy_protocol: bool,
+
+    /// Expect a PROXY protocol (v1 or v2) header on WebSocket connections from trusted proxies
+    #[serde(default)]
+    pub websocket_proxy_protocol: bool,
+
+    /// Use X-Forwarded-For on WebSocket upgrades from trusted proxies
+    ///
+    /// Off by default; turn it on only when a reverse proxy in
+    /// `trusted_proxies` sets the header for every WebSocket connection.
+    #[serde(default)]
+    pub trust_forwarded_for: bool,
+
+    /// Proxies allowed to supply client addresses, in CIDR notation
+    #[serde(default = "default_trusted_proxies")]
+    pub trusted_proxies: Vec<Cidr>,
+}
+
+/// TLS configuration for the WebSocket listener
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// Serve wss:// on the WebSocket port
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Path to the PEM certificate chain
+    #[serde(default = "default_tls_cert_path")]
+    pub cert_path: PathBuf,
+
+    /// Path to the PEM (PKCS#8) private key
+    #[serde(default = "default_tls_key_path")]
+    pub key_path: PathBuf,
+
+    /// How often to check the PEM files for changes, in seconds (0 to disable)
+    #[serde(default = "default_tls_reload_interval")]
+    pub reload_interval_secs: u64,
+}
+
+/// WebSocket listener configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSocketConfig {
+    /// Accept permessage-deflate when the client offers it
+    #[serde(default = "default_true")]
+    pub compression: bool,
+
+    /// zlib compression level (0-9)
+    #[serde(default = "default_compression_level")]
+    pub compression_level: u32,
+
+    /// Messages smaller than this many bytes are sent uncompressed
+    #[serde(default = "default_compression_threshold")]
+    pub compression_threshold: usize,
+}
+
+/// Session configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionConfig {
+    /// Seconds a player stays in the world after its connection drops,
+    /// waiting for the client to resume (0 to remove players immediately)
+    ///
+    /// When enabled, every login is sent a `ResumeToken` packet, so every
+    /// client connecting must know that opcode.
+    #[serde(default = "default_resume_grace")]
+    pub resume_grace_secs: u64,
+
+    /// Maximum open connections from a single IP address
+    #[serde(default = "default_max_connections_per_ip")]
+    pub max_connections_per_ip: usize,
+}
+
+/// Login admission configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginConfig {
+    /// Logins let through to authentication per tick (0 = unlimited)
+    #[serde(default = "default_max_logins_per_tick")]
+    pub max_logins_per_tick: u32,
+
+    /// Players allowed to wait for a login slot
+    #[serde(default = "default_login_queue_capacity")]
+    pub queue_capacity: usize,
+
+    /// Ticks without a retry before a queued player loses their place
+    #[serde(default = "default_login_queue_timeout")]
+    pub queue_timeout_ticks: u64,
+
+    /// Failed logins from one IP address before it is refused (0 = unlimited)
+    #[serde(default = "default_max_failed_per_ip")]
+    pub max_failed_per_ip: u32,
+
+    /// Failed logins for one username before it is refused (0 = unlimited)
+    #[serde(default = "default_max_failed_per_username")]
+    pub max_failed_per_username: u32,
+
+    /// Ticks a failed login counts against its IP address and username
+    #[serde(default = "default_failure_window_ticks")]
+    pub failure_window_ticks: u64,
+
+    /// Seconds a game login token from the web API stays valid
+    #[serde(default = "default_game_token_ttl_secs")]
+    pub game_token_ttl_secs: u64,
+}
+
+/// Login coordinator configuration
+///
+/// Worlds ask a coordinator for a lease before letting an account in, so an
+/// account can only be logged in to one world at a time. Any server can act
+/// as the coordinator by setting `enabled`; the other worlds point `url` at
+/// its management port.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoordinatorConfig {
+    /// Serve the coordinator routes from this server
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Coordinator this world takes leases from (empty to use the local
+    /// coordinator if enabled, or to check logins on this world only)
+    ///
+    /// The secret is sent with every request: use `https://`, or keep a
+    /// plain `http://` coordinator on a private network.
+    #[serde(default)]
+    pub url: String,
+
+    /// Shared secret worlds present to the coordinator
+    #[serde(default = "default_coordinator_secret")]
+    pub secret: String,
+
+    /// Keep leases in memory instead of Redis (single coordinator only)
+    #[serde(default)]
+    pub in_memory: bool,
+
+    /// Seconds a lease survives without a heartbeat from its world
+    #[serde(default = "default_lease_ttl_secs")]
+    pub lease_ttl_secs: u64,
+
+    /// Seconds between lease heartbeats
+    #[serde(default = "default_lease_heartbeat_secs")]
+    pub heartbeat_interval_secs: u64,
+}
+
+/// World list configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorldListConfig {
+    /// Address clients use to reach this world
+    #[serde(default = "default_world_address")]
+    pub address: String,
+
+    /// Ticks between heartbeats to the worlds table
+    #[serde(default = "default_heartbeat_interval_ticks")]
+    pub heartbeat_interval_ticks: u64,
+
+    /// Seconds without a heartbeat before a world is marked offline
+    #[serde(default = "default_stale_after_secs")]
+    pub stale_after_secs: u64,
+}
+
+/// Two-factor authentication configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactorConfig {
+    /// Issuer shown by authenticator apps
+    #[serde(default = "default_two_factor_issuer")]
+    pub issuer: String,
+
+    /// AES-256 key (hex) used to encrypt stored secrets
+    #[serde(default = "default_two_factor_encryption_key")]
+    pub encryption_key: String,
+
+    /// Time steps either side of the current one a code is accepted for
+    #[serde(default = "default_two_factor_skew_steps")]
+    pub skew_steps: u64,
+
+    /// Recovery codes issued when two-factor authentication is enabled
+    #[serde(default = "default_recovery_codes")]
+    pub recovery_codes: usize,
+}
+
+/// Password hashing configuration
+///
+/// New passwords are hashed with Argon2id using these parameters. Stored
+/// hashes using bcrypt or weaker parameters are upgraded on the next
+/// successful login.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordHashConfig {
+    /// Memory cost in KiB
+    #[serde(default = "default_argon2_memory_kib")]
+    pub memory_kib: u32,
+
+    /// Number of passes over memory
+    #[serde(default = "default_argon2_iterations")]
+    pub iterations: u32,
+
+    /// Degree of parallelism
+    #[serde(default = "default_argon2_parallelism")]
+    pub parallelism: u32,
+}
+
+/// Decrypted packet capture configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureConfig {
+    /// Record decrypted game packets to capture files
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Directory capture files are written to
+    #[serde(default = "default_capture_directory")]
+    pub directory: PathBuf,
+
+    /// Only capture these players (empty to capture everyone)
+    #[serde(default)]
+    pub usernames: Vec<String>,
+}
+
+impl CaptureConfig {
+    /// Check whether a player's session should be captured
+    pub fn should_capture(&self, username: &str) -> bool {
+        self.enabled
+            && (self.usernames.is_empty()
+                || self
+                    .usernames
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(username)))
+    }
+}
+
 // Default value functions
 fn default_server_name() -> String {
     "Rustscape".to_string()
@@ -193,6 +572,179 @@ fn default_pool_size() -> u32 {
     10
 }
 
+fn default_redis_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_redis_port() -> u16 {
+    6379
+}
+
+fn default_js5_session_burst() -> u64 {
+    256 * 1024 // 256 KB
+}
+
+fn default_js5_response_cache() -> usize {
+    64 * 1024 * 1024 // 64 MB
+}
+
+fn default_packet_tick_budget() -> usize {
+    10
+}
+
+fn default_packet_queue_capacity() -> usize {
+    64
+}
+
+fn default_max_packets_per_tick() -> u32 {
+    50
+}
+
+fn default_max_bytes_per_tick() -> usize {
+    8 * 1024 // 8 KB
+}
+
+fn default_opcode_limits() -> Vec<OpcodeLimit> {
+    // Walk to position, walk here and commands
+    [(14, 2), (98, 2), (52, 2)]
+        .into_iter()
+        .map(|(opcode, max_per_tick)| OpcodeLimit {
+            opcode,
+            max_per_tick,
+        })
+        .collect()
+}
+
+fn default_max_violations() -> u32 {
+    5
+}
+
+fn default_violation_window_ticks() -> u64 {
+    100 // 1 minute at 600ms ticks
+}
+
+fn default_trusted_proxies() -> Vec<Cidr> {
+    ["127.0.0.1/32", "::1/128"]
+        .iter()
+        .map(|cidr| cidr.parse().expect("valid default CIDR"))
+        .collect()
+}
+
+fn default_tls_cert_path() -> PathBuf {
+    PathBuf::from("config/ssl/server.crt")
+}
+
+fn default_tls_key_path() -> PathBuf {
+    PathBuf::from("config/ssl/server.key")
+}
+
+fn default_tls_reload_interval() -> u64 {
+    300 // 5 minutes
+}
+
+fn default_compression_level() -> u32 {
+    6
+}
+
+fn default_compression_threshold() -> usize {
+    128
+}
+
+fn default_resume_grace() -> u64 {
+    0
+}
+
+fn default_max_connections_per_ip() -> usize {
+    10
+}
+
+fn default_max_logins_per_tick() -> u32 {
+    10
+}
+
+fn default_login_queue_capacity() -> usize {
+    500
+}
+
+fn default_login_queue_timeout() -> u64 {
+    25 // 15 seconds at 600ms ticks
+}
+
+fn default_max_failed_per_ip() -> u32 {
+    20
+}
+
+fn default_max_failed_per_username() -> u32 {
+    5
+}
+
+fn default_failure_window_ticks() -> u64 {
+    500 // 5 minutes at 600ms ticks
+}
+
+fn default_game_token_ttl_secs() -> u64 {
+    30
+}
+
+// Default coordinator secret (DEVELOPMENT ONLY - replace in production!)
+fn default_coordinator_secret() -> String {
+    "rustscape_dev_coordinator_secret_change_in_production".to_string()
+}
+
+fn default_lease_ttl_secs() -> u64 {
+    30
+}
+
+fn default_lease_heartbeat_secs() -> u64 {
+    10
+}
+
+fn default_world_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_heartbeat_interval_ticks() -> u64 {
+    10 // 6 seconds at 600ms ticks
+}
+
+fn default_stale_after_secs() -> u64 {
+    30
+}
+
+fn default_two_factor_issuer() -> String {
+    "Rustscape".to_string()
+}
+
+// Default 2FA encryption key (DEVELOPMENT ONLY - replace in production!)
+fn default_two_factor_encryption_key() -> String {
+    "8f3a1c5e7b9d2f4a6c8e0b1d3f5a7c9e2b4d6f8a0c1e3b5d7f9a2c4e6b8d0f1a".to_string()
+}
+
+fn default_two_factor_skew_steps() -> u64 {
+    1
+}
+
+fn default_recovery_codes() -> usize {
+    10
+}
+
+// Argon2id parameters recommended by OWASP (19 MiB, 2 passes, 1 lane)
+fn default_argon2_memory_kib() -> u32 {
+    19 * 1024
+}
+
+fn default_argon2_iterations() -> u32 {
+    2
+}
+
+fn default_argon2_parallelism() -> u32 {
+    1
+}
+
+fn default_capture_directory() -> PathBuf {
+    PathBuf::from("data/captures")
+}
+
 // Default RSA keys (DEVELOPMENT ONLY - replace in production!)
 fn default_rsa_modulus() -> String {
     // 1024-bit RSA modulus for development
@@ -221,6 +773,17 @@ fn default() -> Self {
     }
 }
 
+impl Default for RedisConfig {
+    fn default() -> Self {
+        Self {
+            host: default_redis_host(),
+            port: default_redis_port(),
+            password: String::new(),
+            database: 0,
+        }
+    }
+}
+
 impl Default for RsaConfig {
     fn default() -> Self {
         Self {
@@ -231,6 +794,140 @@ fn default() -> Self {
     }
 }
 
+impl Default for Js5Config {
+    fn default() -> Self {
+        Self {
+            max_bytes_per_sec: 0,
+            session_bytes_per_sec: 0,
+            session_burst_bytes: default_js5_session_burst(),
+            response_cache_bytes: default_js5_response_cache(),
+        }
+    }
+}
+
+impl Default for PacketConfig {
+    fn default() -> Self {
+        Self {
+            tick_budget: default_packet_tick_budget(),
+            queue_capacity: default_packet_queue_capacity(),
+            max_packets_per_tick: default_max_packets_per_tick(),
+            max_bytes_per_tick: default_max_bytes_per_tick(),
+            opcode_limits: default_opcode_limits(),
+            max_violations: default_max_violations(),
+            violation_window_ticks: default_violation_window_ticks(),
+        }
+    }
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            game_proxy_protocol: false,
+            websocket_proxy_protocol: false,
+            trust_forwarded_for: false,
+            trusted_proxies: default_trusted_proxies(),
+        }
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert_path: default_tls_cert_path(),
+            key_path: default_tls_key_path(),
+            reload_interval_secs: default_tls_reload_interval(),
+        }
+    }
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            compression: true,
+            compression_level: default_compression_level(),
+            compression_threshold: default_compression_threshold(),
+        }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            resume_grace_secs: default_resume_grace(),
+            max_connections_per_ip: default_max_connections_per_ip(),
+        }
+    }
+}
+
+impl Default for LoginConfig {
+    fn default() -> Self {
+        Self {
+            max_logins_per_tick: default_max_logins_per_tick(),
+            queue_capacity: default_login_queue_capacity(),
+            queue_timeout_ticks: default_login_queue_timeout(),
+            max_failed_per_ip: default_max_failed_per_ip(),
+            max_failed_per_username: default_max_failed_per_username(),
+            failure_window_ticks: default_failure_window_ticks(),
+            game_token_ttl_secs: default_game_token_ttl_secs(),
+        }
+    }
+}
+
+impl Default for CoordinatorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            secret: default_coordinator_secret(),
+            in_memory: false,
+            lease_ttl_secs: default_lease_ttl_secs(),
+            heartbeat_interval_secs: default_lease_heartbeat_secs(),
+        }
+    }
+}
+
+impl Default for WorldListConfig {
+    fn default() -> Self {
+        Self {
+            address: default_world_address(),
+            heartbeat_interval_ticks: default_heartbeat_interval_ticks(),
+            stale_after_secs: default_stale_after_secs(),
+        }
+    }
+}
+
+impl Default for TwoFactorConfig {
+    fn default() -> Self {
+        Self {
+            issuer: default_two_factor_issuer(),
+            encryption_key: default_two_factor_encryption_key(),
+            skew_steps: default_two_factor_skew_steps(),
+            recovery_codes: default_recovery_codes(),
+        }
+    }
+}
+
+impl Default for PasswordHashConfig {
+    fn default() -> Self {
+        Self {
+            memory_kib: default_argon2_memory_kib(),
+            iterations: default_argon2_iterations(),
+            parallelism: default_argon2_parallelism(),
+        }
+    }
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: default_capture_directory(),
+            usernames: Vec::new(),
+        }
+    }
+}
+
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
@@ -246,7 +943,20 @@ fn default() -> Self {
             tick_rate_ms: default_tick_rate(),
             autosave_interval_secs: default_autosave_interval(),
             database: DatabaseConfig::default(),
+            redis: RedisConfig::default(),
             rsa: RsaConfig::default(),
+            js5: Js5Config::default(),
+            packets: PacketConfig::default(),
+            proxy: ProxyConfig::default(),
+            tls: TlsConfig::default(),
+            websocket: WebSocketConfig::default(),
+            session: SessionConfig::default(),
+            login: LoginConfig::default(),
+            coordinator: CoordinatorConfig::default(),
+            world_list: WorldListConfig::default(),
+            two_factor: TwoFactorConfig::default(),
+            password_hash: PasswordHashConfig::default(),
+            capture: CaptureConfig::default(),
             dev_mode: false,
             debug: false,
             watchdog_enabled: default_true(),
@@ -335,6 +1045,21 @@ fn apply_env_overrides(&mut self) {
         if let Ok(val) = env::var("RUSTSCAPE_DEBUG") {
             self.debug = val.to_lowercase() == "true" || val == "1";
         }
+        if let Ok(val) = env::var("RUSTSCAPE_TLS_ENABLED") {
+            self.tls.enabled = val.to_lowercase() == "true" || val == "1";
+        }
+        if let Ok(val) = env::var("RUSTSCAPE_TLS_CERT_PATH") {
+            self.tls.cert_path = PathBuf::from(val);
+        }
+        if let Ok(val) = env::var("RUSTSCAPE_TLS_KEY_PATH") {
+            self.tls.key_path = PathBuf::from(val);
+        }
+        if let Ok(val) = env::var("RUSTSCAPE_WORLD_ADDRESS") {
+            self.world_list.address = val;
+        }
+        if let Ok(val) = env::var("RUSTSCAPE_WEBSOCKET_COMPRESSION") {
+            self.websocket.compression = val.to_lowercase() == "true" || val == "1";
+        }
 
         // Database overrides (RUSTSCAPE_DATABASE_* takes precedence over MYSQL_*)
         if let Ok(val) = env::var("MYSQL_HOST") {
@@ -372,6 +1097,19 @@ fn apply_env_overrides(&mut self) {
             self.database.password = val;
         }
 
+        // Redis overrides
+        if let Ok(val) = env::var("RUSTSCAPE_REDIS_HOST") {
+            self.redis.host = val;
+        }
+        if let Ok(val) = env::var("RUSTSCAPE_REDIS_PORT") {
+            if let Ok(port) = val.parse() {
+                self.redis.port = port;
+            }
+        }
+        if let Ok(val) = env::var("RUSTSCAPE_REDIS_PASSWORD") {
+            self.redis.password = val;
+        }
+
         // RSA overrides (from secure environment)
         if let Ok(val) = env::var("RUSTSCAPE_RSA_MODULUS") {
             self.rsa.modulus = val;
@@ -379,6 +1117,34 @@ fn apply_env_overrides(&mut self) {
         if let Ok(val) = env::var("RUSTSCAPE_RSA_PRIVATE_EXPONENT") {
             self.rsa.private_exponent = val;
         }
+
+        // 2FA secret encryption key (from secure environment)
+        if let Ok(val) = env::var("RUSTSCAPE_2FA_ENCRYPTION_KEY") {
+            self.two_factor.encryption_key = val;
+        }
+
+        // Login coordinator overrides
+        if let Ok(val) = env::var("RUSTSCAPE_COORDINATOR_ENABLED") {
+            self.coordinator.enabled = val.to_lowercase() == "true" || val == "1";
+        }
+        if let Ok(val) = env::var("RUSTSCAPE_COORDINATOR_URL") {
+            self.coordinator.url = val;
+        }
+        if let Ok(val) = env::var("RUSTSCAPE_COORDINATOR_SECRET") {
+            self.coordinator.secret = val;
+        }
+
+        // JS5 bandwidth overrides
+        if let Ok(val) = env::var("RUSTSCAPE_JS5_MAX_BYTES_PER_SEC") {
+            if let Ok(rate) = val.parse() {
+                self.js5.max_bytes_per_sec = rate;
+            }
+        }
+        if let Ok(val) = env::var("RUSTSCAPE_JS5_SESSION_BYTES_PER_SEC") {
+            if let Ok(rate) = val.parse() {
+                self.js5.session_bytes_per_sec = rate;
+            }
+        }
     }
 
     /// Validate the configuration
@@ -406,9 +1172,69 @@ fn validate(&self) -> Result<()> {
             anyhow::bail!("Tick rate must be between 100ms and 5000ms");
         }
 
+        if self.websocket.compression_level > 9 {
+            anyhow::bail!("WebSocket compression level must be between 0 and 9");
+        }
+
+        // A live world must never look stale between its own heartbeats
+        if self.world_list.heartbeat_interval_ticks == 0 {
+            anyhow::bail!("World list heartbeat interval must be at least 1 tick");
+        }
+        if self.world_list.stale_after_secs * 1000
+            <= self.world_list.heartbeat_interval_ticks * self.tick_rate_ms
+        {
+            anyhow::bail!("World list stale_after_secs must be longer than the heartbeat interval");
+        }
+        if self.world_list.address.parse::<std::net::IpAddr>().is_err() {
+            anyhow::bail!("World list address must be an IP address");
+        }
+
+        if self.login.game_token_ttl_secs == 0 {
+            anyhow::bail!("Game token TTL must be at least 1 second");
+        }
+
+        // Leases must be renewed before they expire
+        let coordinator = &self.coordinator;
+        if coordinator.heartbeat_interval_secs == 0
+            || coordinator.heartbeat_interval_secs >= coordinator.lease_ttl_secs
+        {
+            anyhow::bail!("Lease heartbeat interval must be shorter than the lease TTL");
+        }
+        if !coordinator.url.is_empty()
+            && !coordinator.url.starts_with("http://")
+            && !coordinator.url.starts_with("https://")
+        {
+            anyhow::bail!("Coordinator URL must start with http:// or https://");
+        }
+
+        // Stored secrets are encrypted with AES-256
+        let key = &self.two_factor.encryption_key;
+        if key.len() != 64 || !key.bytes().all(|b| b.is_ascii_hexdigit()) {
+            anyhow::bail!("2FA encryption key must be 64 hex characters (32 bytes)");
+        }
+
+        let hash = &self.password_hash;
+        if let Err(e) =
+            argon2::Params::new(hash.memory_kib, hash.iterations, hash.parallelism, None)
+        {
+            anyhow::bail!("Invalid Argon2 parameters: {}", e);
+        }
+
+        // Every player must be able to make progress each tick
+        if self.packets.tick_budget == 0 {
+            anyhow::bail!("Packet tick budget must be at least 1");
+        }
+
         Ok(())
     }
 
+    /// Get the number of players the world can hold
+    ///
+    /// `max_players`, limited to the player indices the protocol can address.
+    pub fn player_capacity(&self) -> u16 {
+        self.max_players.min(MAX_PLAYER_INDEX as u32) as u16
+    }
+
     /// Get the actual game port (base port + world ID)
     pub fn actual_game_port(&self) -> u16 {
         self.game_port + self.world_id as u16
@@ -467,5 +1293,75 @@ fn test_validation() {
         // Duplicate ports
         config.websocket_port = config.game_port;
         assert!(config.validate().is_err());
+        config.websocket_port = default_websocket_port();
+
+        // Heartbeats too far apart for the stale timeout
+        config.world_list.stale_after_secs = 6;
+        assert!(config.validate().is_err());
+        config.world_list.stale_after_secs = default_stale_after_secs();
+
+        // Leases that expire before their heartbeat renews them
+        config.coordinator.heartbeat_interval_secs = config.coordinator.lease_ttl_secs;
+        assert!(config.validate().is_err());
+        config.coordinator.heartbeat_interval_secs = default_lease_heartbeat_secs();
+
+        config.coordinator.url = "https://10.0.0.1:5555".to_string();
+        assert!(config.validate().is_ok());
+        config.coordinator.url = "ftp://10.0.0.1:5555".to_string();
+        assert!(config.validate().is_err());
+        config.coordinator.url = String::new();
+
+        // 2FA key that is not 32 bytes of hex
+        config.two_factor.encryption_key = "not-a-key".to_string();
+        assert!(config.validate().is_err());
+        config.two_factor.encryption_key = default_two_factor_encryption_key();
+
+        // Argon2 needs at least 8 KiB per lane
+        config.password_hash.memory_kib = 4;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_redis_url() {
+        let mut redis = RedisConfig::default();
+        assert_eq!(redis.url(), "redis://localhost:6379/0");
+
+        redis.password = "secret".to_string();
+        redis.database = 2;
+        assert_eq!(redis.url(), "redis://:secret@localhost:6379/2");
+    }
+
+    #[test]
+    fn test_player_capacity_limited_to_indices() {
+        let mut config = ServerConfig::default();
+        assert_eq!(config.player_capacity(), 2000);
+
+        config.max_players = 5000;
+        assert_eq!(config.player_capacity(), MAX_PLAYER_INDEX);
+    }
+
+    #[test]
+    fn test_packet_opcode_limits_from_toml() {
+        let config: ServerConfig = toml::from_str(
+            r#"
+            [packets]
+            max_packets_per_tick = 20
+
+            [[packets.opcode_limits]]
+            opcode = 98
+            max_per_tick = 1
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.packets.max_packets_per_tick, 20);
+        assert_eq!(config.packets.max_bytes_per_tick, 8 * 1024);
+        assert_eq!(
+            config.packets.opcode_limits,
+            vec![OpcodeLimit {
+                opcode: 98,
+                max_per_tick: 1,
+            }]
+        );
     }
 }
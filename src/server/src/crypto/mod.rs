@@ -3,10 +3,12 @@
 //! This module provides cryptographic primitives used by the Rustscape server:
 //! - ISAAC cipher for packet opcode encryption
 //! - RSA for secure key exchange during login
+//! - TOTP codes for two-factor authentication
 
 pub mod isaac;
 pub mod rsa;
+pub mod totp;
 
 // Re-export commonly used types
 pub use isaac::IsaacPair;
-pub use rsa::RsaDecryptor;
+pub use rsa::{RsaDecryptor, RsaEncryptor, RsaKeyPair};
@@ -40,6 +40,30 @@ pub fn from_hex(modulus: &str, private_exponent: &str, public_exponent: u64) ->
         })
     }
 
+    /// Create a public-only key pair from a hex modulus
+    ///
+    /// The private exponent is left at zero, so the result can encrypt (as a
+    /// client does) but not decrypt.
+    pub fn from_public_hex(modulus: &str, public_exponent: u64) -> Result<Self> {
+        let modulus =
+            BigUint::parse_bytes(modulus.as_bytes(), 16).context("Failed to parse RSA modulus")?;
+
+        Ok(Self {
+            modulus,
+            private_exponent: BigUint::default(),
+            public_exponent: BigUint::from(public_exponent),
+        })
+    }
+
+    /// Get the public half of this key pair (modulus and public exponent)
+    pub fn public_half(&self) -> Self {
+        Self {
+            modulus: self.modulus.clone(),
+            private_exponent: BigUint::default(),
+            public_exponent: self.public_exponent.clone(),
+        }
+    }
+
     /// Create a key pair from raw BigUint values
     pub fn new(modulus: BigUint, private_exponent: BigUint, public_exponent: BigUint) -> Self {
         Self {
@@ -236,6 +260,18 @@ fn create_test_key_pair() -> RsaKeyPair {
         RsaKeyPair::new(modulus, private_exponent, public_exponent)
     }
 
+    #[test]
+    fn test_public_half_encrypts_for_private_key() {
+        let key_pair = create_test_key_pair();
+        let public = key_pair.public_half();
+        assert_eq!(public.private_exponent, BigUint::default());
+
+        let encryptor = RsaEncryptor::new(public);
+        let decryptor = RsaDecryptor::new(key_pair);
+        let ciphertext = encryptor.encrypt(&[10]).unwrap();
+        assert_eq!(decryptor.decrypt(&ciphertext).unwrap(), vec![10]);
+    }
+
     #[test]
     fn test_rsa_encrypt_decrypt() {
         let key_pair = create_test_key_pair();
@@ -2,9 +2,11 @@
 //!
 //! Defines custom error types for the Rustscape server.
 
+use std::fmt::Display;
 use std::io;
 
 use thiserror::Error;
+use tracing::warn;
 
 /// Main error type for the Rustscape server
 #[derive(Error, Debug)]
@@ -78,6 +80,11 @@ pub enum NetworkError {
 
     #[error("Write error: {0}")]
     WriteError(String),
+
+    #[error("Packet flood from session {0}")]
+    PacketFlood(u64),
+    #[error("TLS error: {0}")]
+    Tls(String),
 }
 
 /// Protocol-specific errors
@@ -115,6 +122,9 @@ pub enum ProtocolError {
 
     #[error("Invalid RSA block")]
     InvalidRsaBlock,
+
+    #[error("Invalid PROXY protocol header: {0}")]
+    InvalidProxyHeader(String),
 }
 
 /// Cache-specific errors
@@ -152,7 +162,7 @@ pub enum CacheError {
 }
 
 /// Authentication-specific errors
-#[derive(Error, Debug, Clone)]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
 pub enum AuthError {
     #[error("Invalid credentials")]
     InvalidCredentials,
@@ -198,6 +208,32 @@ pub enum AuthError {
 
     #[error("Registration failed: {0}")]
     RegistrationFailed(String),
+
+    #[error("Login rejected with response code {0}")]
+    LoginRejected(u8),
+
+    #[error("Login queued at position {0}")]
+    LoginQueued(u16),
+
+    #[error("Two-factor code required")]
+    TwoFactorRequired,
+
+    #[error("Invalid two-factor code")]
+    InvalidTwoFactorCode,
+
+    #[error("Two-factor authentication already enabled")]
+    TwoFactorAlreadyEnabled,
+
+    #[error("Two-factor authentication not enabled")]
+    TwoFactorNotEnabled,
+}
+
+/// Logins can't go ahead while a backend they depend on (Redis, the login
+/// coordinator) is unreachable, so log why and report the login server as
+/// offline
+pub fn login_backend_offline(context: &str, e: impl Display) -> RustscapeError {
+    warn!(error = %e, "{}", context);
+    RustscapeError::Auth(AuthError::LoginServerOffline)
 }
 
 /// Game logic errors
@@ -301,6 +337,10 @@ pub enum LoginResponse {
     InvalidLoginServer = 20,
     /// Profile transfer
     ProfileTransfer = 21,
+    /// Authenticator code required
+    AuthenticatorRequired = 56,
+    /// Authenticator code incorrect
+    InvalidAuthenticator = 57,
 }
 
 impl LoginResponse {
@@ -323,6 +363,9 @@ fn from(err: AuthError) -> Self {
             AuthError::InvalidSessionId => LoginResponse::BadSessionId,
             AuthError::TooManyAttempts => LoginResponse::TooManyIncorrectLogins,
             AuthError::IpBanned => LoginResponse::AccountLocked,
+            AuthError::LoginQueued(_) => LoginResponse::Delay,
+            AuthError::TwoFactorRequired => LoginResponse::AuthenticatorRequired,
+            AuthError::InvalidTwoFactorCode => LoginResponse::InvalidAuthenticator,
             _ => LoginResponse::CouldNotCompleteLogin,
         }
     }
@@ -366,6 +409,15 @@ fn test_auth_error_to_login_response() {
 
         let response: LoginResponse = AuthError::WorldFull.into();
         assert_eq!(response, LoginResponse::WorldFull);
+
+        let response: LoginResponse = AuthError::LoginQueued(3).into();
+        assert_eq!(response, LoginResponse::Delay);
+
+        let err = login_backend_offline("Redis error", "connection refused");
+        assert!(matches!(
+            err,
+            RustscapeError::Auth(AuthError::LoginServerOffline)
+        ));
     }
 
     #[test]
@@ -502,7 +502,7 @@ pub fn from_persistence_format(items: &[Option<(i32, i32)>]) -> Self {
 }
 
 /// Bank item info for client communication
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BankItemInfo {
     pub tab: u8,
     pub slot: u16,
@@ -6,14 +6,22 @@
 //! - Entity systems (NPCs, objects, ground items)
 //! - Region/map management
 //! - Combat and skills (future)
+//! - Tile-by-tile walking and run energy
+//! - Pathfinding over map collision flags
 //! - Player synchronization (multiplayer updates)
+//! - Tick-synchronised inbound packet queues
+//! - World list, shared between servers through the database
 
 pub mod bank;
 pub mod equipment;
 pub mod ground_item;
 pub mod inventory;
 pub mod item;
+pub mod movement;
+pub mod packet_queue;
+pub mod pathfinding;
 pub mod persistence;
 pub mod player;
 pub mod sync;
 pub mod world;
+pub mod world_list;
@@ -0,0 +1,336 @@
+//! Player movement
+//!
+//! Walk packets carry the tiles where the client's path turns. They are
+//! expanded into a queue of single-tile steps which the world takes once per
+//! tick: one step walking, two running. The directions stepped are handed to
+//! the player sync so other clients see the player walk rather than jump.
+//!
+//! Running costs run energy for every tick spent running; energy comes back
+//! slowly while the player stands still.
+
+use std::collections::VecDeque;
+
+use crate::game::player::{Location, Player};
+use crate::game::sync::player_sync::MovementType;
+
+/// Most steps a single walk request can queue
+pub const MAX_QUEUED_STEPS: usize = 100;
+
+/// Run energy lost for each tick spent running
+pub const RUN_ENERGY_DRAIN: u8 = 1;
+
+/// Idle ticks per point of run energy restored
+pub const RUN_ENERGY_RESTORE_TICKS: u32 = 3;
+
+/// Maximum run energy
+pub const MAX_RUN_ENERGY: u8 = 100;
+
+/// Direction of a single-tile step
+///
+/// RS2 directions: 0 = NW, 1 = N, 2 = NE, 3 = W, 4 = E, 5 = SW, 6 = S,
+/// 7 = SE. Returns `None` unless the step is to an adjacent tile.
+pub fn direction(dx: i32, dy: i32) -> Option<u8> {
+    match (dx, dy) {
+        (-1, 1) => Some(0),
+        (0, 1) => Some(1),
+        (1, 1) => Some(2),
+        (-1, 0) => Some(3),
+        (1, 0) => Some(4),
+        (-1, -1) => Some(5),
+        (0, -1) => Some(6),
+        (1, -1) => Some(7),
+        _ => None,
+    }
+}
+
+/// A player's queued steps
+#[derive(Debug, Clone, Default)]
+pub struct WalkingQueue {
+    /// Tiles still to walk, in order
+    steps: VecDeque<Location>,
+    /// Ticks the player has stood still, for energy restore
+    idle_ticks: u32,
+}
+
+impl WalkingQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the queue with a path through the given checkpoints
+    ///
+    /// See `expand_path` for how the checkpoints become steps.
+    pub fn set_path(&mut self, start: Location, checkpoints: &[(u16, u16)]) {
+        self.steps = expand_path(start, checkpoints).into();
+    }
+
+    /// Drop any remaining steps
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+
+    /// Check whether there is nowhere left to walk
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Number of steps left
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Take the next step from a tile
+    ///
+    /// Returns the tile stepped to and its direction. A step that isn't to an
+    /// adjacent tile on the same plane means the player was moved some other
+    /// way, so the rest of the queue is dropped.
+    pub fn next_step(&mut self, from: Location) -> Option<(Location, u8)> {
+        let next = self.steps.pop_front()?;
+        let dir = direction(next.x as i32 - from.x as i32, next.y as i32 - from.y as i32)
+            .filter(|_| next.z == from.z);
+
+        match dir {
+            Some(dir) => Some((next, dir)),
+            None => {
+                self.steps.clear();
+                None
+            }
+        }
+    }
+}
+
+/// Expand a path through checkpoints into single-tile steps
+///
+/// Each leg moves diagonally until it lines up with the checkpoint and then
+/// straight. The path is cut short after `MAX_QUEUED_STEPS` steps.
+pub fn expand_path(start: Location, checkpoints: &[(u16, u16)]) -> Vec<Location> {
+    let mut steps = Vec::new();
+
+    let (mut x, mut y) = (start.x, start.y);
+    for &(target_x, target_y) in checkpoints {
+        while (x, y) != (target_x, target_y) {
+            if steps.len() >= MAX_QUEUED_STEPS {
+                return steps;
+            }
+            x = step_toward(x, target_x);
+            y = step_toward(y, target_y);
+            steps.push(Location::new(x, y, start.z));
+        }
+    }
+    steps
+}
+
+/// Move one step along a 16-bit coordinate toward a target
+fn step_toward(from: u16, to: u16) -> u16 {
+    match from.cmp(&to) {
+        std::cmp::Ordering::Less => from + 1,
+        std::cmp::Ordering::Greater => from - 1,
+        std::cmp::Ordering::Equal => from,
+    }
+}
+
+/// What a player's movement did this tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickMovement {
+    /// Steps taken, for the player sync
+    pub movement: MovementType,
+    /// New run energy, if it changed
+    pub run_energy: Option<u8>,
+}
+
+/// Take a player's steps for this tick
+///
+/// Walks one step, or two while running with energy left. Running drains
+/// `RUN_ENERGY_DRAIN` per tick and switches run off when energy runs out;
+/// standing still restores a point every `RUN_ENERGY_RESTORE_TICKS` ticks.
+pub fn process(player: &Player) -> TickMovement {
+    let mut queue = player.walking_queue.write();
+    let start = player.location();
+
+    let Some((first, walk_dir)) = queue.next_step(start) else {
+        return TickMovement {
+            movement: MovementType::None,
+            run_energy: restore_energy(player, &mut queue),
+        };
+    };
+    queue.idle_ticks = 0;
+
+    let energy = *player.run_energy.read();
+    let running = *player.running.read() && energy > 0;
+    let second = if running {
+        queue.next_step(first)
+    } else {
+        None
+    };
+
+    let (movement, run_energy, end) = match second {
+        Some((second, run_dir)) => {
+            let drained = energy.saturating_sub(RUN_ENERGY_DRAIN);
+            *player.run_energy.write() = drained;
+            if drained == 0 {
+                *player.running.write() = false;
+            }
+            (MovementType::Run(walk_dir, run_dir), Some(drained), second)
+        }
+        None => (MovementType::Walk(walk_dir), None, first),
+    };
+
+    player.set_location(end);
+
+    TickMovement {
+        movement,
+        run_energy,
+    }
+}
+
+/// Count an idle tick and restore energy when one is due
+fn restore_energy(player: &Player, queue: &mut WalkingQueue) -> Option<u8> {
+    queue.idle_ticks = queue.idle_ticks.saturating_add(1);
+    if !queue.idle_ticks.is_multiple_of(RUN_ENERGY_RESTORE_TICKS) {
+        return None;
+    }
+
+    let mut energy = player.run_energy.write();
+    if *energy >= MAX_RUN_ENERGY {
+        return None;
+    }
+    *energy += 1;
+    Some(*energy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player_at(x: u16, y: u16) -> Player {
+        let player = Player::new(1, 1, "walker".to_string());
+        player.teleport(Location::new(x, y, 0));
+        player
+    }
+
+    fn walk(player: &Player, checkpoints: &[(u16, u16)], running: bool) {
+        *player.running.write() = running;
+        player
+            .walking_queue
+            .write()
+            .set_path(player.location(), checkpoints);
+    }
+
+    #[test]
+    fn test_direction() {
+        assert_eq!(direction(-1, 1), Some(0));
+        assert_eq!(direction(0, 1), Some(1));
+        assert_eq!(direction(1, 0), Some(4));
+        assert_eq!(direction(1, -1), Some(7));
+        assert_eq!(direction(0, 0), None);
+        assert_eq!(direction(2, 0), None);
+    }
+
+    #[test]
+    fn test_path_is_expanded_to_single_steps() {
+        let mut queue = WalkingQueue::new();
+        let start = Location::new(3200, 3200, 0);
+        queue.set_path(start, &[(3203, 3201), (3203, 3199)]);
+
+        // Diagonal first, then straight, then down to the second checkpoint
+        let tiles: Vec<(u16, u16)> = queue.steps.iter().map(|l| (l.x, l.y)).collect();
+        assert_eq!(
+            tiles,
+            vec![
+                (3201, 3201),
+                (3202, 3201),
+                (3203, 3201),
+                (3203, 3200),
+                (3203, 3199)
+            ]
+        );
+
+        let (tile, dir) = queue.next_step(start).unwrap();
+        assert_eq!((tile.x, tile.y, dir), (3201, 3201, 2));
+        assert_eq!(queue.len(), 4);
+    }
+
+    #[test]
+    fn test_path_length_is_capped() {
+        let mut queue = WalkingQueue::new();
+        queue.set_path(Location::new(3200, 3200, 0), &[(3400, 3200)]);
+        assert_eq!(queue.len(), MAX_QUEUED_STEPS);
+    }
+
+    #[test]
+    fn test_step_from_elsewhere_clears_queue() {
+        let mut queue = WalkingQueue::new();
+        queue.set_path(Location::new(3200, 3200, 0), &[(3205, 3200)]);
+
+        assert_eq!(queue.next_step(Location::new(3000, 3000, 0)), None);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_walking_takes_one_step_per_tick() {
+        let player = player_at(3200, 3200);
+        walk(&player, &[(3202, 3200)], false);
+
+        let tick = process(&player);
+        assert_eq!(tick.movement, MovementType::Walk(4));
+        assert_eq!(tick.run_energy, None);
+        assert_eq!(player.location(), Location::new(3201, 3200, 0));
+
+        assert_eq!(process(&player).movement, MovementType::Walk(4));
+        assert_eq!(process(&player).movement, MovementType::None);
+        assert_eq!(player.location(), Location::new(3202, 3200, 0));
+        assert_eq!(*player.run_energy.read(), MAX_RUN_ENERGY);
+    }
+
+    #[test]
+    fn test_running_takes_two_steps_and_drains_energy() {
+        let player = player_at(3200, 3200);
+        walk(&player, &[(3200, 3203)], true);
+
+        let tick = process(&player);
+        assert_eq!(tick.movement, MovementType::Run(1, 1));
+        assert_eq!(tick.run_energy, Some(MAX_RUN_ENERGY - RUN_ENERGY_DRAIN));
+        assert_eq!(player.location(), Location::new(3200, 3202, 0));
+
+        // A single step left is walked and costs nothing
+        let tick = process(&player);
+        assert_eq!(tick.movement, MovementType::Walk(1));
+        assert_eq!(tick.run_energy, None);
+    }
+
+    #[test]
+    fn test_running_out_of_energy_walks() {
+        let player = player_at(3200, 3200);
+        *player.run_energy.write() = RUN_ENERGY_DRAIN;
+        walk(&player, &[(3200, 3210)], true);
+
+        let tick = process(&player);
+        assert_eq!(tick.movement, MovementType::Run(1, 1));
+        assert_eq!(tick.run_energy, Some(0));
+        assert!(!*player.running.read());
+
+        assert_eq!(process(&player).movement, MovementType::Walk(1));
+    }
+
+    #[test]
+    fn test_energy_restores_while_idle() {
+        let player = player_at(3200, 3200);
+        *player.run_energy.write() = 50;
+
+        let restored: Vec<Option<u8>> = (0..RUN_ENERGY_RESTORE_TICKS * 2)
+            .map(|_| process(&player).run_energy)
+            .collect();
+        assert_eq!(
+            restored.iter().flatten().copied().collect::<Vec<_>>(),
+            [51, 52]
+        );
+
+        // Walking doesn't restore energy
+        walk(&player, &[(3210, 3200)], false);
+        for _ in 0..RUN_ENERGY_RESTORE_TICKS * 2 {
+            assert_eq!(process(&player).run_energy, None);
+        }
+        assert_eq!(*player.run_energy.read(), 52);
+    }
+}
@@ -0,0 +1,160 @@
+//! Inbound packet queue module
+//!
+//! Game packets are read and decoded on each connection's socket task, but
+//! they are not applied there. Instead they are queued per player and drained
+//! by the game tick, so all game logic runs on the tick task in a fixed order:
+//! - Players are processed in ascending index order
+//! - Each player's packets are applied in the order they were received
+//! - At most `tick_budget` packets per player are applied each tick; the rest
+//!   wait for the next tick
+//! - Packets arriving while a player's queue is full are dropped
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use dashmap::DashMap;
+
+use crate::protocol::game::IncomingGamePacket;
+
+/// Default number of packets applied per player each tick
+pub const DEFAULT_TICK_BUDGET: usize = 10;
+
+/// Default number of packets that may wait in a player's queue
+pub const DEFAULT_QUEUE_CAPACITY: usize = 64;
+
+/// Per-player queues of decoded game packets awaiting the next tick
+#[derive(Debug)]
+pub struct InboundPacketQueues {
+    /// Queued packets by player index
+    queues: DashMap<u16, VecDeque<IncomingGamePacket>>,
+    /// Maximum queued packets per player
+    capacity: usize,
+    /// Packets dropped because a queue was full
+    dropped: AtomicU64,
+}
+
+impl InboundPacketQueues {
+    /// Create empty queues holding at most `capacity` packets per player
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            queues: DashMap::new(),
+            capacity,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Queue a packet for a player
+    ///
+    /// Returns false if the player's queue is full and the packet was dropped.
+    pub fn push(&self, player_index: u16, packet: IncomingGamePacket) -> bool {
+        let mut queue = self.queues.entry(player_index).or_default();
+        if queue.len() >= self.capacity {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        queue.push_back(packet);
+        true
+    }
+
+    /// Take up to `budget` packets from the front of a player's queue
+    pub fn drain(&self, player_index: u16, budget: usize) -> Vec<IncomingGamePacket> {
+        match self.queues.get_mut(&player_index) {
+            Some(mut queue) => {
+                let count = budget.min(queue.len());
+                queue.drain(..count).collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Get the indices of players with queued packets, in ascending order
+    pub fn pending_players(&self) -> Vec<u16> {
+        let mut players: Vec<u16> = self
+            .queues
+            .iter()
+            .filter(|entry| !entry.value().is_empty())
+            .map(|entry| *entry.key())
+            .collect();
+        players.sort_unstable();
+        players
+    }
+
+    /// Get the number of packets queued for a player
+    pub fn len(&self, player_index: u16) -> usize {
+        self.queues
+            .get(&player_index)
+            .map(|queue| queue.len())
+            .unwrap_or(0)
+    }
+
+    /// Check whether no packets are queued for any player
+    pub fn is_empty(&self) -> bool {
+        self.queues.iter().all(|entry| entry.value().is_empty())
+    }
+
+    /// Discard a player's queue (on logout or disconnect)
+    pub fn remove(&self, player_index: u16) {
+        self.queues.remove(&player_index);
+    }
+
+    /// Get the total number of packets dropped because a queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for InboundPacketQueues {
+    fn default() -> Self {
+        Self::new(DEFAULT_QUEUE_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(opcode: u8) -> IncomingGamePacket {
+        IncomingGamePacket::new(opcode, vec![])
+    }
+
+    #[test]
+    fn test_drain_respects_budget_and_order() {
+        let queues = InboundPacketQueues::new(16);
+        for opcode in 0..5 {
+            assert!(queues.push(1, packet(opcode)));
+        }
+
+        let first: Vec<u8> = queues.drain(1, 3).iter().map(|p| p.opcode).collect();
+        assert_eq!(first, vec![0, 1, 2]);
+        assert_eq!(queues.len(1), 2);
+
+        let rest: Vec<u8> = queues.drain(1, 3).iter().map(|p| p.opcode).collect();
+        assert_eq!(rest, vec![3, 4]);
+        assert!(queues.is_empty());
+    }
+
+    #[test]
+    fn test_full_queue_drops_packets() {
+        let queues = InboundPacketQueues::new(2);
+        assert!(queues.push(1, packet(0)));
+        assert!(queues.push(1, packet(1)));
+        assert!(!queues.push(1, packet(2)));
+        assert_eq!(queues.len(1), 2);
+        assert_eq!(queues.dropped(), 1);
+    }
+
+    #[test]
+    fn test_pending_players_sorted() {
+        let queues = InboundPacketQueues::new(4);
+        queues.push(7, packet(0));
+        queues.push(2, packet(0));
+        queues.push(5, packet(0));
+        queues.drain(5, 1);
+
+        assert_eq!(queues.pending_players(), vec![2, 7]);
+
+        queues.remove(2);
+        assert_eq!(queues.pending_players(), vec![7]);
+        assert_eq!(queues.drain(2, 10).len(), 0);
+    }
+}
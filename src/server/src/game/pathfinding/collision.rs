@@ -0,0 +1,814 @@
+//! Tile collision flags
+//!
+//! Every tile carries a set of flags saying what stops a step onto it:
+//! walls on each of its sides and corners, solid objects, and unwalkable
+//! terrain. Regions are loaded from the cache ahead of use by `preload`, or
+//! the first time one of their tiles is looked up.
+//!
+//! A region's flags come from two archives:
+//! - Terrain (`m{x}_{y}`): blocked tiles, and bridges, which move everything
+//!   on a tile down a plane
+//! - Landscape (`l{x}_{y}`): the objects placed in the region. Walls flag the
+//!   sides of their tile, other objects block the tiles they cover (sized and
+//!   rotated as their definitions say), and some floor decorations block
+//!   their tile.
+//!
+//! Landscape archives are XTEA-encrypted in a stock cache. The server sends
+//! clients zero keys, so it expects a cache with them decrypted; an archive
+//! that can't be read leaves only the terrain.
+//!
+//! Walls and objects along a region's edge also flag tiles in the next
+//! region. Those flags are held until that region loads, so loading one
+//! region never loads its neighbours.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use tracing::trace;
+
+use crate::cache::objects::{ObjectDefinition, ObjectDefinitions};
+use crate::cache::CacheStore;
+use crate::net::buffer::PacketBuffer;
+
+/// Cache index holding map archives
+pub const MAP_INDEX: u8 = 5;
+
+/// Wall on the north-west corner
+pub const WALL_NORTHWEST: u32 = 0x1;
+/// Wall on the north side
+pub const WALL_NORTH: u32 = 0x2;
+/// Wall on the north-east corner
+pub const WALL_NORTHEAST: u32 = 0x4;
+/// Wall on the east side
+pub const WALL_EAST: u32 = 0x8;
+/// Wall on the south-east corner
+pub const WALL_SOUTHEAST: u32 = 0x10;
+/// Wall on the south side
+pub const WALL_SOUTH: u32 = 0x20;
+/// Wall on the south-west corner
+pub const WALL_SOUTHWEST: u32 = 0x40;
+/// Wall on the west side
+pub const WALL_WEST: u32 = 0x80;
+/// Solid object on the tile
+pub const OBJECT: u32 = 0x100;
+/// Floor decoration that can't be walked over
+pub const FLOOR_DECORATION: u32 = 0x4_0000;
+/// Unwalkable terrain
+pub const BLOCKED: u32 = 0x20_0000;
+
+/// Flags that stop a step onto a tile whatever its direction
+pub const SOLID: u32 = OBJECT | FLOOR_DECORATION | BLOCKED;
+
+/// Tiles along each side of a region
+const REGION_SIZE: usize = 64;
+
+/// Planes in a region
+pub(crate) const PLANES: usize = 4;
+
+/// Terrain setting marking a tile unwalkable
+const TERRAIN_BLOCKED: u8 = 0x1;
+
+/// Terrain setting (on plane 1) marking a bridge over the tile
+const TERRAIN_BRIDGE: u8 = 0x2;
+
+/// Landscape object types: walls, wall decorations (which don't clip),
+/// objects and roofs, and floor decorations
+const WALL_TYPES: std::ops::RangeInclusive<u8> = 0..=3;
+const OBJECT_TYPES: std::ops::RangeInclusive<u8> = 9..=21;
+const FLOOR_DECORATION_TYPE: u8 = 22;
+
+/// Flags on a tile outside the region that added them, by absolute
+/// coordinates and plane
+type TileFlags = (u16, u16, usize, u32);
+
+/// Wall flags on a tile that stop a step onto it in a direction
+///
+/// A diagonal step is also stopped by either of the two walls it would
+/// squeeze past, which the caller checks on the tiles beside it.
+pub fn entry_walls(dx: i32, dy: i32) -> u32 {
+    match (dx, dy) {
+        (-1, 0) => WALL_EAST,
+        (1, 0) => WALL_WEST,
+        (0, -1) => WALL_NORTH,
+        (0, 1) => WALL_SOUTH,
+        (-1, -1) => WALL_EAST | WALL_NORTHEAST | WALL_NORTH,
+        (1, -1) => WALL_WEST | WALL_NORTHWEST | WALL_NORTH,
+        (-1, 1) => WALL_EAST | WALL_SOUTHEAST | WALL_SOUTH,
+        (1, 1) => WALL_WEST | WALL_SOUTHWEST | WALL_SOUTH,
+        _ => 0,
+    }
+}
+
+/// Flags a wall of a landscape object type (0-3) adds around its tile
+///
+/// Each entry is an offset from the wall's tile and the flags for that tile:
+/// a wall flags its own tile and the one on the other side. Type 0 is a
+/// straight wall on one side (rotation 0 west, 1 north, 2 east, 3 south),
+/// type 2 an L-shaped corner taking that side and the next clockwise, and
+/// types 1 and 3 a pillar on one corner (rotation 0 north-west, then
+/// clockwise).
+pub fn wall_flags(kind: u8, rotation: u8) -> Vec<(i32, i32, u32)> {
+    let straight = |rotation: u8| match rotation & 0x3 {
+        0 => [(0, 0, WALL_WEST), (-1, 0, WALL_EAST)],
+        1 => [(0, 0, WALL_NORTH), (0, 1, WALL_SOUTH)],
+        2 => [(0, 0, WALL_EAST), (1, 0, WALL_WEST)],
+        _ => [(0, 0, WALL_SOUTH), (0, -1, WALL_NORTH)],
+    };
+    match kind {
+        0 => straight(rotation).to_vec(),
+        2 => [straight(rotation), straight(rotation + 1)].concat(),
+        1 | 3 => match rotation & 0x3 {
+            0 => vec![(0, 0, WALL_NORTHWEST), (-1, 1, WALL_SOUTHEAST)],
+            1 => vec![(0, 0, WALL_NORTHEAST), (1, 1, WALL_SOUTHWEST)],
+            2 => vec![(0, 0, WALL_SOUTHEAST), (1, -1, WALL_NORTHWEST)],
+            _ => vec![(0, 0, WALL_SOUTHWEST), (-1, -1, WALL_NORTHEAST)],
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// An object placed by a region's landscape archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LandscapeObject {
+    /// Object definition ID
+    id: u32,
+    /// Tile within the region
+    x: usize,
+    y: usize,
+    /// Plane, before bridges are accounted for
+    z: usize,
+    /// Object type (wall, decoration, object, floor decoration, ...)
+    kind: u8,
+    /// Rotation (0-3, clockwise from west)
+    rotation: u8,
+}
+
+/// Decode the objects in a landscape archive
+///
+/// Objects are grouped by ID, each ID delta-encoded as a smart; each group
+/// lists its tiles as smart deltas of a packed plane/x/y position, each
+/// followed by a byte holding the type and rotation. A zero delta ends a
+/// list.
+fn decode_landscape(data: &[u8]) -> Vec<LandscapeObject> {
+    let mut buffer = PacketBuffer::from_bytes(data);
+    let mut objects = Vec::new();
+    let mut id: u32 = 0;
+    let mut first = true;
+
+    loop {
+        let id_delta = buffer.read_smart() as u32;
+        if id_delta == 0 {
+            break;
+        }
+        // IDs start from -1
+        id = if first { id_delta - 1 } else { id + id_delta };
+        first = false;
+
+        let mut position: u32 = 0;
+        loop {
+            let position_delta = buffer.read_smart() as u32;
+            if position_delta == 0 {
+                break;
+            }
+            position += position_delta - 1;
+            let attributes = buffer.read_ubyte();
+            objects.push(LandscapeObject {
+                id,
+                x: (position >> 6 & 0x3f) as usize,
+                y: (position & 0x3f) as usize,
+                z: (position >> 12 & 0x3) as usize,
+                kind: attributes >> 2,
+                rotation: attributes & 0x3,
+            });
+        }
+    }
+    objects
+}
+
+/// Check a single step against the flags around it
+///
+/// `flags` looks tiles up by absolute coordinates; `extra` adds flags that
+/// block every step (normally `SOLID`).
+pub(crate) fn step_allowed(
+    flags: impl Fn(i32, i32) -> u32,
+    x: i32,
+    y: i32,
+    dx: i32,
+    dy: i32,
+    extra: u32,
+) -> bool {
+    if dx.abs() > 1 || dy.abs() > 1 || (dx == 0 && dy == 0) {
+        return false;
+    }
+    if flags(x + dx, y + dy) & (entry_walls(dx, dy) | extra) != 0 {
+        return false;
+    }
+    if dx != 0 && dy != 0 {
+        return flags(x + dx, y) & (entry_walls(dx, 0) | extra) == 0
+            && flags(x, y + dy) & (entry_walls(0, dy) | extra) == 0;
+    }
+    true
+}
+
+/// Collision flags for one region
+#[derive(Clone)]
+pub(crate) struct RegionCollision {
+    /// Flags indexed by plane, then x, then y
+    flags: Vec<u32>,
+}
+
+impl RegionCollision {
+    fn empty() -> Self {
+        Self {
+            flags: vec![0; PLANES * REGION_SIZE * REGION_SIZE],
+        }
+    }
+
+    fn index(x: usize, y: usize, z: usize) -> usize {
+        (z * REGION_SIZE + x) * REGION_SIZE + y
+    }
+
+    pub(crate) fn get(&self, x: usize, y: usize, z: usize) -> u32 {
+        self.flags[Self::index(x, y, z)]
+    }
+
+    fn add(&mut self, x: usize, y: usize, z: usize, flags: u32) {
+        self.flags[Self::index(x, y, z)] |= flags;
+    }
+
+    /// Read the tile settings from a region's terrain archive
+    ///
+    /// Each tile, plane by plane, is a run of opcodes ended by 0 (or by 1
+    /// followed by a height byte). 2-49 carry an overlay byte, 50-81 are the
+    /// tile settings and 82 upwards the underlay.
+    fn terrain_settings(data: &[u8]) -> Vec<u8> {
+        let mut settings = vec![0u8; PLANES * REGION_SIZE * REGION_SIZE];
+        let mut pos = 0;
+        let mut next = || {
+            let byte = data.get(pos).copied();
+            pos += 1;
+            byte
+        };
+
+        'tiles: for setting in &mut settings {
+            loop {
+                let Some(opcode) = next() else {
+                    break 'tiles;
+                };
+                match opcode {
+                    0 => break,
+                    1 => {
+                        next();
+                        break;
+                    }
+                    2..=49 => {
+                        next();
+                    }
+                    50..=81 => *setting = opcode - 49,
+                    _ => {}
+                }
+            }
+        }
+        settings
+    }
+
+    /// Plane a tile's contents are walked on: under a bridge, the one below
+    fn walked_plane(settings: &[u8], x: usize, y: usize, z: usize) -> Option<usize> {
+        if settings[Self::index(x, y, 1)] & TERRAIN_BRIDGE != 0 {
+            z.checked_sub(1)
+        } else {
+            Some(z)
+        }
+    }
+
+    /// Flag blocked terrain from a region's tile settings
+    fn from_settings(settings: &[u8]) -> Self {
+        let mut region = Self::empty();
+        for z in 0..PLANES {
+            for x in 0..REGION_SIZE {
+                for y in 0..REGION_SIZE {
+                    if settings[Self::index(x, y, z)] & TERRAIN_BLOCKED == 0 {
+                        continue;
+                    }
+                    if let Some(plane) = Self::walked_plane(settings, x, y, z) {
+                        region.add(x, y, plane, BLOCKED);
+                    }
+                }
+            }
+        }
+        region
+    }
+
+    /// Flag the walls and objects placed by a region's landscape
+    ///
+    /// `base` is the region's south-west tile. Flags for tiles past the
+    /// region's edge are returned for the region they belong to.
+    fn add_landscape(
+        &mut self,
+        base: (u16, u16),
+        settings: &[u8],
+        objects: &[LandscapeObject],
+        definition: impl Fn(u32) -> Option<ObjectDefinition>,
+    ) -> Vec<TileFlags> {
+        let mut outside = Vec::new();
+        let mut add = |x: i32, y: i32, z: usize, flags: u32| {
+            let local = (usize::try_from(x), usize::try_from(y));
+            if let (Ok(x), Ok(y)) = local {
+                if x < REGION_SIZE && y < REGION_SIZE {
+                    self.add(x, y, z, flags);
+                    return;
+                }
+            }
+            let absolute = (
+                u16::try_from(base.0 as i32 + x),
+                u16::try_from(base.1 as i32 + y),
+            );
+            if let (Ok(x), Ok(y)) = absolute {
+                outside.push((x, y, z, flags));
+            }
+        };
+
+        for object in objects {
+            let Some(z) = Self::walked_plane(settings, object.x, object.y, object.z) else {
+                continue;
+            };
+            let Some(definition) = definition(object.id) else {
+                continue;
+            };
+            if definition.clip_type == 0 {
+                continue;
+            }
+            let (x, y) = (object.x as i32, object.y as i32);
+
+            if WALL_TYPES.contains(&object.kind) {
+                for (dx, dy, flags) in wall_flags(object.kind, object.rotation) {
+                    add(x + dx, y + dy, z, flags);
+                }
+            } else if OBJECT_TYPES.contains(&object.kind) {
+                let (width, length) = if object.rotation & 0x1 == 1 {
+                    (definition.length, definition.width)
+                } else {
+                    (definition.width, definition.length)
+                };
+                for dx in 0..width as i32 {
+                    for dy in 0..length as i32 {
+                        add(x + dx, y + dy, z, OBJECT);
+                    }
+                }
+            } else if object.kind == FLOOR_DECORATION_TYPE && definition.clip_type == 1 {
+                add(x, y, z, FLOOR_DECORATION);
+            }
+        }
+        outside
+    }
+}
+
+/// Loaded regions, and flags waiting for regions not loaded yet
+#[derive(Default)]
+struct Regions {
+    /// Regions loaded so far, by region ID
+    loaded: HashMap<u32, Arc<RegionCollision>>,
+    /// Flags added by a loaded region's landscape to tiles in a region that
+    /// hasn't loaded yet, by that region's ID
+    pending: HashMap<u32, Vec<TileFlags>>,
+}
+
+impl Regions {
+    /// Add a newly loaded region
+    ///
+    /// The region takes the flags other regions left for it, and the flags
+    /// it leaves for other regions are added or held for them.
+    fn insert(
+        &mut self,
+        region_id: u32,
+        mut region: RegionCollision,
+        outside: Vec<TileFlags>,
+    ) -> &mut Arc<RegionCollision> {
+        for (x, y, z, flags) in self.pending.remove(&region_id).unwrap_or_default() {
+            let (local_x, local_y) = CollisionMap::local(x, y);
+            region.add(local_x, local_y, z, flags);
+        }
+        for tile in outside {
+            let (x, y, z, flags) = tile;
+            let target = CollisionMap::region_id(x, y);
+            match self.loaded.get_mut(&target) {
+                Some(loaded) => {
+                    let (local_x, local_y) = CollisionMap::local(x, y);
+                    Arc::make_mut(loaded).add(local_x, local_y, z, flags);
+                }
+                None => self.pending.entry(target).or_default().push(tile),
+            }
+        }
+        self.loaded.entry(region_id).or_insert(Arc::new(region))
+    }
+}
+
+/// Collision flags for the whole map
+///
+/// Regions missing from the cache (or every region, without a cache) have no
+/// flags, so their tiles are all walkable. Loaded regions are shared with the
+/// scenes taken from them; adding flags copies a region a scene still holds.
+pub struct CollisionMap {
+    /// Loaded regions
+    regions: RwLock<Regions>,
+    /// Cache to load terrain and landscapes from
+    cache: Option<Arc<CacheStore>>,
+    /// Definitions of the objects landscapes place
+    definitions: Option<ObjectDefinitions>,
+}
+
+impl CollisionMap {
+    /// Create a map with no terrain; tiles are only blocked once flagged
+    pub fn new() -> Self {
+        Self {
+            regions: RwLock::new(Regions::default()),
+            cache: None,
+            definitions: None,
+        }
+    }
+
+    /// Create a map that loads terrain and landscapes from the cache as
+    /// regions are used
+    pub fn from_cache(cache: Arc<CacheStore>) -> Self {
+        Self {
+            regions: RwLock::new(Regions::default()),
+            definitions: Some(ObjectDefinitions::new(cache.clone())),
+            cache: Some(cache),
+        }
+    }
+
+    /// Get the flags on a tile
+    pub fn flags(&self, x: u16, y: u16, z: u8) -> u32 {
+        if z as usize >= PLANES {
+            return 0;
+        }
+        let region_id = Self::region_id(x, y);
+        let (local_x, local_y) = Self::local(x, y);
+
+        if let Some(region) = self.regions.read().loaded.get(&region_id) {
+            return region.get(local_x, local_y, z as usize);
+        }
+        self.with_region(region_id, |region| region.get(local_x, local_y, z as usize))
+    }
+
+    /// Add flags to a tile
+    pub fn add_flags(&self, x: u16, y: u16, z: u8, flags: u32) {
+        if z as usize >= PLANES {
+            return;
+        }
+        let (local_x, local_y) = Self::local(x, y);
+        self.with_region(Self::region_id(x, y), |region| {
+            Arc::make_mut(region).add(local_x, local_y, z as usize, flags)
+        });
+    }
+
+    /// Mark a tile as unwalkable terrain
+    pub fn block_tile(&self, x: u16, y: u16, z: u8) {
+        self.add_flags(x, y, z, BLOCKED);
+    }
+
+    /// Add a solid object covering `width` x `height` tiles from its
+    /// south-west corner
+    pub fn add_object(&self, x: u16, y: u16, z: u8, width: u16, height: u16) {
+        for tile_x in x..x.saturating_add(width) {
+            for tile_y in y..y.saturating_add(height) {
+                self.add_flags(tile_x, tile_y, z, OBJECT);
+            }
+        }
+    }
+
+    /// Add a straight wall along one side of a tile
+    ///
+    /// Orientation is 0 for the west side, 1 north, 2 east and 3 south. The
+    /// tile on the other side of the wall is flagged too.
+    pub fn add_wall(&self, x: u16, y: u16, z: u8, orientation: u8) {
+        for (dx, dy, flags) in wall_flags(0, orientation) {
+            self.add_flags(
+                x.wrapping_add_signed(dx as i16),
+                y.wrapping_add_signed(dy as i16),
+                z,
+                flags,
+            );
+        }
+    }
+
+    /// Check whether a single step can be taken from a tile
+    pub fn can_step(&self, x: u16, y: u16, z: u8, dx: i32, dy: i32) -> bool {
+        step_allowed(
+            |x, y| match (u16::try_from(x), u16::try_from(y)) {
+                (Ok(x), Ok(y)) => self.flags(x, y, z),
+                _ => BLOCKED,
+            },
+            x as i32,
+            y as i32,
+            dx,
+            dy,
+            SOLID,
+        )
+    }
+
+    /// Number of regions loaded
+    pub fn region_count(&self) -> usize {
+        self.regions.read().loaded.len()
+    }
+
+    /// Load regions on a blocking thread so the tick doesn't read the cache
+    ///
+    /// Regions already loaded are skipped. Without a cache or a runtime this
+    /// does nothing, and regions load when first used instead.
+    pub fn preload(self: &Arc<Self>, region_ids: impl IntoIterator<Item = u32>) {
+        if self.cache.is_none() {
+            return;
+        }
+        let missing: Vec<u32> = {
+            let regions = self.regions.read();
+            region_ids
+                .into_iter()
+                .filter(|region_id| !regions.loaded.contains_key(region_id))
+                .collect()
+        };
+        if missing.is_empty() {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        let map = self.clone();
+        runtime.spawn_blocking(move || {
+            for region_id in missing {
+                map.region(region_id);
+            }
+        });
+    }
+
+    /// Get a region, loading it first if needed
+    pub(crate) fn region(&self, region_id: u32) -> Arc<RegionCollision> {
+        if let Some(region) = self.regions.read().loaded.get(&region_id) {
+            return region.clone();
+        }
+        self.with_region(region_id, |region| region.clone())
+    }
+
+    pub(crate) fn region_id(x: u16, y: u16) -> u32 {
+        ((x as u32 >> 6) << 8) | (y as u32 >> 6)
+    }
+
+    pub(crate) fn local(x: u16, y: u16) -> (usize, usize) {
+        (x as usize & 0x3f, y as usize & 0x3f)
+    }
+
+    /// Run a closure on a region, loading it first if needed
+    fn with_region<T>(&self, region_id: u32, f: impl FnOnce(&mut Arc<RegionCollision>) -> T) -> T {
+        if let Some(region) = self.regions.write().loaded.get_mut(&region_id) {
+            return f(region);
+        }
+
+        // Load outside the lock; another thread may get there first
+        let (loaded, outside) = self.load_region(region_id);
+        let mut regions = self.regions.write();
+        if let Some(region) = regions.loaded.get_mut(&region_id) {
+            return f(region);
+        }
+        f(regions.insert(region_id, loaded, outside))
+    }
+
+    /// Read a region's terrain and landscape from the cache
+    ///
+    /// Also returns the flags the landscape adds past the region's edge.
+    fn load_region(&self, region_id: u32) -> (RegionCollision, Vec<TileFlags>) {
+        let (Some(cache), Some(definitions)) = (&self.cache, &self.definitions) else {
+            return (RegionCollision::empty(), Vec::new());
+        };
+        let (region_x, region_y) = (region_id >> 8, region_id & 0xff);
+        let read = |prefix: &str| {
+            cache
+                .find_archive(MAP_INDEX, &format!("{}{}_{}", prefix, region_x, region_y))
+                .and_then(|archive| cache.get_decompressed_file(MAP_INDEX, archive).ok())
+                .unwrap_or_default()
+        };
+        let terrain = read("m");
+        let landscape = read("l");
+
+        let settings = RegionCollision::terrain_settings(&terrain);
+        let mut region = RegionCollision::from_settings(&settings);
+        let objects = decode_landscape(&landscape);
+        let base = ((region_x << 6) as u16, (region_y << 6) as u16);
+        let outside = region.add_landscape(base, &settings, &objects, |id| definitions.get(id));
+
+        trace!(
+            region_x = region_x,
+            region_y = region_y,
+            terrain_bytes = terrain.len(),
+            objects = objects.len(),
+            "Loaded region collision"
+        );
+        (region, outside)
+    }
+}
+
+impl Default for CollisionMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for CollisionMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CollisionMap")
+            .field("regions", &self.region_count())
+            .field("cached", &self.cache.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_walls_block_both_sides() {
+        let map = CollisionMap::new();
+        map.add_wall(3200, 3200, 0, 1);
+
+        assert_eq!(map.flags(3200, 3200, 0), WALL_NORTH);
+        assert_eq!(map.flags(3200, 3201, 0), WALL_SOUTH);
+        assert!(!map.can_step(3200, 3200, 0, 0, 1));
+        assert!(!map.can_step(3200, 3201, 0, 0, -1));
+        assert!(map.can_step(3200, 3200, 0, 1, 0));
+
+        // Diagonals can't squeeze past the wall either
+        assert!(!map.can_step(3200, 3200, 0, 1, 1));
+        assert!(!map.can_step(3201, 3201, 0, -1, -1));
+    }
+
+    #[test]
+    fn test_objects_and_blocked_tiles() {
+        let map = CollisionMap::new();
+        map.add_object(3200, 3200, 0, 2, 2);
+        map.block_tile(3210, 3210, 1);
+
+        assert_eq!(map.flags(3201, 3201, 0), OBJECT);
+        assert!(!map.can_step(3199, 3200, 0, 1, 0));
+        assert!(!map.can_step(3199, 3199, 0, 1, 1));
+        assert_eq!(map.flags(3210, 3210, 1), BLOCKED);
+        assert_eq!(map.flags(3210, 3210, 0), 0);
+        assert_eq!(map.region_count(), 1);
+    }
+
+    #[test]
+    fn test_wall_flags() {
+        assert_eq!(wall_flags(0, 2), vec![(0, 0, WALL_EAST), (1, 0, WALL_WEST)]);
+        assert_eq!(
+            wall_flags(2, 3),
+            vec![
+                (0, 0, WALL_SOUTH),
+                (0, -1, WALL_NORTH),
+                (0, 0, WALL_WEST),
+                (-1, 0, WALL_EAST)
+            ]
+        );
+        assert_eq!(
+            wall_flags(3, 1),
+            vec![(0, 0, WALL_NORTHEAST), (1, 1, WALL_SOUTHWEST)]
+        );
+        assert!(wall_flags(4, 0).is_empty());
+    }
+
+    #[test]
+    fn test_landscape_decoding() {
+        // Object 5 at (1, 2) plane 0 and (3, 4) plane 1; object 1005 at (0, 0)
+        let first = (1 << 6 | 2) + 1;
+        let second = ((1 << 12 | 3 << 6 | 4) - (1 << 6 | 2)) + 1;
+        let mut data = vec![6, first as u8, 10 << 2 | 1];
+        data.extend(((second as u16) | 0x8000).to_be_bytes());
+        data.extend([3, 0]);
+        data.extend((1000u16 | 0x8000).to_be_bytes());
+        data.extend([1, 22 << 2, 0, 0]);
+
+        let objects = decode_landscape(&data);
+        let placed: Vec<_> = objects
+            .iter()
+            .map(|o| (o.id, o.x, o.y, o.z, o.kind, o.rotation))
+            .collect();
+        assert_eq!(
+            placed,
+            vec![
+                (5, 1, 2, 0, 10, 1),
+                (5, 3, 4, 1, 0, 3),
+                (1005, 0, 0, 0, 22, 0)
+            ]
+        );
+        assert!(decode_landscape(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_landscape_clipping() {
+        let object = |id, x, y, kind, rotation| LandscapeObject {
+            id,
+            x,
+            y,
+            z: 0,
+            kind,
+            rotation,
+        };
+        let objects = [
+            // A 2x3 table turned a quarter, so it covers 3x2 tiles
+            object(1, 10, 10, 10, 1),
+            // A wall on the west edge of the region
+            object(2, 0, 5, 0, 0),
+            // A rug that can be walked over, and one that can't
+            object(3, 20, 20, 22, 0),
+            object(4, 21, 20, 22, 0),
+            // Something without a definition
+            object(99, 30, 30, 10, 0),
+        ];
+        let definition = |id| match id {
+            1 => Some(ObjectDefinition {
+                width: 2,
+                length: 3,
+                ..Default::default()
+            }),
+            2 | 3 => Some(ObjectDefinition::default()),
+            4 => Some(ObjectDefinition {
+                clip_type: 1,
+                ..Default::default()
+            }),
+            _ => None,
+        };
+
+        let settings = vec![0; PLANES * REGION_SIZE * REGION_SIZE];
+        let mut region = RegionCollision::empty();
+        let outside = region.add_landscape((3200, 3200), &settings, &objects, definition);
+
+        for x in 10..13 {
+            for y in 10..12 {
+                assert_eq!(region.get(x, y, 0), OBJECT);
+            }
+        }
+        assert_eq!(region.get(10, 12, 0), 0);
+        assert_eq!(region.get(0, 5, 0), WALL_WEST);
+        assert_eq!(outside, vec![(3199, 3205, 0, WALL_EAST)]);
+        assert_eq!(region.get(20, 20, 0), 0);
+        assert_eq!(region.get(21, 20, 0), FLOOR_DECORATION);
+        assert_eq!(region.get(30, 30, 0), 0);
+    }
+
+    #[test]
+    fn test_flags_past_the_edge_wait_for_their_region() {
+        let mut regions = Regions::default();
+        let west = CollisionMap::region_id(3199, 3205);
+        let east = CollisionMap::region_id(3200, 3205);
+
+        regions.insert(
+            east,
+            RegionCollision::empty(),
+            vec![(3199, 3205, 0, WALL_EAST)],
+        );
+        assert!(!regions.loaded.contains_key(&west));
+        regions.insert(west, RegionCollision::empty(), Vec::new());
+        assert_eq!(regions.loaded[&west].get(63, 5, 0), WALL_EAST);
+        assert!(regions.pending.is_empty());
+
+        // A region already loaded takes them straight away
+        regions.insert(
+            east,
+            RegionCollision::empty(),
+            vec![(3199, 3206, 0, WALL_EAST)],
+        );
+        assert_eq!(regions.loaded[&west].get(63, 6, 0), WALL_EAST);
+    }
+
+    #[test]
+    fn test_terrain_decoding() {
+        let mut data = Vec::new();
+        for z in 0..PLANES {
+            for x in 0..REGION_SIZE {
+                for y in 0..REGION_SIZE {
+                    match (z, x, y) {
+                        // Blocked, with an overlay and a height
+                        (0, 1, 2) => data.extend([10, 7, 49 + 1, 1, 20]),
+                        // Blocked under a bridge: walked on plane 0
+                        (1, 5, 5) => data.extend([49 + 3, 0]),
+                        (2, 5, 5) => data.extend([49 + 1, 0]),
+                        // Underlay only
+                        _ => data.extend([90, 0]),
+                    }
+                }
+            }
+        }
+
+        let region = RegionCollision::from_settings(&RegionCollision::terrain_settings(&data));
+        assert_eq!(region.get(1, 2, 0), BLOCKED);
+        assert_eq!(region.get(5, 5, 0), BLOCKED);
+        assert_eq!(region.get(5, 5, 1), BLOCKED);
+        assert_eq!(region.get(5, 5, 2), 0);
+        assert_eq!(region.get(0, 0, 0), 0);
+
+        // Truncated data leaves the rest of the region walkable
+        let region =
+            RegionCollision::from_settings(&RegionCollision::terrain_settings(&data[..10]));
+        assert_eq!(region.get(1, 2, 0), 0);
+    }
+}
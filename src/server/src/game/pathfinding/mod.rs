@@ -0,0 +1,528 @@
+//! Server-side pathfinding
+//!
+//! Paths are searched over the collision flags of the 104x104 tile scene
+//! the client has loaded around a player:
+//! - `find_path` walks to a tile, or as close to it as the walls allow
+//! - `find_path_to_entity` walks next to something the player interacts
+//!   with, to a tile from which it can actually be reached
+//! - `dumb_step` takes one step straight toward a target, for NPCs that
+//!   don't path around obstacles
+//!
+//! Client walk requests are checked against the same scene, so a path through
+//! a wall or outside the loaded map is replaced or refused. See `collision`
+//! for which flags are loaded from the cache.
+
+pub mod collision;
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::game::player::Location;
+
+pub use collision::CollisionMap;
+use collision::{step_allowed, RegionCollision, BLOCKED, PLANES, SOLID};
+
+/// Tiles along each side of the scene
+pub const SCENE_SIZE: usize = 104;
+
+/// Regions along each side of the scene: 104 tiles from a chunk boundary
+/// reach into at most three
+const SCENE_REGIONS: usize = 3;
+
+/// How far from an unreachable destination to look for a tile to stop on
+pub const ALTERNATIVE_RADIUS: i32 = 10;
+
+/// Step order for the search: cardinal directions first, so straight paths
+/// are preferred over diagonal ones of the same length
+const STEPS: [(i32, i32); 8] = [
+    (-1, 0),
+    (1, 0),
+    (0, -1),
+    (0, 1),
+    (-1, -1),
+    (1, -1),
+    (-1, 1),
+    (1, 1),
+];
+
+/// Collision flags for the scene around a tile
+///
+/// The scene holds the map's regions it covers rather than copying their
+/// flags, so taking one for each walk request is cheap.
+pub struct Scene {
+    /// South-west corner X
+    pub base_x: u16,
+    /// South-west corner Y
+    pub base_y: u16,
+    /// Plane
+    pub z: u8,
+    /// Coordinates of the south-west region
+    base_region: (u16, u16),
+    /// Regions covered, indexed by x, then y (relative to the base region)
+    regions: Vec<Option<Arc<RegionCollision>>>,
+}
+
+impl Scene {
+    /// Take the scene centred on a tile
+    ///
+    /// The scene starts six 8x8 chunks south-west of the tile's chunk, as
+    /// the client's does.
+    pub fn around(map: &CollisionMap, location: Location) -> Self {
+        let (base_x, base_y) = Self::base(location);
+        let base_region = (base_x >> 6, base_y >> 6);
+
+        let mut regions = Vec::with_capacity(SCENE_REGIONS * SCENE_REGIONS);
+        for region_x in 0..SCENE_REGIONS as u32 {
+            for region_y in 0..SCENE_REGIONS as u32 {
+                let x = (base_region.0 as u32 + region_x) << 6;
+                let y = (base_region.1 as u32 + region_y) << 6;
+                let covered = Self::covers(base_x, x) && Self::covers(base_y, y);
+                regions.push(covered.then(|| map.region((x >> 6 << 8) | (y >> 6))));
+            }
+        }
+
+        Self {
+            base_x,
+            base_y,
+            z: location.z,
+            base_region,
+            regions,
+        }
+    }
+
+    /// IDs of the regions the scene around a tile covers
+    pub fn region_ids(location: Location) -> Vec<u32> {
+        let (base_x, base_y) = Self::base(location);
+        let mut ids = Vec::with_capacity(SCENE_REGIONS * SCENE_REGIONS);
+        for x in (base_x >> 6)..=(Self::last(base_x) >> 6) {
+            for y in (base_y >> 6)..=(Self::last(base_y) >> 6) {
+                ids.push(CollisionMap::region_id(x << 6, y << 6));
+            }
+        }
+        ids
+    }
+
+    fn base(location: Location) -> (u16, u16) {
+        (
+            ((location.x >> 3).saturating_sub(6)) << 3,
+            ((location.y >> 3).saturating_sub(6)) << 3,
+        )
+    }
+
+    /// Last tile of the scene along an axis
+    fn last(base: u16) -> u16 {
+        base.saturating_add(SCENE_SIZE as u16 - 1)
+    }
+
+    /// Check whether a region starting at `start` overlaps the scene
+    fn covers(base: u16, start: u32) -> bool {
+        start <= Self::last(base) as u32
+    }
+
+    /// Check whether a tile is in the scene
+    pub fn contains(&self, x: u16, y: u16) -> bool {
+        self.local(x as i32, y as i32).is_some()
+    }
+
+    /// Get the flags on a tile; tiles outside the scene are blocked
+    pub fn flags(&self, x: i32, y: i32) -> u32 {
+        let (Some(_), Ok(x), Ok(y)) = (self.local(x, y), u16::try_from(x), u16::try_from(y)) else {
+            return BLOCKED;
+        };
+        if self.z as usize >= PLANES {
+            return 0;
+        }
+        let region_x = ((x >> 6) - self.base_region.0) as usize;
+        let region_y = ((y >> 6) - self.base_region.1) as usize;
+        let (local_x, local_y) = CollisionMap::local(x, y);
+        self.regions[region_x * SCENE_REGIONS + region_y]
+            .as_ref()
+            .map_or(0, |region| region.get(local_x, local_y, self.z as usize))
+    }
+
+    /// Check whether a single step can be taken from a tile
+    pub fn can_step(&self, x: u16, y: u16, dx: i32, dy: i32) -> bool {
+        step_allowed(|x, y| self.flags(x, y), x as i32, y as i32, dx, dy, SOLID)
+    }
+
+    /// Check that a run of single steps can be walked from a tile
+    pub fn is_walkable(&self, start: Location, steps: &[Location]) -> bool {
+        let mut from = start;
+        for &step in steps {
+            if step.z != self.z
+                || !self.can_step(
+                    from.x,
+                    from.y,
+                    step.x as i32 - from.x as i32,
+                    step.y as i32 - from.y as i32,
+                )
+            {
+                return false;
+            }
+            from = step;
+        }
+        true
+    }
+
+    fn local(&self, x: i32, y: i32) -> Option<usize> {
+        let local_x = usize::try_from(x - self.base_x as i32).ok()?;
+        let local_y = usize::try_from(y - self.base_y as i32).ok()?;
+        (local_x < SCENE_SIZE && local_y < SCENE_SIZE).then_some(local_x * SCENE_SIZE + local_y)
+    }
+
+    fn tile(&self, index: usize) -> (u16, u16) {
+        (
+            self.base_x + (index / SCENE_SIZE) as u16,
+            self.base_y + (index % SCENE_SIZE) as u16,
+        )
+    }
+}
+
+/// Tiles reached by a breadth-first search from a start tile
+struct Search {
+    /// Tile each reached tile was stepped to from (start points to itself)
+    came_from: Vec<Option<usize>>,
+    /// Steps from the start to each reached tile
+    distance: Vec<u32>,
+}
+
+impl Search {
+    /// Search the scene until a goal tile is reached
+    fn run(
+        scene: &Scene,
+        start: Location,
+        is_goal: impl Fn(u16, u16) -> bool,
+    ) -> (Self, Option<usize>) {
+        let mut search = Self {
+            came_from: vec![None; SCENE_SIZE * SCENE_SIZE],
+            distance: vec![u32::MAX; SCENE_SIZE * SCENE_SIZE],
+        };
+        let Some(start_index) = scene.local(start.x as i32, start.y as i32) else {
+            return (search, None);
+        };
+
+        search.came_from[start_index] = Some(start_index);
+        search.distance[start_index] = 0;
+        let mut queue = VecDeque::from([start_index]);
+
+        while let Some(index) = queue.pop_front() {
+            let (x, y) = scene.tile(index);
+            if is_goal(x, y) {
+                return (search, Some(index));
+            }
+
+            for &(dx, dy) in &STEPS {
+                if !scene.can_step(x, y, dx, dy) {
+                    continue;
+                }
+                let Some(next) = scene.local(x as i32 + dx, y as i32 + dy) else {
+                    continue;
+                };
+                if search.came_from[next].is_none() {
+                    search.came_from[next] = Some(index);
+                    search.distance[next] = search.distance[index] + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        (search, None)
+    }
+
+    /// Reached tile closest to a target area, within `ALTERNATIVE_RADIUS`
+    ///
+    /// Ties go to the tile with the shorter path.
+    fn closest_to(&self, scene: &Scene, min: (i32, i32), max: (i32, i32)) -> Option<usize> {
+        (0..self.came_from.len())
+            .filter(|&index| self.came_from[index].is_some())
+            .filter_map(|index| {
+                let (x, y) = scene.tile(index);
+                let dx = axis_distance(x as i32, min.0, max.0);
+                let dy = axis_distance(y as i32, min.1, max.1);
+                (dx <= ALTERNATIVE_RADIUS && dy <= ALTERNATIVE_RADIUS).then_some((
+                    dx * dx + dy * dy,
+                    self.distance[index],
+                    index,
+                ))
+            })
+            .min()
+            .map(|(_, _, index)| index)
+    }
+
+    /// Tiles from the start (exclusive) to a reached tile
+    fn path_to(&self, scene: &Scene, end: usize) -> Vec<(u16, u16)> {
+        let mut path = Vec::with_capacity(self.distance[end] as usize);
+        let mut index = end;
+        while let Some(previous) = self.came_from[index].filter(|&previous| previous != index) {
+            path.push(scene.tile(index));
+            index = previous;
+        }
+        path.reverse();
+        path
+    }
+}
+
+/// Distance from a coordinate to the range `min..=max`
+fn axis_distance(value: i32, min: i32, max: i32) -> i32 {
+    if value < min {
+        min - value
+    } else if value > max {
+        value - max
+    } else {
+        0
+    }
+}
+
+/// Load the regions of the scene around a tile before a walk request needs
+/// them, off the tick
+pub fn preload_scene(map: &Arc<CollisionMap>, location: Location) {
+    map.preload(Scene::region_ids(location));
+}
+
+/// Find a path to a tile
+///
+/// Returns every tile along the way, start excluded. When the tile can't be
+/// reached the path stops at the reachable tile closest to it, so long as
+/// one is within `ALTERNATIVE_RADIUS`; an empty path means there is nowhere
+/// better to stand. `None` means the tile isn't in the scene, or nothing
+/// near it can be reached.
+pub fn find_path(
+    scene: &Scene,
+    start: Location,
+    dest_x: u16,
+    dest_y: u16,
+) -> Option<Vec<(u16, u16)>> {
+    if !scene.contains(dest_x, dest_y) {
+        return None;
+    }
+
+    let (search, found) = Search::run(scene, start, |x, y| (x, y) == (dest_x, dest_y));
+    let target = (dest_x as i32, dest_y as i32);
+    let end = found.or_else(|| search.closest_to(scene, target, target))?;
+    Some(search.path_to(scene, end))
+}
+
+/// Find a path to a tile next to something the player interacts with
+///
+/// The target covers `width` x `height` tiles from its south-west corner.
+/// The path ends on a tile beside it (not diagonally) with no wall in
+/// between; the target's own tiles are solid, so only walls are checked for
+/// the last step. Falls back like `find_path` when no such tile is reachable.
+pub fn find_path_to_entity(
+    scene: &Scene,
+    start: Location,
+    target: Location,
+    width: u16,
+    height: u16,
+) -> Option<Vec<(u16, u16)>> {
+    if !scene.contains(target.x, target.y) {
+        return None;
+    }
+
+    let min = (target.x as i32, target.y as i32);
+    let max = (
+        min.0 + width.max(1) as i32 - 1,
+        min.1 + height.max(1) as i32 - 1,
+    );
+    let inside = |x: i32, y: i32| (min.0..=max.0).contains(&x) && (min.1..=max.1).contains(&y);
+
+    let is_goal = |x: u16, y: u16| {
+        let (x, y) = (x as i32, y as i32);
+        !inside(x, y)
+            && [(-1, 0), (1, 0), (0, -1), (0, 1)].iter().any(|&(dx, dy)| {
+                inside(x + dx, y + dy) && step_allowed(|x, y| scene.flags(x, y), x, y, dx, dy, 0)
+            })
+    };
+
+    let (search, found) = Search::run(scene, start, is_goal);
+    let end = found.or_else(|| search.closest_to(scene, min, max))?;
+    Some(search.path_to(scene, end))
+}
+
+/// Take one step straight toward a target, without pathing around anything
+///
+/// Tries the diagonal first, then each axis on its own, as NPCs chasing or
+/// wandering do. Returns `None` when already there or every step is blocked.
+pub fn dumb_step(map: &CollisionMap, from: Location, to: Location) -> Option<Location> {
+    let dx = (to.x as i32 - from.x as i32).signum();
+    let dy = (to.y as i32 - from.y as i32).signum();
+
+    [(dx, dy), (dx, 0), (0, dy)]
+        .into_iter()
+        .filter(|&(dx, dy)| dx != 0 || dy != 0)
+        .find(|&(dx, dy)| map.can_step(from.x, from.y, from.z, dx, dy))
+        .map(|(dx, dy)| {
+            Location::new(
+                from.x.wrapping_add_signed(dx as i16),
+                from.y.wrapping_add_signed(dy as i16),
+                from.z,
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start() -> Location {
+        Location::new(3200, 3200, 0)
+    }
+
+    /// A wall running north-south along the east side of x = 3202, from
+    /// y = 3195 to 3205
+    fn walled_map() -> CollisionMap {
+        let map = CollisionMap::new();
+        for y in 3195..=3205 {
+            map.add_wall(3202, y, 0, 2);
+        }
+        map
+    }
+
+    #[test]
+    fn test_scene_bounds() {
+        let map = CollisionMap::new();
+        let scene = Scene::around(&map, start());
+
+        assert_eq!((scene.base_x, scene.base_y), (3152, 3152));
+        assert!(scene.contains(3152, 3255));
+        assert!(!scene.contains(3256, 3200));
+        assert!(!scene.contains(3151, 3200));
+        assert_eq!(scene.flags(3151, 3200), BLOCKED);
+    }
+
+    #[test]
+    fn test_scene_reads_across_regions() {
+        let map = CollisionMap::new();
+        map.block_tile(3199, 3200, 0);
+        map.block_tile(3200, 3255, 0);
+        let scene = Scene::around(&map, start());
+
+        assert_eq!(
+            Scene::region_ids(start()),
+            vec![
+                (49 << 8) | 49,
+                (49 << 8) | 50,
+                (50 << 8) | 49,
+                (50 << 8) | 50
+            ]
+        );
+        assert_eq!(scene.flags(3199, 3200), BLOCKED);
+        assert_eq!(scene.flags(3200, 3255), BLOCKED);
+        assert_eq!(scene.flags(3200, 3200), 0);
+
+        // A scene keeps the flags it was taken with
+        map.block_tile(3200, 3200, 0);
+        assert_eq!(scene.flags(3200, 3200), 0);
+        assert_eq!(Scene::around(&map, start()).flags(3200, 3200), BLOCKED);
+
+        // The scene stops at the edge of the map
+        let edge = Scene::around(&map, Location::new(u16::MAX, u16::MAX, 0));
+        assert_eq!(edge.flags(u16::MAX as i32, u16::MAX as i32), 0);
+        assert_eq!(
+            Scene::region_ids(Location::new(u16::MAX, u16::MAX, 0)).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_open_path_is_direct() {
+        let map = CollisionMap::new();
+        let scene = Scene::around(&map, start());
+
+        let path = find_path(&scene, start(), 3203, 3201).unwrap();
+        assert_eq!(path.len(), 3);
+        assert_eq!(path.last(), Some(&(3203, 3201)));
+        assert_eq!(find_path(&scene, start(), 3200, 3200), Some(vec![]));
+        assert_eq!(find_path(&scene, start(), 3300, 3200), None);
+    }
+
+    #[test]
+    fn test_path_goes_around_walls() {
+        let map = walled_map();
+        let scene = Scene::around(&map, start());
+
+        let path = find_path(&scene, start(), 3204, 3200).unwrap();
+        assert_eq!(path.last(), Some(&(3204, 3200)));
+        // Around the end of the wall rather than through it
+        assert!(path.len() > 4);
+        let steps: Vec<Location> = path.iter().map(|&(x, y)| Location::new(x, y, 0)).collect();
+        assert!(scene.is_walkable(start(), &steps));
+
+        let through = [
+            Location::new(3201, 3200, 0),
+            Location::new(3202, 3200, 0),
+            Location::new(3203, 3200, 0),
+        ];
+        assert!(!scene.is_walkable(start(), &through));
+    }
+
+    #[test]
+    fn test_unreachable_destination_stops_nearby() {
+        let map = CollisionMap::new();
+        // Box a tile in with objects
+        for (x, y) in [(3209, 3200), (3211, 3200), (3210, 3199), (3210, 3201)] {
+            map.add_object(x, y, 0, 1, 1);
+        }
+        for (x, y) in [(3209, 3199), (3211, 3199), (3209, 3201), (3211, 3201)] {
+            map.add_object(x, y, 0, 1, 1);
+        }
+        let scene = Scene::around(&map, start());
+
+        let path = find_path(&scene, start(), 3210, 3200).unwrap();
+        assert_eq!(path.last(), Some(&(3208, 3200)));
+    }
+
+    #[test]
+    fn test_path_to_entity_ends_beside_it() {
+        let map = CollisionMap::new();
+        map.add_object(3205, 3200, 0, 2, 2);
+        let scene = Scene::around(&map, start());
+
+        let path =
+            find_path_to_entity(&scene, start(), Location::new(3205, 3200, 0), 2, 2).unwrap();
+        assert_eq!(path.last(), Some(&(3204, 3200)));
+
+        // Already beside it
+        let beside = Location::new(3207, 3201, 0);
+        assert_eq!(
+            find_path_to_entity(&scene, beside, Location::new(3205, 3200, 0), 2, 2),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn test_path_to_entity_respects_walls() {
+        let map = walled_map();
+        // Target just behind the wall
+        map.add_object(3203, 3200, 0, 1, 1);
+        let scene = Scene::around(&map, start());
+
+        let path =
+            find_path_to_entity(&scene, start(), Location::new(3203, 3200, 0), 1, 1).unwrap();
+        let end = *path.last().unwrap();
+        assert_ne!(end, (3202, 3200));
+        assert!([(3204, 3200), (3203, 3199), (3203, 3201)].contains(&end));
+    }
+
+    #[test]
+    fn test_dumb_step() {
+        let map = CollisionMap::new();
+        let target = Location::new(3205, 3205, 0);
+        assert_eq!(
+            dumb_step(&map, start(), target),
+            Some(Location::new(3201, 3201, 0))
+        );
+        assert_eq!(dumb_step(&map, target, target), None);
+
+        // Blocked diagonally, slides along the free axis
+        map.add_object(3201, 3201, 0, 1, 1);
+        map.add_object(3201, 3200, 0, 1, 1);
+        assert_eq!(
+            dumb_step(&map, start(), target),
+            Some(Location::new(3200, 3201, 0))
+        );
+
+        map.add_object(3200, 3201, 0, 1, 1);
+        assert_eq!(dumb_step(&map, start(), target), None);
+    }
+}
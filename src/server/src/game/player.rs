@@ -18,6 +18,7 @@
 use crate::game::bank::Bank;
 use crate::game::equipment::Equipment;
 use crate::game::inventory::Inventory;
+use crate::game::movement::WalkingQueue;
 use crate::game::persistence::{
     Appearance as PersistenceAppearance, PlayerData, Position, Skill as PersistenceSkill,
 };
@@ -363,8 +364,8 @@ pub fn combat_level(&self) -> u8 {
 pub struct Player {
     /// Player index (1-2047)
     pub index: u16,
-    /// Associated session ID
-    pub session_id: SessionId,
+    /// Associated session ID (changes when the player resumes on a new connection)
+    session_id: AtomicU64,
     /// Database user UUID (from users table, for persistence)
     pub user_id: Option<Uuid>,
     /// Username
@@ -387,6 +388,8 @@ pub struct Player {
     pub run_energy: RwLock<u8>,
     /// Whether running is enabled
     pub running: RwLock<bool>,
+    /// Steps left to walk
+    pub walking_queue: RwLock<WalkingQueue>,
     /// Last activity timestamp (tick number)
     pub last_activity: AtomicU64,
     /// Player bank storage
@@ -406,7 +409,7 @@ pub fn new(index: u16, session_id: SessionId, username: String) -> Self {
 
         Self {
             index,
-            session_id,
+            session_id: AtomicU64::new(session_id),
             user_id: None,
             username,
             display_name,
@@ -418,6 +421,7 @@ pub fn new(index: u16, session_id: SessionId, username: String) -> Self {
             member: RwLock::new(false),
             run_energy: RwLock::new(100),
             running: RwLock::new(false),
+            walking_queue: RwLock::new(WalkingQueue::new()),
             last_activity: AtomicU64::new(0),
             bank: RwLock::new(Bank::new()),
             bank_open: RwLock::new(false),
@@ -515,7 +519,7 @@ pub fn from_player_data(
 
         Self {
             index,
-            session_id,
+            session_id: AtomicU64::new(session_id),
             user_id: Some(data.user_id),
             username: data.display_name.to_lowercase().replace(' ', "_"),
             display_name: data.display_name.clone(),
@@ -527,6 +531,7 @@ pub fn from_player_data(
             member: RwLock::new(member),
             run_energy: RwLock::new((data.run_energy / 100).min(100) as u8),
             running: RwLock::new(false),
+            walking_queue: RwLock::new(WalkingQueue::new()),
             last_activity: AtomicU64::new(0),
             bank: RwLock::new(bank),
             bank_open: RwLock::new(false),
@@ -643,6 +648,11 @@ pub fn to_player_data(&self, player_id: uuid::Uuid, user_id: uuid::Uuid) -> Play
         }
     }
 
+    /// Get the ID of the session the player is attached to
+    pub fn session_id(&self) -> SessionId {
+        self.session_id.load(Ordering::SeqCst)
+    }
+
     /// Get the player's username
     pub fn username(&self) -> &str {
         &self.username
@@ -674,8 +684,9 @@ pub fn set_location(&self, location: Location) {
         *self.location.write() = location;
     }
 
-    /// Teleport the player to a location
+    /// Teleport the player to a location, dropping any queued steps
     pub fn teleport(&self, location: Location) {
+        self.walking_queue.write().clear();
         *self.location.write() = location;
         *self.previous_location.write() = location;
     }
@@ -701,7 +712,7 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Player")
             .field("index", &self.index)
             .field("username", &self.username)
-            .field("session_id", &self.session_id)
+            .field("session_id", &self.session_id())
             .field("location", &self.location())
             .field("rights", &self.rights())
             .finish()
@@ -813,7 +824,7 @@ pub fn unregister(&self, index: u16) {
         if let Some((_, player)) = self.players.remove(&index) {
             self.username_to_index
                 .remove(&player.username.to_lowercase());
-            self.session_to_index.remove(&player.session_id);
+            self.session_to_index.remove(&player.session_id());
 
             info!(
                 index = index,
@@ -823,6 +834,27 @@ pub fn unregister(&self, index: u16) {
         }
     }
 
+    /// Attach a registered player to a new session
+    ///
+    /// Used when a player resumes after a dropped connection; the player keeps
+    /// its index and in-world state.
+    pub fn reattach(&self, index: u16, session_id: SessionId) -> Option<Arc<Player>> {
+        let player = self.get(index)?;
+        let old_session = player.session_id.swap(session_id, Ordering::SeqCst);
+        self.session_to_index.remove(&old_session);
+        self.session_to_index.insert(session_id, index);
+
+        info!(
+            index = index,
+            username = %player.username,
+            old_session_id = old_session,
+            session_id = session_id,
+            "Player reattached to new session"
+        );
+
+        Some(player)
+    }
+
     /// Get a player by index
     pub fn get(&self, index: u16) -> Option<Arc<Player>> {
         self.players.get(&index).map(|r| r.clone())
@@ -969,6 +1001,20 @@ fn test_player_manager() {
         assert_eq!(manager.count(), 0);
     }
 
+    #[test]
+    fn test_player_manager_reattach() {
+        let manager = PlayerManager::new(100);
+        let player = manager.register(1, "TestPlayer".to_string()).unwrap();
+
+        let reattached = manager.reattach(player.index, 2).unwrap();
+        assert_eq!(reattached.index, player.index);
+        assert_eq!(reattached.session_id(), 2);
+        assert!(manager.get_by_session(1).is_none());
+        assert_eq!(manager.get_by_session(2).unwrap().index, player.index);
+
+        assert!(manager.reattach(99, 3).is_none());
+    }
+
     #[test]
     fn test_player_manager_duplicate() {
         let manager = PlayerManager::new(100);
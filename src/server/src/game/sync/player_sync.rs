@@ -14,6 +14,7 @@
 use tracing::{debug, trace, warn};
 
 use crate::error::Result;
+use crate::game::movement;
 use crate::game::player::{Appearance, Location, Player, PlayerManager};
 use crate::net::buffer::PacketBuffer;
 
@@ -25,9 +26,6 @@
 /// View distance in tiles (how far players can see each other)
 pub const VIEW_DISTANCE: u16 = 15;
 
-/// Player update packet opcode
-pub const PLAYER_UPDATE_OPCODE: u8 = 81;
-
 /// Synchronization configuration
 #[derive(Debug, Clone)]
 pub struct SyncConfig {
@@ -259,8 +257,9 @@ pub fn set_run(&self, player_index: u16, dir1: u8, dir2: u8) {
     ///
     /// This should be called once per game tick. It:
     /// 1. Updates local player lists based on proximity
-    /// 2. Builds player update packets for each player
-    /// 3. Returns a map of player_index -> encoded packet data
+    /// 2. Builds player update bodies for each player
+    /// 3. Returns a map of player_index -> update body (framed by the
+    ///    session's protocol)
     pub fn process_tick(&self, players: &PlayerManager) -> HashMap<u16, Vec<u8>> {
         let mut packets = HashMap::new();
 
@@ -303,10 +302,17 @@ pub fn process_tick(&self, players: &PlayerManager) -> HashMap<u16, Vec<u8>> {
     }
 
     /// Detect movement by comparing current location to last known location
+    ///
+    /// Players whose steps were already set this tick (by the walking queue
+    /// or a teleport) are left alone.
     fn detect_movement(&self, players: &PlayerManager) {
         let mut states = self.states.write();
 
         for (player_index, state) in states.iter_mut() {
+            if state.movement != MovementType::None {
+                continue;
+            }
+
             let current_location = match players.get(*player_index) {
                 Some(p) => p.location(),
                 None => continue,
@@ -353,24 +359,8 @@ fn detect_movement(&self, players: &PlayerManager) {
 
     /// Calculate movement direction from one tile to an adjacent tile
     fn calculate_direction(&self, from_x: u16, from_y: u16, to_x: u16, to_y: u16) -> u8 {
-        let dx = to_x as i32 - from_x as i32;
-        let dy = to_y as i32 - from_y as i32;
-
-        // Direction mappings for RS2:
-        // 0 = NW, 1 = N, 2 = NE
-        // 3 = W,       4 = E
-        // 5 = SW, 6 = S, 7 = SE
-        match (dx, dy) {
-            (-1, 1) => 0,  // NW
-            (0, 1) => 1,   // N
-            (1, 1) => 2,   // NE
-            (-1, 0) => 3,  // W
-            (1, 0) => 4,   // E
-            (-1, -1) => 5, // SW
-            (0, -1) => 6,  // S
-            (1, -1) => 7,  // SE
-            _ => 1,        // Default to N
-        }
+        // Default to north for anything that isn't a single step
+        movement::direction(to_x as i32 - from_x as i32, to_y as i32 - from_y as i32).unwrap_or(1)
     }
 
     /// Update local player lists based on proximity
@@ -433,7 +423,8 @@ fn update_local_players(&self, players: &PlayerManager) {
         }
     }
 
-    /// Build a player update packet for a specific player
+    /// Build the player update body (bit section and update blocks) for a
+    /// specific player
     fn build_update_packet(
         &self,
         player: &Arc<Player>,
@@ -473,15 +464,7 @@ fn build_update_packet(
         // Append update blocks
         main_buffer.write_bytes(update_buffer.as_bytes());
 
-        // Build final packet with opcode and variable length
-        let mut final_packet = PacketBuffer::with_capacity(main_buffer.len() + 3);
-        final_packet.write_ubyte(PLAYER_UPDATE_OPCODE);
-        // Variable short length
-        let len = main_buffer.len() as u16;
-        final_packet.write_ushort(len);
-        final_packet.write_bytes(main_buffer.as_bytes());
-
-        Ok(final_packet.as_bytes().to_vec())
+        Ok(main_buffer.as_bytes().to_vec())
     }
 
     /// Write the local player (self) update section
@@ -1048,6 +1031,24 @@ fn test_movement_type_equality() {
         assert_ne!(MovementType::Walk(0), MovementType::Run(0, 0));
     }
 
+    #[test]
+    fn test_detect_movement_keeps_queued_steps() {
+        let manager = PlayerSyncManager::new();
+        let players = PlayerManager::new(10);
+        let player = players.register(1, "runner".to_string()).unwrap();
+        manager.register(&player);
+
+        // Two tiles in one tick would otherwise look like a teleport
+        let start = player.location();
+        player.set_location(Location::new(start.x, start.y + 2, start.z));
+        manager.set_run(player.index, 1, 1);
+        manager.detect_movement(&players);
+        assert_eq!(
+            manager.states.read()[&player.index].movement,
+            MovementType::Run(1, 1)
+        );
+    }
+
     #[test]
     fn test_sync_manager_creation() {
         let manager = PlayerSyncManager::new();
@@ -7,22 +7,31 @@
 //! - World events and broadcasts
 //! - Periodic autosave of player data
 //! - Player synchronization (multiplayer updates)
+//! - Applying queued inbound packets once per tick
 
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
 
 use std::time::{Duration, Instant};
 
 use parking_lot::RwLock;
 use tokio::sync::broadcast;
 use tokio::time::{interval, MissedTickBehavior};
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info, trace, warn};
 
 use crate::error::Result;
+use crate::game::movement;
+use crate::game::packet_queue::{InboundPacketQueues, DEFAULT_QUEUE_CAPACITY, DEFAULT_TICK_BUDGET};
+use crate::game::pathfinding::{self, CollisionMap};
 use crate::game::persistence::PlayerPersistence;
 use crate::game::player::PlayerManager;
+use crate::game::sync::player_sync::MovementType;
 use crate::game::sync::PlayerSyncManager;
-use crate::net::session::SessionManager;
+use crate::net::capture::Direction;
+use crate::net::session::{SessionManager, SessionState};
+use crate::protocol::game::GamePacketHandler;
+use crate::protocol::message::ServerMessage;
 use uuid::Uuid;
 
 /// Standard game tick rate in milliseconds
@@ -56,6 +65,10 @@ pub struct WorldSettings {
     pub max_players: usize,
     /// Autosave interval in ticks (0 to disable)
     pub autosave_interval: u64,
+    /// Maximum inbound packets applied per player each tick
+    pub packet_tick_budget: usize,
+    /// Maximum inbound packets queued per player
+    pub packet_queue_capacity: usize,
 }
 
 impl Default for WorldSettings {
@@ -69,6 +82,8 @@ fn default() -> Self {
             tick_rate_ms: TICK_RATE_MS,
             max_players: MAX_PLAYERS,
             autosave_interval: AUTOSAVE_INTERVAL_TICKS,
+            packet_tick_budget: DEFAULT_TICK_BUDGET,
+            packet_queue_capacity: DEFAULT_QUEUE_CAPACITY,
         }
     }
 }
@@ -148,8 +163,18 @@ pub struct GameWorld {
     pub players: PlayerManager,
     /// Player synchronization manager
     pub sync: PlayerSyncManager,
+    /// Inbound packets awaiting the next tick
+    pub inbound: InboundPacketQueues,
+    /// Collision flags for walking and pathfinding
+    pub collision: Arc<CollisionMap>,
+    /// Handler applying inbound packets to players
+    packet_handler: GamePacketHandler,
     /// Ticks since last autosave
     ticks_since_autosave: AtomicU64,
+    /// Inbound packets that failed to process
+    packet_errors: AtomicU64,
+    /// Ticks that took longer than the tick rate
+    slow_ticks: AtomicU64,
 }
 
 impl GameWorld {
@@ -168,6 +193,8 @@ pub fn with_settings(settings: WorldSettings) -> Result<Self> {
         );
 
         let max_players = settings.max_players.min(MAX_PLAYERS) as u16;
+        let inbound = InboundPacketQueues::new(settings.packet_queue_capacity);
+        let collision = Arc::new(CollisionMap::new());
 
         Ok(Self {
             settings,
@@ -179,10 +206,22 @@ pub fn with_settings(settings: WorldSettings) -> Result<Self> {
             player_count: AtomicU64::new(0),
             players: PlayerManager::new(max_players),
             sync: PlayerSyncManager::new(),
+            inbound,
+            packet_handler: GamePacketHandler::with_collision(collision.clone()),
+            collision,
             ticks_since_autosave: AtomicU64::new(0),
+            packet_errors: AtomicU64::new(0),
+            slow_ticks: AtomicU64::new(0),
         })
     }
 
+    /// Use a collision map (normally loaded from the cache) for walking
+    pub fn with_collision(mut self, collision: CollisionMap) -> Self {
+        self.collision = Arc::new(collision);
+        self.packet_handler = GamePacketHandler::with_collision(self.collision.clone());
+        self
+    }
+
     /// Get the current world state
     pub fn state(&self) -> WorldState {
         *self.state.read()
@@ -215,6 +254,16 @@ pub fn player_count(&self) -> u64 {
         self.player_count.load(Ordering::SeqCst)
     }
 
+    /// Get the number of inbound packets that failed to process
+    pub fn packet_errors(&self) -> u64 {
+        self.packet_errors.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of ticks that overran the tick rate
+    pub fn slow_ticks(&self) -> u64 {
+        self.slow_ticks.load(Ordering::Relaxed)
+    }
+
     /// Increment the player count
     pub fn increment_player_count(&self) {
         self.player_count.fetch_add(1, Ordering::SeqCst);
@@ -303,14 +352,27 @@ async fn run_full(
                         break;
                     }
 
+                    let tick_start = Instant::now();
+
                     // Process game tick with persistence for autosave
-                    if let Err(e) = self.process_tick(persistence).await {
+                    if let Err(e) = self.process_tick(persistence, session_manager).await {
                         error!(error = %e, "Error processing game tick");
                     }
 
-                    // Send player sync packets if session manager is available
+                    // Queue player sync packets and flush this tick's outboxes
                     if let Some(sessions) = session_manager {
                         self.send_sync_packets(sessions).await;
+                        Self::flush_outboxes(sessions);
+                    }
+
+                    let elapsed = tick_start.elapsed();
+                    if elapsed > Duration::from_millis(self.settings.tick_rate_ms) {
+                        self.slow_ticks.fetch_add(1, Ordering::Relaxed);
+                        warn!(
+                            tick = self.tick(),
+                            elapsed_ms = elapsed.as_millis() as u64,
+                            "Game tick overran the tick rate"
+                        );
                     }
 
                     // Check update countdown
@@ -350,7 +412,11 @@ async fn run_full(
     }
 
     /// Process a single game tick
-    async fn process_tick(&self, persistence: Option<&PlayerPersistence>) -> Result<()> {
+    async fn process_tick(
+        &self,
+        persistence: Option<&PlayerPersistence>,
+        session_manager: Option<&SessionManager>,
+    ) -> Result<()> {
         let tick_num = self.tick.fetch_add(1, Ordering::SeqCst);
 
         // Log periodically
@@ -363,13 +429,19 @@ async fn process_tick(&self, persistence: Option<&PlayerPersistence>) -> Result<
         }
 
         // Process in order:
-        // 1. Handle incoming packets (done in connection handlers)
+        // 1. Handle incoming packets queued by the connection handlers
         // 2. Process player actions
         // 3. Process NPC actions
         // 4. Process timers and events
-        // 5. Update entity positions
+        // 5. Update entity positions (walking queues)
         // 6. Build and prepare sync packets (actual sending done by caller)
 
+        let mut responses = self.process_inbound_packets();
+        self.process_movement(&mut responses);
+        if let Some(sessions) = session_manager {
+            Self::queue_responses(sessions, &self.players, tick_num, responses);
+        }
+
         // Check if autosave is due
         if self.settings.autosave_interval > 0 {
             let ticks = self.ticks_since_autosave.fetch_add(1, Ordering::SeqCst) + 1;
@@ -384,42 +456,160 @@ async fn process_tick(&self, persistence: Option<&PlayerPersistence>) -> Result<
         Ok(())
     }
 
+    /// Apply queued inbound packets for every player
+    ///
+    /// Players are handled in ascending index order, each with at most
+    /// `packet_tick_budget` packets. Returns the responses produced for each
+    /// player, in the order they were generated.
+    pub fn process_inbound_packets(&self) -> HashMap<u16, Vec<ServerMessage>> {
+        let mut responses = HashMap::new();
+
+        for player_index in self.inbound.pending_players() {
+            let Some(player) = self.players.get(player_index) else {
+                // Player logged out with packets still queued
+                self.inbound.remove(player_index);
+                continue;
+            };
+
+            let packets = self
+                .inbound
+                .drain(player_index, self.settings.packet_tick_budget);
+            let player_responses: &mut Vec<ServerMessage> =
+                responses.entry(player_index).or_default();
+
+            for packet in packets {
+                match self.packet_handler.process_with_player(&packet, &player) {
+                    Ok(result) => player_responses.extend(result.responses),
+                    Err(e) => {
+                        self.packet_errors.fetch_add(1, Ordering::Relaxed);
+                        warn!(
+                            player_index = player_index,
+                            opcode = packet.opcode,
+                            error = %e,
+                            "Error processing game packet"
+                        );
+                    }
+                }
+            }
+        }
+
+        responses.retain(|_, messages| !messages.is_empty());
+        responses
+    }
+
+    /// Take every player's queued steps for this tick
+    ///
+    /// Steps are passed to the player sync, and run energy changes are added
+    /// to the player's responses.
+    pub fn process_movement(&self, responses: &mut HashMap<u16, Vec<ServerMessage>>) {
+        self.players.for_each(|player| {
+            let tick = movement::process(player);
+            match tick.movement {
+                MovementType::Walk(dir) => self.sync.set_walk(player.index, dir),
+                MovementType::Run(dir1, dir2) => self.sync.set_run(player.index, dir1, dir2),
+                MovementType::None | MovementType::Teleport => {}
+            }
+            if tick.movement != MovementType::None {
+                // Have the regions the player walks toward ready for its next
+                // walk request
+                pathfinding::preload_scene(&self.collision, player.location());
+            }
+            if let Some(energy) = tick.run_energy {
+                responses
+                    .entry(player.index)
+                    .or_default()
+                    .push(ServerMessage::RunEnergy(energy));
+            }
+        });
+    }
+
+    /// Encode responses for each player's client and queue them in its
+    /// session outbox
+    fn queue_responses(
+        session_manager: &SessionManager,
+        players: &PlayerManager,
+        tick: u64,
+        responses: HashMap<u16, Vec<ServerMessage>>,
+    ) {
+        for (player_index, messages) in responses {
+            let Some(player) = players.get(player_index) else {
+                continue;
+            };
+            let Some(session) = session_manager.get_by_username(&player.username) else {
+                continue;
+            };
+
+            for packet in messages
+                .iter()
+                .filter_map(|message| session.encode_message(message))
+            {
+                session.capture(Direction::Outbound, tick, packet.opcode, &packet.data);
+                session.queue_packet(&packet);
+            }
+        }
+    }
+
     /// Process player synchronization and return packets to send
     ///
-    /// This builds player update packets for all connected players.
-    /// Returns a map of player_index -> encoded packet data.
+    /// This builds player update bodies for all connected players.
+    /// Returns a map of player_index -> update body.
     pub fn process_sync(&self) -> HashMap<u16, Vec<u8>> {
         self.sync.process_tick(&self.players)
     }
 
-    /// Send player update packets to all sessions
+    /// Queue player update packets in every session's outbox
     ///
-    /// This should be called after process_tick() each game tick.
+    /// This should be called after process_tick() each game tick, followed by
+    /// flush_outboxes() to send them.
     pub async fn send_sync_packets(&self, session_manager: &SessionManager) {
-        let packets = self.process_sync();
+        let updates = self.process_sync();
 
-        if packets.is_empty() {
+        if updates.is_empty() {
             return;
         }
 
-        trace!(player_count = packets.len(), "Sending player sync packets");
+        trace!(player_count = updates.len(), "Queueing player sync packets");
+
+        let tick = self.tick();
 
-        for (player_index, packet_data) in packets {
+        for (player_index, body) in updates {
             // Find the session for this player
-            if let Some(player) = self.players.get(player_index) {
-                if let Some(session) = session_manager.get_by_username(&player.username) {
-                    if let Err(e) = session.try_send(packet_data) {
-                        trace!(
-                            player_index = player_index,
-                            error = %e,
-                            "Failed to send sync packet"
-                        );
-                    }
-                }
+            let Some(player) = self.players.get(player_index) else {
+                continue;
+            };
+            let Some(session) = session_manager.get_by_username(&player.username) else {
+                continue;
+            };
+            let Some(packet) = session.encode_message(&ServerMessage::PlayerUpdate(body)) else {
+                continue;
+            };
+
+            let frame = packet.encode_raw();
+            if let Some((&opcode, payload)) = frame.split_first() {
+                session.capture(Direction::OutboundFrame, tick, opcode, payload);
             }
+            session.queue_frame(frame);
         }
     }
 
+    /// Encode and send every in-game session's outbox as one write
+    ///
+    /// Called once at the end of each tick. A session whose connection can't
+    /// keep up has already missed packets (and ISAAC values), so it is
+    /// disconnected rather than left desynchronised.
+    pub fn flush_outboxes(session_manager: &SessionManager) {
+        session_manager.for_each_in_game(|session| {
+            if let Err(e) = session.flush_outbox() {
+                warn!(
+                    session_id = session.id,
+                    error = %e,
+                    "Failed to flush outbound packets, disconnecting"
+                );
+                session.set_state(SessionState::Disconnecting);
+            }
+        });
+    }
+
     /// Register a player for synchronization
     ///
     /// Should be called when a player finishes login and enters the game world.
@@ -610,6 +800,9 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::game::player::Location;
+    use crate::net::buffer::PacketBuffer;
+    use crate::protocol::game::IncomingGamePacket;
 
     #[test]
     fn test_world_settings_default() {
@@ -688,6 +881,75 @@ fn test_update_countdown() {
         assert_eq!(world.state(), WorldState::Running);
     }
 
+    #[test]
+    fn test_inbound_packets_applied_within_budget() {
+        let settings = WorldSettings {
+            packet_tick_budget: 2,
+            ..WorldSettings::default()
+        };
+        let world = GameWorld::with_settings(settings).unwrap();
+        let player = world.players.register(1, "tick_test".to_string()).unwrap();
+
+        for _ in 0..3 {
+            // Keep-alive packets produce no responses
+            world
+                .inbound
+                .push(player.index, IncomingGamePacket::new(0, vec![]));
+        }
+
+        assert!(world.process_inbound_packets().is_empty());
+        assert_eq!(world.inbound.len(player.index), 1);
+
+        world.process_inbound_packets();
+        assert!(world.inbound.is_empty());
+    }
+
+    #[test]
+    fn test_walk_request_is_taken_over_ticks() {
+        let world = GameWorld::new(1).unwrap();
+        let player = world.players.register(1, "walk_test".to_string()).unwrap();
+        player.teleport(Location::new(3200, 3200, 0));
+        world.register_player_sync(player.index);
+
+        // Run to (3203, 3200), then north two tiles
+        let mut buffer = PacketBuffer::with_capacity(7);
+        buffer.write_ushort_le(3203);
+        buffer.write_short_a(3200);
+        buffer.write_byte_s(1);
+        buffer.write_byte(0);
+        buffer.write_byte(2);
+        world.inbound.push(
+            player.index,
+            IncomingGamePacket::new(98, buffer.as_bytes().to_vec()),
+        );
+
+        let mut responses = world.process_inbound_packets();
+        assert_eq!(player.location(), Location::new(3200, 3200, 0));
+
+        world.process_movement(&mut responses);
+        assert_eq!(player.location(), Location::new(3202, 3200, 0));
+        assert!(matches!(
+            responses[&player.index].last(),
+            Some(ServerMessage::RunEnergy(99))
+        ));
+        world.process_sync();
+
+        let mut responses = HashMap::new();
+        world.process_movement(&mut responses);
+        world.process_movement(&mut responses);
+        world.process_movement(&mut responses);
+        assert_eq!(player.location(), Location::new(3203, 3202, 0));
+    }
+
+    #[test]
+    fn test_inbound_packets_dropped_for_unknown_player() {
+        let world = GameWorld::new(1).unwrap();
+        world.inbound.push(42, IncomingGamePacket::new(0, vec![]));
+
+        world.process_inbound_packets();
+        assert!(world.inbound.pending_players().is_empty());
+    }
+
     #[test]
     fn test_world_info() {
         let world = GameWorld::new(1).unwrap();
@@ -0,0 +1,340 @@
+//! World list
+//!
+//! Every server keeps its own row in the `worlds` table up to date:
+//! - On startup the row is upserted and marked online
+//! - Every `heartbeat_interval_ticks` the player count and `last_heartbeat`
+//!   are refreshed, worlds whose heartbeat is older than `stale_after_secs`
+//!   are marked offline, and the online worlds are read back
+//! - On shutdown the row is marked offline
+//!
+//! The world list handshake and `/api/worlds` are answered from the worlds
+//! read at the last heartbeat, with this world's own entry always present and
+//! carrying its live player count. Without a database only this world is
+//! listed.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+use sqlx::FromRow;
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+use crate::config::ServerConfig;
+use crate::error::{GameError, Result, RustscapeError};
+use crate::game::world::GameWorld;
+use crate::net::buffer::PacketBuffer;
+
+/// World flag: members only
+pub const WORLD_FLAG_MEMBERS: i32 = 0x1;
+
+/// A world as shown in the world list
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorldEntry {
+    /// World ID
+    pub id: u16,
+    /// Display name
+    pub name: String,
+    /// Address clients connect to
+    pub address: String,
+    /// Port clients connect to
+    pub port: u16,
+    /// Members only
+    pub members: bool,
+    /// Players online
+    pub players: u32,
+    /// Player capacity
+    pub max_players: u32,
+    /// ISO country code
+    pub country_code: String,
+}
+
+/// Row of the `worlds` table
+#[derive(Debug, FromRow)]
+struct WorldRow {
+    id: i16,
+    name: String,
+    address: String,
+    port: i32,
+    is_members: bool,
+    player_count: i32,
+    max_players: i32,
+    country_code: String,
+}
+
+impl From<WorldRow> for WorldEntry {
+    fn from(row: WorldRow) -> Self {
+        Self {
+            id: row.id as u16,
+            name: row.name,
+            address: row.address,
+            port: row.port as u16,
+            members: row.is_members,
+            players: row.player_count.max(0) as u32,
+            max_players: row.max_players.max(0) as u32,
+            country_code: row.country_code,
+        }
+    }
+}
+
+/// This server's view of the world list
+pub struct WorldDirectory {
+    /// This world's entry (player count filled in when listed)
+    local: WorldEntry,
+    /// Time between heartbeats
+    heartbeat_interval: Duration,
+    /// Heartbeat age after which a world is considered offline
+    stale_after: Duration,
+    /// Database holding the `worlds` table (None to list only this world)
+    pool: Option<PgPool>,
+    /// Online worlds as of the last heartbeat
+    online: RwLock<Vec<WorldEntry>>,
+}
+
+impl WorldDirectory {
+    /// Create a directory for the world described by the config
+    pub fn new(config: &ServerConfig, pool: Option<PgPool>) -> Self {
+        Self {
+            local: WorldEntry {
+                id: config.world_id as u16,
+                name: config.server_name.clone(),
+                address: config.world_list.address.clone(),
+                port: config.websocket_port,
+                members: false,
+                players: 0,
+                max_players: config.player_capacity() as u32,
+                country_code: "US".to_string(),
+            },
+            heartbeat_interval: Duration::from_millis(
+                config.tick_rate_ms * config.world_list.heartbeat_interval_ticks,
+            ),
+            stale_after: Duration::from_secs(config.world_list.stale_after_secs),
+            pool,
+            online: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Get the online worlds in ID order, with `players` as this world's count
+    pub fn worlds(&self, players: u32) -> Vec<WorldEntry> {
+        let mut worlds = self.online.read().clone();
+        match worlds.iter_mut().find(|world| world.id == self.local.id) {
+            Some(local) => local.players = players,
+            None => {
+                worlds.push(WorldEntry {
+                    players,
+                    ..self.local.clone()
+                });
+                worlds.sort_by_key(|world| world.id);
+            }
+        }
+        worlds
+    }
+
+    /// Upsert this world's row, expire stale worlds and reload the online list
+    pub async fn heartbeat(&self, players: u32) -> Result<()> {
+        let Some(pool) = &self.pool else {
+            return Ok(());
+        };
+
+        // Membership and region are managed in the table, not by the server
+        sqlx::query(
+            r#"
+            INSERT INTO worlds (id, name, ip_address, port, is_online, player_count, max_players, last_heartbeat)
+            VALUES ($1, $2, $3::inet, $4, TRUE, $5, $6, NOW())
+            ON CONFLICT (id) DO UPDATE SET
+                name = $2,
+                ip_address = $3::inet,
+                port = $4,
+                is_online = TRUE,
+                player_count = $5,
+                max_players = $6,
+                last_heartbeat = NOW()
+            "#,
+        )
+        .bind(self.local.id as i16)
+        .bind(&self.local.name)
+        .bind(&self.local.address)
+        .bind(self.local.port as i32)
+        .bind(players as i32)
+        .bind(self.local.max_players as i32)
+        .execute(pool)
+        .await
+        .map_err(database_error)?;
+
+        let expired = sqlx::query(
+            r#"
+            UPDATE worlds SET is_online = FALSE, player_count = 0
+            WHERE is_online
+              AND (last_heartbeat IS NULL OR last_heartbeat < NOW() - make_interval(secs => $1))
+            "#,
+        )
+        .bind(self.stale_after.as_secs_f64())
+        .execute(pool)
+        .await
+        .map_err(database_error)?
+        .rows_affected();
+        if expired > 0 {
+            info!(
+                worlds = expired,
+                "Marked worlds with stale heartbeats offline"
+            );
+        }
+
+        let rows: Vec<WorldRow> = sqlx::query_as(
+            r#"
+            SELECT id, name, host(ip_address) AS address, port, is_members,
+                   player_count, max_players, country_code
+            FROM worlds
+            WHERE is_online
+            ORDER BY id
+            "#,
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(database_error)?;
+
+        *self.online.write() = rows.into_iter().map(WorldEntry::from).collect();
+        Ok(())
+    }
+
+    /// Mark this world offline
+    pub async fn go_offline(&self) -> Result<()> {
+        let Some(pool) = &self.pool else {
+            return Ok(());
+        };
+
+        sqlx::query("UPDATE worlds SET is_online = FALSE, player_count = 0 WHERE id = $1")
+            .bind(self.local.id as i16)
+            .execute(pool)
+            .await
+            .map_err(database_error)?;
+        Ok(())
+    }
+
+    /// Heartbeat until shutdown, then mark this world offline
+    pub async fn run(
+        self: Arc<Self>,
+        world: Arc<GameWorld>,
+        mut shutdown_rx: broadcast::Receiver<()>,
+    ) {
+        if self.pool.is_none() {
+            debug!("No database, world list will only contain this world");
+            return;
+        }
+
+        let mut ticker = tokio::time::interval(self.heartbeat_interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    if let Err(e) = self.heartbeat(world.player_count() as u32).await {
+                        warn!(error = %e, "World heartbeat failed");
+                    }
+                }
+                _ = shutdown_rx.recv() => break,
+            }
+        }
+
+        match self.go_offline().await {
+            Ok(()) => info!(world_id = self.local.id, "World marked offline"),
+            Err(e) => warn!(error = %e, "Failed to mark world offline"),
+        }
+    }
+}
+
+impl std::fmt::Debug for WorldDirectory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorldDirectory")
+            .field("local", &self.local)
+            .field("database", &self.pool.is_some())
+            .finish()
+    }
+}
+
+/// Encode the world list handshake response
+pub fn encode_world_list(worlds: &[WorldEntry]) -> Vec<u8> {
+    let mut response = PacketBuffer::with_capacity(2 + worlds.len() * 32);
+    response.write_ubyte(1); // Success
+    response.write_ubyte(worlds.len().min(u8::MAX as usize) as u8);
+
+    for world in worlds.iter().take(u8::MAX as usize) {
+        let flags = if world.members { WORLD_FLAG_MEMBERS } else { 0 };
+        response.write_ushort(world.id);
+        response.write_int(flags);
+        response.write_string(&world.name);
+        response.write_string(&world.address);
+        response.write_ushort(world.players.min(u16::MAX as u32) as u16);
+    }
+
+    response.as_bytes().to_vec()
+}
+
+fn database_error(e: sqlx::Error) -> RustscapeError {
+    RustscapeError::Game(GameError::DatabaseError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world(id: u16, players: u32) -> WorldEntry {
+        WorldEntry {
+            id,
+            name: format!("World {}", id),
+            address: "10.0.0.1".to_string(),
+            port: 43596,
+            members: id == 2,
+            players,
+            max_players: 2000,
+            country_code: "US".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_local_world_listed_without_database() {
+        let directory = WorldDirectory::new(&ServerConfig::default(), None);
+        let worlds = directory.worlds(12);
+
+        assert_eq!(worlds.len(), 1);
+        assert_eq!(worlds[0].id, 1);
+        assert_eq!(worlds[0].name, "Rustscape");
+        assert_eq!(worlds[0].players, 12);
+    }
+
+    #[test]
+    fn test_local_entry_carries_live_player_count() {
+        let config = ServerConfig {
+            world_id: 2,
+            ..Default::default()
+        };
+        let directory = WorldDirectory::new(&config, None);
+        *directory.online.write() = vec![world(1, 40), world(2, 5), world(3, 0)];
+
+        let worlds = directory.worlds(7);
+        assert_eq!(
+            worlds.iter().map(|w| (w.id, w.players)).collect::<Vec<_>>(),
+            vec![(1, 40), (2, 7), (3, 0)]
+        );
+
+        // Missing from the table (e.g. database briefly unreachable)
+        *directory.online.write() = vec![world(1, 40), world(3, 0)];
+        let ids: Vec<u16> = directory.worlds(7).iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_encode_world_list() {
+        let data = encode_world_list(&[world(2, 300)]);
+
+        let mut buffer = PacketBuffer::from_bytes(&data);
+        assert_eq!(buffer.read_ubyte(), 1);
+        assert_eq!(buffer.read_ubyte(), 1);
+        assert_eq!(buffer.read_ushort(), 2);
+        assert_eq!(buffer.read_int(), WORLD_FLAG_MEMBERS);
+        assert_eq!(buffer.read_string(), "World 2");
+        assert_eq!(buffer.read_string(), "10.0.0.1");
+        assert_eq!(buffer.read_ushort(), 300);
+        assert!(!buffer.has_remaining());
+    }
+}
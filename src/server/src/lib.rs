@@ -6,6 +6,7 @@
 //! ## Modules
 //!
 //! - `cache` - Game cache reading and serving (sprites, models, maps, etc.)
+//! - `client` - Headless protocol client for bots, load tests and integration tests
 //! - `config` - Server configuration management
 //! - `crypto` - Cryptographic utilities (ISAAC, RSA, etc.)
 //! - `error` - Error types and result definitions
@@ -16,6 +17,7 @@
 pub mod api;
 pub mod auth;
 pub mod cache;
+pub mod client;
 pub mod config;
 pub mod crypto;
 pub mod error;
@@ -62,6 +62,11 @@ async fn main() -> Result<()> {
     };
     info!("Application state initialized");
 
+    // Encode and pin the JS5 reference tables before clients connect
+    if let Err(e) = state.js5_responses.warm() {
+        warn!("Failed to pin JS5 reference tables: {}", e);
+    }
+
     // Initialize API state (with PostgreSQL and Redis)
     let api_state = match AuthState::new(&config).await {
         Ok(auth_state) => {
@@ -104,6 +109,22 @@ async fn main() -> Result<()> {
         }
     });
 
+    // Keep this world's row in the world list current
+    let worlds_handle = tokio::spawn(
+        state
+            .worlds
+            .clone()
+            .run(state.world.clone(), shutdown_tx.subscribe()),
+    );
+
+    // Renew this world's login leases with the coordinator
+    let leases_handle = tokio::spawn(
+        state
+            .leases
+            .clone()
+            .run(state.clone(), shutdown_tx.subscribe()),
+    );
+
     // Start TCP listener for game connections
     let game_addr: SocketAddr = format!("0.0.0.0:{}", config.game_port).parse()?;
     let game_listener = TcpListener::bind(game_addr).await?;
@@ -112,7 +133,18 @@ async fn main() -> Result<()> {
     // Start WebSocket listener for browser clients
     let ws_addr: SocketAddr = format!("0.0.0.0:{}", config.websocket_port).parse()?;
     let ws_listener = TcpListener::bind(ws_addr).await?;
-    info!("WebSocket server listening on: {}", ws_addr);
+    match &state.tls {
+        Some(tls) => {
+            info!("WebSocket server listening on: {} (wss)", ws_addr);
+
+            // Pick up renewed certificates without a restart
+            if config.tls.reload_interval_secs > 0 {
+                let interval = std::time::Duration::from_secs(config.tls.reload_interval_secs);
+                tokio::spawn(tls.clone().watch(interval, shutdown_tx.subscribe()));
+            }
+        }
+        None => info!("WebSocket server listening on: {}", ws_addr),
+    }
 
     // Spawn game connection acceptor
     let game_state = state.clone();
@@ -128,19 +160,33 @@ async fn main() -> Result<()> {
         accept_websocket_connections(ws_listener, ws_state, &mut ws_shutdown_rx).await;
     });
 
-    // Start HTTP API server if initialized
-    let api_handle = if let Some(api_state) = api_state {
-        let api_addr: SocketAddr = format!("0.0.0.0:{}", config.management_port).parse()?;
-        let api_listener = TcpListener::bind(api_addr).await?;
-        info!("REST API server listening on: {}", api_addr);
-
-        let api_shutdown_rx = shutdown_tx.subscribe();
-        Some(tokio::spawn(async move {
-            run_api_server(api_listener, api_state, api_shutdown_rx).await;
-        }))
-    } else {
-        None
+    // Start HTTP server (asset, stats and world list routes always, REST API if initialized)
+    let asset_router = api::assets::create_router(state.cache.clone())
+        .merge(api::stats::create_router(state.clone()))
+        .merge(api::worlds::create_router(state.clone()));
+    let asset_router = match &state.coordinator {
+        Some(coordinator) => asset_router.merge(api::coordinator::create_router(
+            coordinator.clone(),
+            &config.coordinator.secret,
+        )),
+        None => asset_router,
     };
+    let router = match api_state {
+        Some(api_state) => api::create_router(api_state).merge(asset_router),
+        None => {
+            warn!("REST API disabled; serving assets, stats and the world list only");
+            asset_router
+        }
+    };
+
+    let api_addr: SocketAddr = format!("0.0.0.0:{}", config.management_port).parse()?;
+    let api_listener = TcpListener::bind(api_addr).await?;
+    info!("HTTP server listening on: {}", api_addr);
+
+    let api_shutdown_rx = shutdown_tx.subscribe();
+    let api_handle = tokio::spawn(async move {
+        run_api_server(api_listener, router, api_shutdown_rx).await;
+    });
 
     info!("Server startup complete!");
     info!("World {} is ready for connections", config.world_id);
@@ -153,9 +199,9 @@ async fn main() -> Result<()> {
     // Wait for handlers to finish
     let _ = game_handle.await;
     let _ = ws_handle.await;
-    if let Some(handle) = api_handle {
-        let _ = handle.await;
-    }
+    let _ = api_handle.await;
+    let _ = worlds_handle.await;
+    let _ = leases_handle.await;
 
     // Cleanup
     state.session_manager.disconnect_all().await;
@@ -247,12 +293,9 @@ async fn accept_websocket_connections(
 /// Run the HTTP API server
 async fn run_api_server(
     listener: TcpListener,
-    state: ApiState,
+    router: axum::Router,
     mut shutdown_rx: broadcast::Receiver<()>,
 ) {
-    // Create the API router
-    let router = api::create_router(state);
-
     // Serve with graceful shutdown
     info!("Starting REST API server...");
 
@@ -0,0 +1,490 @@
+//! Login admission control
+//!
+//! Decides, before authentication, whether a login may go ahead:
+//! - At most `max_logins_per_tick` logins are let through per world tick
+//! - Logins over that cap wait in a queue and are answered with `Delay` and
+//!   their position; the client retries and keeps its place until it stops
+//!   retrying for `queue_timeout_ticks`. Each tick's slots go to the front of
+//!   the queue first, and are held until the player's next attempt
+//! - A full world, or a full queue, turns logins away
+//! - Failed logins are counted per IP address and per username over
+//!   `failure_window_ticks`; past the limit further attempts are refused
+//!   without checking the password. Expired windows are swept at most once a
+//!   tick as failures are recorded, so addresses and usernames that stop
+//!   failing don't pile up
+//! - Lobby logins don't enter the world, so only the failure limits apply
+//!
+//! Like flood protection, everything is counted in world ticks.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::normalize_username;
+use crate::config::LoginConfig;
+use crate::error::AuthError;
+
+/// Outcome of a login that was not turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// Go ahead and authenticate
+    Admitted,
+    /// Wait and retry; `position` is 1-based
+    Queued { position: usize },
+}
+
+/// A player waiting to log in
+#[derive(Debug)]
+struct QueueEntry {
+    /// Normalized username
+    username: String,
+    /// Tick of the most recent attempt
+    last_seen: u64,
+    /// Whether a login slot has been set aside for them
+    granted: bool,
+}
+
+/// Ticks of recent failed logins for one IP address or username
+#[derive(Debug, Default)]
+struct FailureWindow {
+    ticks: VecDeque<u64>,
+}
+
+impl FailureWindow {
+    /// Drop failures older than `window` ticks and count the rest
+    fn count(&mut self, tick: u64, window: u64) -> usize {
+        while let Some(&oldest) = self.ticks.front() {
+            if tick.saturating_sub(oldest) < window {
+                break;
+            }
+            self.ticks.pop_front();
+        }
+        self.ticks.len()
+    }
+}
+
+/// Login admission statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdmissionStats {
+    /// Logins let through to authentication
+    pub admitted: u64,
+    /// Attempts answered with a queue position
+    pub queued: u64,
+    /// Logins refused because the world was full
+    pub world_full: u64,
+    /// Logins refused because the queue was full
+    pub queue_full: u64,
+    /// Logins refused after too many failed attempts
+    pub throttled: u64,
+    /// Players currently waiting
+    pub queue_length: usize,
+}
+
+#[derive(Debug, Default)]
+struct AdmissionState {
+    /// Tick `admitted_this_tick` belongs to
+    tick: u64,
+    /// Logins let through during `tick`
+    admitted_this_tick: u32,
+    /// Players waiting, oldest first
+    queue: VecDeque<QueueEntry>,
+    /// Recent failures by IP address
+    ip_failures: HashMap<IpAddr, FailureWindow>,
+    /// Recent failures by normalized username
+    username_failures: HashMap<String, FailureWindow>,
+    /// Tick the failure maps were last swept
+    swept_tick: u64,
+    /// Counters
+    stats: AdmissionStats,
+}
+
+/// Process-wide login admission controller
+#[derive(Debug)]
+pub struct LoginAdmission {
+    /// Most players online at once
+    capacity: usize,
+    /// Logins let through per tick (0 = unlimited)
+    max_per_tick: u32,
+    /// Most players waiting at once
+    queue_capacity: usize,
+    /// Ticks without a retry before a queued player loses their place
+    queue_timeout: u64,
+    /// Failed logins from one IP address before refusing it (0 = unlimited)
+    max_ip_failures: u32,
+    /// Failed logins for one username before refusing it (0 = unlimited)
+    max_username_failures: u32,
+    /// Ticks a failed login counts against its IP address and username
+    failure_window: u64,
+    state: Mutex<AdmissionState>,
+}
+
+impl LoginAdmission {
+    /// Create a controller for a world holding `capacity` players
+    pub fn new(config: &LoginConfig, capacity: usize) -> Self {
+        Self {
+            capacity,
+            max_per_tick: config.max_logins_per_tick,
+            queue_capacity: config.queue_capacity,
+            queue_timeout: config.queue_timeout_ticks,
+            max_ip_failures: config.max_failed_per_ip,
+            max_username_failures: config.max_failed_per_username,
+            failure_window: config.failure_window_ticks,
+            state: Mutex::new(AdmissionState::default()),
+        }
+    }
+
+    /// Decide whether a login during world tick `tick` may authenticate,
+    /// with `online` players already in the world
+    ///
+    /// Refusals are returned as the `AuthError` to report to the client.
+    pub fn admit(
+        &self,
+        tick: u64,
+        ip: IpAddr,
+        username: &str,
+        online: usize,
+    ) -> Result<Admission, AuthError> {
+        let username = normalize_username(username);
+        let mut state = self.state.lock();
+
+        if self.over_failure_limit(&mut state, tick, ip, &username) {
+            state.stats.throttled += 1;
+            return Err(AuthError::TooManyAttempts);
+        }
+
+        if online >= self.capacity {
+            state.stats.world_full += 1;
+            return Err(AuthError::WorldFull);
+        }
+
+        let timeout = self.queue_timeout;
+        state
+            .queue
+            .retain(|entry| tick.saturating_sub(entry.last_seen) <= timeout);
+        self.grant_slots(&mut state, tick);
+
+        if let Some(index) = state
+            .queue
+            .iter()
+            .position(|entry| entry.username == username)
+        {
+            if state.queue[index].granted {
+                state.queue.remove(index);
+                state.stats.admitted += 1;
+                return Ok(Admission::Admitted);
+            }
+            state.queue[index].last_seen = tick;
+            state.stats.queued += 1;
+            return Ok(Admission::Queued {
+                position: index + 1,
+            });
+        }
+
+        // Slots only remain once everyone waiting has been granted one
+        if self.max_per_tick == 0 || state.admitted_this_tick < self.max_per_tick {
+            state.admitted_this_tick += 1;
+            state.stats.admitted += 1;
+            return Ok(Admission::Admitted);
+        }
+
+        if state.queue.len() >= self.queue_capacity {
+            state.stats.queue_full += 1;
+            return Err(AuthError::LoginLimitExceeded);
+        }
+        state.queue.push_back(QueueEntry {
+            username,
+            last_seen: tick,
+            granted: false,
+        });
+        state.stats.queued += 1;
+        Ok(Admission::Queued {
+            position: state.queue.len(),
+        })
+    }
+
+    /// Hand free login slots to the players at the front of the queue
+    ///
+    /// Queued clients only retry every few ticks, so slots left unused since
+    /// the previous call are handed out as well; a granted player is let in
+    /// on their next attempt.
+    fn grant_slots(&self, state: &mut AdmissionState, tick: u64) {
+        if self.max_per_tick == 0 {
+            return;
+        }
+        let per_tick = self.max_per_tick as u64;
+
+        if tick != state.tick {
+            let mut unused = per_tick
+                .saturating_sub(state.admitted_this_tick as u64)
+                .saturating_add(
+                    per_tick.saturating_mul(tick.saturating_sub(state.tick).saturating_sub(1)),
+                );
+            for entry in state.queue.iter_mut().filter(|entry| !entry.granted) {
+                if unused == 0 {
+                    break;
+                }
+                entry.granted = true;
+                unused -= 1;
+            }
+            state.tick = tick;
+            state.admitted_this_tick = 0;
+        }
+
+        for entry in state.queue.iter_mut().filter(|entry| !entry.granted) {
+            if state.admitted_this_tick >= self.max_per_tick {
+                break;
+            }
+            entry.granted = true;
+            state.admitted_this_tick += 1;
+        }
+    }
+
+    /// Check only the failure limits, for logins that don't enter the world
+    pub fn check_failures(&self, tick: u64, ip: IpAddr, username: &str) -> Result<(), AuthError> {
+        let username = normalize_username(username);
+        let mut state = self.state.lock();
+
+        if self.over_failure_limit(&mut state, tick, ip, &username) {
+            state.stats.throttled += 1;
+            return Err(AuthError::TooManyAttempts);
+        }
+        Ok(())
+    }
+
+    /// Count a failed login against its IP address and username
+    pub fn record_failure(&self, tick: u64, ip: IpAddr, username: &str) {
+        let mut state = self.state.lock();
+        if tick != state.swept_tick {
+            let window = self.failure_window;
+            state
+                .ip_failures
+                .retain(|_, failures| failures.count(tick, window) > 0);
+            state
+                .username_failures
+                .retain(|_, failures| failures.count(tick, window) > 0);
+            state.swept_tick = tick;
+        }
+
+        state
+            .ip_failures
+            .entry(ip)
+            .or_default()
+            .ticks
+            .push_back(tick);
+        state
+            .username_failures
+            .entry(normalize_username(username))
+            .or_default()
+            .ticks
+            .push_back(tick);
+    }
+
+    /// Forget a username's failed logins after it logs in
+    pub fn record_success(&self, username: &str) {
+        self.state
+            .lock()
+            .username_failures
+            .remove(&normalize_username(username));
+    }
+
+    /// Get the current counters
+    pub fn stats(&self) -> AdmissionStats {
+        let state = self.state.lock();
+        AdmissionStats {
+            queue_length: state.queue.len(),
+            ..state.stats
+        }
+    }
+
+    fn over_failure_limit(
+        &self,
+        state: &mut AdmissionState,
+        tick: u64,
+        ip: IpAddr,
+        username: &str,
+    ) -> bool {
+        let window = self.failure_window;
+        let ip_failures = Self::recent_failures(&mut state.ip_failures, &ip, tick, window);
+        let username_failures =
+            Self::recent_failures(&mut state.username_failures, username, tick, window);
+
+        (self.max_ip_failures > 0 && ip_failures >= self.max_ip_failures as usize)
+            || (self.max_username_failures > 0
+                && username_failures >= self.max_username_failures as usize)
+    }
+
+    /// Count a key's failures within the window, dropping it once none are left
+    fn recent_failures<K, Q>(
+        failures: &mut HashMap<K, FailureWindow>,
+        key: &Q,
+        tick: u64,
+        window: u64,
+    ) -> usize
+    where
+        K: std::borrow::Borrow<Q> + std::hash::Hash + Eq,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        let Some(entry) = failures.get_mut(key) else {
+            return 0;
+        };
+        let count = entry.count(tick, window);
+        if count == 0 {
+            failures.remove(key);
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
+
+    fn admission(capacity: usize) -> LoginAdmission {
+        let config = LoginConfig {
+            max_logins_per_tick: 2,
+            queue_capacity: 2,
+            queue_timeout_ticks: 5,
+            max_failed_per_ip: 4,
+            max_failed_per_username: 2,
+            failure_window_ticks: 10,
+            ..LoginConfig::default()
+        };
+        LoginAdmission::new(&config, capacity)
+    }
+
+    #[test]
+    fn test_logins_over_tick_cap_are_queued_in_order() {
+        let admission = admission(100);
+
+        assert_eq!(admission.admit(1, IP, "a", 0), Ok(Admission::Admitted));
+        assert_eq!(admission.admit(1, IP, "b", 0), Ok(Admission::Admitted));
+        assert_eq!(
+            admission.admit(1, IP, "c", 0),
+            Ok(Admission::Queued { position: 1 })
+        );
+        assert_eq!(
+            admission.admit(1, IP, "d", 0),
+            Ok(Admission::Queued { position: 2 })
+        );
+        assert_eq!(
+            admission.admit(1, IP, "e", 0),
+            Err(AuthError::LoginLimitExceeded)
+        );
+
+        // Next tick's slots go to the queue, ahead of new logins
+        assert_eq!(admission.admit(2, IP, "D", 0), Ok(Admission::Admitted));
+        assert_eq!(
+            admission.admit(2, IP, "f", 0),
+            Ok(Admission::Queued { position: 2 })
+        );
+        assert_eq!(admission.admit(3, IP, "c", 0), Ok(Admission::Admitted));
+        assert_eq!(admission.admit(3, IP, "f", 0), Ok(Admission::Admitted));
+
+        let stats = admission.stats();
+        assert_eq!(stats.admitted, 5);
+        assert_eq!(stats.queued, 3);
+        assert_eq!(stats.queue_full, 1);
+        assert_eq!(stats.queue_length, 0);
+    }
+
+    #[test]
+    fn test_slots_unused_between_attempts_go_to_the_queue() {
+        let admission = admission(100);
+        for name in ["a", "b", "c", "d"] {
+            admission.admit(1, IP, name, 0).unwrap();
+        }
+
+        // Nobody called during ticks 2-4; their slots were still available
+        assert_eq!(admission.admit(5, IP, "e", 0), Ok(Admission::Admitted));
+        assert_eq!(admission.admit(5, IP, "d", 0), Ok(Admission::Admitted));
+        assert_eq!(admission.admit(5, IP, "c", 0), Ok(Admission::Admitted));
+    }
+
+    #[test]
+    fn test_queued_players_that_stop_retrying_lose_their_place() {
+        let admission = admission(100);
+        admission.admit(1, IP, "a", 0).unwrap();
+        admission.admit(1, IP, "b", 0).unwrap();
+        admission.admit(1, IP, "c", 0).unwrap();
+
+        // "c" never comes back
+        assert_eq!(admission.admit(7, IP, "d", 0), Ok(Admission::Admitted));
+        assert_eq!(admission.admit(7, IP, "e", 0), Ok(Admission::Admitted));
+        assert_eq!(admission.stats().queue_length, 0);
+    }
+
+    #[test]
+    fn test_full_world_is_refused() {
+        let admission = admission(10);
+        assert_eq!(admission.admit(1, IP, "a", 10), Err(AuthError::WorldFull));
+        assert_eq!(admission.admit(1, IP, "a", 9), Ok(Admission::Admitted));
+        assert_eq!(admission.stats().world_full, 1);
+    }
+
+    #[test]
+    fn test_failed_logins_throttle_username_and_ip() {
+        let admission = admission(100);
+
+        admission.record_failure(1, IP, "alice");
+        admission.record_failure(1, IP, "alice");
+        assert_eq!(
+            admission.admit(2, IP, "Alice", 0),
+            Err(AuthError::TooManyAttempts)
+        );
+
+        // Another name from the same address until the address runs out
+        assert_eq!(admission.admit(2, IP, "bob", 0), Ok(Admission::Admitted));
+        admission.record_failure(2, IP, "bob");
+        admission.record_failure(2, IP, "carol");
+        assert_eq!(
+            admission.admit(3, IP, "dave", 0),
+            Err(AuthError::TooManyAttempts)
+        );
+        let other = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!(
+            admission.admit(3, other, "dave", 0),
+            Ok(Admission::Admitted)
+        );
+
+        // Lobby logins are throttled the same way
+        assert_eq!(
+            admission.check_failures(3, IP, "erin"),
+            Err(AuthError::TooManyAttempts)
+        );
+
+        // Failures expire with the window
+        assert_eq!(admission.admit(12, IP, "dave", 0), Ok(Admission::Admitted));
+        assert_eq!(admission.check_failures(12, IP, "erin"), Ok(()));
+        assert_eq!(admission.stats().throttled, 3);
+    }
+
+    #[test]
+    fn test_expired_failures_are_swept() {
+        let admission = admission(10);
+        for i in 0..100u8 {
+            let ip = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 1, i));
+            admission.record_failure(1, ip, &format!("user{}", i));
+        }
+
+        // Nobody is looked up again, but the next failure after the window
+        // drops them all
+        let window = admission.failure_window;
+        admission.record_failure(1 + window, IP, "zezima");
+        let state = admission.state.lock();
+        assert_eq!(state.ip_failures.len(), 1);
+        assert_eq!(state.username_failures.len(), 1);
+    }
+
+    #[test]
+    fn test_success_clears_username_failures() {
+        let admission = admission(100);
+        admission.record_failure(1, IP, "alice");
+        admission.record_success("alice");
+        admission.record_failure(1, IP, "alice");
+        assert_eq!(admission.admit(1, IP, "alice", 0), Ok(Admission::Admitted));
+    }
+}
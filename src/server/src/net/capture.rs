@@ -0,0 +1,646 @@
+//! Decrypted game packet capture
+//!
+//! An opt-in per-session recorder that writes the decrypted game packets of a
+//! session to a compact capture file, so player-reported bugs can be replayed
+//! exactly with the `replay` tool:
+//! - The header holds the client revision, the player's index, rights and a
+//!   snapshot of the player's state when the capture started
+//! - Each record holds the direction, world tick, opcode and payload
+//! - Ticks and lengths are LEB128 varints to keep captures small
+//!
+//! Outbound packets built by the packet handler are recorded as `Outbound`
+//! with their payload; pre-framed packets (player updates) are recorded as
+//! `OutboundFrame` with everything after the opcode, size header included.
+//!
+//! Packets are recorded as the client's revision sends and receives them.
+//! `replay` decodes the inbound packets of a capture with that revision's
+//! protocol and feeds them through a headless `GamePacketHandler`, starting
+//! from the player snapshot in the header, then encodes its responses the
+//! same way and diffs them against the recorded `Outbound` packets.
+
+use std::fs::File;
+use std::io::{BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::info;
+
+use crate::error::{ProtocolError, Result, RustscapeError};
+use crate::game::persistence::PlayerData;
+use crate::game::player::{Player, PlayerRights};
+use crate::protocol::game::{GamePacketHandler, IncomingGamePacket, OutgoingOpcode};
+use crate::protocol::revision::Protocols;
+
+/// Magic bytes at the start of every capture file
+pub const CAPTURE_MAGIC: [u8; 4] = *b"RSCP";
+
+/// Current capture format version
+pub const CAPTURE_VERSION: u8 = 1;
+
+/// File extension for capture files
+pub const CAPTURE_EXTENSION: &str = "rscap";
+
+/// Direction of a captured packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Direction {
+    /// Client to server
+    Inbound = 0,
+    /// Server to client, as built by the packet handler
+    Outbound = 1,
+    /// Server to client, already framed (e.g. player updates)
+    OutboundFrame = 2,
+}
+
+impl Direction {
+    /// Convert a u8 to a Direction
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Inbound),
+            1 => Some(Self::Outbound),
+            2 => Some(Self::OutboundFrame),
+            _ => None,
+        }
+    }
+}
+
+/// Capture file header
+#[derive(Debug, Clone)]
+pub struct CaptureHeader {
+    /// Client revision
+    pub revision: u32,
+    /// Username of the captured player
+    pub username: String,
+    /// Player index in the game world
+    pub player_index: u16,
+    /// Player rights
+    pub rights: u8,
+    /// Whether the player is a member
+    pub member: bool,
+    /// Player state when the capture started
+    pub snapshot: PlayerData,
+}
+
+/// A single captured packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureRecord {
+    /// Packet direction
+    pub direction: Direction,
+    /// World tick the packet was received or queued in
+    pub tick: u64,
+    /// Decrypted opcode
+    pub opcode: u8,
+    /// Packet payload
+    pub payload: Vec<u8>,
+}
+
+/// Writes a capture to any `Write`
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Write the capture header and return a writer for the records
+    pub fn new(mut writer: W, header: &CaptureHeader) -> Result<Self> {
+        let snapshot = serde_json::to_vec(&header.snapshot)
+            .map_err(|e| RustscapeError::Internal(e.to_string()))?;
+
+        writer.write_all(&CAPTURE_MAGIC)?;
+        writer.write_all(&[CAPTURE_VERSION])?;
+        writer.write_all(&header.revision.to_be_bytes())?;
+        writer.write_all(&header.player_index.to_be_bytes())?;
+        writer.write_all(&[header.rights, header.member as u8])?;
+        write_bytes(&mut writer, header.username.as_bytes())?;
+        write_bytes(&mut writer, &snapshot)?;
+
+        Ok(Self { writer })
+    }
+
+    /// Append a record
+    pub fn write(
+        &mut self,
+        direction: Direction,
+        tick: u64,
+        opcode: u8,
+        payload: &[u8],
+    ) -> Result<()> {
+        self.writer.write_all(&[direction as u8])?;
+        write_varint(&mut self.writer, tick)?;
+        self.writer.write_all(&[opcode])?;
+        write_bytes(&mut self.writer, payload)?;
+        Ok(())
+    }
+
+    /// Flush buffered records
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads a capture from any `Read`
+pub struct CaptureReader<R: Read> {
+    reader: R,
+    header: CaptureHeader,
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Read and validate the capture header
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != CAPTURE_MAGIC {
+            return Err(malformed("not a capture file"));
+        }
+
+        let mut fixed = [0u8; 9];
+        reader.read_exact(&mut fixed)?;
+        if fixed[0] != CAPTURE_VERSION {
+            return Err(malformed(&format!(
+                "unsupported capture version {}",
+                fixed[0]
+            )));
+        }
+
+        let revision = u32::from_be_bytes([fixed[1], fixed[2], fixed[3], fixed[4]]);
+        let player_index = u16::from_be_bytes([fixed[5], fixed[6]]);
+        let username = String::from_utf8(read_bytes(&mut reader)?)
+            .map_err(|_| malformed("username is not UTF-8"))?;
+        let snapshot = serde_json::from_slice(&read_bytes(&mut reader)?)
+            .map_err(|e| malformed(&format!("bad player snapshot: {}", e)))?;
+
+        Ok(Self {
+            reader,
+            header: CaptureHeader {
+                revision,
+                username,
+                player_index,
+                rights: fixed[7],
+                member: fixed[8] == 1,
+                snapshot,
+            },
+        })
+    }
+
+    /// Get the capture header
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    /// Read the next record, or `None` at the end of the capture
+    pub fn next_record(&mut self) -> Result<Option<CaptureRecord>> {
+        let mut direction = [0u8; 1];
+        match self.reader.read_exact(&mut direction) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let direction = Direction::from_u8(direction[0])
+            .ok_or_else(|| malformed(&format!("invalid direction {}", direction[0])))?;
+        let tick = read_varint(&mut self.reader)?;
+        let mut opcode = [0u8; 1];
+        self.reader.read_exact(&mut opcode)?;
+        let payload = read_bytes(&mut self.reader)?;
+
+        Ok(Some(CaptureRecord {
+            direction,
+            tick,
+            opcode: opcode[0],
+            payload,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = Result<CaptureRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+/// Records one session's packets to a capture file
+pub struct PacketRecorder {
+    /// Capture file path
+    path: PathBuf,
+    /// Buffered capture writer
+    writer: CaptureWriter<BufWriter<File>>,
+    /// Number of records written
+    records: u64,
+}
+
+impl PacketRecorder {
+    /// Start a capture file for a session in `directory`
+    pub fn create(directory: &Path, session_id: u64, header: &CaptureHeader) -> Result<Self> {
+        std::fs::create_dir_all(directory)?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let path = directory.join(format!(
+            "{}-{}-{}.{}",
+            header.username.to_lowercase(),
+            timestamp,
+            session_id,
+            CAPTURE_EXTENSION
+        ));
+
+        let writer = CaptureWriter::new(BufWriter::new(File::create(&path)?), header)?;
+        info!(session_id = session_id, path = %path.display(), "Started packet capture");
+
+        Ok(Self {
+            path,
+            writer,
+            records: 0,
+        })
+    }
+
+    /// Record a packet
+    ///
+    /// Resume tokens are never written to captures.
+    pub fn record(
+        &mut self,
+        direction: Direction,
+        tick: u64,
+        opcode: u8,
+        payload: &[u8],
+    ) -> Result<()> {
+        if direction == Direction::Outbound && opcode == OutgoingOpcode::ResumeToken.as_u8() {
+            return Ok(());
+        }
+
+        self.writer.write(direction, tick, opcode, payload)?;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Flush the capture file
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+
+    /// Get the capture file path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the number of records written
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+}
+
+impl std::fmt::Debug for PacketRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PacketRecorder")
+            .field("path", &self.path)
+            .field("records", &self.records)
+            .finish()
+    }
+}
+
+/// A response that differs between the capture and the replay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayMismatch {
+    /// Position of the response in the outbound stream
+    pub position: usize,
+    /// Response recorded in the capture
+    pub recorded: Option<CaptureRecord>,
+    /// Response produced by the replay, with the opcode of the inbound
+    /// packet that produced it
+    pub replayed: Option<(u8, CaptureRecord)>,
+}
+
+/// Result of replaying a capture
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    /// Inbound packets fed to the packet handler
+    pub inbound: usize,
+    /// Inbound packets the packet handler rejected
+    pub errors: usize,
+    /// Outbound responses recorded in the capture
+    pub recorded: usize,
+    /// Outbound responses produced by the replay
+    pub replayed: usize,
+    /// Responses that differ
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+impl ReplayReport {
+    /// Check whether the replay matched the capture exactly
+    pub fn is_match(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Replay a capture through a headless packet handler and diff the responses
+pub fn replay<R: Read>(reader: CaptureReader<R>) -> Result<ReplayReport> {
+    let header = reader.header().clone();
+    let protocol = Protocols::default()
+        .get(header.revision)
+        .ok_or(RustscapeError::Protocol(ProtocolError::InvalidRevision {
+            expected: crate::REVISION,
+            actual: header.revision,
+        }))?;
+    let player = Arc::new(Player::from_player_data(
+        header.player_index,
+        0,
+        &header.snapshot,
+        PlayerRights::from_u8(header.rights),
+        header.member,
+    ));
+    let handler = GamePacketHandler::new();
+
+    let mut report = ReplayReport::default();
+    let mut recorded = Vec::new();
+    let mut replayed = Vec::new();
+
+    for record in reader {
+        let record = record?;
+        match record.direction {
+            Direction::Inbound => {
+                report.inbound += 1;
+                let packet = IncomingGamePacket::new(record.opcode, record.payload.clone());
+                let Some(packet) = protocol.decode(packet) else {
+                    continue;
+                };
+                match handler.process_with_player(&packet, &player) {
+                    Ok(result) => {
+                        let responses = result
+                            .responses
+                            .iter()
+                            .filter_map(|message| protocol.encode(message));
+                        replayed.extend(responses.map(|response| {
+                            (
+                                record.opcode,
+                                CaptureRecord {
+                                    direction: Direction::Outbound,
+                                    tick: record.tick,
+                                    opcode: response.opcode,
+                                    payload: response.data,
+                                },
+                            )
+                        }));
+                    }
+                    Err(_) => report.errors += 1,
+                }
+            }
+            Direction::Outbound => recorded.push(record),
+            Direction::OutboundFrame => {}
+        }
+    }
+
+    report.recorded = recorded.len();
+    report.replayed = replayed.len();
+
+    let mut recorded = recorded.into_iter();
+    let mut replayed = replayed.into_iter();
+    for position in 0.. {
+        let (expected, actual) = match (recorded.next(), replayed.next()) {
+            (None, None) => break,
+            pair => pair,
+        };
+
+        let same = matches!(
+            (&expected, &actual),
+            (Some(e), Some((_, a))) if e.opcode == a.opcode && e.payload == a.payload
+        );
+        if !same {
+            report.mismatches.push(ReplayMismatch {
+                position,
+                recorded: expected,
+                replayed: actual,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+fn malformed(reason: &str) -> RustscapeError {
+    RustscapeError::Protocol(ProtocolError::MalformedPacket(format!(
+        "Invalid capture: {}",
+        reason
+    )))
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> Result<()> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            writer.write_all(&[byte])?;
+            return Ok(());
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7F) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(malformed("varint too long"))
+}
+
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+    write_varint(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> Result<Vec<u8>> {
+    let len = read_varint(reader)? as usize;
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(malformed("truncated record"));
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::player::Player;
+    use crate::protocol::revision::{Protocol, Revision530};
+    use uuid::Uuid;
+
+    fn header() -> CaptureHeader {
+        let player = Player::new(7, 1, "tester".to_string());
+        CaptureHeader {
+            revision: 530,
+            username: "tester".to_string(),
+            player_index: 7,
+            rights: 2,
+            member: true,
+            snapshot: player.to_player_data(Uuid::nil(), Uuid::nil()),
+        }
+    }
+
+    #[test]
+    fn test_varint_roundtrip() {
+        for value in [0u64, 1, 127, 128, 300, 65_535, u64::MAX] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, value).unwrap();
+            assert_eq!(read_varint(&mut buf.as_slice()).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_capture_roundtrip() {
+        let mut buf = Vec::new();
+        let mut writer = CaptureWriter::new(&mut buf, &header()).unwrap();
+        writer
+            .write(Direction::Inbound, 10, 98, &[1, 2, 3])
+            .unwrap();
+        writer.write(Direction::Outbound, 11, 53, &[]).unwrap();
+        writer
+            .write(Direction::OutboundFrame, 300, 81, &[0, 1, 9])
+            .unwrap();
+        writer.flush().unwrap();
+
+        let reader = CaptureReader::new(buf.as_slice()).unwrap();
+        assert_eq!(reader.header().revision, 530);
+        assert_eq!(reader.header().username, "tester");
+        assert_eq!(reader.header().player_index, 7);
+        assert_eq!(reader.header().rights, 2);
+        assert!(reader.header().member);
+        assert_eq!(reader.header().snapshot.display_name, "tester");
+
+        let records: Vec<CaptureRecord> = reader.map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records[0],
+            CaptureRecord {
+                direction: Direction::Inbound,
+                tick: 10,
+                opcode: 98,
+                payload: vec![1, 2, 3],
+            }
+        );
+        assert_eq!(records[2].direction, Direction::OutboundFrame);
+        assert_eq!(records[2].tick, 300);
+    }
+
+    #[test]
+    fn test_reader_rejects_bad_magic_and_truncation() {
+        assert!(CaptureReader::new(&b"NOPE\x01"[..]).is_err());
+
+        let mut buf = Vec::new();
+        let mut writer = CaptureWriter::new(&mut buf, &header()).unwrap();
+        writer
+            .write(Direction::Inbound, 1, 4, &[1, 2, 3, 4])
+            .unwrap();
+        buf.truncate(buf.len() - 2);
+
+        let mut reader = CaptureReader::new(buf.as_slice()).unwrap();
+        assert!(reader.next_record().is_err());
+    }
+
+    #[test]
+    fn test_replay_diffs_responses() {
+        let snapshot = header().snapshot;
+        let handler = GamePacketHandler::new();
+        let player = Arc::new(Player::from_player_data(
+            7,
+            0,
+            &snapshot,
+            PlayerRights::Administrator,
+            true,
+        ));
+
+        // Inventory swap of slots 0 and 1 answers with two slot updates
+        let inbound = IncomingGamePacket::new(243, vec![0, 0, 1, 0]);
+        let responses = handler
+            .process_with_player(&inbound, &player)
+            .unwrap()
+            .responses;
+        assert_eq!(responses.len(), 2);
+
+        let write_capture = |outbound: &[(u8, Vec<u8>)]| {
+            let mut buf = Vec::new();
+            let mut writer = CaptureWriter::new(&mut buf, &header()).unwrap();
+            writer
+                .write(Direction::Inbound, 1, inbound.opcode, &inbound.data)
+                .unwrap();
+            for (opcode, payload) in outbound {
+                writer
+                    .write(Direction::Outbound, 2, *opcode, payload)
+                    .unwrap();
+            }
+            writer
+                .write(Direction::OutboundFrame, 2, 81, &[0, 0])
+                .unwrap();
+            buf
+        };
+
+        let protocol = Revision530;
+        let faithful: Vec<(u8, Vec<u8>)> = responses
+            .iter()
+            .filter_map(|message| protocol.encode(message))
+            .map(|packet| (packet.opcode, packet.data))
+            .collect();
+        let buf = write_capture(&faithful);
+        let report = replay(CaptureReader::new(buf.as_slice()).unwrap()).unwrap();
+        assert_eq!(report.inbound, 1);
+        assert_eq!(report.recorded, faithful.len());
+        assert!(report.is_match());
+
+        let mut tampered = faithful.clone();
+        tampered.push((253, b"extra".to_vec()));
+        let buf = write_capture(&tampered);
+        let report = replay(CaptureReader::new(buf.as_slice()).unwrap()).unwrap();
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].position, faithful.len());
+        assert!(report.mismatches[0].replayed.is_none());
+    }
+
+    #[test]
+    fn test_replay_rejects_unsupported_revision() {
+        let mut header = header();
+        header.revision = 317;
+        let mut buf = Vec::new();
+        CaptureWriter::new(&mut buf, &header).unwrap();
+
+        assert!(matches!(
+            replay(CaptureReader::new(buf.as_slice()).unwrap()),
+            Err(RustscapeError::Protocol(ProtocolError::InvalidRevision {
+                actual: 317,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_recorder_skips_resume_tokens() {
+        let dir = std::env::temp_dir().join(format!("rscap-test-{}", std::process::id()));
+        let mut recorder = PacketRecorder::create(&dir, 42, &header()).unwrap();
+        recorder
+            .record(
+                Direction::Outbound,
+                1,
+                OutgoingOpcode::ResumeToken.as_u8(),
+                b"secret",
+            )
+            .unwrap();
+        recorder.record(Direction::Inbound, 1, 98, &[1]).unwrap();
+        recorder.flush().unwrap();
+        assert_eq!(recorder.records(), 1);
+
+        let file = File::open(recorder.path()).unwrap();
+        let records: Vec<_> = CaptureReader::new(file).unwrap().collect();
+        assert_eq!(records.len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,697 @@
+//! WebSocket permessage-deflate compression (RFC 7692)
+//!
+//! Browser clients that offer `permessage-deflate` get their outbound
+//! messages compressed:
+//! - The extension is negotiated in the WebSocket handshake (`DeflateParams`)
+//! - Messages at or above a configurable size are deflated and sent with the
+//!   RSV1 bit set (`MessageDeflater`); smaller ones are sent as-is
+//! - Compressed client messages are inflated by `InflateStream`, which sits
+//!   between the socket and tungstenite, since tungstenite rejects frames
+//!   with reserved bits set
+//!
+//! Both directions keep their sliding window between messages unless the
+//! client asked for `*_no_context_takeover`. Window sizes below 15 bits are
+//! not supported for our side; offers that require one are declined.
+
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+
+use bytes::{Buf, BytesMut};
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Extension token in `Sec-WebSocket-Extensions`
+pub const EXTENSION_NAME: &str = "permessage-deflate";
+
+/// Largest client message accepted, compressed or inflated
+pub const MAX_MESSAGE_SIZE: usize = 1 << 20;
+
+/// Trailer every deflated message ends with, stripped on the wire
+const DEFLATE_TRAILER: [u8; 4] = [0x00, 0x00, 0xFF, 0xFF];
+
+/// Largest window the server's compressor uses
+const MAX_WINDOW_BITS: u8 = 15;
+
+/// Socket read size while looking for frames
+const READ_CHUNK_SIZE: usize = 8192;
+
+/// Parameters agreed for a connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeflateParams {
+    /// Reset the server's compressor after every message
+    pub server_no_context_takeover: bool,
+    /// The client resets its compressor after every message
+    pub client_no_context_takeover: bool,
+    /// The client offered `server_max_window_bits=15`, which must be echoed
+    pub echo_server_max_window_bits: bool,
+}
+
+impl DeflateParams {
+    /// Pick the first acceptable offer from a `Sec-WebSocket-Extensions` header
+    ///
+    /// Returns `None` if no offer is `permessage-deflate` with parameters the
+    /// server supports.
+    pub fn negotiate(header: &str) -> Option<Self> {
+        header.split(',').find_map(Self::accept_offer)
+    }
+
+    fn accept_offer(offer: &str) -> Option<Self> {
+        let mut parts = offer.split(';').map(str::trim);
+        if !parts.next()?.eq_ignore_ascii_case(EXTENSION_NAME) {
+            return None;
+        }
+
+        let mut params = Self::default();
+        let mut seen = Vec::new();
+        for part in parts {
+            let (name, value) = match part.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (part, None),
+            };
+            let name = name.to_ascii_lowercase();
+            if seen.contains(&name) {
+                return None;
+            }
+
+            match (name.as_str(), value) {
+                ("server_no_context_takeover", None) => params.server_no_context_takeover = true,
+                ("client_no_context_takeover", None) => params.client_no_context_takeover = true,
+                ("server_max_window_bits", Some(bits)) => {
+                    // Our compressor always uses the largest window
+                    if bits.parse::<u8>().ok()? != MAX_WINDOW_BITS {
+                        return None;
+                    }
+                    params.echo_server_max_window_bits = true;
+                }
+                // Any client window fits in ours, so there is nothing to agree
+                ("client_max_window_bits", None) => {}
+                ("client_max_window_bits", Some(bits)) => {
+                    if !(8..=15).contains(&bits.parse::<u8>().ok()?) {
+                        return None;
+                    }
+                }
+                _ => return None,
+            }
+            seen.push(name);
+        }
+
+        Some(params)
+    }
+
+    /// Value of the `Sec-WebSocket-Extensions` response header
+    pub fn response_header(&self) -> String {
+        let mut header = EXTENSION_NAME.to_string();
+        if self.server_no_context_takeover {
+            header.push_str("; server_no_context_takeover");
+        }
+        if self.client_no_context_takeover {
+            header.push_str("; client_no_context_takeover");
+        }
+        if self.echo_server_max_window_bits {
+            header.push_str("; server_max_window_bits=15");
+        }
+        header
+    }
+}
+
+/// Compresses outbound messages for one connection
+pub struct MessageDeflater {
+    compress: Compress,
+    /// Reset the compressor after every message
+    no_context_takeover: bool,
+    /// Messages smaller than this are sent uncompressed
+    threshold: usize,
+}
+
+impl MessageDeflater {
+    /// Create a compressor with a zlib level (0-9) and size threshold
+    pub fn new(params: &DeflateParams, level: u32, threshold: usize) -> Self {
+        Self {
+            compress: Compress::new(Compression::new(level.min(9)), false),
+            no_context_takeover: params.server_no_context_takeover,
+            threshold,
+        }
+    }
+
+    /// Compress a message
+    ///
+    /// Returns `None` when the message is below the threshold and should be
+    /// sent uncompressed. Once compressed, a message must be sent compressed
+    /// even if it grew, as the client's window now depends on it.
+    pub fn compress(&mut self, data: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        if data.len() < self.threshold {
+            return Ok(None);
+        }
+
+        let start = self.compress.total_in();
+        let mut output = Vec::with_capacity(data.len() / 2 + 64);
+        loop {
+            let consumed = (self.compress.total_in() - start) as usize;
+            self.compress
+                .compress_vec(&data[consumed..], &mut output, FlushCompress::Sync)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+            let consumed = (self.compress.total_in() - start) as usize;
+            if consumed == data.len() && output.len() < output.capacity() {
+                break;
+            }
+            output.reserve(output.capacity().max(64));
+        }
+
+        if output.ends_with(&DEFLATE_TRAILER) {
+            output.truncate(output.len() - DEFLATE_TRAILER.len());
+        }
+        if self.no_context_takeover {
+            self.compress.reset();
+        }
+
+        Ok(Some(output))
+    }
+}
+
+impl std::fmt::Debug for MessageDeflater {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MessageDeflater")
+            .field("no_context_takeover", &self.no_context_takeover)
+            .field("threshold", &self.threshold)
+            .finish()
+    }
+}
+
+/// Inflate a complete compressed message
+fn inflate_message(decompress: &mut Decompress, payload: &[u8]) -> io::Result<Vec<u8>> {
+    let mut input = Vec::with_capacity(payload.len() + DEFLATE_TRAILER.len());
+    input.extend_from_slice(payload);
+    input.extend_from_slice(&DEFLATE_TRAILER);
+
+    let start = decompress.total_in();
+    let mut output = Vec::with_capacity(payload.len() * 4 + 64);
+    loop {
+        let consumed = (decompress.total_in() - start) as usize;
+        let status = decompress
+            .decompress_vec(&input[consumed..], &mut output, FlushDecompress::Sync)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let consumed = (decompress.total_in() - start) as usize;
+        if status == Status::StreamEnd
+            || (consumed == input.len() && output.len() < output.capacity())
+        {
+            break;
+        }
+        if output.len() > MAX_MESSAGE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "inflated WebSocket message too large",
+            ));
+        }
+        output.reserve(output.capacity().max(64));
+    }
+
+    if output.len() > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "inflated WebSocket message too large",
+        ));
+    }
+    Ok(output)
+}
+
+/// A parsed WebSocket frame header
+struct FrameHeader {
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    header_len: usize,
+    payload_len: usize,
+}
+
+impl FrameHeader {
+    /// Parse a frame header, returning `None` until enough bytes are buffered
+    fn parse(data: &[u8]) -> io::Result<Option<Self>> {
+        if data.len() < 2 {
+            return Ok(None);
+        }
+
+        let masked = data[1] & 0x80 != 0;
+        let (payload_len, mut header_len) = match data[1] & 0x7F {
+            126 => {
+                if data.len() < 4 {
+                    return Ok(None);
+                }
+                (u16::from_be_bytes([data[2], data[3]]) as u64, 4)
+            }
+            127 => {
+                if data.len() < 10 {
+                    return Ok(None);
+                }
+                let mut len = [0u8; 8];
+                len.copy_from_slice(&data[2..10]);
+                (u64::from_be_bytes(len), 10)
+            }
+            len => (len as u64, 2),
+        };
+        if payload_len > MAX_MESSAGE_SIZE as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "WebSocket frame too large",
+            ));
+        }
+
+        let mask = if masked {
+            if data.len() < header_len + 4 {
+                return Ok(None);
+            }
+            let mut key = [0u8; 4];
+            key.copy_from_slice(&data[header_len..header_len + 4]);
+            header_len += 4;
+            Some(key)
+        } else {
+            None
+        };
+
+        Ok(Some(Self {
+            fin: data[0] & 0x80 != 0,
+            rsv1: data[0] & 0x40 != 0,
+            opcode: data[0] & 0x0F,
+            mask,
+            header_len,
+            payload_len: payload_len as usize,
+        }))
+    }
+
+    fn is_control(&self) -> bool {
+        self.opcode & 0x08 != 0
+    }
+}
+
+/// Write a final, masked (with a zero key) frame
+fn write_frame(out: &mut BytesMut, opcode: u8, payload: &[u8]) {
+    out.extend_from_slice(&[0x80 | opcode]);
+    match payload.len() {
+        len if len < 126 => out.extend_from_slice(&[0x80 | len as u8]),
+        len if len <= u16::MAX as usize => {
+            out.extend_from_slice(&[0x80 | 126]);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.extend_from_slice(&[0x80 | 127]);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(payload);
+}
+
+/// Inbound decompression state
+struct Inflater {
+    decompress: Decompress,
+    /// Reset the decompressor after every message
+    no_context_takeover: bool,
+    /// Opcode and payload so far of a compressed message split across frames
+    message: Option<(u8, Vec<u8>)>,
+}
+
+/// Stream adapter that inflates compressed client frames
+///
+/// Until `enable` is called the stream is passed through untouched, so the
+/// HTTP upgrade can run over it. Afterwards, frames are parsed from the
+/// socket: compressed messages are inflated and handed on as a single
+/// uncompressed frame; all other frames are passed through as-is. Writes are
+/// never modified.
+pub struct InflateStream<S> {
+    inner: S,
+    inflater: Option<Inflater>,
+    /// Bytes read from the socket that haven't been parsed yet
+    raw: BytesMut,
+    /// Frames ready to be read
+    ready: BytesMut,
+    /// The socket reached end of stream
+    eof: bool,
+}
+
+impl<S> InflateStream<S> {
+    /// Wrap a stream, initially passing everything through
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            inflater: None,
+            raw: BytesMut::new(),
+            ready: BytesMut::new(),
+            eof: false,
+        }
+    }
+
+    /// Start inflating compressed frames with the negotiated parameters
+    pub fn enable(&mut self, params: &DeflateParams) {
+        self.inflater = Some(Inflater {
+            decompress: Decompress::new(false),
+            no_context_takeover: params.client_no_context_takeover,
+            message: None,
+        });
+    }
+
+    /// Check whether compressed frames are being inflated
+    pub fn is_enabled(&self) -> bool {
+        self.inflater.is_some()
+    }
+
+    /// Get a reference to the underlying stream
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Move complete frames from `raw` to `ready`
+    ///
+    /// Returns whether any progress was made.
+    fn process(&mut self) -> io::Result<bool> {
+        let Some(inflater) = self.inflater.as_mut() else {
+            let progress = !self.raw.is_empty();
+            self.ready.extend_from_slice(&self.raw.split());
+            return Ok(progress);
+        };
+
+        let mut progress = false;
+        while let Some(header) = FrameHeader::parse(&self.raw)? {
+            let frame_len = header.header_len + header.payload_len;
+            if self.raw.len() < frame_len {
+                break;
+            }
+            progress = true;
+
+            let compressed = header.rsv1 && matches!(header.opcode, 1 | 2);
+            let continuation = header.opcode == 0 && inflater.message.is_some();
+            if inflater.message.is_some() && !header.is_control() && !continuation {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "new WebSocket message before the compressed one finished",
+                ));
+            }
+            if header.is_control() || !(compressed || continuation) {
+                // Not ours - tungstenite validates it
+                self.ready.extend_from_slice(&self.raw.split_to(frame_len));
+                continue;
+            }
+
+            let frame = self.raw.split_to(frame_len);
+            let mut payload = frame[header.header_len..].to_vec();
+            if let Some(key) = header.mask {
+                for (i, byte) in payload.iter_mut().enumerate() {
+                    *byte ^= key[i % 4];
+                }
+            }
+
+            let (opcode, message) = inflater
+                .message
+                .get_or_insert_with(|| (header.opcode, Vec::new()));
+            if message.len() + payload.len() > MAX_MESSAGE_SIZE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "compressed WebSocket message too large",
+                ));
+            }
+            message.extend_from_slice(&payload);
+            let opcode = *opcode;
+
+            if header.fin {
+                let (_, message) = inflater.message.take().unwrap_or_default();
+                let inflated = inflate_message(&mut inflater.decompress, &message)?;
+                if inflater.no_context_takeover {
+                    inflater.decompress.reset(false);
+                }
+                write_frame(&mut self.ready, opcode, &inflated);
+            }
+        }
+
+        Ok(progress)
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for InflateStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        loop {
+            if !this.ready.is_empty() {
+                let len = this.ready.len().min(buf.remaining());
+                buf.put_slice(&this.ready[..len]);
+                this.ready.advance(len);
+                return Poll::Ready(Ok(()));
+            }
+
+            if this.inflater.is_none() && this.raw.is_empty() {
+                return Pin::new(&mut this.inner).poll_read(cx, buf);
+            }
+
+            if this.process()? {
+                continue;
+            }
+            if this.eof {
+                // A partial frame at end of stream is left for tungstenite
+                this.ready.extend_from_slice(&this.raw.split());
+                if this.ready.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                continue;
+            }
+
+            let mut chunk = [0u8; READ_CHUNK_SIZE];
+            let mut chunk_buf = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut this.inner).poll_read(cx, &mut chunk_buf) {
+                Poll::Ready(Ok(())) => {
+                    let filled = chunk_buf.filled();
+                    if filled.is_empty() {
+                        this.eof = true;
+                    } else {
+                        this.raw.extend_from_slice(filled);
+                    }
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for InflateStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// WebSocket compression statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompressionStats {
+    /// Messages sent compressed
+    pub messages_compressed: u64,
+    /// Messages below the threshold, sent uncompressed
+    pub messages_uncompressed: u64,
+    /// Message bytes before compression
+    pub bytes_in: u64,
+    /// Message bytes sent on the wire
+    pub bytes_out: u64,
+    /// `bytes_out / bytes_in` (1.0 before anything was sent)
+    pub ratio: f64,
+}
+
+/// Process-wide WebSocket compression counters
+///
+/// Only connections that negotiated compression are counted.
+#[derive(Debug, Default)]
+pub struct CompressionMetrics {
+    messages_compressed: AtomicU64,
+    messages_uncompressed: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+impl CompressionMetrics {
+    /// Create zeroed counters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a message sent compressed
+    pub fn record_compressed(&self, original: usize, compressed: usize) {
+        self.messages_compressed.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(original as u64, Ordering::Relaxed);
+        self.bytes_out
+            .fetch_add(compressed as u64, Ordering::Relaxed);
+    }
+
+    /// Count a message sent uncompressed
+    pub fn record_uncompressed(&self, len: usize) {
+        self.messages_uncompressed.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(len as u64, Ordering::Relaxed);
+        self.bytes_out.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    /// Get the current counters
+    pub fn stats(&self) -> CompressionStats {
+        let bytes_in = self.bytes_in.load(Ordering::Relaxed);
+        let bytes_out = self.bytes_out.load(Ordering::Relaxed);
+        CompressionStats {
+            messages_compressed: self.messages_compressed.load(Ordering::Relaxed),
+            messages_uncompressed: self.messages_uncompressed.load(Ordering::Relaxed),
+            bytes_in,
+            bytes_out,
+            ratio: if bytes_in == 0 {
+                1.0
+            } else {
+                bytes_out as f64 / bytes_in as f64
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_negotiate_offers() {
+        // Chrome's default offer
+        let params =
+            DeflateParams::negotiate("permessage-deflate; client_max_window_bits").unwrap();
+        assert_eq!(params, DeflateParams::default());
+        assert_eq!(params.response_header(), "permessage-deflate");
+
+        let params = DeflateParams::negotiate(
+            "permessage-deflate; server_max_window_bits=10, \
+             permessage-deflate; client_no_context_takeover; server_max_window_bits=\"15\"",
+        )
+        .unwrap();
+        assert!(params.client_no_context_takeover);
+        assert_eq!(
+            params.response_header(),
+            "permessage-deflate; client_no_context_takeover; server_max_window_bits=15"
+        );
+
+        assert!(DeflateParams::negotiate("x-webkit-deflate-frame").is_none());
+        assert!(DeflateParams::negotiate("permessage-deflate; unknown_param").is_none());
+        assert!(DeflateParams::negotiate(
+            "permessage-deflate; server_no_context_takeover; server_no_context_takeover"
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_deflater_threshold_and_round_trip() {
+        let params = DeflateParams::default();
+        let mut deflater = MessageDeflater::new(&params, 6, 32);
+        let mut decompress = Decompress::new(false);
+
+        assert!(deflater.compress(&[1, 2, 3]).unwrap().is_none());
+
+        // Context is kept between messages, so the repeat compresses better
+        let message = vec![81u8; 500];
+        let first = deflater.compress(&message).unwrap().unwrap();
+        let second = deflater.compress(&message).unwrap().unwrap();
+        assert!(first.len() < message.len());
+        assert!(second.len() <= first.len());
+
+        assert_eq!(inflate_message(&mut decompress, &first).unwrap(), message);
+        assert_eq!(inflate_message(&mut decompress, &second).unwrap(), message);
+    }
+
+    /// Frame a compressed client message the way a browser would
+    fn client_frames(message: &[u8], split: usize) -> Vec<u8> {
+        let mut compress = Compress::new(Compression::default(), false);
+        let mut payload = Vec::with_capacity(message.len() + 64);
+        compress
+            .compress_vec(message, &mut payload, FlushCompress::Sync)
+            .unwrap();
+        payload.truncate(payload.len() - DEFLATE_TRAILER.len());
+
+        let key = [0x12, 0x34, 0x56, 0x78];
+        let mask = |data: &[u8]| -> Vec<u8> {
+            data.iter()
+                .enumerate()
+                .map(|(i, b)| b ^ key[i % 4])
+                .collect()
+        };
+
+        let (first, rest) = payload.split_at(split.min(payload.len()));
+        let mut frames = Vec::new();
+        frames.extend_from_slice(&[if rest.is_empty() { 0xC2 } else { 0x42 }]);
+        frames.push(0x80 | first.len() as u8);
+        frames.extend_from_slice(&key);
+        frames.extend_from_slice(&mask(first));
+        // A ping may arrive between fragments
+        frames.extend_from_slice(&[0x89, 0x80]);
+        frames.extend_from_slice(&key);
+        if !rest.is_empty() {
+            frames.extend_from_slice(&[0x80, 0x80 | rest.len() as u8]);
+            frames.extend_from_slice(&key);
+            frames.extend_from_slice(&mask(rest));
+        }
+        frames
+    }
+
+    #[tokio::test]
+    async fn test_inflate_stream_rewrites_compressed_frames() {
+        let message = b"hello hello hello hello".to_vec();
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut stream = InflateStream::new(server);
+        stream.enable(&DeflateParams::default());
+
+        client.write_all(&client_frames(&message, 4)).await.unwrap();
+        drop(client);
+
+        let mut output = Vec::new();
+        stream.read_to_end(&mut output).await.unwrap();
+
+        // The ping passes through first, then the reassembled message
+        assert_eq!(&output[..6], &[0x89, 0x80, 0x12, 0x34, 0x56, 0x78]);
+        let mut expected = BytesMut::new();
+        write_frame(&mut expected, 2, &message);
+        assert_eq!(&output[6..], &expected[..]);
+    }
+
+    #[tokio::test]
+    async fn test_inflate_stream_passes_through_until_enabled() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut stream = InflateStream::new(server);
+
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        drop(client);
+
+        let mut output = Vec::new();
+        stream.read_to_end(&mut output).await.unwrap();
+        assert_eq!(output, b"GET / HTTP/1.1\r\n");
+        assert!(!stream.is_enabled());
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        let metrics = CompressionMetrics::new();
+        assert_eq!(metrics.stats().ratio, 1.0);
+
+        metrics.record_compressed(400, 100);
+        metrics.record_uncompressed(100);
+        let stats = metrics.stats();
+        assert_eq!(stats.messages_compressed, 1);
+        assert_eq!(stats.messages_uncompressed, 1);
+        assert_eq!((stats.bytes_in, stats.bytes_out), (500, 200));
+        assert!((stats.ratio - 0.4).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,263 @@
+//! Packet flood protection
+//!
+//! Limits how much a single in-game session may send per game tick:
+//! - A maximum number of packets per tick
+//! - A maximum number of bytes per tick
+//! - Optional per-opcode caps (e.g. walking and commands) from config
+//!
+//! Limits are counted against the world tick the packet arrived in. Packets
+//! over a limit are dropped, and each tick in which a session went over a
+//! limit counts as one violation. A session that racks up `max_violations`
+//! violations, each within `violation_window_ticks` of the previous one, is
+//! disconnected.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::PacketConfig;
+
+/// What to do with an inbound packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloodVerdict {
+    /// Within limits - process the packet
+    Accept,
+    /// Over a limit - drop the packet
+    Drop,
+    /// Repeated abuse - drop the packet and disconnect the session
+    Disconnect,
+}
+
+/// Per-session flood protection state, owned by the connection task
+#[derive(Debug)]
+pub struct FloodGuard {
+    /// Maximum packets per tick (0 = unlimited)
+    max_packets: u32,
+    /// Maximum bytes per tick (0 = unlimited)
+    max_bytes: usize,
+    /// Maximum packets per tick by opcode (0 = no cap)
+    opcode_limits: [u32; 256],
+    /// Violating ticks before disconnecting (0 = never disconnect)
+    max_violations: u32,
+    /// Ticks after which a previous violation no longer counts
+    violation_window: u64,
+    /// Tick the counters below belong to
+    tick: u64,
+    /// Packets received this tick
+    packets: u32,
+    /// Bytes received this tick
+    bytes: usize,
+    /// Packets received this tick by opcode
+    opcode_counts: [u32; 256],
+    /// Whether a violation was already counted this tick
+    violated_this_tick: bool,
+    /// Recent violations
+    violations: u32,
+    /// Tick of the most recent violation
+    last_violation: u64,
+    /// Shared counters
+    metrics: Arc<FloodMetrics>,
+}
+
+impl FloodGuard {
+    /// Create a guard with the limits from the packet config
+    pub fn new(config: &PacketConfig, metrics: Arc<FloodMetrics>) -> Self {
+        let mut opcode_limits = [0u32; 256];
+        for limit in &config.opcode_limits {
+            opcode_limits[limit.opcode as usize] = limit.max_per_tick;
+        }
+
+        Self {
+            max_packets: config.max_packets_per_tick,
+            max_bytes: config.max_bytes_per_tick,
+            opcode_limits,
+            max_violations: config.max_violations,
+            violation_window: config.violation_window_ticks,
+            tick: 0,
+            packets: 0,
+            bytes: 0,
+            opcode_counts: [0; 256],
+            violated_this_tick: false,
+            violations: 0,
+            last_violation: 0,
+            metrics,
+        }
+    }
+
+    /// Account for a packet of `size` bytes received during world tick `tick`
+    pub fn check(&mut self, tick: u64, opcode: u8, size: usize) -> FloodVerdict {
+        if tick != self.tick {
+            self.tick = tick;
+            self.packets = 0;
+            self.bytes = 0;
+            self.opcode_counts = [0; 256];
+            self.violated_this_tick = false;
+        }
+
+        self.packets += 1;
+        self.bytes += size;
+        let opcode_count = &mut self.opcode_counts[opcode as usize];
+        *opcode_count += 1;
+
+        let opcode_limit = self.opcode_limits[opcode as usize];
+        let over_limit = (self.max_packets > 0 && self.packets > self.max_packets)
+            || (self.max_bytes > 0 && self.bytes > self.max_bytes)
+            || (opcode_limit > 0 && *opcode_count > opcode_limit);
+
+        if !over_limit {
+            return FloodVerdict::Accept;
+        }
+
+        self.metrics.dropped_packets.fetch_add(1, Ordering::Relaxed);
+        if !self.violated_this_tick {
+            self.metrics.violations.fetch_add(1, Ordering::Relaxed);
+            self.violated_this_tick = true;
+            if self.violations > 0
+                && tick.saturating_sub(self.last_violation) > self.violation_window
+            {
+                self.violations = 0;
+            }
+            self.violations += 1;
+            self.last_violation = tick;
+        }
+
+        if self.max_violations > 0 && self.violations >= self.max_violations {
+            self.metrics.disconnects.fetch_add(1, Ordering::Relaxed);
+            FloodVerdict::Disconnect
+        } else {
+            FloodVerdict::Drop
+        }
+    }
+
+    /// Get the number of recent violations
+    pub fn violations(&self) -> u32 {
+        self.violations
+    }
+}
+
+/// Flood protection statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FloodStats {
+    /// Packets dropped for exceeding a limit
+    pub dropped_packets: u64,
+    /// Ticks in which a session exceeded a limit
+    pub violations: u64,
+    /// Sessions disconnected for repeated abuse
+    pub disconnects: u64,
+}
+
+/// Process-wide flood protection counters
+#[derive(Debug, Default)]
+pub struct FloodMetrics {
+    dropped_packets: AtomicU64,
+    violations: AtomicU64,
+    disconnects: AtomicU64,
+}
+
+impl FloodMetrics {
+    /// Create zeroed counters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the current counters
+    pub fn stats(&self) -> FloodStats {
+        FloodStats {
+            dropped_packets: self.dropped_packets.load(Ordering::Relaxed),
+            violations: self.violations.load(Ordering::Relaxed),
+            disconnects: self.disconnects.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OpcodeLimit;
+
+    fn guard() -> (FloodGuard, Arc<FloodMetrics>) {
+        let metrics = Arc::new(FloodMetrics::new());
+        (FloodGuard::new(&config(), metrics.clone()), metrics)
+    }
+
+    fn config() -> PacketConfig {
+        PacketConfig {
+            max_packets_per_tick: 3,
+            max_bytes_per_tick: 100,
+            opcode_limits: vec![OpcodeLimit {
+                opcode: 98,
+                max_per_tick: 1,
+            }],
+            max_violations: 2,
+            violation_window_ticks: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_packet_count_limit_resets_each_tick() {
+        let (mut guard, _) = guard();
+        for _ in 0..3 {
+            assert_eq!(guard.check(1, 0, 1), FloodVerdict::Accept);
+        }
+        assert_eq!(guard.check(1, 0, 1), FloodVerdict::Drop);
+        assert_eq!(guard.check(1, 0, 1), FloodVerdict::Drop);
+        assert_eq!(guard.violations(), 1);
+
+        assert_eq!(guard.check(2, 0, 1), FloodVerdict::Accept);
+    }
+
+    #[test]
+    fn test_byte_and_opcode_limits() {
+        let (mut guard, _) = guard();
+        assert_eq!(guard.check(1, 98, 5), FloodVerdict::Accept);
+        assert_eq!(guard.check(1, 98, 5), FloodVerdict::Drop);
+
+        assert_eq!(guard.check(20, 4, 90), FloodVerdict::Accept);
+        assert_eq!(guard.check(20, 4, 20), FloodVerdict::Drop);
+    }
+
+    #[test]
+    fn test_repeated_violations_disconnect() {
+        let (mut guard, _) = guard();
+        for _ in 0..2 {
+            guard.check(1, 98, 1);
+        }
+        assert_eq!(guard.violations(), 1);
+
+        guard.check(5, 98, 1);
+        assert_eq!(guard.check(5, 98, 1), FloodVerdict::Disconnect);
+    }
+
+    #[test]
+    fn test_old_violations_expire() {
+        let (mut guard, _) = guard();
+        guard.check(1, 98, 1);
+        guard.check(1, 98, 1);
+
+        // Next violation is outside the 10-tick window
+        guard.check(50, 98, 1);
+        assert_eq!(guard.check(50, 98, 1), FloodVerdict::Drop);
+        assert_eq!(guard.violations(), 1);
+    }
+
+    #[test]
+    fn test_metrics_count_drops_violations_and_disconnects() {
+        let (mut guard, metrics) = guard();
+        guard.check(1, 98, 1);
+        guard.check(1, 98, 1);
+        guard.check(1, 98, 1);
+        guard.check(2, 98, 1);
+        guard.check(2, 98, 1);
+
+        assert_eq!(
+            metrics.stats(),
+            FloodStats {
+                dropped_packets: 3,
+                violations: 2,
+                disconnects: 1,
+            }
+        );
+    }
+}
@@ -1,43 +1,139 @@
 //! Connection handler module
 //!
 //! Handles the lifecycle of client connections including:
-//! - Initial connection setup (TCP or WebSocket)
+//! - Initial connection setup (TCP or WebSocket, optionally over TLS, with
+//!   negotiated permessage-deflate)
+//! - Real client addresses behind trusted proxies (PROXY protocol, X-Forwarded-For)
 //! - Protocol handshake (JS5 or Login)
 //! - Message routing based on connection state
 //! - RSA decryption and ISAAC cipher initialization
-//! - Game packet processing with ISAAC decryption
+//! - Login admission (queueing and throttling before authentication)
+//! - Lobby logins and world selection
+//! - Game packet decoding with ISAAC decryption (applied by the game tick)
+//! - Packet flood protection
 //! - Player persistence (load on login, save on disconnect)
+//! - Session resume after brief disconnects
 //! - Graceful disconnection
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 
 use crate::error::Result;
+use http::HeaderValue;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
-use tokio_tungstenite::accept_async;
+use tokio::sync::mpsc;
+use tokio_tungstenite::accept_hdr_async;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
 use tracing::{debug, error, info, trace, warn};
 use uuid::Uuid;
 
+use crate::auth::game_token::is_game_token;
+use crate::auth::Account;
 use crate::crypto::IsaacPair;
 use crate::error::{
     AuthError, Js5Response, LoginResponse, NetworkError, ProtocolError, RustscapeError,
 };
+use crate::game::pathfinding;
 use crate::game::player::PlayerRights;
+use crate::game::world_list::encode_world_list;
 use crate::net::buffer::PacketBuffer;
-use crate::net::session::{ClientInfo, SessionState};
-use crate::net::transport::{BufferedTransport, UnifiedTransport};
-use crate::protocol::game::{GamePacketHandler, IncomingGamePacket, INCOMING_PACKET_SIZES};
+use crate::net::capture::{CaptureHeader, Direction, PacketRecorder};
+use crate::net::deflate::{DeflateParams, InflateStream, MessageDeflater};
+use crate::net::admission::Admission;
+use crate::net::flood::{FloodGuard, FloodVerdict};
+use crate::net::proxy::{self, forwarded_client, is_trusted};
+use crate::net::resume::{ParkedPlayer, ResumeRegistry};
+use crate::net::session::{ClientInfo, Session, SessionState};
+use crate::net::transport::{BufferedTransport, UnifiedTransport, WebSocketTransport};
+use crate::protocol::game::IncomingGamePacket;
 use crate::protocol::handshake::HandshakeOpcode;
+use crate::protocol::js5::Js5FileRequest;
+use crate::protocol::lobby::LobbyResponse;
 use crate::protocol::login::LoginType;
-use crate::protocol::login_init;
+use crate::protocol::login_init::InitialPlayerState;
+use crate::protocol::message::ServerMessage;
+use crate::protocol::packets::PacketSize;
 use crate::state::AppState;
 use crate::REVISION;
 
 /// Maximum packet size (64KB)
 const MAX_PACKET_SIZE: usize = 65535;
 
+/// Capacity of a session's outbound packet channel
+const OUTBOUND_CHANNEL_SIZE: usize = 256;
+
 /// Read timeout in seconds
 
+/// Perform the WebSocket handshake on a (possibly TLS) stream
+///
+/// Negotiates permessage-deflate when enabled and offered by the client.
+/// Returns the X-Forwarded-For header when `honour_forwarded` is set and the
+/// request carried one.
+async fn accept_websocket<S>(
+    stream: S,
+    state: &AppState,
+    honour_forwarded: bool,
+) -> Result<(WebSocketTransport<S>, Option<String>)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let config = &state.config.websocket;
+    let mut forwarded_for = None;
+    let mut deflate = None;
+    // The callback signature is fixed by tungstenite
+    #[allow(clippy::result_large_err)]
+    let callback = |request: &Request,
+                    mut response: Response|
+     -> std::result::Result<Response, ErrorResponse> {
+        if honour_forwarded {
+            forwarded_for = request
+                .headers()
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string());
+        }
+
+        if config.compression {
+            let params = request
+                .headers()
+                .get_all("sec-websocket-extensions")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .find_map(DeflateParams::negotiate);
+            if let Some(params) = params {
+                if let Ok(value) = HeaderValue::from_str(&params.response_header()) {
+                    response
+                        .headers_mut()
+                        .insert("sec-websocket-extensions", value);
+                    deflate = Some(params);
+                }
+            }
+        }
+        Ok(response)
+    };
+    let mut ws_stream = accept_hdr_async(InflateStream::new(stream), callback)
+        .await
+        .map_err(|e| RustscapeError::Network(NetworkError::WebSocket(e.to_string())))?;
+
+    let transport = match deflate {
+        Some(params) => {
+            debug!(params = ?params, "Negotiated WebSocket compression");
+            ws_stream.get_mut().enable(&params);
+            let deflater = MessageDeflater::new(
+                &params,
+                config.compression_level,
+                config.compression_threshold,
+            );
+            WebSocketTransport::new(ws_stream)
+                .with_compression(deflater, state.compression_metrics.clone())
+        }
+        None => WebSocketTransport::new(ws_stream),
+    };
+
+    Ok((transport, forwarded_for))
+}
+
 /// Connection handler for processing client connections
 pub struct ConnectionHandler {
     /// Shared application state
@@ -57,7 +153,14 @@ pub fn new(state: Arc<AppState>, is_websocket: bool) -> Self {
     }
 
     /// Handle a TCP connection (native client)
-    pub async fn handle_tcp(&self, stream: TcpStream, addr: SocketAddr) -> Result<()> {
+    pub async fn handle_tcp(&self, mut stream: TcpStream, addr: SocketAddr) -> Result<()> {
+        let addr = self
+            .resolve_proxy_protocol(
+                &mut stream,
+                addr,
+                self.state.config.proxy.game_proxy_protocol,
+            )
+            .await?;
         debug!(address = %addr, "Handling TCP connection");
 
         // Set TCP options
@@ -68,41 +171,110 @@ pub async fn handle_tcp(&self, stream: TcpStream, addr: SocketAddr) -> Result<()
         let transport = BufferedTransport::new(transport);
 
         // Create session
-        let session = self.state.session_manager.create_session(addr, false)?;
+        let (outbound_tx, outbound_rx) = mpsc::channel(OUTBOUND_CHANNEL_SIZE);
+        let session = self
+            .state
+            .session_manager
+            .create_session_with_channel(addr, false, outbound_tx)
+            .inspect_err(|e| self.state.connection_metrics.record(e))?;
 
         // Handle the connection
-        self.handle_connection(transport, session.id).await
+        self.handle_connection(transport, outbound_rx, session.id)
+            .await
     }
 
     /// Handle a WebSocket connection (browser client)
-    pub async fn handle_websocket(&self, stream: TcpStream, addr: SocketAddr) -> Result<()> {
+    pub async fn handle_websocket(&self, mut stream: TcpStream, addr: SocketAddr) -> Result<()> {
+        let proxy_config = &self.state.config.proxy;
+        let mut addr = self
+            .resolve_proxy_protocol(&mut stream, addr, proxy_config.websocket_proxy_protocol)
+            .await?;
         debug!(address = %addr, "Handling WebSocket connection");
 
         // Set TCP options before upgrade
         stream.set_nodelay(true)?;
 
-        // Perform WebSocket handshake
-        let ws_stream = accept_async(stream)
-            .await
-            .map_err(|e| RustscapeError::Network(NetworkError::WebSocket(e.to_string())))?;
+        // Terminate TLS when configured, then perform the WebSocket handshake,
+        // capturing X-Forwarded-For from trusted proxies
+        let honour_forwarded = proxy_config.trust_forwarded_for
+            && is_trusted(&proxy_config.trusted_proxies, addr.ip());
+        let (transport, forwarded_for) = match &self.state.tls {
+            Some(tls) => {
+                let tls_stream = tls.accept(stream).await?;
+                let (ws, forwarded_for) =
+                    accept_websocket(tls_stream, &self.state, honour_forwarded).await?;
+                (UnifiedTransport::secure_websocket(ws), forwarded_for)
+            }
+            None => {
+                let (ws, forwarded_for) =
+                    accept_websocket(stream, &self.state, honour_forwarded).await?;
+                (UnifiedTransport::websocket(ws), forwarded_for)
+            }
+        };
+
+        if let Some(header) = forwarded_for {
+            match forwarded_client(&header, &proxy_config.trusted_proxies) {
+                Some(ip) => {
+                    debug!(proxy = %addr, client = %ip, "Using X-Forwarded-For client address");
+                    addr = SocketAddr::new(ip, addr.port());
+                }
+                None => {
+                    warn!(proxy = %addr, header = %header, "Ignoring malformed X-Forwarded-For");
+                }
+            }
+        }
 
-        info!(address = %addr, "WebSocket connection established");
+        info!(
+            address = %addr,
+            secure = transport.is_secure(),
+            compressed = transport.is_compressed(),
+            "WebSocket connection established"
+        );
 
         // Create transport
-        let transport = UnifiedTransport::websocket(ws_stream);
         let transport = BufferedTransport::new(transport);
 
         // Create session
-        let session = self.state.session_manager.create_session(addr, true)?;
+        let (outbound_tx, outbound_rx) = mpsc::channel(OUTBOUND_CHANNEL_SIZE);
+        let session = self
+            .state
+            .session_manager
+            .create_session_with_channel(addr, true, outbound_tx)
+            .inspect_err(|e| self.state.connection_metrics.record(e))?;
 
         // Handle the connection
-        self.handle_connection(transport, session.id).await
+        self.handle_connection(transport, outbound_rx, session.id)
+            .await
+    }
+
+    /// Read a PROXY protocol header from a trusted proxy and return the client address
+    ///
+    /// Returns the peer address unchanged when PROXY protocol is disabled for
+    /// the listener or the peer is not a trusted proxy.
+    async fn resolve_proxy_protocol(
+        &self,
+        stream: &mut TcpStream,
+        peer: SocketAddr,
+        enabled: bool,
+    ) -> Result<SocketAddr> {
+        if !enabled || !is_trusted(&self.state.config.proxy.trusted_proxies, peer.ip()) {
+            return Ok(peer);
+        }
+
+        match proxy::read_proxy_header(stream).await? {
+            Some(client) => {
+                debug!(proxy = %peer, client = %client, "PROXY protocol client address");
+                Ok(client)
+            }
+            None => Ok(peer),
+        }
     }
 
     /// Main connection handling loop
     async fn handle_connection(
         &self,
         mut transport: BufferedTransport,
+        mut outbound_rx: mpsc::Receiver<Vec<u8>>,
         session_id: u64,
     ) -> Result<()> {
         let session =
@@ -117,12 +289,15 @@ async fn handle_connection(
         );
 
         // Main processing loop
-        let result = self.process_connection(&mut transport, session_id).await;
+        let result = self
+            .process_connection(&mut transport, &mut outbound_rx, session_id)
+            .await;
 
         // Cleanup - save player and release resources
         debug!(session_id = session_id, "Connection handler ending");
         self.cleanup_session(session_id).await;
 
+        self.state.js5_scheduler.remove_session(session_id);
         self.state.session_manager.remove(session_id);
 
         // Attempt graceful shutdown
@@ -137,8 +312,12 @@ async fn handle_connection(
     async fn process_connection(
         &self,
         transport: &mut BufferedTransport,
+        outbound_rx: &mut mpsc::Receiver<Vec<u8>>,
         session_id: u64,
     ) -> Result<()> {
+        let mut flood =
+            FloodGuard::new(&self.state.config.packets, self.state.flood_metrics.clone());
+
         loop {
             let session = match self.state.session_manager.get(session_id) {
                 Some(s) => s,
@@ -166,7 +345,11 @@ async fn process_connection(
                     self.handle_login_handshake(transport, session_id).await
                 }
                 SessionState::LoggingIn => self.handle_login(transport, session_id).await,
-                SessionState::InGame => self.handle_game(transport, session_id).await,
+                SessionState::Lobby => self.handle_lobby(transport, session_id).await,
+                SessionState::InGame => {
+                    self.handle_game(transport, outbound_rx, &mut flood, session_id)
+                        .await
+                }
                 SessionState::Disconnecting | SessionState::Disconnected => {
                     debug!(session_id = session_id, state = %state, "Session disconnecting");
                     break;
@@ -192,10 +375,12 @@ async fn process_connection(
                         );
                         // Send appropriate error response based on state
                         let _ = self.send_revision_mismatch(transport, state).await;
+                        self.state.connection_metrics.record(&e);
                         break;
                     }
                     _ => {
                         warn!(session_id = session_id, error = %e, "Connection error");
+                        self.state.connection_metrics.record(&e);
                         break;
                     }
                 }
@@ -257,13 +442,13 @@ async fn handle_js5_handshake(
             "JS5 handshake"
         );
 
-        // Validate revision
-        if revision != REVISION {
+        // Select the protocol for the client's revision
+        let Some(protocol) = self.state.protocols.get(revision) else {
             warn!(
                 session_id = session_id,
-                expected = REVISION,
+                supported = ?self.state.protocols.revisions(),
                 actual = revision,
-                "JS5 revision mismatch"
+                "JS5 revision not supported"
             );
             // Send error response
             transport.write(&[Js5Response::OutOfDate.as_u8()]).await?;
@@ -272,7 +457,8 @@ async fn handle_js5_handshake(
                 expected: REVISION,
                 actual: revision,
             }));
-        }
+        };
+        session.set_protocol(protocol);
 
         // Send success response
         transport.write(&[Js5Response::Ok.as_u8()]).await?;
@@ -302,13 +488,13 @@ async fn handle_login_init(
 
         debug!(session_id = session_id, revision = revision, "Login init");
 
-        // Validate revision
-        if revision != REVISION {
+        // Select the protocol for the client's revision
+        let Some(protocol) = self.state.protocols.get(revision) else {
             warn!(
                 session_id = session_id,
-                expected = REVISION,
+                supported = ?self.state.protocols.revisions(),
                 actual = revision,
-                "Login revision mismatch"
+                "Login revision not supported"
             );
             // Send error response
             transport
@@ -319,7 +505,8 @@ async fn handle_login_init(
                 expected: REVISION,
                 actual: revision,
             }));
-        }
+        };
+        session.set_protocol(protocol);
 
         // Generate server key
         let server_key: u64 = rand::random();
@@ -359,28 +546,158 @@ async fn handle_world_list(
             "World list request"
         );
 
-        // Build world list response
-        // For now, send a simple response with just our world
-        let mut response = PacketBuffer::with_capacity(128);
+        let worlds = self
+            .state
+            .worlds
+            .worlds(self.state.world.player_count() as u32);
+        let response = encode_world_list(&worlds);
 
-        // Response format varies by client version
-        // This is a simplified version
-        response.write_ubyte(1); // Success
-        response.write_ubyte(1); // Number of worlds
+        transport.write(&response).await?;
+        transport.flush().await?;
 
-        // World entry
-        response.write_ushort(self.state.config.world_id as u16); // World ID
-        response.write_int(0); // Flags
-        response.write_string(&self.state.config.server_name); // Name
-        response.write_string("localhost"); // Address
-        response.write_ushort(self.state.session_manager.player_count() as u16); // Player count
+        Ok(())
+    }
 
-        transport.write(response.as_bytes()).await?;
+    /// Handle a lobby login: authenticate without entering the world, send
+    /// the lobby response and the world list, and wait in the lobby
+    async fn handle_lobby_login(
+        &self,
+        transport: &mut BufferedTransport,
+        session: &Session,
+        username: &str,
+        password: &str,
+        authenticator: Option<u32>,
+    ) -> Result<()> {
+        let session_id = session.id;
+        let tick = self.state.world.tick();
+        let ip = session.address.ip();
+
+        if let Err(e) = self.state.admission.check_failures(tick, ip, username) {
+            transport
+                .write(&[LoginResponse::from(e.clone()).as_u8()])
+                .await?;
+            transport.flush().await?;
+            return Err(RustscapeError::Auth(e));
+        }
+
+        let verified = match self.redeem_game_token(username, password).await {
+            Ok(Some(user_id)) => self.state.auth.verify_user_db(user_id).await,
+            Ok(None) => self.state.auth.verify_db(username, password, Some(ip)).await,
+            Err(e) => Err(e),
+        };
+        let account = match verified {
+            Ok(account) => account,
+            Err(e) => {
+                if matches!(e, RustscapeError::Auth(AuthError::InvalidCredentials)) {
+                    self.state.admission.record_failure(tick, ip, username);
+                }
+                let response_code = match e {
+                    RustscapeError::Auth(ref auth_err) => LoginResponse::from(auth_err.clone()),
+                    _ => LoginResponse::CouldNotCompleteLogin,
+                };
+                transport.write(&[response_code.as_u8()]).await?;
+                transport.flush().await?;
+                return Err(e);
+            }
+        };
+        // Game tokens are only issued to web sessions, which passed 2FA
+        // when they logged in
+        if !is_game_token(password) {
+            self.check_authenticator(transport, &account, authenticator, tick, ip, username)
+                .await?;
+        }
+        self.state.admission.record_success(username);
+
+        let activity = self.state.auth.record_lobby_login(&account, ip).await;
+        let response = LobbyResponse::new(&account, &activity, chrono::Utc::now());
+        transport.write(&response.encode()).await?;
+
+        // Hand off to world selection
+        let worlds = self
+            .state
+            .worlds
+            .worlds(self.state.world.player_count() as u32);
+        transport.write(&encode_world_list(&worlds)).await?;
         transport.flush().await?;
 
+        session.set_state(SessionState::Lobby);
+
+        info!(
+            session_id = session_id,
+            username = %account.username,
+            worlds = worlds.len(),
+            "Lobby login successful"
+        );
         Ok(())
     }
 
+    /// Redeem a game token sent in place of the password, returning the
+    /// user it was issued for (`None` if the password is not a token)
+    async fn redeem_game_token(&self, username: &str, password: &str) -> Result<Option<Uuid>> {
+        if !is_game_token(password) {
+            return Ok(None);
+        }
+
+        match self.state.game_tokens.redeem(password).await? {
+            Some(claims) if claims.matches(username) => Ok(Some(claims.user_id)),
+            _ => Err(RustscapeError::Auth(AuthError::InvalidCredentials)),
+        }
+    }
+
+    /// Enforce two-factor authentication for an account whose password
+    /// checked out, answering the client if the code is missing or wrong
+    async fn check_authenticator(
+        &self,
+        transport: &mut BufferedTransport,
+        account: &Account,
+        authenticator: Option<u32>,
+        tick: u64,
+        ip: IpAddr,
+        username: &str,
+    ) -> Result<()> {
+        let code = authenticator.map(|code| format!("{:06}", code));
+        let now = chrono::Utc::now().timestamp() as u64;
+        let Err(e) = self
+            .state
+            .two_factor
+            .check_login(account.user_id, code.as_deref(), now)
+            .await
+        else {
+            return Ok(());
+        };
+
+        if matches!(e, RustscapeError::Auth(AuthError::InvalidTwoFactorCode)) {
+            self.state.admission.record_failure(tick, ip, username);
+        }
+        let response_code = match e {
+            RustscapeError::Auth(ref auth_err) => LoginResponse::from(auth_err.clone()),
+            _ => LoginResponse::CouldNotCompleteLogin,
+        };
+        transport.write(&[response_code.as_u8()]).await?;
+        transport.flush().await?;
+        Err(e)
+    }
+
+    /// Handle lobby requests (world list refreshes until a world is chosen)
+    async fn handle_lobby(&self, transport: &mut BufferedTransport, session_id: u64) -> Result<()> {
+        let opcode = transport.read_byte().await?;
+
+        match HandshakeOpcode::from_u8(opcode) {
+            Some(HandshakeOpcode::WorldList) => self.handle_world_list(transport, session_id).await,
+            _ => {
+                warn!(
+                    session_id = session_id,
+                    opcode = opcode,
+                    "Unexpected lobby opcode"
+                );
+                Err(RustscapeError::Protocol(ProtocolError::UnexpectedPacket {
+                    state: SessionState::Lobby.name().to_string(),
+                    opcode,
+                }))
+            }
+        }
+    }
+
     /// Handle JS5 file requests
     async fn handle_js5(&self, transport: &mut BufferedTransport, session_id: u64) -> Result<()> {
         let session =
@@ -408,12 +725,13 @@ async fn handle_js5(&self, transport: &mut BufferedTransport, session_id: u64) -
                 );
 
                 // Get file data from cache and send response
-                self.send_js5_file(transport, session_id, index, archive, priority)
-                    .await?;
+                let request = Js5FileRequest::new(index, archive, priority);
+                self.send_js5_file(transport, &session, request).await?;
             }
             2 | 3 => {
-                // Music/logged in status - just acknowledge
+                // Logged out/in status - logged-in clients get scheduling priority
                 let _data = transport.read_exact(3).await?;
+                session.set_js5_logged_in(opcode == 3);
             }
             4 => {
                 // Encryption key
@@ -460,66 +778,29 @@ async fn handle_js5(&self, transport: &mut BufferedTransport, session_id: u64) -
     async fn send_js5_file(
         &self,
         transport: &mut BufferedTransport,
-        session_id: u64,
-        index: u8,
-        archive: u16,
-        priority: bool,
+        session: &Session,
+        request: Js5FileRequest,
     ) -> Result<()> {
-        // Special case: index 255, archive 255 = checksum table
-        let data = if index == 255 && archive == 255 {
-            self.state.cache.get_checksum_table()?
-        } else {
-            self.state.cache.get_file(index, archive as u32)?
-        };
-
-        // Build response
-        let mut response = PacketBuffer::with_capacity(data.len() + 8);
-
-        // Header: index (1), archive (2), compression info (1), size (4)
-        response.write_ubyte(index);
-        response.write_ushort(archive);
-
-        // For checksum table (255/255), send raw data
-        if index == 255 && archive == 255 {
-            // Settings byte: compression type (0) | priority flag
-            let settings = if priority { 0x80 } else { 0x00 };
-            response.write_ubyte(settings);
-            response.write_int(data.len() as i32);
-
-            // Write data with block markers every 512 bytes
-            let mut offset = 0;
-            for (_i, &byte) in data.iter().enumerate() {
-                if offset == 512 {
-                    response.write_ubyte(0xFF); // Block marker
-                    offset = 1;
-                }
-                response.write_ubyte(byte);
-                offset += 1;
-            }
-        } else {
-            // Regular file - include compression header from cache
-            if data.len() >= 5 {
-                let compression = data[0];
-                let length = i32::from_be_bytes([data[1], data[2], data[3], data[4]]);
-
-                let settings = compression | if priority { 0x80 } else { 0x00 };
-                response.write_ubyte(settings);
-                response.write_int(length);
-
-                // Write remaining data with block markers
-                let mut offset = 8; // Header takes 8 bytes (3 + 1 + 4)
-                for &byte in &data[5..] {
-                    if offset == 512 {
-                        response.write_ubyte(0xFF);
-                        offset = 1;
-                    }
-                    response.write_ubyte(byte);
-                    offset += 1;
-                }
-            }
+        let (session_id, index, archive) = (session.id, request.index, request.archive);
+
+        // Pre-encoded frames are shared between sessions
+        let response = self
+            .state
+            .js5_responses
+            .get_encrypted(&request, session.js5_encryption())?;
+
+        // Wait for a share of the global JS5 bandwidth, logged-in clients first
+        let scheduling_priority = request.priority || session.js5_logged_in();
+        if !self
+            .state
+            .js5_scheduler
+            .acquire(session_id, response.len(), scheduling_priority)
+            .await
+        {
+            return Err(RustscapeError::Network(NetworkError::ConnectionClosed));
         }
 
-        transport.write(response.as_bytes()).await?;
+        transport.write(&response).await?;
         transport.flush().await?;
 
         trace!(
@@ -569,6 +850,7 @@ async fn handle_login_handshake(
             login_type = ?login_type,
             "Valid login type, transitioning to LoggingIn"
         );
+        session.set_login_type(login_type);
         session.set_state(SessionState::LoggingIn);
 
         Ok(())
@@ -603,14 +885,16 @@ async fn handle_login(&self, transport: &mut BufferedTransport, session_id: u64)
         let mut buffer = PacketBuffer::from_bytes(&login_data);
 
         // Parse login block
+        // The login block must repeat the revision chosen at the handshake
         let revision = buffer.read_int() as u32;
-        if revision != REVISION {
+        let expected_revision = session.protocol().revision();
+        if revision != expected_revision {
             transport
                 .write(&[LoginResponse::GameUpdated.as_u8()])
                 .await?;
             transport.flush().await?;
             return Err(RustscapeError::Protocol(ProtocolError::InvalidRevision {
-                expected: REVISION,
+                expected: expected_revision,
                 actual: revision,
             }));
         }
@@ -637,7 +921,7 @@ async fn handle_login(&self, transport: &mut BufferedTransport, session_id: u64)
         let decrypted_block = self.decrypt_rsa_block(&rsa_block)?;
 
         // Parse the decrypted RSA block
-        let (isaac_seeds, uid, password) = self.parse_rsa_block(&decrypted_block)?;
+        let (isaac_seeds, uid, password, authenticator) = self.parse_rsa_block(&decrypted_block)?;
 
         debug!(
             session_id = session_id,
@@ -666,6 +950,27 @@ async fn handle_login(&self, transport: &mut BufferedTransport, session_id: u64)
             "Processing login credentials"
         );
 
+        if session.login_type() == LoginType::Lobby {
+            return self
+                .handle_lobby_login(transport, &session, &username, &password, authenticator)
+                .await;
+        }
+
+        // A reconnect presenting its resume token (in place of the password)
+        // reattaches to the player parked when its connection dropped
+        if session.login_type() == LoginType::Reconnect {
+            if let Some(parked) = self.state.resume.resume(&username, &password) {
+                return self
+                    .resume_player(transport, &session, parked, &isaac_seeds, client_info)
+                    .await;
+            }
+            debug!(
+                session_id = session_id,
+                username = %username,
+                "No resumable player for reconnect, continuing with normal login"
+            );
+        }
+
         // Check if already logged in
         if self.state.session_manager.is_logged_in(&username) {
             transport
@@ -675,10 +980,56 @@ async fn handle_login(&self, transport: &mut BufferedTransport, session_id: u64)
             return Err(RustscapeError::Auth(AuthError::AlreadyLoggedIn));
         }
 
-        // Authenticate with auth service (use async DB auth if available)
-        let auth_result = match self.state.auth.authenticate_db(&username, &password).await {
+        // Logins over the per-tick cap wait their turn, and addresses or
+        // names with too many failed attempts are refused outright
+        let tick = self.state.world.tick();
+        let ip = session.address.ip();
+        let online = self.state.world.players.count();
+        match self.state.admission.admit(tick, ip, &username, online) {
+            Ok(Admission::Admitted) => {}
+            Ok(Admission::Queued { position }) => {
+                debug!(
+                    session_id = session_id,
+                    username = %username,
+                    position = position,
+                    "Login queued"
+                );
+                let mut response = PacketBuffer::with_capacity(3);
+                response.write_ubyte(LoginResponse::Delay.as_u8());
+                response.write_ushort(position.min(u16::MAX as usize) as u16);
+                transport.write(response.as_bytes()).await?;
+                transport.flush().await?;
+                session.set_state(SessionState::Disconnecting);
+                return Ok(());
+            }
+            Err(e) => {
+                transport
+                    .write(&[LoginResponse::from(e.clone()).as_u8()])
+                    .await?;
+                transport.flush().await?;
+                return Err(RustscapeError::Auth(e));
+            }
+        }
+
+        // Authenticate with auth service (use async DB auth if available),
+        // or with a game token from the web API in place of the password
+        let via_token = is_game_token(&password);
+        let authenticated = match self.redeem_game_token(&username, &password).await {
+            Ok(Some(user_id)) => self.state.auth.authenticate_user_db(user_id).await,
+            Ok(None) => {
+                self.state
+                    .auth
+                    .authenticate_db(&username, &password, Some(ip))
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        let auth_result = match authenticated {
             Ok(result) => result,
             Err(e) => {
+                if matches!(e, RustscapeError::Auth(AuthError::InvalidCredentials)) {
+                    self.state.admission.record_failure(tick, ip, &username);
+                }
                 let response_code = match e {
                     RustscapeError::Auth(ref auth_err) => LoginResponse::from(auth_err.clone()),
                     _ => LoginResponse::CouldNotCompleteLogin,
@@ -688,6 +1039,52 @@ async fn handle_login(&self, transport: &mut BufferedTransport, session_id: u64)
                 return Err(e);
             }
         };
+        // Game tokens are only issued to web sessions, which passed 2FA
+        // when they logged in
+        if !via_token {
+            if let Err(e) = self
+                .check_authenticator(
+                    transport,
+                    &auth_result.account,
+                    authenticator,
+                    tick,
+                    ip,
+                    &username,
+                )
+                .await
+            {
+                self.state
+                    .auth
+                    .release_player_index(auth_result.player_index);
+                return Err(e);
+            }
+        }
+        self.state.admission.record_success(&username);
+
+        // An authenticated login also reclaims a player still parked after a
+        // dropped connection, rather than loading a second copy
+        if let Some(parked) = self.state.resume.claim(&username) {
+            self.state
+                .auth
+                .release_player_index(auth_result.player_index);
+            return self
+                .resume_player(transport, &session, parked, &isaac_seeds, client_info)
+                .await;
+        }
+
+        // The account may only be in one world at a time
+        if let Err(e) = self.state.leases.acquire(&username).await {
+            self.state
+                .auth
+                .release_player_index(auth_result.player_index);
+            let response_code = match e {
+                RustscapeError::Auth(ref auth_err) => LoginResponse::from(auth_err.clone()),
+                _ => LoginResponse::CouldNotCompleteLogin,
+            };
+            transport.write(&[response_code.as_u8()]).await?;
+            transport.flush().await?;
+            return Err(e);
+        }
 
         // Set up ISAAC ciphers for packet encryption
         let isaac_pair = IsaacPair::new(&isaac_seeds);
@@ -811,6 +1208,11 @@ async fn handle_login(&self, transport: &mut BufferedTransport, session_id: u64)
             }
         }
 
+        // Load the collision regions around the player before its first walk
+        if let Some(player) = self.state.world.players.get_by_username(&username) {
+            pathfinding::preload_scene(&self.state.world.collision, player.location());
+        }
+
         // Send success response
         let mut response = PacketBuffer::with_capacity(16);
         response.write_ubyte(LoginResponse::Success.as_u8());
@@ -833,13 +1235,14 @@ async fn handle_login(&self, transport: &mut BufferedTransport, session_id: u64)
             "Login successful - ISAAC ciphers initialized"
         );
 
-        // Send login initialization packets (map region, skills, etc.)
-        let init_packets = login_init::build_login_init_packets(
+        // Send login initialization packets (map region, skills, etc.). The
+        // client expects this first batch unencrypted.
+        let init_state = InitialPlayerState::new(
             auth_result.player_index,
             auth_result.account.rights,
             auth_result.account.member,
-            None, // Don't use ISAAC for init packets yet (client expects unencrypted first batch)
         );
+        let init_packets = session.protocol().login_init(&init_state);
 
         for packet in init_packets {
             transport.write(&packet).await?;
@@ -848,9 +1251,141 @@ async fn handle_login(&self, transport: &mut BufferedTransport, session_id: u64)
 
         debug!(session_id = session_id, "Login initialization packets sent");
 
+        self.start_capture(&session, &username);
+        self.issue_resume_token(&session);
+
         Ok(())
     }
 
+    /// Reattach a login to a player parked after a dropped connection
+    ///
+    /// The player keeps its world state; only the connection-level state
+    /// (ISAAC ciphers, session, player sync view) starts fresh.
+    async fn resume_player(
+        &self,
+        transport: &mut BufferedTransport,
+        session: &Session,
+        parked: ParkedPlayer,
+        isaac_seeds: &[u32; 4],
+        client_info: Option<ClientInfo>,
+    ) -> Result<()> {
+        let Some(player) = self
+            .state
+            .world
+            .players
+            .reattach(parked.player_index, session.id)
+        else {
+            if let Some(auth_index) = parked.auth_index {
+                self.state.auth.release_player_index(auth_index);
+            }
+            self.state.leases.release(&parked.username).await;
+            transport
+                .write(&[LoginResponse::CouldNotCompleteLogin.as_u8()])
+                .await?;
+            transport.flush().await?;
+            return Err(RustscapeError::Network(NetworkError::SessionNotFound(
+                session.id,
+            )));
+        };
+
+        session.set_isaac(IsaacPair::new(isaac_seeds));
+        self.state
+            .session_manager
+            .register_username(session.id, &parked.username);
+        session.set_username(parked.username.clone());
+        if let Some(auth_index) = parked.auth_index {
+            session.set_player_index(auth_index);
+        }
+        if let Some(info) = client_info {
+            session.set_client_info(info);
+        }
+
+        // The new client knows nothing about the surrounding players yet
+        self.state.world.inbound.remove(player.index);
+        self.state.world.unregister_player_sync(player.index);
+        self.state.world.register_player_sync(player.index);
+
+        let client_index = parked.auth_index.unwrap_or(player.index);
+        let mut response = PacketBuffer::with_capacity(16);
+        response.write_ubyte(LoginResponse::Success.as_u8());
+        response.write_ubyte(parked.rights);
+        response.write_ubyte(0); // Flagged status
+        response.write_ushort(client_index);
+        response.write_ubyte(if parked.member { 1 } else { 0 });
+
+        transport.write(response.as_bytes()).await?;
+        transport.flush().await?;
+
+        session.set_state(SessionState::InGame);
+
+        // Send the map region for where the player actually is
+        let location = player.location();
+        let mut init_state = InitialPlayerState::new(client_index, parked.rights, parked.member)
+            .with_location(location.x, location.y, location.z);
+        init_state.run_energy = *player.run_energy.read();
+        for packet in session.protocol().login_init(&init_state) {
+            transport.write(&packet).await?;
+        }
+        transport.flush().await?;
+
+        info!(
+            session_id = session.id,
+            username = %parked.username,
+            player_index = player.index,
+            "Player resumed after dropped connection"
+        );
+
+        self.start_capture(session, &parked.username);
+        self.issue_resume_token(session);
+
+        Ok(())
+    }
+
+    /// Start capturing a player's packets if capture is enabled for them
+    fn start_capture(&self, session: &Session, username: &str) {
+        let config = &self.state.config.capture;
+        if !config.should_capture(username) {
+            return;
+        }
+        let Some(player) = self.state.world.players.get_by_username(username) else {
+            return;
+        };
+
+        let header = CaptureHeader {
+            revision: session.protocol().revision(),
+            username: username.to_string(),
+            player_index: player.index,
+            rights: player.rights().as_u8(),
+            member: *player.member.read(),
+            snapshot: player.to_player_data(Uuid::nil(), player.user_id.unwrap_or(Uuid::nil())),
+        };
+
+        match PacketRecorder::create(&config.directory, session.id, &header) {
+            Ok(recorder) => session.start_capture(recorder),
+            Err(e) => {
+                warn!(
+                    session_id = session.id,
+                    username = %username,
+                    error = %e,
+                    "Failed to start packet capture"
+                );
+            }
+        }
+    }
+
+    /// Issue a new resume token and queue it for the client
+    fn issue_resume_token(&self, session: &Session) {
+        if !self.state.resume.is_enabled() {
+            return;
+        }
+
+        let token = ResumeRegistry::issue_token();
+        if let Some(packet) = session.encode_message(&ServerMessage::ResumeToken(token.clone())) {
+            session.queue_packet(&packet);
+        }
+        session.set_resume_token(token);
+    }
+
     /// Decrypt the RSA block from login packet
     fn decrypt_rsa_block(&self, encrypted: &[u8]) -> Result<Vec<u8>> {
         // In dev mode, skip RSA decryption entirely and treat as plaintext
@@ -872,8 +1407,9 @@ fn decrypt_rsa_block(&self, encrypted: &[u8]) -> Result<Vec<u8>> {
         }
     }
 
-    /// Parse the decrypted RSA block to extract ISAAC seeds, UID, and password
-    fn parse_rsa_block(&self, data: &[u8]) -> Result<([u32; 4], u32, String)> {
+    /// Parse the decrypted RSA block to extract ISAAC seeds, UID, password
+    /// and the optional authenticator code
+    fn parse_rsa_block(&self, data: &[u8]) -> Result<([u32; 4], u32, String, Option<u32>)> {
         if data.is_empty() {
             return Err(RustscapeError::Protocol(ProtocolError::InvalidRsaBlock));
         }
@@ -915,14 +1451,22 @@ fn decrypt_rsa_block(&self, encrypted: &[u8]) -> Result<Vec<u8>> {
             String::new()
         };
 
+        // Authenticator code (only sent by clients of accounts with 2FA)
+        let authenticator = if buffer.remaining() >= 4 {
+            Some(buffer.read_int() as u32)
+        } else {
+            None
+        };
+
         debug!(
             isaac_seeds = ?isaac_seeds,
             uid = uid,
             password_len = password.len(),
+            has_authenticator = authenticator.is_some(),
             "Parsed RSA block"
         );
 
-        Ok((isaac_seeds, uid, password))
+        Ok((isaac_seeds, uid, password, authenticator))
     }
 
     /// Parse client information from remaining login packet data
@@ -962,16 +1506,43 @@ fn parse_client_info(
         Some(info)
     }
 
-    /// Handle in-game packets
-    async fn handle_game(&self, transport: &mut BufferedTransport, session_id: u64) -> Result<()> {
+    /// Handle in-game traffic
+    ///
+    /// Waits for either the next inbound packet or the outbound batch flushed
+    /// by the game tick. Inbound packets are decoded here and queued for the
+    /// tick; they are applied to the player in `GameWorld::process_tick`. Each
+    /// outbound batch is written as a single write (one WebSocket frame).
+    async fn handle_game(
+        &self,
+        transport: &mut BufferedTransport,
+        outbound_rx: &mut mpsc::Receiver<Vec<u8>>,
+        flood: &mut FloodGuard,
+        session_id: u64,
+    ) -> Result<()> {
         let session =
             self.state.session_manager.get(session_id).ok_or_else(|| {
                 RustscapeError::Network(NetworkError::SessionNotFound(session_id))
             })?;
 
+        // Only wait for the first byte of a packet here, so cancelling the read
+        // never drops a partially received packet
+        tokio::select! {
+            biased;
+            outbound = outbound_rx.recv() => {
+                let data = outbound
+                    .ok_or(RustscapeError::Network(NetworkError::ConnectionClosed))?;
+                transport.write(&data).await?;
+                transport.flush().await?;
+                return Ok(());
+            }
+            result = transport.peek_byte() => {
+                result?;
+            }
+        }
+
         // Read packet opcode (encrypted with ISAAC)
         let encoded_opcode = transport.read_byte().await?;
-        let opcode = session.decode_opcode(encoded_opcode as u8);
+        let opcode = session.decode_opcode(encoded_opcode);
 
         trace!(
             session_id = session_id,
@@ -980,18 +1551,13 @@ async fn handle_game(&self, transport: &mut BufferedTransport, session_id: u64)
             "Received game packet"
         );
 
-        // Look up packet size from protocol definitions
-        let packet_size = INCOMING_PACKET_SIZES[opcode as usize];
-
-        // Read packet data based on size type
-        let data = match packet_size {
-            // Fixed size packet
-            size if size > 0 => transport.read_exact(size as usize).await?,
+        // Look up packet framing in the client's protocol
+        let protocol = session.protocol();
+        let data = match protocol.incoming_size(opcode) {
+            PacketSize::Fixed(0) => vec![],
+            PacketSize::Fixed(size) => transport.read_exact(size).await?,
             // Variable byte (1-byte length prefix)
-            0 => {
-                vec![] // Zero-length packet
-            }
-            -1 => {
+            PacketSize::VariableByte => {
                 let len = transport.read_byte().await? as usize;
                 if len > 0 {
                     transport.read_exact(len).await?
@@ -1000,7 +1566,7 @@ async fn handle_game(&self, transport: &mut BufferedTransport, session_id: u64)
                 }
             }
             // Variable short (2-byte length prefix)
-            -2 => {
+            PacketSize::VariableShort => {
                 let len = {
                     let len_bytes = transport.read_exact(2).await?;
                     u16::from_be_bytes([len_bytes[0], len_bytes[1]]) as usize
@@ -1011,58 +1577,78 @@ async fn handle_game(&self, transport: &mut BufferedTransport, session_id: u64)
                     vec![]
                 }
             }
-            // Unknown packet - try to skip gracefully
-            _ => {
-                trace!(
+            // Unregistered packet - its length is unknown, so the rest of
+            // the stream can't be framed
+            PacketSize::Unknown => {
+                warn!(
                     session_id = session_id,
                     opcode = opcode,
-                    "Unknown packet opcode, cannot determine size"
+                    "Unregistered game packet opcode, disconnecting"
                 );
-                return Ok(());
-            }
-        };
-
-        // Create incoming packet and process with game handler
-        let packet = IncomingGamePacket::new(opcode, data);
-        let handler = GamePacketHandler::new();
-
-        // Try to get the player for this session to process with player context
-        let result = if let Some(player_index) = session.player_index() {
-            if let Some(player) = self.state.world.players.get(player_index) {
-                // Process with player context for movement, commands, etc.
-                handler.process_with_player(&packet, &player)
-            } else {
-                // Player not found, process without context
-                handler.process(&packet)
+                return Err(RustscapeError::Protocol(ProtocolError::InvalidOpcode(
+                    opcode,
+                )));
             }
-        } else {
-            // No player index set, process without context
-            handler.process(&packet)
         };
 
-        match result {
-            Ok(packet_result) => {
-                // Send any response packets
-                if !packet_result.responses.is_empty() {
-                    for response in packet_result.responses {
-                        let encoded = session
-                            .with_isaac(|isaac| response.encode(isaac))
-                            .unwrap_or_else(|| response.encode_raw());
-                        transport.write(&encoded).await?;
-                    }
-                    transport.flush().await?;
-                }
+        // Enforce per-tick packet, byte and opcode limits (opcode plus payload)
+        match flood.check(self.state.world.tick(), opcode, data.len() + 1) {
+            FloodVerdict::Accept => {}
+            FloodVerdict::Drop => {
+                trace!(
+                    session_id = session_id,
+                    opcode = opcode,
+                    "Packet over flood limit, dropping"
+                );
+                return Ok(());
             }
-            Err(e) => {
+            FloodVerdict::Disconnect => {
                 warn!(
                     session_id = session_id,
+                    address = %session.address,
+                    username = ?session.username(),
                     opcode = opcode,
-                    error = %e,
-                    "Error processing game packet"
+                    violations = flood.violations(),
+                    "Disconnecting session for packet flooding"
                 );
+                session.set_state(SessionState::Disconnecting);
+                return Err(RustscapeError::Network(NetworkError::PacketFlood(
+                    session_id,
+                )));
             }
         }
 
+        session.capture(Direction::Inbound, self.state.world.tick(), opcode, &data);
+
+        // Queue the packet for the next game tick
+        let Some(player_index) = session.player_index() else {
+            debug!(
+                session_id = session_id,
+                opcode = opcode,
+                "Dropping game packet for session without a player"
+            );
+            return Ok(());
+        };
+
+        let Some(packet) = protocol.decode(IncomingGamePacket::new(opcode, data)) else {
+            trace!(
+                session_id = session_id,
+                opcode = opcode,
+                revision = protocol.revision(),
+                "Game packet ignored by client protocol"
+            );
+            return Ok(());
+        };
+
+        if !self.state.world.inbound.push(player_index, packet) {
+            debug!(
+                session_id = session_id,
+                player_index = player_index,
+                opcode = opcode,
+                "Inbound packet queue full, dropping packet"
+            );
+        }
+
         Ok(())
     }
 
@@ -1082,19 +1668,81 @@ async fn send_revision_mismatch(
         Ok(())
     }
 
-    /// Cleanup session resources - save player data and release indices
+    /// Cleanup session resources - park or save the player and release indices
     async fn cleanup_session(&self, session_id: u64) {
         // Get session info before cleanup
-        let (username, player_index) =
-            if let Some(session) = self.state.session_manager.get(session_id) {
-                (
-                    session.username().map(|s| s.to_string()),
-                    session.player_index(),
-                )
-            } else {
-                return;
-            };
+        let Some(session) = self.state.session_manager.get(session_id) else {
+            return;
+        };
+        let username = session.username();
+        let player_index = session.player_index();
+        session.stop_capture();
+
+        // Keep the player in the world for a while if the connection dropped
+        if self.park_player(&session) {
+            return;
+        }
 
+        self.release_player(session_id, username, player_index)
+            .await;
+    }
+
+    /// Park an in-game player whose connection dropped so it can resume
+    ///
+    /// Returns false if the player should be removed now instead: resuming is
+    /// disabled, the session wasn't in game (or was disconnected on purpose),
+    /// or no resume token was issued.
+    fn park_player(&self, session: &Session) -> bool {
+        if !self.state.resume.is_enabled() || !session.is_state(SessionState::InGame) {
+            return false;
+        }
+        let (Some(username), Some(token)) = (session.username(), session.resume_token()) else {
+            return false;
+        };
+        let Some(player) = self.state.world.players.get_by_username(&username) else {
+            return false;
+        };
+
+        self.state.resume.park(ParkedPlayer::new(
+            username.clone(),
+            player.index,
+            session.player_index(),
+            player.rights().as_u8(),
+            *player.member.read(),
+            token.clone(),
+        ));
+
+        let grace = self.state.resume.grace();
+        info!(
+            session_id = session.id,
+            username = %username,
+            grace_secs = grace.as_secs(),
+            "Connection dropped, keeping player in world for resume"
+        );
+
+        // Remove the player for good if it hasn't resumed by the end of the window
+        let state = self.state.clone();
+        let session_id = session.id;
+        tokio::spawn(async move {
+            tokio::time::sleep(grace).await;
+            if let Some(parked) = state.resume.expire(&username, &token) {
+                info!(username = %parked.username, "Resume window expired, removing player");
+                ConnectionHandler::new(state, false)
+                    .release_player(session_id, Some(parked.username), parked.auth_index)
+                    .await;
+            }
+        });
+
+        true
+    }
+
+    /// Save a player, remove it from the game world and release its index
+    async fn release_player(
+        &self,
+        session_id: u64,
+        username: Option<String>,
+        player_index: Option<u16>,
+    ) {
         // Save player data if persistence is enabled
         if let Some(username) = &username {
             if let Some(player) = self.state.world.players.get_by_username(username) {
@@ -1144,8 +1792,9 @@ async fn cleanup_session(&self, session_id: u64) {
                     }
                 }
 
-                // Unregister player from sync system
+                // Unregister player from sync system and drop queued packets
                 self.state.world.unregister_player_sync(player.index);
+                self.state.world.inbound.remove(player.index);
 
                 // Unregister player from game world
                 self.state.world.players.unregister(player.index);
@@ -1158,6 +1807,11 @@ async fn cleanup_session(&self, session_id: u64) {
                     "Player unregistered from game world and sync"
                 );
             }
+
+            // Let the account log in to other worlds. The session only carries
+            // a username once the lease is held, so this also covers logins
+            // that failed to register a player.
+            self.state.leases.release(username).await;
         }
 
         // Release player index from auth service
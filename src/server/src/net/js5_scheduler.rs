@@ -0,0 +1,491 @@
+//! Global JS5 bandwidth scheduler
+//!
+//! Each JS5 connection used to write file responses as fast as its socket
+//! allowed, so one fresh client downloading the whole cache could starve
+//! players who were already logged in. The scheduler hands out send permits
+//! from a global token bucket (the total bytes/sec cap) and a per-session token
+//! bucket. Priority requests are served before normal ones, and sessions are
+//! visited round-robin so no single connection can take all the bandwidth.
+//!
+//! Waiting senders drive the scheduler themselves, so no background task is
+//! needed: every waiter retries dispatch on a short interval until it is granted.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tracing::trace;
+
+use crate::config::Js5Config;
+
+/// How often waiting senders retry dispatch while throttled
+const DISPATCH_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Token bucket measured in bytes
+///
+/// A rate of 0 means unlimited. Tokens may go negative when a response larger
+/// than the remaining budget is sent; the bucket then has to refill past zero
+/// before the next send, which keeps the long-term rate exact without having
+/// to split responses.
+#[derive(Debug)]
+struct TokenBucket {
+    /// Refill rate in bytes per second (0 = unlimited)
+    rate: u64,
+    /// Maximum number of tokens the bucket can hold
+    capacity: f64,
+    /// Currently available tokens
+    tokens: f64,
+    /// Time of the last refill
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Create a full bucket
+    fn new(rate: u64, burst: u64, now: Instant) -> Self {
+        let capacity = if burst > 0 { burst } else { rate } as f64;
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            last_refill: now,
+        }
+    }
+
+    /// Add tokens for the time elapsed since the last refill
+    fn refill(&mut self, now: Instant) {
+        if self.rate == 0 {
+            return;
+        }
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate as f64).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Check whether a send may start now
+    fn has_tokens(&self) -> bool {
+        self.rate == 0 || self.tokens > 0.0
+    }
+
+    /// Take tokens for a send of the given size
+    fn consume(&mut self, bytes: usize) {
+        if self.rate != 0 {
+            self.tokens -= bytes as f64;
+        }
+    }
+}
+
+/// A send waiting for its permit
+#[derive(Debug)]
+struct PendingSend {
+    /// Size of the response in bytes
+    bytes: usize,
+    /// Channel used to grant the permit
+    permit: oneshot::Sender<()>,
+}
+
+/// Per-session scheduling state
+#[derive(Debug)]
+struct SessionQueue {
+    /// Per-session bandwidth budget
+    bucket: TokenBucket,
+    /// Pending priority sends
+    priority: VecDeque<PendingSend>,
+    /// Pending normal sends
+    normal: VecDeque<PendingSend>,
+}
+
+/// Mutable scheduler state, guarded by a single lock
+#[derive(Debug)]
+struct SchedulerState {
+    /// Global bandwidth budget
+    global: TokenBucket,
+    /// Per-session queues
+    sessions: HashMap<u64, SessionQueue>,
+    /// Round-robin order of sessions
+    rotation: VecDeque<u64>,
+}
+
+/// Queue-depth and throughput statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Js5SchedulerStats {
+    /// Number of sessions known to the scheduler
+    pub sessions: usize,
+    /// Number of priority sends waiting for a permit
+    pub priority_queued: usize,
+    /// Number of normal sends waiting for a permit
+    pub normal_queued: usize,
+    /// Total bytes granted since startup
+    pub bytes_sent: u64,
+    /// Total responses granted since startup
+    pub requests_served: u64,
+    /// Number of sends that had to wait for bandwidth
+    pub throttled: u64,
+}
+
+/// Global JS5 bandwidth scheduler
+pub struct Js5Scheduler {
+    /// Total bytes per second across all sessions (0 = unlimited)
+    max_bytes_per_sec: u64,
+    /// Bytes per second for a single session (0 = unlimited)
+    session_bytes_per_sec: u64,
+    /// Burst size of the per-session bucket in bytes
+    session_burst_bytes: u64,
+    /// Scheduler state
+    state: Mutex<SchedulerState>,
+    /// Total bytes granted
+    bytes_sent: AtomicU64,
+    /// Total responses granted
+    requests_served: AtomicU64,
+    /// Sends that had to wait
+    throttled: AtomicU64,
+}
+
+impl Js5Scheduler {
+    /// Create a new scheduler from the JS5 configuration
+    pub fn new(config: &Js5Config) -> Self {
+        Self {
+            max_bytes_per_sec: config.max_bytes_per_sec,
+            session_bytes_per_sec: config.session_bytes_per_sec,
+            session_burst_bytes: config.session_burst_bytes,
+            state: Mutex::new(SchedulerState {
+                global: TokenBucket::new(config.max_bytes_per_sec, 0, Instant::now()),
+                sessions: HashMap::new(),
+                rotation: VecDeque::new(),
+            }),
+            bytes_sent: AtomicU64::new(0),
+            requests_served: AtomicU64::new(0),
+            throttled: AtomicU64::new(0),
+        }
+    }
+
+    /// Create a scheduler without any bandwidth limits
+    pub fn unlimited() -> Self {
+        Self::new(&Js5Config {
+            max_bytes_per_sec: 0,
+            session_bytes_per_sec: 0,
+            ..Default::default()
+        })
+    }
+
+    /// Check whether no limits are configured
+    pub fn is_unlimited(&self) -> bool {
+        self.max_bytes_per_sec == 0 && self.session_bytes_per_sec == 0
+    }
+
+    /// Wait until a response of `bytes` may be sent for a session
+    ///
+    /// Returns `false` if the session was removed while waiting.
+    pub async fn acquire(&self, session_id: u64, bytes: usize, priority: bool) -> bool {
+        if self.is_unlimited() {
+            self.record_grant(bytes);
+            return true;
+        }
+
+        let mut rx = self.enqueue(session_id, bytes, priority);
+
+        match rx.try_recv() {
+            Ok(()) => return true,
+            Err(oneshot::error::TryRecvError::Closed) => return false,
+            Err(oneshot::error::TryRecvError::Empty) => {}
+        }
+
+        self.throttled.fetch_add(1, Ordering::Relaxed);
+        trace!(
+            session_id = session_id,
+            bytes = bytes,
+            priority = priority,
+            "JS5 send throttled"
+        );
+
+        loop {
+            tokio::select! {
+                result = &mut rx => return result.is_ok(),
+                _ = tokio::time::sleep(DISPATCH_INTERVAL) => {
+                    self.dispatch(Instant::now());
+                }
+            }
+        }
+    }
+
+    /// Queue a send for a session and dispatch whatever the budgets allow
+    fn enqueue(&self, session_id: u64, bytes: usize, priority: bool) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        let mut guard = self.state.lock();
+        let state = &mut *guard;
+        let now = Instant::now();
+
+        let queue = state.sessions.entry(session_id).or_insert_with(|| {
+            state.rotation.push_back(session_id);
+            SessionQueue {
+                bucket: TokenBucket::new(self.session_bytes_per_sec, self.session_burst_bytes, now),
+                priority: VecDeque::new(),
+                normal: VecDeque::new(),
+            }
+        });
+
+        let pending = PendingSend { bytes, permit: tx };
+        if priority {
+            queue.priority.push_back(pending);
+        } else {
+            queue.normal.push_back(pending);
+        }
+
+        self.dispatch_locked(state, now);
+        rx
+    }
+
+    /// Grant as many pending sends as the budgets allow
+    ///
+    /// Returns the number of sends granted.
+    pub fn dispatch(&self, now: Instant) -> usize {
+        let mut state = self.state.lock();
+        self.dispatch_locked(&mut state, now)
+    }
+
+    /// Dispatch with the state lock already held
+    fn dispatch_locked(&self, state: &mut SchedulerState, now: Instant) -> usize {
+        state.global.refill(now);
+        for queue in state.sessions.values_mut() {
+            queue.bucket.refill(now);
+        }
+
+        let mut granted = 0;
+
+        // Serve every priority send before any normal send
+        for priority in [true, false] {
+            loop {
+                let mut progress = false;
+
+                for _ in 0..state.rotation.len() {
+                    if !state.global.has_tokens() {
+                        return granted;
+                    }
+
+                    let Some(session_id) = state.rotation.pop_front() else {
+                        break;
+                    };
+                    state.rotation.push_back(session_id);
+
+                    let Some(queue) = state.sessions.get_mut(&session_id) else {
+                        continue;
+                    };
+                    if !queue.bucket.has_tokens() {
+                        continue;
+                    }
+
+                    let pending = if priority {
+                        queue.priority.pop_front()
+                    } else {
+                        queue.normal.pop_front()
+                    };
+
+                    if let Some(pending) = pending {
+                        queue.bucket.consume(pending.bytes);
+                        state.global.consume(pending.bytes);
+
+                        if pending.permit.send(()).is_ok() {
+                            self.record_grant(pending.bytes);
+                            granted += 1;
+                        }
+                        progress = true;
+                    }
+                }
+
+                if !progress {
+                    break;
+                }
+            }
+        }
+
+        granted
+    }
+
+    /// Forget a session, cancelling any sends it still has queued
+    pub fn remove_session(&self, session_id: u64) {
+        let mut state = self.state.lock();
+        if state.sessions.remove(&session_id).is_some() {
+            state.rotation.retain(|&id| id != session_id);
+        }
+    }
+
+    /// Get queue-depth and throughput statistics
+    pub fn stats(&self) -> Js5SchedulerStats {
+        let state = self.state.lock();
+        Js5SchedulerStats {
+            sessions: state.sessions.len(),
+            priority_queued: state.sessions.values().map(|q| q.priority.len()).sum(),
+            normal_queued: state.sessions.values().map(|q| q.normal.len()).sum(),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            requests_served: self.requests_served.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Record a granted send in the statistics
+    fn record_grant(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.requests_served.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl std::fmt::Debug for Js5Scheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Js5Scheduler")
+            .field("max_bytes_per_sec", &self.max_bytes_per_sec)
+            .field("session_bytes_per_sec", &self.session_bytes_per_sec)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn limited(max_bytes_per_sec: u64, session_bytes_per_sec: u64) -> Js5Scheduler {
+        Js5Scheduler::new(&Js5Config {
+            max_bytes_per_sec,
+            session_bytes_per_sec,
+            session_burst_bytes: 0,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_token_bucket_refill() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1000, 0, start);
+        assert!(bucket.has_tokens());
+
+        bucket.consume(1500);
+        assert!(!bucket.has_tokens());
+
+        bucket.refill(start + Duration::from_millis(400));
+        assert!(!bucket.has_tokens());
+
+        bucket.refill(start + Duration::from_millis(600));
+        assert!(bucket.has_tokens());
+
+        // Never exceeds capacity
+        bucket.refill(start + Duration::from_secs(60));
+        assert!(bucket.tokens <= 1000.0);
+    }
+
+    #[test]
+    fn test_unlimited_bucket() {
+        let mut bucket = TokenBucket::new(0, 0, Instant::now());
+        bucket.consume(usize::MAX);
+        assert!(bucket.has_tokens());
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_scheduler_grants_immediately() {
+        let scheduler = Js5Scheduler::unlimited();
+        assert!(scheduler.is_unlimited());
+        assert!(scheduler.acquire(1, 100_000, false).await);
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.requests_served, 1);
+        assert_eq!(stats.bytes_sent, 100_000);
+        assert_eq!(stats.sessions, 0);
+    }
+
+    #[tokio::test]
+    async fn test_priority_served_first() {
+        let scheduler = Arc::new(limited(1000, 0));
+
+        // Overdraw the global budget
+        assert!(scheduler.acquire(1, 1500, false).await);
+
+        let normal = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move { scheduler.acquire(2, 500, false).await })
+        };
+        tokio::task::yield_now().await;
+        let priority = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move { scheduler.acquire(3, 500, true).await })
+        };
+        tokio::task::yield_now().await;
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.priority_queued, 1);
+        assert_eq!(stats.normal_queued, 1);
+
+        // Refilling just enough for one send grants the priority request
+        let granted = scheduler.dispatch(Instant::now() + Duration::from_millis(600));
+        assert_eq!(granted, 1);
+        let stats = scheduler.stats();
+        assert_eq!(stats.priority_queued, 0);
+        assert_eq!(stats.normal_queued, 1);
+
+        assert!(priority.await.unwrap());
+        assert!(normal.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_session_budgets_are_independent() {
+        let scheduler = limited(0, 1000);
+
+        // Session 1 exhausts its own budget, session 2 is unaffected
+        assert!(scheduler.acquire(1, 5000, false).await);
+        assert!(scheduler.acquire(2, 100, false).await);
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.sessions, 2);
+        assert_eq!(stats.requests_served, 2);
+    }
+
+    #[test]
+    fn test_sessions_rotate_fairly() {
+        let scheduler = limited(1000, 0);
+
+        // Overdraw the global budget so everything below has to queue
+        assert!(scheduler.enqueue(1, 5000, false).try_recv().is_ok());
+
+        let mut pending = vec![
+            (1, scheduler.enqueue(1, 1000, false)),
+            (1, scheduler.enqueue(1, 1000, false)),
+            (2, scheduler.enqueue(2, 1000, false)),
+            (2, scheduler.enqueue(2, 1000, false)),
+        ];
+        assert_eq!(scheduler.stats().normal_queued, 4);
+
+        // Each refill covers exactly one send; session 1 queued its sends
+        // first but the sessions still take turns
+        let mut order = Vec::new();
+        for round in 1..=4 {
+            let granted = scheduler.dispatch(Instant::now() + Duration::from_secs(10 * round));
+            assert_eq!(granted, 1);
+            let position = pending
+                .iter_mut()
+                .position(|(_, rx)| rx.try_recv().is_ok())
+                .unwrap();
+            order.push(pending.remove(position).0);
+        }
+        assert_eq!(order, [1, 2, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_remove_session_cancels_pending() {
+        let scheduler = Arc::new(limited(100, 0));
+        assert!(scheduler.acquire(1, 1000, false).await);
+
+        let waiter = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move { scheduler.acquire(1, 1000, false).await })
+        };
+        tokio::task::yield_now().await;
+        assert_eq!(scheduler.stats().normal_queued, 1);
+
+        scheduler.remove_session(1);
+        assert!(!waiter.await.unwrap());
+        assert_eq!(scheduler.stats().sessions, 0);
+    }
+}
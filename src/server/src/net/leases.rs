@@ -0,0 +1,604 @@
+//! Login leases held by this world
+//!
+//! Before a player enters the world, the world takes a lease for the account
+//! from the login coordinator (see `auth::coordinator`): the one running in
+//! this process, or a remote one over HTTP(S). Held leases are renewed every
+//! heartbeat and released when the player is removed, or all at once on
+//! shutdown. Without a coordinator only this world's own sessions are
+//! checked for duplicate logins.
+
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use dashmap::DashMap;
+use http::header::{AUTHORIZATION, CONTENT_TYPE, HOST};
+use http::{Request, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper_util::rt::TokioIo;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio_native_tls::TlsConnector;
+use tracing::{debug, info, warn};
+
+use crate::auth::coordinator::{LoginCoordinator, LoginLease};
+use crate::auth::normalize_username;
+use crate::config::ServerConfig;
+use crate::error::{login_backend_offline, AuthError, Result, RustscapeError};
+use crate::state::AppState;
+
+/// Time allowed for a request to a remote coordinator
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Logged when the coordinator can't be reached
+const COORDINATOR_FAILED: &str = "Login coordinator request failed";
+
+/// Body of a lease request
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcquireRequest {
+    /// Account to log in
+    pub username: String,
+    /// World the account is logging in to
+    pub world_id: u16,
+}
+
+/// Body of a heartbeat
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenewRequest {
+    /// Leases to extend
+    pub leases: Vec<LoginLease>,
+}
+
+/// Response to a heartbeat
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenewResponse {
+    /// Leases that were no longer held
+    pub lost: Vec<LoginLease>,
+}
+
+/// Coordinator reached over HTTP or HTTPS
+///
+/// The shared secret is sent with every request, so a plain `http://`
+/// coordinator must only be reached over a private network.
+#[derive(Debug, Clone)]
+pub struct RemoteCoordinator {
+    /// `host[:port]` from the URL, sent as the Host header
+    authority: String,
+    /// `host:port` to connect to (the scheme's default port if none given)
+    address: String,
+    /// Host name, for TLS server name checks
+    host: String,
+    /// Path prefix before `/api/...` (usually empty)
+    base_path: String,
+    /// Shared secret sent as a bearer token
+    secret: String,
+    /// TLS connector for `https://` coordinators
+    tls: Option<TlsConnector>,
+}
+
+impl RemoteCoordinator {
+    /// Create a client for an `http(s)://host[:port]` coordinator URL
+    pub fn new(url: &str, secret: &str) -> Result<Self> {
+        let (https, rest) = if let Some(rest) = url.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            (false, rest)
+        } else {
+            return Err(RustscapeError::Config(format!(
+                "Coordinator URL must start with http:// or https://: {}",
+                url
+            )));
+        };
+        let (authority, base_path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], rest[slash..].trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        if authority.is_empty() {
+            return Err(RustscapeError::Config(format!(
+                "Coordinator URL has no host: {}",
+                url
+            )));
+        }
+
+        // A port follows the last colon, unless that colon is inside an IPv6
+        // address
+        let (host, address) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host, authority.to_string()),
+            _ => {
+                let port = if https { 443 } else { 80 };
+                (authority, format!("{}:{}", authority, port))
+            }
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        let tls = if https {
+            let connector = native_tls::TlsConnector::new().map_err(|e| {
+                RustscapeError::Config(format!("Failed to set up coordinator TLS: {}", e))
+            })?;
+            Some(TlsConnector::from(connector))
+        } else {
+            if !is_loopback(host) {
+                warn!(
+                    url = %url,
+                    "Coordinator URL is plain http://, so the coordinator secret is sent \
+                     unencrypted; keep the coordinator on a private network or use https://"
+                );
+            }
+            None
+        };
+
+        Ok(Self {
+            authority: authority.to_string(),
+            address,
+            host: host.to_string(),
+            base_path: base_path.to_string(),
+            secret: secret.to_string(),
+            tls,
+        })
+    }
+
+    /// POST a JSON body and decode the JSON response
+    async fn post<B: Serialize, R: DeserializeOwned>(&self, path: &str, body: &B) -> Result<R> {
+        let (status, bytes) = tokio::time::timeout(REQUEST_TIMEOUT, self.send(path, body))
+            .await
+            .map_err(|_| login_backend_offline(COORDINATOR_FAILED, "request timed out"))??;
+
+        match status {
+            StatusCode::OK => serde_json::from_slice(&bytes).map_err(|e| {
+                login_backend_offline(COORDINATOR_FAILED, format!("invalid response: {}", e))
+            }),
+            StatusCode::CONFLICT => Err(RustscapeError::Auth(AuthError::AlreadyLoggedIn)),
+            StatusCode::UNAUTHORIZED => Err(login_backend_offline(
+                COORDINATOR_FAILED,
+                "coordinator rejected our secret",
+            )),
+            status => Err(login_backend_offline(
+                COORDINATOR_FAILED,
+                format!("unexpected status {}", status),
+            )),
+        }
+    }
+
+    /// Send one request on a new connection
+    async fn send<B: Serialize>(&self, path: &str, body: &B) -> Result<(StatusCode, Bytes)> {
+        let body = serde_json::to_vec(body)
+            .map_err(|e| RustscapeError::Internal(format!("Failed to encode request: {}", e)))?;
+
+        let stream = TcpStream::connect(&self.address)
+            .await
+            .map_err(|e| login_backend_offline(COORDINATOR_FAILED, e))?;
+        match &self.tls {
+            Some(tls) => {
+                let stream = tls
+                    .connect(&self.host, stream)
+                    .await
+                    .map_err(|e| login_backend_offline(COORDINATOR_FAILED, e))?;
+                self.exchange(stream, path, body).await
+            }
+            None => self.exchange(stream, path, body).await,
+        }
+    }
+
+    /// Make a request over an open connection
+    async fn exchange<S>(&self, stream: S, path: &str, body: Vec<u8>) -> Result<(StatusCode, Bytes)>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(|e| login_backend_offline(COORDINATOR_FAILED, e))?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                debug!(error = %e, "Coordinator connection closed with error");
+            }
+        });
+
+        let request = Request::post(format!("{}{}", self.base_path, path))
+            .header(HOST, &self.authority)
+            .header(CONTENT_TYPE, "application/json")
+            .header(AUTHORIZATION, format!("Bearer {}", self.secret))
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|e| RustscapeError::Internal(format!("Failed to build request: {}", e)))?;
+
+        let response = sender
+            .send_request(request)
+            .await
+            .map_err(|e| login_backend_offline(COORDINATOR_FAILED, e))?;
+        let status = response.status();
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| login_backend_offline(COORDINATOR_FAILED, e))?
+            .to_bytes();
+
+        Ok((status, bytes))
+    }
+}
+
+/// Check whether a URL host is this machine
+fn is_loopback(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// The coordinator a world takes its leases from
+#[derive(Debug, Clone)]
+pub enum Coordinator {
+    /// Running in this process
+    Local(Arc<LoginCoordinator>),
+    /// Running on another server
+    Remote(RemoteCoordinator),
+}
+
+impl Coordinator {
+    async fn acquire(&self, username: &str, world_id: u16) -> Result<LoginLease> {
+        match self {
+            Coordinator::Local(coordinator) => coordinator.acquire(username, world_id).await,
+            Coordinator::Remote(remote) => {
+                let request = AcquireRequest {
+                    username: username.to_string(),
+                    world_id,
+                };
+                remote.post("/api/v1/coordinator/leases", &request).await
+            }
+        }
+    }
+
+    async fn renew(&self, leases: Vec<LoginLease>) -> Result<Vec<LoginLease>> {
+        match self {
+            Coordinator::Local(coordinator) => coordinator.renew(&leases).await,
+            Coordinator::Remote(remote) => {
+                let response: RenewResponse = remote
+                    .post("/api/v1/coordinator/leases/renew", &RenewRequest { leases })
+                    .await?;
+                Ok(response.lost)
+            }
+        }
+    }
+
+    async fn release(&self, lease: &LoginLease) -> Result<()> {
+        match self {
+            Coordinator::Local(coordinator) => coordinator.release(lease).await.map(|_| ()),
+            Coordinator::Remote(remote) => {
+                let _: serde_json::Value = remote
+                    .post("/api/v1/coordinator/leases/release", lease)
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Leases this world holds for its players
+pub struct LoginLeases {
+    /// This world's ID
+    world_id: u16,
+    /// Where leases come from (None to only check this world)
+    coordinator: Option<Coordinator>,
+    /// Held leases by normalized username
+    held: DashMap<String, LoginLease>,
+    /// Time between heartbeats
+    heartbeat_interval: Duration,
+}
+
+impl LoginLeases {
+    /// Create the lease set for a world
+    pub fn new(config: &ServerConfig, coordinator: Option<Coordinator>) -> Self {
+        Self {
+            world_id: config.world_id as u16,
+            coordinator,
+            held: DashMap::new(),
+            heartbeat_interval: Duration::from_secs(config.coordinator.heartbeat_interval_secs),
+        }
+    }
+
+    /// Create the lease set described by the config
+    ///
+    /// A configured URL takes precedence over `local`, the coordinator this
+    /// process serves (if enabled).
+    pub fn from_config(
+        config: &ServerConfig,
+        local: Option<Arc<LoginCoordinator>>,
+    ) -> Result<Self> {
+        let coordinator = if !config.coordinator.url.is_empty() {
+            Some(Coordinator::Remote(RemoteCoordinator::new(
+                &config.coordinator.url,
+                &config.coordinator.secret,
+            )?))
+        } else {
+            local.map(Coordinator::Local)
+        };
+        Ok(Self::new(config, coordinator))
+    }
+
+    /// Check whether logins are coordinated with other worlds
+    pub fn is_enabled(&self) -> bool {
+        self.coordinator.is_some()
+    }
+
+    /// Number of leases held
+    pub fn count(&self) -> usize {
+        self.held.len()
+    }
+
+    /// Take the lease for an account logging in to this world
+    ///
+    /// Fails with `AlreadyLoggedIn` if the account is logged in to any world
+    /// and `LoginServerOffline` if the coordinator can't be reached.
+    pub async fn acquire(&self, username: &str) -> Result<()> {
+        let Some(coordinator) = &self.coordinator else {
+            return Ok(());
+        };
+
+        let lease = coordinator.acquire(username, self.world_id).await?;
+        self.held.insert(lease.username.clone(), lease);
+        Ok(())
+    }
+
+    /// Release the lease for an account leaving this world
+    ///
+    /// A lease that can't be released expires once heartbeats stop renewing it.
+    pub async fn release(&self, username: &str) {
+        let Some(coordinator) = &self.coordinator else {
+            return;
+        };
+        let Some((_, lease)) = self.held.remove(&normalize_username(username)) else {
+            return;
+        };
+
+        if let Err(e) = coordinator.release(&lease).await {
+            warn!(username = %lease.username, error = %e, "Failed to release login lease");
+        }
+    }
+
+    /// Renew every held lease
+    ///
+    /// A lease that was lost (the coordinator restarted, or this world
+    /// missed heartbeats) is taken again if it can be. Returns the usernames
+    /// whose lease went to a login on another world; those players must be
+    /// removed from this one.
+    pub async fn heartbeat(&self) -> Vec<String> {
+        let Some(coordinator) = &self.coordinator else {
+            return Vec::new();
+        };
+        let leases: Vec<LoginLease> = self.held.iter().map(|entry| entry.clone()).collect();
+        if leases.is_empty() {
+            return Vec::new();
+        }
+
+        let lost = match coordinator.renew(leases).await {
+            Ok(lost) => lost,
+            Err(e) => {
+                warn!(error = %e, "Login lease heartbeat failed");
+                return Vec::new();
+            }
+        };
+
+        let mut taken = Vec::new();
+        for lease in lost {
+            // Released while the heartbeat was in flight
+            if self.held.get(&lease.username).as_deref() != Some(&lease) {
+                continue;
+            }
+
+            match coordinator.acquire(&lease.username, self.world_id).await {
+                Ok(renewed) => {
+                    info!(username = %lease.username, "Login lease lost, took it again");
+                    self.held.insert(renewed.username.clone(), renewed);
+                }
+                Err(RustscapeError::Auth(AuthError::AlreadyLoggedIn)) => {
+                    warn!(
+                        username = %lease.username,
+                        "Login lease lost to a login on another world"
+                    );
+                    self.held.remove(&lease.username);
+                    taken.push(lease.username);
+                }
+                // Coordinator unreachable; try again next heartbeat
+                Err(_) => {}
+            }
+        }
+        taken
+    }
+
+    /// Release every held lease
+    pub async fn release_all(&self) {
+        let usernames: Vec<String> = self.held.iter().map(|entry| entry.key().clone()).collect();
+        for username in usernames {
+            self.release(&username).await;
+        }
+    }
+
+    /// Heartbeat until shutdown, then release every lease
+    pub async fn run(
+        self: Arc<Self>,
+        state: Arc<AppState>,
+        mut shutdown_rx: broadcast::Receiver<()>,
+    ) {
+        if self.coordinator.is_none() {
+            debug!("No login coordinator, duplicate logins are only checked on this world");
+            return;
+        }
+
+        let mut ticker = tokio::time::interval(self.heartbeat_interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    for username in self.heartbeat().await {
+                        if let Some(session) = state.session_manager.get_by_username(&username) {
+                            state.session_manager.disconnect(session.id).await;
+                        }
+                    }
+                }
+                _ = shutdown_rx.recv() => break,
+            }
+        }
+
+        let held = self.count();
+        self.release_all().await;
+        info!(leases = held, "Released login leases");
+    }
+}
+
+impl std::fmt::Debug for LoginLeases {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoginLeases")
+            .field("world_id", &self.world_id)
+            .field("coordinator", &self.coordinator)
+            .field("held", &self.held.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world(id: u8, coordinator: Option<Coordinator>) -> LoginLeases {
+        let config = ServerConfig {
+            world_id: id,
+            ..Default::default()
+        };
+        LoginLeases::new(&config, coordinator)
+    }
+
+    fn already_logged_in(result: Result<()>) -> bool {
+        matches!(
+            result,
+            Err(RustscapeError::Auth(AuthError::AlreadyLoggedIn))
+        )
+    }
+
+    #[tokio::test]
+    async fn test_without_coordinator() {
+        let leases = world(1, None);
+        assert!(!leases.is_enabled());
+        assert!(leases.acquire("zezima").await.is_ok());
+        assert!(leases.acquire("zezima").await.is_ok());
+        assert_eq!(leases.count(), 0);
+        assert!(leases.heartbeat().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_worlds_share_local_coordinator() {
+        let coordinator = Arc::new(LoginCoordinator::in_memory(Duration::from_secs(30)));
+        let world1 = world(1, Some(Coordinator::Local(coordinator.clone())));
+        let world2 = world(2, Some(Coordinator::Local(coordinator.clone())));
+
+        world1.acquire("Zezima").await.unwrap();
+        assert_eq!(world1.count(), 1);
+        assert!(already_logged_in(world2.acquire("zezima").await));
+
+        // Logging out on world 1 frees the account for world 2
+        world1.release("zezima").await;
+        assert_eq!(world1.count(), 0);
+        world2.acquire("zezima").await.unwrap();
+        assert_eq!(
+            coordinator
+                .holder("zezima")
+                .await
+                .unwrap()
+                .unwrap()
+                .world_id,
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_reports_leases_taken_elsewhere() {
+        let coordinator = Arc::new(LoginCoordinator::in_memory(Duration::from_millis(20)));
+        let world1 = world(1, Some(Coordinator::Local(coordinator.clone())));
+        let world2 = world(2, Some(Coordinator::Local(coordinator.clone())));
+
+        world1.acquire("zezima").await.unwrap();
+        world1.acquire("durial321").await.unwrap();
+
+        // World 1 misses its heartbeats and one account logs in to world 2
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        world2.acquire("zezima").await.unwrap();
+
+        // The other lease is taken again; the stolen one must be logged out
+        assert_eq!(world1.heartbeat().await, vec!["zezima".to_string()]);
+        assert_eq!(world1.count(), 1);
+        assert_eq!(
+            coordinator
+                .holder("durial321")
+                .await
+                .unwrap()
+                .unwrap()
+                .world_id,
+            1
+        );
+        assert_eq!(
+            coordinator
+                .holder("zezima")
+                .await
+                .unwrap()
+                .unwrap()
+                .world_id,
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remote_coordinator_over_http() {
+        let coordinator = Arc::new(LoginCoordinator::in_memory(Duration::from_secs(30)));
+        let router = crate::api::coordinator::create_router(coordinator.clone(), "secret");
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let remote = |secret| Coordinator::Remote(RemoteCoordinator::new(&url, secret).unwrap());
+        let world1 = world(1, Some(remote("secret")));
+        let world2 = world(2, Some(remote("secret")));
+
+        world1.acquire("zezima").await.unwrap();
+        assert!(already_logged_in(world2.acquire("zezima").await));
+        assert!(world1.heartbeat().await.is_empty());
+
+        world1.release("zezima").await;
+        assert_eq!(coordinator.holder("zezima").await.unwrap(), None);
+        world2.acquire("zezima").await.unwrap();
+
+        // Worlds without the shared secret are turned away
+        let intruder = world(3, Some(remote("wrong")));
+        assert!(matches!(
+            intruder.acquire("durial321").await,
+            Err(RustscapeError::Auth(AuthError::LoginServerOffline))
+        ));
+    }
+
+    #[test]
+    fn test_remote_coordinator_url() {
+        let remote = RemoteCoordinator::new("http://10.0.0.1:5555", "s").unwrap();
+        assert_eq!(remote.authority, "10.0.0.1:5555");
+        assert_eq!(remote.address, "10.0.0.1:5555");
+        assert_eq!(remote.base_path, "");
+        assert!(remote.tls.is_none());
+
+        let remote = RemoteCoordinator::new("http://login.example:80/game/", "s").unwrap();
+        assert_eq!(remote.authority, "login.example:80");
+        assert_eq!(remote.base_path, "/game");
+
+        // https uses TLS, on port 443 unless another is given
+        let remote = RemoteCoordinator::new("https://login.example/game", "s").unwrap();
+        assert_eq!(remote.address, "login.example:443");
+        assert_eq!(remote.host, "login.example");
+        assert!(remote.tls.is_some());
+
+        let remote = RemoteCoordinator::new("http://[::1]", "s").unwrap();
+        assert_eq!(remote.address, "[::1]:80");
+        assert_eq!(remote.host, "::1");
+        assert!(is_loopback(&remote.host));
+        assert!(!is_loopback("10.0.0.1"));
+
+        assert!(RemoteCoordinator::new("ftp://10.0.0.1:5555", "s").is_err());
+        assert!(RemoteCoordinator::new("http:///path", "s").is_err());
+    }
+}
@@ -0,0 +1,76 @@
+//! Connection error counters
+//!
+//! Process-wide counts of connections that ended in an error, kept so load
+//! tests and operators can spot failures without trawling the logs:
+//! - Logins rejected by authentication (bad credentials, already online, ...)
+//! - Connections closed by any other error (protocol violations, I/O errors,
+//!   per-IP connection limits)
+//!
+//! Clean disconnects are not counted.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::RustscapeError;
+
+/// Connection error statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionStats {
+    /// Logins rejected by authentication
+    pub login_failures: u64,
+    /// Connections closed by any other error
+    pub connection_errors: u64,
+}
+
+/// Process-wide connection error counters
+#[derive(Debug, Default)]
+pub struct ConnectionMetrics {
+    login_failures: AtomicU64,
+    connection_errors: AtomicU64,
+}
+
+impl ConnectionMetrics {
+    /// Create zeroed counters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count the error a connection ended with
+    pub fn record(&self, error: &RustscapeError) {
+        match error {
+            RustscapeError::Auth(_) => self.login_failures.fetch_add(1, Ordering::Relaxed),
+            _ => self.connection_errors.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    /// Get the current counters
+    pub fn stats(&self) -> ConnectionStats {
+        ConnectionStats {
+            login_failures: self.login_failures.load(Ordering::Relaxed),
+            connection_errors: self.connection_errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{AuthError, ProtocolError};
+
+    #[test]
+    fn test_record_splits_login_failures() {
+        let metrics = ConnectionMetrics::new();
+        metrics.record(&RustscapeError::Auth(AuthError::InvalidCredentials));
+        metrics.record(&RustscapeError::Protocol(ProtocolError::InvalidHandshake));
+        metrics.record(&RustscapeError::Protocol(ProtocolError::InvalidOpcode(7)));
+
+        assert_eq!(
+            metrics.stats(),
+            ConnectionStats {
+                login_failures: 1,
+                connection_errors: 2,
+            }
+        );
+    }
+}
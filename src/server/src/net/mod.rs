@@ -2,11 +2,31 @@
 //!
 //! This module handles all network-related functionality for the Rustscape server:
 //! - TCP socket handling for native clients
-//! - WebSocket handling for browser clients
-//! - Session management
+//! - WebSocket handling for browser clients (optionally over TLS)
+//! - WebSocket permessage-deflate compression
+//! - Session management and resume after brief disconnects
+//! - Client address resolution behind reverse proxies
+//! - Per-tick outbound packet batching
+//! - JS5 bandwidth scheduling
+//! - Login admission (per-tick cap, queue and failed-login throttling)
+//! - Login leases from the coordinator, so an account plays on one world
+//! - Packet flood protection
+//! - Connection error counters
+//! - Decrypted packet capture for debugging
 //! - Connection lifecycle
 
+pub mod admission;
 pub mod buffer;
+pub mod capture;
+pub mod deflate;
+pub mod flood;
 pub mod handler;
+pub mod js5_scheduler;
+pub mod leases;
+pub mod metrics;
+pub mod outbox;
+pub mod proxy;
+pub mod resume;
 pub mod session;
+pub mod tls;
 pub mod transport;
@@ -0,0 +1,129 @@
+//! Per-tick outbound packet batching
+//!
+//! Everything sent to an in-game player during a tick (packet responses,
+//! player updates, etc.) is collected in the session's outbox instead of being
+//! written immediately. Once per tick the outbox is encoded into a single
+//! buffer:
+//! - Frames keep the order they were queued in
+//! - Each frame's opcode is ISAAC-encoded at flush time, in that same order,
+//!   so the cipher stream always matches what the client reads
+//! - The buffer is handed to the connection task as one write, which
+//!   WebSocket sessions send as one binary frame
+
+use crate::crypto::IsaacPair;
+use crate::protocol::game::OutgoingGamePacket;
+
+/// Outbound packets queued for the current tick
+#[derive(Debug, Default)]
+pub struct Outbox {
+    /// Framed packets with plain (not yet ISAAC-encoded) opcodes
+    frames: Vec<Vec<u8>>,
+    /// Total size of the queued frames in bytes
+    size: usize,
+}
+
+impl Outbox {
+    /// Create an empty outbox
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a game packet
+    pub fn push(&mut self, packet: &OutgoingGamePacket) {
+        self.push_frame(packet.encode_raw());
+    }
+
+    /// Queue an already framed packet whose first byte is the plain opcode
+    pub fn push_frame(&mut self, frame: Vec<u8>) {
+        if frame.is_empty() {
+            return;
+        }
+        self.size += frame.len();
+        self.frames.push(frame);
+    }
+
+    /// Get the number of queued packets
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Check whether the outbox is empty
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Get the total size of the queued packets in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Encode all queued packets into one buffer and empty the outbox
+    ///
+    /// Opcodes are encoded with `isaac` when given. Returns `None` if nothing
+    /// was queued.
+    pub fn encode(&mut self, mut isaac: Option<&mut IsaacPair>) -> Option<Vec<u8>> {
+        if self.frames.is_empty() {
+            return None;
+        }
+
+        let mut buffer = Vec::with_capacity(self.size);
+        for mut frame in self.frames.drain(..) {
+            if let Some(isaac) = isaac.as_deref_mut() {
+                frame[0] = isaac.encode_opcode(frame[0]);
+            }
+            buffer.extend_from_slice(&frame);
+        }
+        self.size = 0;
+
+        Some(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEEDS: [u32; 4] = [1, 2, 3, 4];
+
+    #[test]
+    fn test_empty_outbox_encodes_nothing() {
+        let mut outbox = Outbox::new();
+        outbox.push_frame(vec![]);
+        assert!(outbox.is_empty());
+        assert!(outbox.encode(None).is_none());
+    }
+
+    #[test]
+    fn test_encode_preserves_order_and_isaac_stream() {
+        let first = OutgoingGamePacket::fixed(10, vec![1, 2]);
+        let second = OutgoingGamePacket::variable(20, vec![3]);
+
+        let mut outbox = Outbox::new();
+        outbox.push(&first);
+        outbox.push(&second);
+        outbox.push_frame(vec![30, 0, 1, 4]);
+        assert_eq!(outbox.len(), 3);
+        assert_eq!(outbox.size(), 3 + 3 + 4);
+
+        let mut isaac = IsaacPair::new(&SEEDS);
+        let batched = outbox.encode(Some(&mut isaac)).unwrap();
+
+        // Same bytes as encoding each packet individually with a fresh cipher
+        let mut reference = IsaacPair::new(&SEEDS);
+        let mut expected = first.encode(&mut reference);
+        expected.extend(second.encode(&mut reference));
+        expected.push(reference.encode_opcode(30));
+        expected.extend([0, 1, 4]);
+
+        assert_eq!(batched, expected);
+        assert!(outbox.is_empty());
+        assert_eq!(outbox.size(), 0);
+    }
+
+    #[test]
+    fn test_encode_without_isaac() {
+        let mut outbox = Outbox::new();
+        outbox.push(&OutgoingGamePacket::fixed(10, vec![1]));
+        assert_eq!(outbox.encode(None).unwrap(), vec![10, 1]);
+    }
+}
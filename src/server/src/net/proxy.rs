@@ -0,0 +1,398 @@
+//! Client address resolution behind reverse proxies
+//!
+//! When the game or WebSocket port sits behind a proxy (e.g. nginx), the TCP
+//! peer address is the proxy's, not the player's. This module recovers the
+//! real client address from:
+//! - PROXY protocol v1 (text) and v2 (binary) headers
+//! - `X-Forwarded-For` headers on WebSocket upgrade requests
+//!
+//! Either source is only honoured when the connecting peer falls inside the
+//! configured `trusted_proxies` CIDR allowlist; anyone else could simply
+//! claim to be any address.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::error::{NetworkError, ProtocolError, Result, RustscapeError};
+
+/// Time allowed for a proxy to send its PROXY protocol header
+pub const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum length of a PROXY protocol v1 header, including CRLF
+const V1_MAX_LEN: usize = 107;
+
+/// PROXY protocol v2 signature
+const V2_SIGNATURE: [u8; 12] = [
+    0x0D, 0x0A, 0x0D, 0x0A, 0x00, 0x0D, 0x0A, 0x51, 0x55, 0x49, 0x54, 0x0A,
+];
+
+/// Maximum accepted length of the v2 address block (addresses plus TLVs)
+const V2_MAX_LEN: usize = 512;
+
+/// An IP network in CIDR notation (e.g. `10.0.0.0/8`, `::1/128`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cidr {
+    /// Network address with host bits cleared
+    network: IpAddr,
+    /// Prefix length in bits
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Create a CIDR range, clearing any host bits
+    pub fn new(address: IpAddr, prefix: u8) -> Option<Self> {
+        let network = match address {
+            IpAddr::V4(v4) if prefix <= 32 => {
+                IpAddr::V4(Ipv4Addr::from(u32::from(v4) & v4_mask(prefix)))
+            }
+            IpAddr::V6(v6) if prefix <= 128 => {
+                IpAddr::V6(Ipv6Addr::from(u128::from(v6) & v6_mask(prefix)))
+            }
+            _ => return None,
+        };
+        Some(Self { network, prefix })
+    }
+
+    /// Check whether an address is inside this range
+    ///
+    /// IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) match IPv4 ranges.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                u32::from(ip) & v4_mask(self.prefix) == u32::from(network)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                u128::from(ip) & v6_mask(self.prefix) == u128::from(network)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn v4_mask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0)
+}
+
+fn v6_mask(prefix: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0)
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+
+        let address: IpAddr = address
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid CIDR address: {}", s))?;
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid CIDR prefix: {}", s))?,
+            None if address.is_ipv4() => 32,
+            None => 128,
+        };
+
+        Self::new(address, prefix).ok_or_else(|| format!("CIDR prefix out of range: {}", s))
+    }
+}
+
+impl TryFrom<String> for Cidr {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Cidr> for String {
+    fn from(cidr: Cidr) -> Self {
+        cidr.to_string()
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+/// Check whether an address is inside any of the trusted ranges
+pub fn is_trusted(trusted: &[Cidr], ip: IpAddr) -> bool {
+    trusted.iter().any(|cidr| cidr.contains(ip))
+}
+
+/// Read a PROXY protocol header (v1 or v2) from the start of a stream
+///
+/// Consumes exactly the header bytes. Returns the client address it carries,
+/// or `None` for `LOCAL`/`UNKNOWN` headers (health checks and the like),
+/// where the peer address should be used.
+pub async fn read_proxy_header<R>(stream: &mut R) -> Result<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin,
+{
+    tokio::time::timeout(PROXY_HEADER_TIMEOUT, read_header(stream))
+        .await
+        .map_err(|_| RustscapeError::Network(NetworkError::Timeout))?
+}
+
+async fn read_header<R>(stream: &mut R) -> Result<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin,
+{
+    // Both versions are at least 12 bytes long (v1: "PROXY UNKNOWN\r\n")
+    let mut header = vec![0u8; 12];
+    read_exact(stream, &mut header).await?;
+
+    if header[..] == V2_SIGNATURE {
+        let mut fixed = [0u8; 4];
+        read_exact(stream, &mut fixed).await?;
+        let len = u16::from_be_bytes([fixed[2], fixed[3]]) as usize;
+        if len > V2_MAX_LEN {
+            return Err(invalid(format!("v2 address block too long: {}", len)));
+        }
+        let mut body = vec![0u8; len];
+        read_exact(stream, &mut body).await?;
+        return parse_v2(fixed[0], fixed[1], &body);
+    }
+
+    if !header.starts_with(b"PROXY ") {
+        return Err(invalid("missing PROXY protocol header".to_string()));
+    }
+
+    // v1: read the rest of the line one byte at a time so nothing past the
+    // header is consumed
+    while !header.ends_with(b"\r\n") {
+        if header.len() >= V1_MAX_LEN {
+            return Err(invalid("v1 header too long".to_string()));
+        }
+        let byte = stream.read_u8().await.map_err(read_error)?;
+        header.push(byte);
+    }
+
+    parse_v1(&header)
+}
+
+async fn read_exact<R>(stream: &mut R, buf: &mut [u8]) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    stream.read_exact(buf).await.map_err(read_error)?;
+    Ok(())
+}
+
+fn read_error(e: std::io::Error) -> RustscapeError {
+    if e.kind() == std::io::ErrorKind::UnexpectedEof {
+        RustscapeError::Network(NetworkError::ConnectionClosed)
+    } else {
+        RustscapeError::Network(NetworkError::ReadError(e.to_string()))
+    }
+}
+
+fn invalid(message: String) -> RustscapeError {
+    RustscapeError::Protocol(ProtocolError::InvalidProxyHeader(message))
+}
+
+/// Parse a complete PROXY protocol v1 line (including the trailing CRLF)
+pub fn parse_v1(line: &[u8]) -> Result<Option<SocketAddr>> {
+    let line = std::str::from_utf8(line)
+        .ok()
+        .and_then(|line| line.strip_suffix("\r\n"))
+        .ok_or_else(|| invalid("v1 header is not a CRLF-terminated line".to_string()))?;
+
+    let parts: Vec<&str> = line.split(' ').collect();
+    match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", family @ ("TCP4" | "TCP6"), source, _destination, source_port, _destination_port] =>
+        {
+            let ip: IpAddr = source
+                .parse()
+                .map_err(|_| invalid(format!("v1 source address: {}", source)))?;
+            if (*family == "TCP4") != ip.is_ipv4() {
+                return Err(invalid(format!("v1 address does not match {}", family)));
+            }
+            let port: u16 = source_port
+                .parse()
+                .map_err(|_| invalid(format!("v1 source port: {}", source_port)))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid(format!("v1 header: {}", line))),
+    }
+}
+
+/// Parse a PROXY protocol v2 header after the signature
+///
+/// `version_command` and `family` are bytes 13 and 14 of the header, and
+/// `body` is the address block that follows the length field.
+pub fn parse_v2(version_command: u8, family: u8, body: &[u8]) -> Result<Option<SocketAddr>> {
+    if version_command >> 4 != 2 {
+        return Err(invalid(format!("v2 version: {}", version_command >> 4)));
+    }
+
+    match version_command & 0x0F {
+        // LOCAL: connection made by the proxy itself
+        0x0 => return Ok(None),
+        // PROXY
+        0x1 => {}
+        command => return Err(invalid(format!("v2 command: {}", command))),
+    }
+
+    match family {
+        // TCP over IPv4
+        0x11 => {
+            if body.len() < 12 {
+                return Err(invalid("v2 IPv4 address block too short".to_string()));
+            }
+            let ip = Ipv4Addr::new(body[0], body[1], body[2], body[3]);
+            let port = u16::from_be_bytes([body[8], body[9]]);
+            Ok(Some(SocketAddr::new(IpAddr::V4(ip), port)))
+        }
+        // TCP over IPv6
+        0x21 => {
+            if body.len() < 36 {
+                return Err(invalid("v2 IPv6 address block too short".to_string()));
+            }
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&body[..16]);
+            let port = u16::from_be_bytes([body[32], body[33]]);
+            Ok(Some(SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::from(octets)),
+                port,
+            )))
+        }
+        // UNSPEC or non-TCP families carry no usable client address
+        _ => Ok(None),
+    }
+}
+
+/// Pick the client address out of an `X-Forwarded-For` header
+///
+/// Entries are appended by each proxy in turn, so the list is walked from the
+/// right, skipping trusted proxies; the first untrusted entry is the client.
+/// If every entry is trusted, the left-most one is used.
+pub fn forwarded_client(header: &str, trusted: &[Cidr]) -> Option<IpAddr> {
+    let addresses: Vec<IpAddr> = header
+        .split(',')
+        .map(|entry| entry.trim().parse().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    addresses
+        .iter()
+        .rev()
+        .find(|ip| !is_trusted(trusted, **ip))
+        .or_else(|| addresses.first())
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidrs(ranges: &[&str]) -> Vec<Cidr> {
+        ranges.iter().map(|r| r.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_cidr_contains() {
+        let cidr: Cidr = "10.1.0.0/16".parse().unwrap();
+        assert!(cidr.contains("10.1.255.3".parse().unwrap()));
+        assert!(!cidr.contains("10.2.0.1".parse().unwrap()));
+        assert!(cidr.contains("::ffff:10.1.0.9".parse().unwrap()));
+
+        let v6: Cidr = "fd00::/8".parse().unwrap();
+        assert!(v6.contains("fd12::1".parse().unwrap()));
+        assert!(!v6.contains("10.1.0.1".parse().unwrap()));
+
+        let single: Cidr = "127.0.0.1".parse().unwrap();
+        assert_eq!(single.to_string(), "127.0.0.1/32");
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("nonsense/8".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_parse_v1() {
+        let addr = parse_v1(b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 43596\r\n").unwrap();
+        assert_eq!(addr, Some("203.0.113.7:51234".parse().unwrap()));
+
+        let addr = parse_v1(b"PROXY TCP6 2001:db8::5 2001:db8::1 4000 443\r\n").unwrap();
+        assert_eq!(addr, Some("[2001:db8::5]:4000".parse().unwrap()));
+
+        assert_eq!(parse_v1(b"PROXY UNKNOWN\r\n").unwrap(), None);
+        assert!(parse_v1(b"PROXY TCP4 2001:db8::5 10.0.0.1 1 2\r\n").is_err());
+        assert!(parse_v1(b"PROXY TCP4 1.2.3.4\r\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        let mut body = vec![198, 51, 100, 20, 10, 0, 0, 1];
+        body.extend(40000u16.to_be_bytes());
+        body.extend(43594u16.to_be_bytes());
+
+        let addr = parse_v2(0x21, 0x11, &body).unwrap();
+        assert_eq!(addr, Some("198.51.100.20:40000".parse().unwrap()));
+
+        // LOCAL command and unsupported families fall back to the peer address
+        assert_eq!(parse_v2(0x20, 0x11, &body).unwrap(), None);
+        assert_eq!(parse_v2(0x21, 0x00, &[]).unwrap(), None);
+
+        assert!(parse_v2(0x11, 0x11, &body).is_err());
+        assert!(parse_v2(0x21, 0x11, &body[..4]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_header_consumes_only_header() {
+        let mut input: &[u8] = b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 43596\r\n\x0e\x00";
+        let addr = read_proxy_header(&mut input).await.unwrap();
+        assert_eq!(addr, Some("203.0.113.7:51234".parse().unwrap()));
+        assert_eq!(input, b"\x0e\x00");
+
+        let mut v2 = V2_SIGNATURE.to_vec();
+        v2.extend([0x21, 0x11, 0x00, 0x0C]);
+        v2.extend([192, 0, 2, 1, 10, 0, 0, 1, 0x1F, 0x90, 0xAA, 0x4A]);
+        v2.push(15);
+        let mut input: &[u8] = &v2;
+        let addr = read_proxy_header(&mut input).await.unwrap();
+        assert_eq!(addr, Some("192.0.2.1:8080".parse().unwrap()));
+        assert_eq!(input, [15]);
+
+        let mut input: &[u8] = b"\x0e\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+        assert!(read_proxy_header(&mut input).await.is_err());
+    }
+
+    #[test]
+    fn test_forwarded_client() {
+        let trusted = cidrs(&["127.0.0.1/32", "10.0.0.0/8"]);
+
+        assert_eq!(
+            forwarded_client("203.0.113.7", &trusted),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        // A spoofed left-most entry is ignored
+        assert_eq!(
+            forwarded_client("1.1.1.1, 203.0.113.7, 10.0.0.2", &trusted),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        assert_eq!(
+            forwarded_client("10.0.0.3, 127.0.0.1", &trusted),
+            Some("10.0.0.3".parse().unwrap())
+        );
+        assert_eq!(forwarded_client("not-an-ip", &trusted), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! Session resume after brief disconnects
+//!
+//! When an in-game connection drops (e.g. a browser tab losing its WebSocket),
+//! the player is not removed from the world straight away. Instead it is
+//! parked here for a grace window:
+//! - The `Player` stays registered in the world, in a disconnected state
+//! - A reconnect login (`LoginType::Reconnect`) with the same username and the
+//!   resume token issued at login reattaches to the parked player with fresh
+//!   ISAAC seeds, without reloading it from the database
+//! - A normal login that passes authentication also reclaims the parked player
+//! - If nobody reclaims the player within the grace window, it is saved and
+//!   removed from the world as on a normal disconnect
+//!
+//! The resume token is sent to the client after login in a `ResumeToken`
+//! packet and is presented in place of the password on reconnect. A new token
+//! is issued on every login, so each token can be used at most once.
+
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+/// Length of a resume token in bytes (before hex encoding)
+const TOKEN_BYTES: usize = 16;
+
+/// A player waiting in the world for its client to reconnect
+#[derive(Debug, Clone)]
+pub struct ParkedPlayer {
+    /// Player username
+    pub username: String,
+    /// Index of the player in the game world
+    pub player_index: u16,
+    /// Player index allocated by the auth service
+    pub auth_index: Option<u16>,
+    /// Player rights
+    pub rights: u8,
+    /// Whether the player is a member
+    pub member: bool,
+    /// Token the client must present to resume
+    token: String,
+    /// When the connection dropped
+    parked_at: Instant,
+}
+
+impl ParkedPlayer {
+    /// Create a parked player entry for a connection that just dropped
+    pub fn new(
+        username: String,
+        player_index: u16,
+        auth_index: Option<u16>,
+        rights: u8,
+        member: bool,
+        token: String,
+    ) -> Self {
+        Self {
+            username,
+            player_index,
+            auth_index,
+            rights,
+            member,
+            token,
+            parked_at: Instant::now(),
+        }
+    }
+
+    /// Get the resume token this entry was parked with
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+}
+
+/// Players parked after a dropped connection, by lowercase username
+#[derive(Debug)]
+pub struct ResumeRegistry {
+    /// Parked players
+    parked: DashMap<String, ParkedPlayer>,
+    /// How long a player stays parked (zero disables resuming)
+    grace: Duration,
+}
+
+impl ResumeRegistry {
+    /// Create an empty registry with the given grace window
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            parked: DashMap::new(),
+            grace,
+        }
+    }
+
+    /// Check whether session resume is enabled
+    pub fn is_enabled(&self) -> bool {
+        !self.grace.is_zero()
+    }
+
+    /// Get the grace window
+    pub fn grace(&self) -> Duration {
+        self.grace
+    }
+
+    /// Generate a new random resume token
+    pub fn issue_token() -> String {
+        let bytes: [u8; TOKEN_BYTES] = rand::random();
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Park a player whose connection dropped
+    pub fn park(&self, player: ParkedPlayer) {
+        self.parked.insert(player.username.to_lowercase(), player);
+    }
+
+    /// Check whether a player is parked
+    pub fn is_parked(&self, username: &str) -> bool {
+        self.parked.contains_key(&username.to_lowercase())
+    }
+
+    /// Take a parked player for a reconnect login presenting `token`
+    ///
+    /// Returns `None` if the player isn't parked, the token doesn't match, or
+    /// the grace window has passed.
+    pub fn resume(&self, username: &str, token: &str) -> Option<ParkedPlayer> {
+        let grace = self.grace;
+        self.parked
+            .remove_if(&username.to_lowercase(), |_, parked| {
+                tokens_match(&parked.token, token) && parked.parked_at.elapsed() <= grace
+            })
+            .map(|(_, parked)| parked)
+    }
+
+    /// Take a parked player for a login that has already been authenticated
+    pub fn claim(&self, username: &str) -> Option<ParkedPlayer> {
+        self.parked
+            .remove(&username.to_lowercase())
+            .map(|(_, parked)| parked)
+    }
+
+    /// Take a parked player whose grace window has run out
+    ///
+    /// Only removes the entry if it is still the one parked with `token`, so a
+    /// player that resumed and dropped again gets its own full window.
+    pub fn expire(&self, username: &str, token: &str) -> Option<ParkedPlayer> {
+        self.parked
+            .remove_if(&username.to_lowercase(), |_, parked| parked.token == token)
+            .map(|(_, parked)| parked)
+    }
+
+    /// Get the number of parked players
+    pub fn len(&self) -> usize {
+        self.parked.len()
+    }
+
+    /// Check whether no players are parked
+    pub fn is_empty(&self) -> bool {
+        self.parked.is_empty()
+    }
+}
+
+/// Compare tokens without exiting early on the first differing byte
+fn tokens_match(expected: &str, actual: &str) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .bytes()
+            .zip(actual.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parked(username: &str, token: &str) -> ParkedPlayer {
+        ParkedPlayer::new(
+            username.to_string(),
+            5,
+            Some(5),
+            0,
+            false,
+            token.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_issue_token_is_random_hex() {
+        let a = ResumeRegistry::issue_token();
+        let b = ResumeRegistry::issue_token();
+        assert_eq!(a.len(), TOKEN_BYTES * 2);
+        assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_resume_requires_matching_token() {
+        let registry = ResumeRegistry::new(Duration::from_secs(30));
+        registry.park(parked("Zezima", "abcd"));
+
+        assert!(registry.resume("zezima", "wrong").is_none());
+        assert!(registry.is_parked("ZEZIMA"));
+
+        let resumed = registry.resume("zezima", "abcd").unwrap();
+        assert_eq!(resumed.player_index, 5);
+        assert!(registry.is_empty());
+
+        // Tokens are single use
+        assert!(registry.resume("zezima", "abcd").is_none());
+    }
+
+    #[test]
+    fn test_resume_after_grace_window_fails() {
+        let registry = ResumeRegistry::new(Duration::from_millis(1));
+        registry.park(parked("zezima", "abcd"));
+        std::thread::sleep(Duration::from_millis(10));
+
+        assert!(registry.resume("zezima", "abcd").is_none());
+        // Still parked until the expiry task removes it
+        assert!(registry.claim("zezima").is_some());
+    }
+
+    #[test]
+    fn test_expire_only_removes_same_parking() {
+        let registry = ResumeRegistry::new(Duration::from_secs(30));
+        registry.park(parked("zezima", "first"));
+        registry.park(parked("zezima", "second"));
+
+        assert!(registry.expire("zezima", "first").is_none());
+        assert_eq!(registry.len(), 1);
+        assert!(registry.expire("zezima", "second").is_some());
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_disabled_with_zero_grace() {
+        assert!(!ResumeRegistry::new(Duration::ZERO).is_enabled());
+        assert!(ResumeRegistry::new(Duration::from_secs(1)).is_enabled());
+    }
+}
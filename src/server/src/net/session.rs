@@ -4,6 +4,7 @@
 //! - Session lifecycle (creation, tracking, cleanup)
 //! - Session state machine (handshake -> login -> game)
 //! - Per-session data (ISAAC cipher, player info, etc.)
+//! - Per-tick outbound packet batching
 //! - Thread-safe session registry
 
 use std::collections::HashMap;
@@ -13,12 +14,21 @@
 use std::time::{Duration, Instant};
 
 use dashmap::DashMap;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use tokio::sync::mpsc;
-use tracing::{debug, info, warn};
+use tracing::{debug, info, trace, warn};
 
 use crate::crypto::IsaacPair;
 use crate::error::{NetworkError, Result, RustscapeError};
+use crate::net::capture::{Direction, PacketRecorder};
+use crate::net::outbox::Outbox;
+use crate::protocol::game::OutgoingGamePacket;
+use crate::protocol::login::LoginType;
+use crate::protocol::message::ServerMessage;
+use crate::protocol::revision::{Protocol, Revision530};
+
+/// Default idle time before a session is disconnected (5 minutes)
+pub const DEFAULT_MAX_IDLE_SECS: u64 = 300;
 
 /// Unique session identifier
 pub type SessionId = u64;
@@ -34,6 +44,8 @@ pub enum SessionState {
     LoginHandshake,
     /// Login in progress - processing credentials
     LoggingIn,
+    /// Authenticated in the lobby - choosing a world
+    Lobby,
     /// Fully authenticated and in-game
     InGame,
     /// Session is disconnecting
@@ -63,6 +75,7 @@ pub fn name(&self) -> &'static str {
             SessionState::Js5 => "JS5",
             SessionState::LoginHandshake => "LoginHandshake",
             SessionState::LoggingIn => "LoggingIn",
+            SessionState::Lobby => "Lobby",
             SessionState::InGame => "InGame",
             SessionState::Disconnecting => "Disconnecting",
             SessionState::Disconnected => "Disconnected",
@@ -111,8 +124,16 @@ pub struct Session {
     server_key: AtomicU64,
     /// JS5 encryption key
     js5_encryption: RwLock<u8>,
+    /// Whether the JS5 client reported being logged in to the game
+    js5_logged_in: RwLock<bool>,
     /// Username (set after login)
     username: RwLock<Option<String>>,
+    /// Wire protocol for the client's revision (chosen at the handshake)
+    protocol: RwLock<Arc<dyn Protocol>>,
+    /// Login type requested in the login handshake
+    login_type: RwLock<LoginType>,
+    /// Token the client can present to resume after a dropped connection
+    resume_token: RwLock<Option<String>>,
     /// Client information
     client_info: RwLock<Option<ClientInfo>>,
     /// Time of session creation
@@ -123,6 +144,10 @@ pub struct Session {
     player_index: RwLock<Option<u16>>,
     /// Outbound message channel (for sending packets)
     outbound_tx: Option<mpsc::Sender<Vec<u8>>>,
+    /// Packets queued for the end of the current tick
+    outbox: Mutex<Outbox>,
+    /// Decrypted packet capture (if enabled for this session)
+    capture: Mutex<Option<PacketRecorder>>,
 }
 
 impl Session {
@@ -137,12 +162,18 @@ pub fn new(id: SessionId, address: SocketAddr, is_websocket: bool) -> Self {
             isaac: RwLock::new(None),
             server_key: AtomicU64::new(0),
             js5_encryption: RwLock::new(0),
+            js5_logged_in: RwLock::new(false),
             username: RwLock::new(None),
+            protocol: RwLock::new(Arc::new(Revision530)),
+            login_type: RwLock::new(LoginType::Normal),
+            resume_token: RwLock::new(None),
             client_info: RwLock::new(None),
             created_at: now,
             last_activity: RwLock::new(now),
             player_index: RwLock::new(None),
             outbound_tx: None,
+            outbox: Mutex::new(Outbox::new()),
+            capture: Mutex::new(None),
         }
     }
 
@@ -225,6 +256,16 @@ pub fn set_js5_encryption(&self, key: u8) {
         *self.js5_encryption.write() = key;
     }
 
+    /// Check if the JS5 client reported being logged in to the game
+    pub fn js5_logged_in(&self) -> bool {
+        *self.js5_logged_in.read()
+    }
+
+    /// Set the JS5 logged-in status
+    pub fn set_js5_logged_in(&self, logged_in: bool) {
+        *self.js5_logged_in.write() = logged_in;
+    }
+
     /// Set the ISAAC cipher pair
     pub fn set_isaac(&self, isaac: IsaacPair) {
         *self.isaac.write() = Some(isaac);
@@ -266,6 +307,53 @@ pub fn username(&self) -> Option<String> {
         self.username.read().clone()
     }
 
+    /// Set the wire protocol for the client's revision
+    pub fn set_protocol(&self, protocol: Arc<dyn Protocol>) {
+        *self.protocol.write() = protocol;
+    }
+
+    /// Get the wire protocol for the client's revision
+    pub fn protocol(&self) -> Arc<dyn Protocol> {
+        self.protocol.read().clone()
+    }
+
+    /// Encode a message for the client's revision
+    ///
+    /// Returns `None` if the revision can't represent the message.
+    pub fn encode_message(&self, message: &ServerMessage) -> Option<OutgoingGamePacket> {
+        let protocol = self.protocol();
+        let packet = protocol.encode(message);
+        if packet.is_none() {
+            trace!(
+                session_id = self.id,
+                revision = protocol.revision(),
+                message = ?message,
+                "Message not supported by client revision"
+            );
+        }
+        packet
+    }
+
+    /// Set the login type requested in the login handshake
+    pub fn set_login_type(&self, login_type: LoginType) {
+        *self.login_type.write() = login_type;
+    }
+
+    /// Get the login type requested in the login handshake
+    pub fn login_type(&self) -> LoginType {
+        *self.login_type.read()
+    }
+
+    /// Set the resume token issued at login
+    pub fn set_resume_token(&self, token: String) {
+        *self.resume_token.write() = Some(token);
+    }
+
+    /// Get the resume token issued at login
+    pub fn resume_token(&self) -> Option<String> {
+        self.resume_token.read().clone()
+    }
+
     /// Set client information
     pub fn set_client_info(&self, info: ClientInfo) {
         *self.client_info.write() = Some(info);
@@ -324,6 +412,83 @@ pub fn try_send(&self, data: Vec<u8>) -> Result<()> {
         }
         Ok(())
     }
+
+    /// Queue a game packet to be sent with the rest of this tick's packets
+    pub fn queue_packet(&self, packet: &OutgoingGamePacket) {
+        self.outbox.lock().push(packet);
+    }
+
+    /// Queue an already framed packet (plain opcode first) for this tick
+    pub fn queue_frame(&self, frame: Vec<u8>) {
+        self.outbox.lock().push_frame(frame);
+    }
+
+    /// Get the number of packets queued for this tick
+    pub fn queued_packets(&self) -> usize {
+        self.outbox.lock().len()
+    }
+
+    /// Start recording this session's packets
+    pub fn start_capture(&self, recorder: PacketRecorder) {
+        *self.capture.lock() = Some(recorder);
+    }
+
+    /// Check whether this session's packets are being recorded
+    pub fn is_capturing(&self) -> bool {
+        self.capture.lock().is_some()
+    }
+
+    /// Record a decrypted packet if capture is enabled
+    ///
+    /// A capture that fails to write is stopped rather than retried.
+    pub fn capture(&self, direction: Direction, tick: u64, opcode: u8, payload: &[u8]) {
+        let mut capture = self.capture.lock();
+        let Some(recorder) = capture.as_mut() else {
+            return;
+        };
+
+        if let Err(e) = recorder.record(direction, tick, opcode, payload) {
+            warn!(session_id = self.id, error = %e, "Packet capture failed, stopping");
+            *capture = None;
+        }
+    }
+
+    /// Stop recording and flush the capture file
+    pub fn stop_capture(&self) {
+        let Some(mut recorder) = self.capture.lock().take() else {
+            return;
+        };
+
+        match recorder.flush() {
+            Ok(()) => info!(
+                session_id = self.id,
+                path = %recorder.path().display(),
+                records = recorder.records(),
+                "Packet capture finished"
+            ),
+            Err(e) => warn!(session_id = self.id, error = %e, "Failed to flush packet capture"),
+        }
+    }
+
+    /// Encode this tick's queued packets with ISAAC and send them as a single write
+    ///
+    /// Returns the number of bytes sent.
+    pub fn flush_outbox(&self) -> Result<usize> {
+        let encoded = {
+            let mut outbox = self.outbox.lock();
+            let mut isaac = self.isaac.write();
+            outbox.encode(isaac.as_mut())
+        };
+
+        match encoded {
+            Some(data) => {
+                let len = data.len();
+                self.try_send(data)?;
+                Ok(len)
+            }
+            None => Ok(0),
+        }
+    }
 }
 
 impl std::fmt::Debug for Session {
@@ -333,6 +498,7 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             .field("address", &self.address)
             .field("state", &self.state())
             .field("is_websocket", &self.is_websocket)
+            .field("revision", &self.protocol().revision())
             .field("username", &self.username())
             .field("created_at", &self.created_at)
             .field("idle_duration", &self.idle_duration())
@@ -365,7 +531,7 @@ pub fn new() -> Self {
             ip_to_sessions: DashMap::new(),
             next_id: AtomicU64::new(1),
             max_per_ip: 10,
-            max_idle_time: Duration::from_secs(300), // 5 minutes
+            max_idle_time: Duration::from_secs(DEFAULT_MAX_IDLE_SECS),
         }
     }
 
@@ -645,6 +811,24 @@ fn test_session_creation() {
         assert!(session.is_active());
     }
 
+    #[test]
+    fn test_flush_outbox_sends_one_batch() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let session = Session::with_channel(1, test_address(), true, tx);
+
+        session.queue_packet(&OutgoingGamePacket::fixed(10, vec![1, 2]));
+        session.queue_frame(vec![20, 3]);
+        assert_eq!(session.queued_packets(), 2);
+
+        assert_eq!(session.flush_outbox().unwrap(), 5);
+        assert_eq!(rx.try_recv().unwrap(), vec![10, 1, 2, 20, 3]);
+        assert!(rx.try_recv().is_err());
+
+        // Nothing queued, nothing sent
+        assert_eq!(session.flush_outbox().unwrap(), 0);
+        assert!(rx.try_recv().is_err());
+    }
+
     #[test]
     fn test_session_state_transition() {
         let session = Session::new(1, test_address(), false);
@@ -0,0 +1,207 @@
+//! TLS for the WebSocket listener
+//!
+//! Lets the WebSocket port serve wss:// directly, so small deployments don't
+//! need a reverse proxy in front of it:
+//! - The certificate chain and PKCS#8 private key are loaded from PEM files
+//! - The acceptor can be swapped at runtime; new connections pick up the new
+//!   certificate while existing ones keep theirs
+//! - `watch` polls the PEM files and reloads them when they change, so
+//!   renewed certificates (e.g. from certbot) apply without a restart
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use parking_lot::RwLock;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio_native_tls::{TlsAcceptor, TlsStream};
+use tracing::{info, warn};
+
+use crate::config::TlsConfig;
+use crate::error::{NetworkError, Result, RustscapeError};
+
+/// Reloadable TLS acceptor for the WebSocket listener
+pub struct TlsReloader {
+    /// Path to the PEM certificate chain
+    cert_path: PathBuf,
+    /// Path to the PEM private key
+    key_path: PathBuf,
+    /// Acceptor for new connections
+    acceptor: RwLock<TlsAcceptor>,
+    /// Modification times of the certificate and key when last loaded
+    loaded_mtimes: RwLock<(Option<SystemTime>, Option<SystemTime>)>,
+}
+
+impl TlsReloader {
+    /// Load the certificate and key named in the config
+    pub fn load(config: &TlsConfig) -> Result<Self> {
+        let acceptor = build_acceptor(&config.cert_path, &config.key_path)?;
+
+        info!(
+            cert = %config.cert_path.display(),
+            key = %config.key_path.display(),
+            "Loaded TLS certificate"
+        );
+
+        Ok(Self {
+            cert_path: config.cert_path.clone(),
+            key_path: config.key_path.clone(),
+            acceptor: RwLock::new(acceptor),
+            loaded_mtimes: RwLock::new((modified(&config.cert_path), modified(&config.key_path))),
+        })
+    }
+
+    /// Perform the TLS handshake on an accepted connection
+    pub async fn accept(&self, stream: TcpStream) -> Result<TlsStream<TcpStream>> {
+        let acceptor = self.acceptor.read().clone();
+        acceptor.accept(stream).await.map_err(tls_error)
+    }
+
+    /// Reload the certificate and key from disk
+    ///
+    /// On failure the current certificate stays in use.
+    pub fn reload(&self) -> Result<()> {
+        let mtimes = (modified(&self.cert_path), modified(&self.key_path));
+        let acceptor = build_acceptor(&self.cert_path, &self.key_path)?;
+
+        *self.acceptor.write() = acceptor;
+        *self.loaded_mtimes.write() = mtimes;
+
+        info!(cert = %self.cert_path.display(), "Reloaded TLS certificate");
+        Ok(())
+    }
+
+    /// Reload the certificate and key if either file changed since the last load
+    ///
+    /// Returns true if a new certificate was loaded.
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let current = (modified(&self.cert_path), modified(&self.key_path));
+        if current == *self.loaded_mtimes.read() {
+            return Ok(false);
+        }
+
+        self.reload()?;
+        Ok(true)
+    }
+
+    /// Poll the PEM files every `interval` and reload them when they change
+    pub async fn watch(
+        self: Arc<Self>,
+        interval: Duration,
+        mut shutdown_rx: broadcast::Receiver<()>,
+    ) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    if let Err(e) = self.reload_if_changed() {
+                        warn!(error = %e, "Failed to reload TLS certificate, keeping the current one");
+                    }
+                }
+                _ = shutdown_rx.recv() => break,
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for TlsReloader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsReloader")
+            .field("cert_path", &self.cert_path)
+            .field("key_path", &self.key_path)
+            .finish()
+    }
+}
+
+/// Build a TLS acceptor from a PEM certificate chain and PKCS#8 PEM key
+fn build_acceptor(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor> {
+    let cert = std::fs::read(cert_path).map_err(|e| {
+        RustscapeError::Network(NetworkError::Tls(format!(
+            "failed to read {}: {}",
+            cert_path.display(),
+            e
+        )))
+    })?;
+    let key = std::fs::read(key_path).map_err(|e| {
+        RustscapeError::Network(NetworkError::Tls(format!(
+            "failed to read {}: {}",
+            key_path.display(),
+            e
+        )))
+    })?;
+
+    let identity = native_tls::Identity::from_pkcs8(&cert, &key).map_err(tls_error)?;
+    let acceptor = native_tls::TlsAcceptor::new(identity).map_err(tls_error)?;
+    Ok(TlsAcceptor::from(acceptor))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn tls_error(e: native_tls::Error) -> RustscapeError {
+    RustscapeError::Network(NetworkError::Tls(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn test_config() -> TlsConfig {
+        let ssl = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../config/ssl");
+        TlsConfig {
+            enabled: true,
+            cert_path: ssl.join("server.crt"),
+            key_path: ssl.join("server.key"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_load_missing_files() {
+        let config = TlsConfig {
+            cert_path: PathBuf::from("/nonexistent/server.crt"),
+            ..test_config()
+        };
+        assert!(TlsReloader::load(&config).is_err());
+    }
+
+    #[test]
+    fn test_reload_if_unchanged() {
+        let reloader = TlsReloader::load(&test_config()).unwrap();
+        assert!(!reloader.reload_if_changed().unwrap());
+        assert!(reloader.reload().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_tls_handshake() {
+        let reloader = Arc::new(TlsReloader::load(&test_config()).unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut tls = reloader.accept(stream).await.unwrap();
+            tls.write_all(b"hello").await.unwrap();
+            tls.flush().await.unwrap();
+        });
+
+        let connector = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+        let connector = tokio_native_tls::TlsConnector::from(connector);
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut tls = connector.connect("localhost", stream).await.unwrap();
+
+        let mut buf = [0u8; 5];
+        tls.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        server.await.unwrap();
+    }
+}
@@ -2,9 +2,13 @@
 //!
 //! Provides a unified interface for TCP and WebSocket connections, allowing
 //! the server to handle both native desktop clients (TCP) and browser clients
-//! (WebSocket) using the same protocol handling code.
+//! (WebSocket, plain or over TLS) using the same protocol handling code.
+//!
+//! WebSocket connections that negotiated permessage-deflate compress their
+//! outbound messages here; the TCP path is unaffected.
 
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use crate::error::Result;
@@ -12,10 +16,14 @@
 use futures_util::{SinkExt, StreamExt};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
 use tokio::net::TcpStream;
+use tokio_native_tls::TlsStream;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::{Data, OpCode};
+use tokio_tungstenite::tungstenite::protocol::frame::Frame;
 use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
 use tracing::{debug, trace, warn};
 
 use crate::error::{NetworkError, RustscapeError};
+use crate::net::deflate::{CompressionMetrics, InflateStream, MessageDeflater};
 
 /// Maximum read buffer size (64KB)
 const MAX_BUFFER_SIZE: usize = 65536;
@@ -142,33 +150,66 @@ fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io
 }
 
 /// WebSocket transport for browser clients
-pub struct WebSocketTransport {
-    stream: WebSocketStream<TcpStream>,
+///
+/// `S` is the underlying stream: a plain `TcpStream` for ws:// or a
+/// `TlsStream<TcpStream>` for wss://. It is wrapped in an `InflateStream`
+/// so compressed client messages can be read once permessage-deflate is
+/// negotiated.
+pub struct WebSocketTransport<S = TcpStream> {
+    stream: WebSocketStream<InflateStream<S>>,
     /// Buffer for incoming data (WebSocket messages may contain multiple packets)
     read_buffer: BytesMut,
     /// Buffer for outgoing data
     write_buffer: BytesMut,
+    /// Outbound compression (if negotiated)
+    deflater: Option<MessageDeflater>,
+    /// Shared compression counters
+    compression_metrics: Option<Arc<CompressionMetrics>>,
 }
 
-impl WebSocketTransport {
+impl<S> WebSocketTransport<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
     /// Create a new WebSocket transport from an already-upgraded WebSocket stream
-    pub fn new(stream: WebSocketStream<TcpStream>) -> Self {
+    pub fn new(stream: WebSocketStream<InflateStream<S>>) -> Self {
         Self {
             stream,
             read_buffer: BytesMut::with_capacity(MAX_BUFFER_SIZE),
             write_buffer: BytesMut::with_capacity(MAX_BUFFER_SIZE),
+            deflater: None,
+            compression_metrics: None,
         }
     }
 
-    /// Upgrade a TCP stream to a WebSocket connection
-    pub async fn accept(stream: TcpStream) -> Result<Self> {
-        let ws_stream = tokio_tungstenite::accept_async(stream)
+    /// Upgrade a stream to a WebSocket connection (without compression)
+    pub async fn accept(stream: S) -> Result<Self> {
+        let ws_stream = tokio_tungstenite::accept_async(InflateStream::new(stream))
             .await
             .map_err(|e| RustscapeError::Network(NetworkError::WebSocket(e.to_string())))?;
 
         Ok(Self::new(ws_stream))
     }
 
+    /// Compress outbound messages with a negotiated deflater
+    ///
+    /// The stream must already be inflating client messages
+    /// (`InflateStream::enable`).
+    pub fn with_compression(
+        mut self,
+        deflater: MessageDeflater,
+        metrics: Arc<CompressionMetrics>,
+    ) -> Self {
+        self.deflater = Some(deflater);
+        self.compression_metrics = Some(metrics);
+        self
+    }
+
+    /// Check if outbound messages are compressed
+    pub fn is_compressed(&self) -> bool {
+        self.deflater.is_some()
+    }
+
     /// Read the next message from the WebSocket
     pub async fn read_message(&mut self) -> Result<Option<Vec<u8>>> {
         // First, check if we have buffered data
@@ -217,15 +258,70 @@ pub async fn read_message(&mut self) -> Result<Option<Vec<u8>>> {
         }
     }
 
+    /// Read the next message into `buf`, keeping any excess for the next read
+    ///
+    /// Returns 0 when the connection is closed.
+    pub async fn read_into(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self.read_message().await? {
+            Some(data) => {
+                let len = data.len().min(buf.len());
+                buf[..len].copy_from_slice(&data[..len]);
+                // If there's more data than the buffer can hold, store it
+                if data.len() > len {
+                    self.read_buffer.extend_from_slice(&data[len..]);
+                }
+                Ok(len)
+            }
+            None => Ok(0), // Connection closed
+        }
+    }
+
     /// Write a binary message to the WebSocket
+    ///
+    /// With compression negotiated, messages over the threshold are sent
+    /// deflated with the RSV1 bit set.
     pub async fn write_message(&mut self, data: &[u8]) -> Result<()> {
-        trace!(len = data.len(), "Sending binary WebSocket message");
+        let message = match self.compress(data)? {
+            Some(payload) => {
+                trace!(
+                    len = data.len(),
+                    compressed = payload.len(),
+                    "Sending compressed WebSocket message"
+                );
+                let mut frame = Frame::message(payload, OpCode::Data(Data::Binary), true);
+                frame.header_mut().rsv1 = true;
+                Message::Frame(frame)
+            }
+            None => {
+                trace!(len = data.len(), "Sending binary WebSocket message");
+                Message::Binary(data.to_vec())
+            }
+        };
+
         self.stream
-            .send(Message::Binary(data.to_vec()))
+            .send(message)
             .await
             .map_err(|e| RustscapeError::Network(NetworkError::WebSocket(e.to_string())))
     }
 
+    /// Compress a message if compression is negotiated and it's large enough
+    fn compress(&mut self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        let Some(deflater) = self.deflater.as_mut() else {
+            return Ok(None);
+        };
+
+        let compressed = deflater
+            .compress(data)
+            .map_err(|e| RustscapeError::Network(NetworkError::WebSocket(e.to_string())))?;
+        if let Some(metrics) = &self.compression_metrics {
+            match &compressed {
+                Some(payload) => metrics.record_compressed(data.len(), payload.len()),
+                None => metrics.record_uncompressed(data.len()),
+            }
+        }
+        Ok(compressed)
+    }
+
     /// Send queued data as a single message
     pub async fn flush(&mut self) -> Result<()> {
         if !self.write_buffer.is_empty() {
@@ -253,6 +349,7 @@ pub async fn close(&mut self) -> Result<()> {
 pub enum UnifiedTransport {
     Tcp(TcpTransport),
     WebSocket(WebSocketTransport),
+    SecureWebSocket(WebSocketTransport<TlsStream<TcpStream>>),
 }
 
 impl UnifiedTransport {
@@ -262,33 +359,40 @@ pub fn tcp(stream: TcpStream) -> Self {
     }
 
     /// Create a WebSocket transport
-    pub fn websocket(stream: WebSocketStream<TcpStream>) -> Self {
-        Self::WebSocket(WebSocketTransport::new(stream))
+    pub fn websocket(transport: WebSocketTransport) -> Self {
+        Self::WebSocket(transport)
+    }
+
+    /// Create a WebSocket transport over TLS (wss://)
+    pub fn secure_websocket(transport: WebSocketTransport<TlsStream<TcpStream>>) -> Self {
+        Self::SecureWebSocket(transport)
     }
 
     /// Check if this is a WebSocket transport
     pub fn is_websocket(&self) -> bool {
-        matches!(self, Self::WebSocket(_))
+        matches!(self, Self::WebSocket(_) | Self::SecureWebSocket(_))
+    }
+
+    /// Check if this transport is encrypted with TLS
+    pub fn is_secure(&self) -> bool {
+        matches!(self, Self::SecureWebSocket(_))
+    }
+
+    /// Check if this transport compresses outbound messages
+    pub fn is_compressed(&self) -> bool {
+        match self {
+            Self::Tcp(_) => false,
+            Self::WebSocket(ws) => ws.is_compressed(),
+            Self::SecureWebSocket(ws) => ws.is_compressed(),
+        }
     }
 
     /// Read data from the transport
     pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
         match self {
             Self::Tcp(tcp) => tcp.read(buf).await,
-            Self::WebSocket(ws) => {
-                match ws.read_message().await? {
-                    Some(data) => {
-                        let len = data.len().min(buf.len());
-                        buf[..len].copy_from_slice(&data[..len]);
-                        // If there's more data than the buffer can hold, store it
-                        if data.len() > len {
-                            ws.read_buffer.extend_from_slice(&data[len..]);
-                        }
-                        Ok(len)
-                    }
-                    None => Ok(0), // Connection closed
-                }
-            }
+            Self::WebSocket(ws) => ws.read_into(buf).await,
+            Self::SecureWebSocket(ws) => ws.read_into(buf).await,
         }
     }
 
@@ -300,6 +404,10 @@ pub async fn write(&mut self, buf: &[u8]) -> Result<usize> {
                 ws.write_message(buf).await?;
                 Ok(buf.len())
             }
+            Self::SecureWebSocket(ws) => {
+                ws.write_message(buf).await?;
+                Ok(buf.len())
+            }
         }
     }
 
@@ -308,6 +416,7 @@ pub async fn write_all(&mut self, buf: &[u8]) -> Result<()> {
         match self {
             Self::Tcp(tcp) => tcp.write_all(buf).await,
             Self::WebSocket(ws) => ws.write_message(buf).await,
+            Self::SecureWebSocket(ws) => ws.write_message(buf).await,
         }
     }
 
@@ -316,6 +425,7 @@ pub async fn flush(&mut self) -> Result<()> {
         match self {
             Self::Tcp(tcp) => tcp.flush().await,
             Self::WebSocket(ws) => ws.flush().await,
+            Self::SecureWebSocket(ws) => ws.flush().await,
         }
     }
 
@@ -324,6 +434,7 @@ pub async fn shutdown(&mut self) -> Result<()> {
         match self {
             Self::Tcp(tcp) => tcp.shutdown().await,
             Self::WebSocket(ws) => ws.close().await,
+            Self::SecureWebSocket(ws) => ws.close().await,
         }
     }
 }
@@ -456,6 +567,47 @@ fn test_buffered_transport_queue() {
         assert_eq!(&buffer[..], b"Hello World");
     }
 
+    #[tokio::test]
+    async fn test_websocket_compresses_large_messages() {
+        use crate::net::deflate::DeflateParams;
+        use flate2::{Decompress, FlushDecompress};
+        use tokio_tungstenite::tungstenite::protocol::Role;
+
+        let (mut client, server) = tokio::io::duplex(4096);
+        let ws =
+            WebSocketStream::from_raw_socket(InflateStream::new(server), Role::Server, None).await;
+        let metrics = Arc::new(CompressionMetrics::new());
+        let deflater = MessageDeflater::new(&DeflateParams::default(), 6, 16);
+        let mut transport = WebSocketTransport::new(ws).with_compression(deflater, metrics.clone());
+
+        transport.write_message(&[1, 2, 3]).await.unwrap();
+        transport.write_message(&[7; 200]).await.unwrap();
+
+        // Small message: plain binary frame
+        let mut header = [0u8; 2];
+        client.read_exact(&mut header).await.unwrap();
+        assert_eq!(header, [0x82, 3]);
+        let mut payload = [0u8; 3];
+        client.read_exact(&mut payload).await.unwrap();
+
+        // Large message: RSV1 set, deflated payload
+        client.read_exact(&mut header).await.unwrap();
+        assert_eq!(header[0], 0xC2);
+        let mut payload = vec![0u8; header[1] as usize];
+        client.read_exact(&mut payload).await.unwrap();
+        payload.extend_from_slice(&[0, 0, 0xFF, 0xFF]);
+        let mut inflated = Vec::with_capacity(256);
+        Decompress::new(false)
+            .decompress_vec(&payload, &mut inflated, FlushDecompress::Sync)
+            .unwrap();
+        assert_eq!(inflated, vec![7; 200]);
+
+        let stats = metrics.stats();
+        assert_eq!(stats.messages_compressed, 1);
+        assert_eq!(stats.messages_uncompressed, 1);
+        assert!(stats.ratio < 1.0);
+    }
+
     #[test]
     fn test_read_buffer() {
         let mut buffer = BytesMut::with_capacity(1024);
@@ -9,58 +9,42 @@
 //!
 //! All in-game packets are encrypted with ISAAC cipher.
 
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 use tracing::{debug, info, trace, warn};
 
 use crate::crypto::IsaacPair;
 use crate::error::Result;
-use crate::game::bank::BankError;
+use crate::game::bank::{BankError, BankItemInfo};
 use crate::game::equipment::{Equipment, EquipmentError, EQUIPMENT_SLOT_COUNT};
 use crate::game::inventory::{InventoryError, INVENTORY_SIZE};
 use crate::game::item::{get_equipment_slot, is_equippable, is_stackable};
+use crate::game::movement;
+use crate::game::pathfinding::{self, CollisionMap, Scene};
 use crate::game::player::{Location, Player};
 use crate::net::buffer::PacketBuffer;
-
-/// Incoming packet sizes (0 = variable byte, -1 = variable short, >0 = fixed)
-/// This is a subset of common packets for revision 530
-pub const INCOMING_PACKET_SIZES: [i16; 256] = {
-    let mut sizes = [-3i16; 256]; // -3 = unknown/unhandled
-
-    // Common incoming packets (client -> server)
-    sizes[0] = 0; // Keep-alive/ping
-    sizes[3] = 1; // Window focus change
-    sizes[4] = -1; // Chat message
-    sizes[14] = 8; // Walk to position
-    sizes[17] = 2; // NPC examine
-    sizes[21] = 2; // Item examine
-    sizes[39] = 6; // Object action 1
-    sizes[41] = 4; // Item equip
-    sizes[42] = 4; // Item unequip
-    sizes[43] = 11; // Bank withdraw
-    sizes[52] = -1; // Command
-    sizes[55] = 1; // Bank tab select
-    sizes[77] = 0; // Map region loaded
-    sizes[86] = 4; // Mouse click
-    sizes[98] = 8; // Walk here
-    sizes[115] = 1; // Bank withdraw mode
-    sizes[116] = -1; // Bank search
-    sizes[117] = 1; // Bank note mode
-    sizes[121] = -1; // Mouse movement
-    sizes[129] = 0; // Bank deposit all
-    sizes[145] = 4; // Item drop
-    sizes[150] = 6; // Item action 1
-    sizes[164] = 2; // Button click
-    sizes[185] = 0; // Bank close
-    sizes[195] = 0; // Bank deposit equipment
-    sizes[210] = 0; // Close interface
-    sizes[214] = 5; // Bank move item
-    sizes[233] = 8; // Bank deposit
-    sizes[236] = 6; // Ground item action
-    sizes[243] = 4; // Inventory swap
-
-    sizes
+use crate::protocol::message::ServerMessage;
+use crate::protocol::packets::{
+    BankClosePacket, BankDepositAllPacket, BankDepositEquipmentPacket, BankDepositPacket,
+    BankMoveItemPacket, BankNoteModePacket, BankSearchPacket, BankTabSelectPacket,
+    BankWithdrawModePacket, BankWithdrawPacket, ButtonClickPacket, ChatPacket,
+    CloseInterfacePacket, CommandPacket, FocusChangePacket, InventorySwapPacket, ItemDropPacket,
+    ItemEquipPacket, ItemUnequipPacket, KeepAlivePacket, MapLoadedPacket, MinimapWalkPacket,
+    MouseClickPacket, PacketSize, WalkPacket,
 };
+use crate::protocol::registry::PacketRegistry;
+
+/// Incoming game packet registry, built from `GamePacketHandler::register_packets`
+static INCOMING_PACKETS: OnceLock<PacketRegistry> = OnceLock::new();
+
+/// Get the incoming game packet registry
+pub fn incoming_packets() -> &'static PacketRegistry {
+    INCOMING_PACKETS.get_or_init(|| {
+        let mut registry = PacketRegistry::new();
+        GamePacketHandler::register_packets(&mut registry);
+        registry
+    })
+}
 
 /// Outgoing packet opcodes (server -> client)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -122,6 +106,8 @@ pub enum OutgoingOpcode {
     BankSettings = 250,
     /// Bank tab info (tab sizes)
     BankTabInfo = 251,
+    /// Session resume token (presented on reconnect)
+    ResumeToken = 252,
 }
 
 impl OutgoingOpcode {
@@ -161,6 +147,8 @@ pub struct OutgoingGamePacket {
     pub data: Vec<u8>,
     /// Whether this packet has variable length
     pub variable_length: bool,
+    /// Whether the length is always a short, rather than picked from the size
+    pub short_length: bool,
 }
 
 impl OutgoingGamePacket {
@@ -170,6 +158,7 @@ pub fn fixed(opcode: u8, data: Vec<u8>) -> Self {
             opcode,
             data,
             variable_length: false,
+            short_length: false,
         }
     }
 
@@ -179,6 +168,17 @@ pub fn variable(opcode: u8, data: Vec<u8>) -> Self {
             opcode,
             data,
             variable_length: true,
+            short_length: false,
+        }
+    }
+
+    /// Create a new variable-length outgoing packet whose length is always a short
+    pub fn variable_short(opcode: u8, data: Vec<u8>) -> Self {
+        Self {
+            opcode,
+            data,
+            variable_length: true,
+            short_length: true,
         }
     }
 
@@ -191,7 +191,7 @@ pub fn encode(&self, isaac: &mut IsaacPair) -> Vec<u8> {
             let mut buffer = PacketBuffer::with_capacity(3 + self.data.len());
             buffer.write_ubyte(encoded_opcode);
 
-            if self.data.len() < 256 {
+            if self.data.len() < 256 && !self.short_length {
                 // Variable byte
                 buffer.write_ubyte(self.data.len() as u8);
             } else {
@@ -216,7 +216,7 @@ pub fn encode_raw(&self) -> Vec<u8> {
             let mut buffer = PacketBuffer::with_capacity(3 + self.data.len());
             buffer.write_ubyte(self.opcode);
 
-            if self.data.len() < 256 {
+            if self.data.len() < 256 && !self.short_length {
                 buffer.write_ubyte(self.data.len() as u8);
             } else {
                 buffer.write_ushort(self.data.len() as u16);
@@ -242,15 +242,31 @@ pub struct MovementRequest {
     pub dest_y: u16,
     /// Whether the player is running
     pub running: bool,
-    /// Path waypoints (intermediate steps)
+    /// Path waypoints, relative to the first tile
     pub waypoints: Vec<(i8, i8)>,
 }
 
+impl MovementRequest {
+    /// Tiles the path passes through: the first tile, then each waypoint
+    /// (relative to the first tile)
+    pub fn checkpoints(&self) -> Vec<(u16, u16)> {
+        let mut checkpoints = Vec::with_capacity(self.waypoints.len() + 1);
+        checkpoints.push((self.dest_x, self.dest_y));
+        for &(dx, dy) in &self.waypoints {
+            checkpoints.push((
+                self.dest_x.wrapping_add_signed(dx as i16),
+                self.dest_y.wrapping_add_signed(dy as i16),
+            ));
+        }
+        checkpoints
+    }
+}
+
 /// Result of processing a game packet
 #[derive(Debug)]
 pub struct PacketResult {
-    /// Response packets to send to the client
-    pub responses: Vec<OutgoingGamePacket>,
+    /// Messages to send to the client
+    pub responses: Vec<ServerMessage>,
     /// Movement request if this was a walk packet
     pub movement: Option<MovementRequest>,
     /// Chat message to broadcast
@@ -271,7 +287,7 @@ pub fn empty() -> Self {
     }
 
     /// Create a result with responses
-    pub fn with_responses(responses: Vec<OutgoingGamePacket>) -> Self {
+    pub fn with_responses(responses: Vec<ServerMessage>) -> Self {
         Self {
             responses,
             movement: None,
@@ -313,61 +329,82 @@ pub fn with_chat(message: String) -> Self {
 
 /// Game packet handler
 pub struct GamePacketHandler {
-    // Stateless handler - player context is passed to process methods
+    // Player context is passed to process methods
+    /// Collision flags walk requests are checked against
+    collision: Arc<CollisionMap>,
 }
 
 impl GamePacketHandler {
-    /// Create a new game packet handler
+    /// Create a new game packet handler with no collision data
     pub fn new() -> Self {
-        Self {}
-    }
-
-    /// Get the expected size for an incoming packet
-    pub fn get_packet_size(&self, opcode: u8) -> i16 {
-        INCOMING_PACKET_SIZES[opcode as usize]
-    }
-
-    /// Check if an opcode is valid/handled
+        Self::with_collision(Arc::new(CollisionMap::new()))
+    }
+
+    /// Create a handler checking walk requests against a collision map
+    pub fn with_collision(collision: Arc<CollisionMap>) -> Self {
+        Self { collision }
+    }
+
+    /// Declare every incoming game packet
+    ///
+    /// Adding a packet is one line here: the typed struct (see
+    /// `IncomingPacket`) gives the opcode and framing, the handler gives the
+    /// behaviour. Opcodes whose layout is known but which are not handled
+    /// yet are registered as `unhandled` so the stream can still be framed.
+    fn register_packets(registry: &mut PacketRegistry) {
+        registry
+            // General
+            .on(Self::handle_keepalive)
+            .on(Self::handle_focus_change)
+            .on(Self::handle_chat)
+            .on(Self::handle_minimap_walk)
+            .on(Self::handle_walk)
+            .on(Self::handle_command)
+            .on(Self::handle_map_loaded)
+            .on(Self::handle_mouse_click)
+            .on(Self::handle_button_click)
+            .on(Self::handle_close_interface)
+            // Bank
+            .on_player(Self::handle_bank_close)
+            .on_player(Self::handle_bank_withdraw)
+            .on_player(Self::handle_bank_deposit)
+            .on_player(Self::handle_bank_deposit_all)
+            .on_player(Self::handle_bank_deposit_equipment)
+            .on_player(Self::handle_bank_tab_select)
+            .on_player(Self::handle_bank_move_item)
+            .on_player(Self::handle_bank_search)
+            .on_player(Self::handle_bank_note_mode)
+            .on_player(Self::handle_bank_withdraw_mode)
+            // Items
+            .on_player(Self::handle_item_drop)
+            .on_player(Self::handle_item_equip)
+            .on_player(Self::handle_item_unequip)
+            .on_player(Self::handle_inventory_swap)
+            // Not handled yet
+            .unhandled(17, PacketSize::Fixed(2), "NpcExamine")
+            .unhandled(21, PacketSize::Fixed(2), "ItemExamine")
+            .unhandled(39, PacketSize::Fixed(6), "ObjectAction")
+            .unhandled(121, PacketSize::VariableByte, "MouseMovement")
+            .unhandled(150, PacketSize::Fixed(6), "ItemAction")
+            .unhandled(236, PacketSize::Fixed(6), "GroundItemAction");
+    }
+
+    /// Get the framing for an incoming packet
+    pub fn get_packet_size(&self, opcode: u8) -> PacketSize {
+        incoming_packets().size(opcode)
+    }
+
+    /// Check if an opcode is registered
     pub fn is_valid_opcode(&self, opcode: u8) -> bool {
-        INCOMING_PACKET_SIZES[opcode as usize] != -3
+        incoming_packets().contains(opcode)
     }
 
-    /// Process an incoming game packet
+    /// Process an incoming game packet without player context
+    ///
+    /// Packets that act on a player are decoded and then ignored; use
+    /// `process_with_player` to handle them.
     pub fn process(&self, packet: &IncomingGamePacket) -> Result<PacketResult> {
-        trace!(
-            opcode = packet.opcode,
-            size = packet.data.len(),
-            "Processing game packet"
-        );
-
-        match packet.opcode {
-            0 => self.handle_keepalive(packet),
-            3 => self.handle_focus_change(packet),
-            4 => self.handle_chat(packet),
-            14 | 98 => self.handle_walk(packet),
-            52 => self.handle_command(packet),
-            77 => self.handle_map_loaded(packet),
-            86 => self.handle_mouse_click(packet),
-            164 => self.handle_button_click(packet),
-            210 => self.handle_close_interface(packet),
-            // Bank and inventory packets need player context - handled in process_with_player
-            41 | 43 | 55 | 115 | 116 | 117 | 129 | 145 | 185 | 195 | 214 | 233 | 243 => {
-                debug!(
-                    opcode = packet.opcode,
-                    "Bank/inventory packet requires player context"
-                );
-                Ok(PacketResult::empty())
-            }
-            _ => {
-                if self.is_valid_opcode(packet.opcode) {
-                    debug!(opcode = packet.opcode, "Unimplemented game packet");
-                    Ok(PacketResult::empty())
-                } else {
-                    warn!(opcode = packet.opcode, "Unknown game packet");
-                    Ok(PacketResult::empty())
-                }
-            }
-        }
+        incoming_packets().dispatch(self, packet, None)
     }
 
     /// Process a packet with player context
@@ -377,26 +414,7 @@ pub fn process_with_player(
         packet: &IncomingGamePacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
-        // Handle bank and inventory packets that need player context
-        match packet.opcode {
-            41 => return self.handle_item_equip(packet, player),
-            42 => return self.handle_item_unequip(packet, player),
-            43 => return self.handle_bank_withdraw(packet, player),
-            55 => return self.handle_bank_tab_select(packet, player),
-            115 => return self.handle_bank_withdraw_mode(packet, player),
-            116 => return self.handle_bank_search(packet, player),
-            117 => return self.handle_bank_note_mode(packet, player),
-            129 => return self.handle_bank_deposit_all(packet, player),
-            145 => return self.handle_item_drop(packet, player),
-            185 => return self.handle_bank_close(packet, player),
-            195 => return self.handle_bank_deposit_equipment(packet, player),
-            214 => return self.handle_bank_move_item(packet, player),
-            233 => return self.handle_bank_deposit(packet, player),
-            243 => return self.handle_inventory_swap(packet, player),
-            _ => {}
-        }
-
-        let result = self.process(packet)?;
+        let result = incoming_packets().dispatch(self, packet, Some(player))?;
 
         // Apply movement if present
         if let Some(ref movement) = result.movement {
@@ -411,26 +429,62 @@ pub fn process_with_player(
         Ok(result)
     }
 
-    /// Apply movement to a player
+    /// Queue a walk request on a player
+    ///
+    /// The packet's first tile and waypoints become the path's checkpoints;
+    /// the world walks it tile by tile on the following ticks. A path that
+    /// leaves the player's scene is refused, and one that walks through
+    /// walls, objects or blocked terrain is replaced with a path found
+    /// server-side. The scene
+    /// shares the collision map's regions, which are normally preloaded as
+    /// the player moves; the search only runs for paths that need it.
     fn apply_movement(&self, player: &Arc<Player>, movement: &MovementRequest) {
         let current = player.location();
-        let dest = Location::new(movement.dest_x, movement.dest_y, current.z);
+        let checkpoints = movement.checkpoints();
+        let scene = Scene::around(&self.collision, current);
 
         // Set running state
         *player.running.write() = movement.running;
 
-        // For now, just teleport to the destination
-        // In a full implementation, this would queue the movement path
-        // and process it tick by tick
-        player.set_location(dest);
+        let dest = checkpoints[checkpoints.len() - 1];
+        if checkpoints.iter().any(|&(x, y)| !scene.contains(x, y)) {
+            player.walking_queue.write().clear();
+            warn!(
+                player = %player.username(),
+                from = %current,
+                to = ?dest,
+                "Rejected walk request outside the scene"
+            );
+            return;
+        }
+
+        let steps = movement::expand_path(current, &checkpoints);
+        let reaches = steps.last().is_some_and(|step| (step.x, step.y) == dest);
+        let path = if reaches && scene.is_walkable(current, &steps) {
+            checkpoints
+        } else {
+            match pathfinding::find_path(&scene, current, dest.0, dest.1) {
+                Some(path) => {
+                    debug!(
+                        player = %player.username(),
+                        to = ?dest,
+                        corrected_to = ?path.last(),
+                        "Corrected blocked walk request"
+                    );
+                    path
+                }
+                None => Vec::new(),
+            }
+        };
+        player.walking_queue.write().set_path(current, &path);
 
         debug!(
             player = %player.username(),
             from = %current,
-            to = %dest,
+            to = ?path.last(),
             running = movement.running,
             waypoints = movement.waypoints.len(),
-            "Player movement processed"
+            "Player movement queued"
         );
     }
 
@@ -460,7 +514,7 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                     loc.region_x(),
                     loc.region_y()
                 );
-                Ok(PacketResult::with_responses(vec![build_system_message(
+                Ok(PacketResult::with_responses(vec![ServerMessage::system(
                     &message,
                 )]))
             }
@@ -470,15 +524,16 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                         let z = args.get(2).and_then(|s| s.parse::<u8>().ok()).unwrap_or(0);
                         let dest = Location::new(x, y, z);
                         player.teleport(dest);
+                        pathfinding::preload_scene(&self.collision, dest);
 
                         let message = format!("Teleported to {}", dest);
                         return Ok(PacketResult::with_responses(vec![
-                            build_system_message(&message),
-                            build_map_region(dest),
+                            ServerMessage::system(&message),
+                            ServerMessage::MapRegion(dest),
                         ]));
                     }
                 }
-                Ok(PacketResult::with_responses(vec![build_system_message(
+                Ok(PacketResult::with_responses(vec![ServerMessage::system(
                     "Usage: ::tele x y [z]",
                 )]))
             }
@@ -494,13 +549,17 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
 
                             let message = format!("Set skill {} to level {}", skill_id, level);
                             return Ok(PacketResult::with_responses(vec![
-                                build_system_message(&message),
-                                build_skill_update(skill_id, level, 0),
+                                ServerMessage::system(&message),
+                                ServerMessage::Skill {
+                                    skill_id,
+                                    level,
+                                    experience: 0,
+                                },
                             ]));
                         }
                     }
                 }
-                Ok(PacketResult::with_responses(vec![build_system_message(
+                Ok(PacketResult::with_responses(vec![ServerMessage::system(
                     "Usage: ::setlevel skill_id level",
                 )]))
             }
@@ -511,8 +570,8 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                     .unwrap_or(100);
                 *player.run_energy.write() = energy.min(100);
                 Ok(PacketResult::with_responses(vec![
-                    build_system_message(&format!("Run energy set to {}", energy)),
-                    build_run_energy(energy),
+                    ServerMessage::system(format!("Run energy set to {}", energy)),
+                    ServerMessage::RunEnergy(energy),
                 ]))
             }
             "bank" => {
@@ -524,15 +583,13 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                 let total_items = bank.total_items();
                 let capacity = bank.capacity;
 
-                // Build the packets to send
-                let bank_open_packet = build_bank_open(capacity as u16);
-                let bank_settings_packet = build_bank_settings(
-                    bank.withdraw_as_note,
-                    bank.placeholders_enabled,
-                    bank.withdraw_x_amount,
-                );
-                let bank_tab_info_packet = build_bank_tab_info(&bank);
-                let bank_contents_packet = build_bank_full_update(&bank);
+                // Build the messages to send
+                let bank_open = ServerMessage::BankOpen {
+                    capacity: capacity as u16,
+                };
+                let bank_settings = ServerMessage::bank_settings(&bank);
+                let bank_tabs = ServerMessage::bank_tabs(&bank);
+                let bank_contents = ServerMessage::bank_contents(&bank);
 
                 drop(bank);
 
@@ -544,10 +601,10 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                 );
 
                 Ok(PacketResult::with_responses(vec![
-                    bank_open_packet,
-                    bank_settings_packet,
-                    bank_tab_info_packet,
-                    bank_contents_packet,
+                    bank_open,
+                    bank_settings,
+                    bank_tabs,
+                    bank_contents,
                 ]))
             }
             "bankgive" | "addbank" => {
@@ -560,7 +617,7 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                         match bank.deposit(item_id, amount, None) {
                             Ok((tab, slot)) => {
                                 return Ok(PacketResult::with_responses(vec![
-                                    build_system_message(&format!(
+                                    ServerMessage::system(format!(
                                         "Added {} x item {} to bank (tab {}, slot {})",
                                         amount, item_id, tab, slot
                                     )),
@@ -568,13 +625,13 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                             }
                             Err(e) => {
                                 return Ok(PacketResult::with_responses(vec![
-                                    build_system_message(&format!("Failed to add to bank: {}", e)),
+                                    ServerMessage::system(format!("Failed to add to bank: {}", e)),
                                 ]));
                             }
                         }
                     }
                 }
-                Ok(PacketResult::with_responses(vec![build_system_message(
+                Ok(PacketResult::with_responses(vec![ServerMessage::system(
                     "Usage: ::bankgive item_id amount",
                 )]))
             }
@@ -592,7 +649,7 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                     format!("  Note mode: {}", bank.withdraw_as_note),
                     format!("  Placeholders: {}", bank.placeholders_enabled),
                 ];
-                let responses: Vec<_> = messages.iter().map(|m| build_system_message(m)).collect();
+                let responses: Vec<_> = messages.iter().map(ServerMessage::system).collect();
                 Ok(PacketResult::with_responses(responses))
             }
             "item" | "give" => {
@@ -605,10 +662,10 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                         let mut inventory = player.inventory.write();
                         match inventory.add(item_id, amount, stackable) {
                             Ok(slot) => {
-                                let inv_update = build_player_inventory_update(&inventory);
+                                let inv_update = ServerMessage::inventory(&inventory);
                                 return Ok(PacketResult::with_responses(vec![
                                     inv_update,
-                                    build_system_message(&format!(
+                                    ServerMessage::system(format!(
                                         "Added {} x item {} to slot {}",
                                         amount, item_id, slot
                                     )),
@@ -616,13 +673,13 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                             }
                             Err(_) => {
                                 return Ok(PacketResult::with_responses(vec![
-                                    build_system_message("Inventory is full."),
+                                    ServerMessage::system("Inventory is full."),
                                 ]));
                             }
                         }
                     }
                 }
-                Ok(PacketResult::with_responses(vec![build_system_message(
+                Ok(PacketResult::with_responses(vec![ServerMessage::system(
                     "Usage: ::item item_id [amount]",
                 )]))
             }
@@ -646,7 +703,7 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                         bonuses[10], bonuses[11], bonuses[12], bonuses[13]
                     ),
                 ];
-                let responses: Vec<_> = messages.iter().map(|m| build_system_message(m)).collect();
+                let responses: Vec<_> = messages.iter().map(ServerMessage::system).collect();
                 Ok(PacketResult::with_responses(responses))
             }
             "equipment" | "worn" => {
@@ -655,7 +712,7 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                 let items = equipment.get_all_items();
 
                 if items.is_empty() {
-                    return Ok(PacketResult::with_responses(vec![build_system_message(
+                    return Ok(PacketResult::with_responses(vec![ServerMessage::system(
                         "You have nothing equipped.",
                     )]));
                 }
@@ -678,17 +735,17 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                     };
                     messages.push(format!("  {}: {} x{}", slot_name, item_id, amount));
                 }
-                let responses: Vec<_> = messages.iter().map(|m| build_system_message(m)).collect();
+                let responses: Vec<_> = messages.iter().map(ServerMessage::system).collect();
                 Ok(PacketResult::with_responses(responses))
             }
             "clearinv" => {
                 // Clear inventory
                 let mut inventory = player.inventory.write();
                 *inventory = crate::game::inventory::Inventory::new();
-                let inv_update = build_player_inventory_update(&inventory);
+                let inv_update = ServerMessage::inventory(&inventory);
                 Ok(PacketResult::with_responses(vec![
                     inv_update,
-                    build_system_message("Inventory cleared."),
+                    ServerMessage::system("Inventory cleared."),
                 ]))
             }
             "drop" | "spawn" => {
@@ -699,7 +756,11 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                         let location = player.location.read().clone();
 
                         // Send ground item spawn packet
-                        let spawn_packet = build_ground_item_spawn(item_id, amount, &location);
+                        let spawn_packet = ServerMessage::GroundItemSpawn {
+                            item_id,
+                            amount,
+                            location,
+                        };
 
                         info!(
                             player = %player.username(),
@@ -711,14 +772,14 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
 
                         return Ok(PacketResult::with_responses(vec![
                             spawn_packet,
-                            build_system_message(&format!(
+                            ServerMessage::system(format!(
                                 "Spawned {} x item {} at your location",
                                 amount, item_id
                             )),
                         ]));
                     }
                 }
-                Ok(PacketResult::with_responses(vec![build_system_message(
+                Ok(PacketResult::with_responses(vec![ServerMessage::system(
                     "Usage: ::drop item_id [amount]",
                 )]))
             }
@@ -729,18 +790,18 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                         let location = player.location.read().clone();
 
                         // Send ground item remove packet
-                        let remove_packet = build_ground_item_remove(item_id, &location);
+                        let remove_packet = ServerMessage::GroundItemRemove { item_id, location };
 
                         return Ok(PacketResult::with_responses(vec![
                             remove_packet,
-                            build_system_message(&format!(
+                            ServerMessage::system(format!(
                                 "Removed ground item {} at your location",
                                 item_id
                             )),
                         ]));
                     }
                 }
-                Ok(PacketResult::with_responses(vec![build_system_message(
+                Ok(PacketResult::with_responses(vec![ServerMessage::system(
                     "Usage: ::pickup item_id",
                 )]))
             }
@@ -761,144 +822,97 @@ fn execute_command(&self, player: &Arc<Player>, command: &str) -> Result<PacketR
                     "::bankgive item_id amount - Add item to bank",
                     "::bankinfo - Show bank information",
                 ];
-                let responses: Vec<_> = messages.iter().map(|m| build_system_message(m)).collect();
+                let responses: Vec<_> =
+                    messages.iter().map(|m| ServerMessage::system(*m)).collect();
                 Ok(PacketResult::with_responses(responses))
             }
-            _ => Ok(PacketResult::with_responses(vec![build_system_message(
-                &format!("Unknown command: {}", cmd),
+            _ => Ok(PacketResult::with_responses(vec![ServerMessage::system(
+                format!("Unknown command: {}", cmd),
             )])),
         }
     }
 
     /// Handle keep-alive packet
-    fn handle_keepalive(&self, _packet: &IncomingGamePacket) -> Result<PacketResult> {
+    fn handle_keepalive(&self, _packet: KeepAlivePacket) -> Result<PacketResult> {
         trace!("Keep-alive received");
         Ok(PacketResult::empty())
     }
 
     /// Handle window focus change
-    fn handle_focus_change(&self, packet: &IncomingGamePacket) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-        let focused = buffer.read_ubyte() == 1;
-        trace!(focused = focused, "Focus change");
+    fn handle_focus_change(&self, packet: FocusChangePacket) -> Result<PacketResult> {
+        trace!(focused = packet.focused, "Focus change");
         Ok(PacketResult::empty())
     }
 
     /// Handle chat message
-    fn handle_chat(&self, packet: &IncomingGamePacket) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        // Chat format: effects (2 bytes), message (huffman encoded)
-        if buffer.remaining() < 2 {
-            return Ok(PacketResult::empty());
-        }
-
-        let _effects = buffer.read_ushort();
-        let message_data = buffer.read_bytes(buffer.remaining());
-
+    fn handle_chat(&self, packet: ChatPacket) -> Result<PacketResult> {
         // TODO: Decode huffman-encoded message
-        debug!(message_len = message_data.len(), "Chat message received");
+        debug!(
+            message_len = packet.message_data.len(),
+            "Chat message received"
+        );
 
         // For now, just acknowledge receipt
         Ok(PacketResult::empty())
     }
 
-    /// Handle walk/movement
-    fn handle_walk(&self, packet: &IncomingGamePacket) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        if buffer.remaining() < 5 {
-            return Ok(PacketResult::empty());
-        }
-
-        // Read base coordinates
-        // The format varies slightly between opcode 14 and 98
-        let first_step_x: u16;
-        let first_step_y: u16;
-        let running: bool;
-
-        if packet.opcode == 14 {
-            // Walk to position (click on minimap)
-            first_step_x = buffer.read_ushort_le();
-            first_step_y = buffer.read_short_a();
-            running = buffer.read_byte_s() == 1;
-        } else {
-            // Walk here (click on game screen) - opcode 98
-            first_step_x = buffer.read_ushort_le();
-            first_step_y = buffer.read_short_a();
-            running = buffer.read_byte_s() == 1;
-        }
-
-        // Read additional waypoints if present
-        let mut waypoints = Vec::new();
-        while buffer.remaining() >= 2 {
-            let dx = buffer.read_byte() as i8;
-            let dy = buffer.read_byte() as i8;
-            waypoints.push((dx, dy));
-        }
+    /// Handle walk here (click on game screen)
+    fn handle_walk(&self, packet: WalkPacket) -> Result<PacketResult> {
+        let WalkPacket {
+            dest_x,
+            dest_y,
+            running,
+            waypoints,
+        } = packet;
 
         debug!(
-            dest_x = first_step_x,
-            dest_y = first_step_y,
+            dest_x = dest_x,
+            dest_y = dest_y,
             running = running,
             waypoints = waypoints.len(),
             "Walk request received"
         );
 
         Ok(PacketResult::with_movement(MovementRequest {
-            dest_x: first_step_x,
-            dest_y: first_step_y,
+            dest_x,
+            dest_y,
             running,
             waypoints,
         }))
     }
 
-    /// Handle command (::command)
-    fn handle_command(&self, packet: &IncomingGamePacket) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-        let command = buffer.read_string();
+    /// Handle walk to position (click on minimap)
+    fn handle_minimap_walk(&self, packet: MinimapWalkPacket) -> Result<PacketResult> {
+        self.handle_walk(packet.0)
+    }
 
-        debug!(command = %command, "Command received");
+    /// Handle command (::command)
+    fn handle_command(&self, packet: CommandPacket) -> Result<PacketResult> {
+        debug!(command = %packet.command, "Command received");
 
-        Ok(PacketResult::with_command(command))
+        Ok(PacketResult::with_command(packet.command))
     }
 
     /// Handle map region loaded confirmation
-    fn handle_map_loaded(&self, _packet: &IncomingGamePacket) -> Result<PacketResult> {
+    fn handle_map_loaded(&self, _packet: MapLoadedPacket) -> Result<PacketResult> {
         trace!("Map region loaded");
         Ok(PacketResult::empty())
     }
 
     /// Handle mouse click
-    fn handle_mouse_click(&self, packet: &IncomingGamePacket) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        if buffer.remaining() < 4 {
-            return Ok(PacketResult::empty());
-        }
-
-        let _packed_data = buffer.read_int();
-        // Contains: time since last click, right-click flag, x, y
-
+    fn handle_mouse_click(&self, _packet: MouseClickPacket) -> Result<PacketResult> {
         Ok(PacketResult::empty())
     }
 
     /// Handle button click
-    fn handle_button_click(&self, packet: &IncomingGamePacket) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        if buffer.remaining() < 2 {
-            return Ok(PacketResult::empty());
-        }
-
-        let button_id = buffer.read_ushort();
-        debug!(button_id = button_id, "Button click");
+    fn handle_button_click(&self, packet: ButtonClickPacket) -> Result<PacketResult> {
+        debug!(button_id = packet.button_id, "Button click");
 
         Ok(PacketResult::empty())
     }
 
     /// Handle close interface
-    fn handle_close_interface(&self, _packet: &IncomingGamePacket) -> Result<PacketResult> {
+    fn handle_close_interface(&self, _packet: CloseInterfacePacket) -> Result<PacketResult> {
         trace!("Close interface");
         Ok(PacketResult::empty())
     }
@@ -908,7 +922,7 @@ fn handle_close_interface(&self, _packet: &IncomingGamePacket) -> Result<PacketR
     /// Handle bank close packet
     fn handle_bank_close(
         &self,
-        _packet: &IncomingGamePacket,
+        _packet: BankClosePacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
         *player.bank_open.write() = false;
@@ -919,19 +933,15 @@ fn handle_bank_close(
     /// Handle bank withdraw packet
     fn handle_bank_withdraw(
         &self,
-        packet: &IncomingGamePacket,
+        packet: BankWithdrawPacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        if buffer.remaining() < 11 {
-            return Ok(PacketResult::empty());
-        }
-
-        let slot = buffer.read_ushort_le();
-        let item_id = buffer.read_ushort_le();
-        let amount = buffer.read_uint();
-        let as_note = buffer.read_ubyte() == 1;
+        let BankWithdrawPacket {
+            slot,
+            item_id,
+            amount,
+            as_note,
+        } = packet;
 
         debug!(
             player = %player.username(),
@@ -948,7 +958,7 @@ fn handle_bank_withdraw(
         {
             let inventory = player.inventory.read();
             if !inventory.has_room_for(item_id, amount, stackable) {
-                return Ok(PacketResult::with_responses(vec![build_system_message(
+                return Ok(PacketResult::with_responses(vec![ServerMessage::system(
                     "You don't have enough inventory space.",
                 )]));
             }
@@ -992,19 +1002,14 @@ fn handle_bank_withdraw(
 
                         // Send inventory update and bank slot update packets
                         Ok(PacketResult::with_responses(vec![
-                            build_inventory_slot_update(
-                                CONTAINER_INVENTORY,
-                                inv_slot as u16,
-                                item_id,
-                                item_amount,
-                            ),
-                            build_bank_slot_update(
-                                tab as u8,
+                            ServerMessage::inventory_slot(inv_slot as u16, item_id, item_amount),
+                            ServerMessage::BankSlot {
+                                tab: tab as u8,
                                 slot,
-                                bank_slot_item.0,
-                                bank_slot_item.1,
-                                bank_slot_item.2,
-                            ),
+                                item_id: bank_slot_item.0,
+                                amount: bank_slot_item.1,
+                                placeholder: bank_slot_item.2,
+                            },
                         ]))
                     }
                     Err(remaining) => {
@@ -1016,7 +1021,7 @@ fn handle_bank_withdraw(
                             remaining = remaining,
                             "Partial withdrawal - inventory full"
                         );
-                        Ok(PacketResult::with_responses(vec![build_system_message(
+                        Ok(PacketResult::with_responses(vec![ServerMessage::system(
                             "Your inventory is full.",
                         )]))
                     }
@@ -1029,7 +1034,7 @@ fn handle_bank_withdraw(
                     BankError::InvalidSlot => "Invalid bank slot.",
                     _ => "Cannot withdraw that item.",
                 };
-                Ok(PacketResult::with_responses(vec![build_system_message(
+                Ok(PacketResult::with_responses(vec![ServerMessage::system(
                     msg,
                 )]))
             }
@@ -1039,18 +1044,14 @@ fn handle_bank_withdraw(
     /// Handle bank deposit packet
     fn handle_bank_deposit(
         &self,
-        packet: &IncomingGamePacket,
+        packet: BankDepositPacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        if buffer.remaining() < 8 {
-            return Ok(PacketResult::empty());
-        }
-
-        let inventory_slot = buffer.read_ushort_le();
-        let item_id = buffer.read_ushort_le();
-        let amount = buffer.read_uint();
+        let BankDepositPacket {
+            inventory_slot,
+            item_id,
+            amount,
+        } = packet;
 
         debug!(
             player = %player.username(),
@@ -1062,7 +1063,7 @@ fn handle_bank_deposit(
 
         // Validate inventory slot
         if inventory_slot as usize >= INVENTORY_SIZE {
-            return Ok(PacketResult::with_responses(vec![build_system_message(
+            return Ok(PacketResult::with_responses(vec![ServerMessage::system(
                 "Invalid inventory slot.",
             )]));
         }
@@ -1079,12 +1080,12 @@ fn handle_bank_deposit(
                         actual = item.item_id,
                         "Item ID mismatch in deposit"
                     );
-                    return Ok(PacketResult::with_responses(vec![build_system_message(
+                    return Ok(PacketResult::with_responses(vec![ServerMessage::system(
                         "Item not found.",
                     )]));
                 }
                 None => {
-                    return Ok(PacketResult::with_responses(vec![build_system_message(
+                    return Ok(PacketResult::with_responses(vec![ServerMessage::system(
                         "That slot is empty.",
                     )]));
                 }
@@ -1137,19 +1138,14 @@ fn handle_bank_deposit(
 
                         // Send inventory update and bank slot update packets
                         Ok(PacketResult::with_responses(vec![
-                            build_inventory_slot_update(
-                                CONTAINER_INVENTORY,
-                                inventory_slot,
-                                item_id,
-                                item_amount,
-                            ),
-                            build_bank_slot_update(
-                                tab as u8,
-                                slot as u16,
-                                bank_slot_info.0,
-                                bank_slot_info.1,
-                                bank_slot_info.2,
-                            ),
+                            ServerMessage::inventory_slot(inventory_slot, item_id, item_amount),
+                            ServerMessage::BankSlot {
+                                tab: tab as u8,
+                                slot: slot as u16,
+                                item_id: bank_slot_info.0,
+                                amount: bank_slot_info.1,
+                                placeholder: bank_slot_info.2,
+                            },
                         ]))
                     }
                     Err(e) => {
@@ -1159,7 +1155,7 @@ fn handle_bank_deposit(
                             error = ?e,
                             "Failed to remove item from inventory after bank deposit"
                         );
-                        Ok(PacketResult::with_responses(vec![build_system_message(
+                        Ok(PacketResult::with_responses(vec![ServerMessage::system(
                             "Something went wrong.",
                         )]))
                     }
@@ -1171,7 +1167,7 @@ fn handle_bank_deposit(
                     BankError::InvalidAmount => "Invalid amount.",
                     _ => "Cannot deposit that item.",
                 };
-                Ok(PacketResult::with_responses(vec![build_system_message(
+                Ok(PacketResult::with_responses(vec![ServerMessage::system(
                     msg,
                 )]))
             }
@@ -1181,7 +1177,7 @@ fn handle_bank_deposit(
     /// Handle bank deposit all inventory packet
     fn handle_bank_deposit_all(
         &self,
-        _packet: &IncomingGamePacket,
+        _packet: BankDepositAllPacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
         debug!(player = %player.username(), "Bank deposit all inventory");
@@ -1193,7 +1189,7 @@ fn handle_bank_deposit_all(
         };
 
         if items_to_deposit.is_empty() {
-            return Ok(PacketResult::with_responses(vec![build_system_message(
+            return Ok(PacketResult::with_responses(vec![ServerMessage::system(
                 "You have nothing to deposit.",
             )]));
         }
@@ -1243,21 +1239,21 @@ fn handle_bank_deposit_all(
         let inventory = player.inventory.read();
         let bank = player.bank.read();
         Ok(PacketResult::with_responses(vec![
-            build_player_inventory_update(&inventory),
-            build_bank_full_update(&bank),
-            build_system_message(&msg),
+            ServerMessage::inventory(&inventory),
+            ServerMessage::bank_contents(&bank),
+            ServerMessage::system(&msg),
         ]))
     }
 
     /// Handle bank deposit equipment packet
     fn handle_bank_deposit_equipment(
         &self,
-        _packet: &IncomingGamePacket,
+        _packet: BankDepositEquipmentPacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
         debug!(player = %player.username(), "Bank deposit all equipment");
         // TODO: Iterate through equipment and deposit all items
-        Ok(PacketResult::with_responses(vec![build_system_message(
+        Ok(PacketResult::with_responses(vec![ServerMessage::system(
             "Deposited all worn items.",
         )]))
     }
@@ -1265,16 +1261,10 @@ fn handle_bank_deposit_equipment(
     /// Handle bank tab select packet
     fn handle_bank_tab_select(
         &self,
-        packet: &IncomingGamePacket,
+        packet: BankTabSelectPacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        if buffer.remaining() < 1 {
-            return Ok(PacketResult::empty());
-        }
-
-        let tab = buffer.read_ubyte();
+        let BankTabSelectPacket { tab } = packet;
         debug!(player = %player.username(), tab = tab, "Bank tab selected");
         // Tab selection is client-side state; server just acknowledges
         Ok(PacketResult::empty())
@@ -1283,18 +1273,14 @@ fn handle_bank_tab_select(
     /// Handle bank move item packet
     fn handle_bank_move_item(
         &self,
-        packet: &IncomingGamePacket,
+        packet: BankMoveItemPacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        if buffer.remaining() < 5 {
-            return Ok(PacketResult::empty());
-        }
-
-        let from_slot = buffer.read_ushort_le();
-        let to_slot = buffer.read_ushort_le();
-        let mode = buffer.read_ubyte();
+        let BankMoveItemPacket {
+            from_slot,
+            to_slot,
+            mode,
+        } = packet;
 
         debug!(
             player = %player.username(),
@@ -1320,8 +1306,20 @@ fn handle_bank_move_item(
 
                 // Send updates for both affected slots
                 Ok(PacketResult::with_responses(vec![
-                    build_bank_slot_update(0, from_slot, from_item.0, from_item.1, from_item.2),
-                    build_bank_slot_update(0, to_slot, to_item.0, to_item.1, to_item.2),
+                    ServerMessage::BankSlot {
+                        tab: 0,
+                        slot: from_slot,
+                        item_id: from_item.0,
+                        amount: from_item.1,
+                        placeholder: from_item.2,
+                    },
+                    ServerMessage::BankSlot {
+                        tab: 0,
+                        slot: to_slot,
+                        item_id: to_item.0,
+                        amount: to_item.1,
+                        placeholder: to_item.2,
+                    },
                 ]))
             }
             Err(e) => {
@@ -1334,11 +1332,10 @@ fn handle_bank_move_item(
     /// Handle bank search packet
     fn handle_bank_search(
         &self,
-        packet: &IncomingGamePacket,
+        packet: BankSearchPacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-        let query = buffer.read_string();
+        let BankSearchPacket { query } = packet;
 
         debug!(player = %player.username(), query = %query, "Bank search");
         // Search is handled client-side with server-provided item data
@@ -1348,52 +1345,32 @@ fn handle_bank_search(
     /// Handle bank note mode packet
     fn handle_bank_note_mode(
         &self,
-        packet: &IncomingGamePacket,
+        packet: BankNoteModePacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        if buffer.remaining() < 1 {
-            return Ok(PacketResult::empty());
-        }
-
-        let enabled = buffer.read_ubyte() == 1;
+        let BankNoteModePacket { enabled } = packet;
         debug!(player = %player.username(), enabled = enabled, "Bank note mode");
 
         let mut bank = player.bank.write();
         bank.set_note_mode(enabled);
 
         // Send settings update to confirm
-        let settings_packet = build_bank_settings(
-            bank.withdraw_as_note,
-            bank.placeholders_enabled,
-            bank.withdraw_x_amount,
-        );
+        let settings_packet = ServerMessage::bank_settings(&bank);
         Ok(PacketResult::with_responses(vec![settings_packet]))
     }
 
     /// Handle bank withdraw mode packet
     fn handle_bank_withdraw_mode(
         &self,
-        packet: &IncomingGamePacket,
+        packet: BankWithdrawModePacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        if buffer.remaining() < 1 {
-            return Ok(PacketResult::empty());
-        }
-
-        let mode = buffer.read_ubyte();
+        let BankWithdrawModePacket { mode } = packet;
         debug!(player = %player.username(), mode = mode, "Bank withdraw mode");
         // 0 = single, 1 = 5, 2 = 10, 3 = X, 4 = All
         // Store the withdraw-X amount if mode is X
         let bank = player.bank.read();
-        let settings_packet = build_bank_settings(
-            bank.withdraw_as_note,
-            bank.placeholders_enabled,
-            bank.withdraw_x_amount,
-        );
+        let settings_packet = ServerMessage::bank_settings(&bank);
         Ok(PacketResult::with_responses(vec![settings_packet]))
     }
 
@@ -1402,17 +1379,10 @@ fn handle_bank_withdraw_mode(
     /// Handle item drop packet
     fn handle_item_drop(
         &self,
-        packet: &IncomingGamePacket,
+        packet: ItemDropPacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        if buffer.remaining() < 4 {
-            return Ok(PacketResult::empty());
-        }
-
-        let slot = buffer.read_ushort_le();
-        let item_id = buffer.read_ushort_le();
+        let ItemDropPacket { slot, item_id } = packet;
 
         debug!(
             player = %player.username(),
@@ -1476,8 +1446,12 @@ fn handle_item_drop(
             // Note: In a full implementation, the GroundItemManager would track this item
             // and handle visibility transitions. For now, we send the packet directly.
             Ok(PacketResult::with_responses(vec![
-                build_inventory_clear_slot(CONTAINER_INVENTORY, slot),
-                build_ground_item_spawn(dropped_id, dropped_amount, &location),
+                ServerMessage::inventory_slot(slot, 0, 0),
+                ServerMessage::GroundItemSpawn {
+                    item_id: dropped_id,
+                    amount: dropped_amount,
+                    location,
+                },
             ]))
         } else {
             Ok(PacketResult::empty())
@@ -1487,17 +1461,13 @@ fn handle_item_drop(
     /// Handle item equip packet
     fn handle_item_equip(
         &self,
-        packet: &IncomingGamePacket,
+        packet: ItemEquipPacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        if buffer.remaining() < 4 {
-            return Ok(PacketResult::empty());
-        }
-
-        let inv_slot = buffer.read_ushort_le();
-        let item_id = buffer.read_ushort_le();
+        let ItemEquipPacket {
+            slot: inv_slot,
+            item_id,
+        } = packet;
 
         debug!(
             player = %player.username(),
@@ -1508,14 +1478,14 @@ fn handle_item_equip(
 
         // Validate inventory slot
         if inv_slot as usize >= INVENTORY_SIZE {
-            return Ok(PacketResult::with_responses(vec![build_system_message(
+            return Ok(PacketResult::with_responses(vec![ServerMessage::system(
                 "Invalid inventory slot.",
             )]));
         }
 
         // Check if item is equippable
         if !is_equippable(item_id) {
-            return Ok(PacketResult::with_responses(vec![build_system_message(
+            return Ok(PacketResult::with_responses(vec![ServerMessage::system(
                 "You can't equip that.",
             )]));
         }
@@ -1524,7 +1494,7 @@ fn handle_item_equip(
         let equipment_slot = match get_equipment_slot(item_id) {
             Some(slot) => slot,
             None => {
-                return Ok(PacketResult::with_responses(vec![build_system_message(
+                return Ok(PacketResult::with_responses(vec![ServerMessage::system(
                     "You can't equip that.",
                 )]));
             }
@@ -1542,12 +1512,12 @@ fn handle_item_equip(
                         actual = item.item_id,
                         "Item ID mismatch in equip"
                     );
-                    return Ok(PacketResult::with_responses(vec![build_system_message(
+                    return Ok(PacketResult::with_responses(vec![ServerMessage::system(
                         "Item not found.",
                     )]));
                 }
                 None => {
-                    return Ok(PacketResult::with_responses(vec![build_system_message(
+                    return Ok(PacketResult::with_responses(vec![ServerMessage::system(
                         "That slot is empty.",
                     )]));
                 }
@@ -1594,12 +1564,12 @@ fn handle_item_equip(
 
                 // Build response packets
                 // Send full inventory update (simpler than tracking individual slots)
-                responses.push(build_player_inventory_update(&inventory));
+                responses.push(ServerMessage::inventory(&inventory));
                 drop(inventory);
 
                 // Send full equipment update
                 let equipment = player.equipment.read();
-                responses.push(build_equipment_full_update(&equipment));
+                responses.push(ServerMessage::equipment(&equipment));
 
                 info!(
                     player = %player.username(),
@@ -1620,7 +1590,7 @@ fn handle_item_equip(
                     EquipmentError::ItemNotFound => "Item not found.",
                     _ => "You can't equip that.",
                 };
-                Ok(PacketResult::with_responses(vec![build_system_message(
+                Ok(PacketResult::with_responses(vec![ServerMessage::system(
                     msg,
                 )]))
             }
@@ -1630,17 +1600,13 @@ fn handle_item_equip(
     /// Handle item unequip packet
     fn handle_item_unequip(
         &self,
-        packet: &IncomingGamePacket,
+        packet: ItemUnequipPacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        if buffer.remaining() < 4 {
-            return Ok(PacketResult::empty());
-        }
-
-        let equip_slot = buffer.read_ushort_le();
-        let item_id = buffer.read_ushort_le();
+        let ItemUnequipPacket {
+            slot: equip_slot,
+            item_id,
+        } = packet;
 
         debug!(
             player = %player.username(),
@@ -1651,7 +1617,7 @@ fn handle_item_unequip(
 
         // Validate equipment slot
         if equip_slot as usize >= EQUIPMENT_SLOT_COUNT {
-            return Ok(PacketResult::with_responses(vec![build_system_message(
+            return Ok(PacketResult::with_responses(vec![ServerMessage::system(
                 "Invalid equipment slot.",
             )]));
         }
@@ -1665,7 +1631,7 @@ fn handle_item_unequip(
                 if equipped.item_id > 0 {
                     let stackable = is_stackable(equipped.item_id);
                     if !inventory.has_room_for(equipped.item_id, equipped.amount, stackable) {
-                        return Ok(PacketResult::with_responses(vec![build_system_message(
+                        return Ok(PacketResult::with_responses(vec![ServerMessage::system(
                             "You don't have enough inventory space.",
                         )]));
                     }
@@ -1693,11 +1659,11 @@ fn handle_item_unequip(
                         );
 
                         // Send updates
-                        let inv_update = build_player_inventory_update(&inventory);
+                        let inv_update = ServerMessage::inventory(&inventory);
                         drop(inventory);
 
                         let equipment = player.equipment.read();
-                        let equip_update = build_equipment_full_update(&equipment);
+                        let equip_update = ServerMessage::equipment(&equipment);
 
                         Ok(PacketResult::with_responses(vec![inv_update, equip_update]))
                     }
@@ -1708,7 +1674,7 @@ fn handle_item_unequip(
                             *slot = unequipped_item;
                         });
 
-                        Ok(PacketResult::with_responses(vec![build_system_message(
+                        Ok(PacketResult::with_responses(vec![ServerMessage::system(
                             "You don't have enough inventory space.",
                         )]))
                     }
@@ -1720,7 +1686,7 @@ fn handle_item_unequip(
                     EquipmentError::InvalidSlot => "Invalid equipment slot.",
                     _ => "Cannot unequip that.",
                 };
-                Ok(PacketResult::with_responses(vec![build_system_message(
+                Ok(PacketResult::with_responses(vec![ServerMessage::system(
                     msg,
                 )]))
             }
@@ -1730,17 +1696,10 @@ fn handle_item_unequip(
     /// Handle inventory swap packet
     fn handle_inventory_swap(
         &self,
-        packet: &IncomingGamePacket,
+        packet: InventorySwapPacket,
         player: &Arc<Player>,
     ) -> Result<PacketResult> {
-        let mut buffer = packet.buffer();
-
-        if buffer.remaining() < 4 {
-            return Ok(PacketResult::empty());
-        }
-
-        let from_slot = buffer.read_ushort_le();
-        let to_slot = buffer.read_ushort_le();
+        let InventorySwapPacket { from_slot, to_slot } = packet;
 
         debug!(
             player = %player.username(),
@@ -1772,13 +1731,8 @@ fn handle_inventory_swap(
 
                 // Send inventory updates for both affected slots
                 Ok(PacketResult::with_responses(vec![
-                    build_inventory_slot_update(
-                        CONTAINER_INVENTORY,
-                        from_slot,
-                        from_item.0,
-                        from_item.1,
-                    ),
-                    build_inventory_slot_update(CONTAINER_INVENTORY, to_slot, to_item.0, to_item.1),
+                    ServerMessage::inventory_slot(from_slot, from_item.0, from_item.1),
+                    ServerMessage::inventory_slot(to_slot, to_item.0, to_item.1),
                 ]))
             }
             Err(InventoryError::InvalidSlot) => {
@@ -1823,6 +1777,16 @@ pub fn build_logout() -> OutgoingGamePacket {
     OutgoingGamePacket::fixed(OutgoingOpcode::Logout.as_u8(), vec![])
 }
 
+/// Build a resume token packet
+pub fn build_resume_token(token: &str) -> OutgoingGamePacket {
+    let mut buffer = PacketBuffer::with_capacity(token.len() + 1);
+    buffer.write_string(token);
+    OutgoingGamePacket::variable(
+        OutgoingOpcode::ResumeToken.as_u8(),
+        buffer.as_bytes().to_vec(),
+    )
+}
+
 /// Build a map region packet
 pub fn build_map_region(location: Location) -> OutgoingGamePacket {
     let mut buffer = PacketBuffer::with_capacity(4);
@@ -1967,14 +1931,12 @@ pub fn build_bank_settings(
 
 /// Build a bank tab info packet (sizes of each tab)
 /// Format: tab_count (1), then for each tab: item_count (2)
-pub fn build_bank_tab_info(bank: &crate::game::bank::Bank) -> OutgoingGamePacket {
-    use crate::game::bank::BANK_TAB_COUNT;
-
-    let mut buffer = PacketBuffer::with_capacity(1 + BANK_TAB_COUNT * 2);
-    buffer.write_ubyte(BANK_TAB_COUNT as u8);
+pub fn build_bank_tab_info(tab_sizes: &[u16]) -> OutgoingGamePacket {
+    let mut buffer = PacketBuffer::with_capacity(1 + tab_sizes.len() * 2);
+    buffer.write_ubyte(tab_sizes.len() as u8);
 
-    for tab in &bank.tabs {
-        buffer.write_ushort(tab.items.len() as u16);
+    for size in tab_sizes {
+        buffer.write_ushort(*size);
     }
 
     OutgoingGamePacket::fixed(
@@ -1985,13 +1947,11 @@ pub fn build_bank_tab_info(bank: &crate::game::bank::Bank) -> OutgoingGamePacket
 
 /// Build a full bank update packet (all items across all tabs)
 /// Format: total_items (2), then for each item: tab (1), slot (2), item_id (2), amount (4), placeholder (1)
-pub fn build_bank_full_update(bank: &crate::game::bank::Bank) -> OutgoingGamePacket {
-    let all_items = bank.get_all_items();
-
-    let mut buffer = PacketBuffer::with_capacity(2 + all_items.len() * 10);
-    buffer.write_ushort(all_items.len() as u16);
+pub fn build_bank_full_update(items: &[BankItemInfo]) -> OutgoingGamePacket {
+    let mut buffer = PacketBuffer::with_capacity(2 + items.len() * 10);
+    buffer.write_ushort(items.len() as u16);
 
-    for item in &all_items {
+    for item in items {
         buffer.write_ubyte(item.tab);
         buffer.write_ushort(item.slot);
         buffer.write_ushort(item.item_id);
@@ -2164,12 +2124,15 @@ pub fn build_ground_item_update(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::{ProtocolError, RustscapeError};
 
     #[test]
     fn test_packet_sizes() {
-        assert_eq!(INCOMING_PACKET_SIZES[0], 0); // Keep-alive
-        assert_eq!(INCOMING_PACKET_SIZES[3], 1); // Focus change
-        assert_eq!(INCOMING_PACKET_SIZES[77], 0); // Map loaded
+        let handler = GamePacketHandler::new();
+        assert_eq!(handler.get_packet_size(0), PacketSize::Fixed(0)); // Keep-alive
+        assert_eq!(handler.get_packet_size(3), PacketSize::Fixed(1)); // Focus change
+        assert_eq!(handler.get_packet_size(77), PacketSize::Fixed(0)); // Map loaded
+        assert_eq!(handler.get_packet_size(4), PacketSize::VariableByte); // Chat
     }
 
     #[test]
@@ -2180,6 +2143,32 @@ fn test_handler_valid_opcode() {
         assert!(!handler.is_valid_opcode(255)); // Unknown
     }
 
+    #[test]
+    fn test_process_unregistered_opcode() {
+        let handler = GamePacketHandler::new();
+        let packet = IncomingGamePacket::new(255, vec![]);
+
+        assert!(matches!(
+            handler.process(&packet),
+            Err(RustscapeError::Protocol(ProtocolError::InvalidOpcode(255)))
+        ));
+    }
+
+    #[test]
+    fn test_process_with_player_dispatches_typed_packet() {
+        let handler = GamePacketHandler::new();
+        let player = Arc::new(Player::new(1, 1, "tester".to_string()));
+
+        // Bank note mode needs the player; without one it is ignored
+        let packet = IncomingGamePacket::new(117, vec![1]);
+        assert!(handler.process(&packet).unwrap().responses.is_empty());
+        assert!(!player.bank.read().withdraw_as_note);
+
+        let result = handler.process_with_player(&packet, &player).unwrap();
+        assert_eq!(result.responses.len(), 1);
+        assert!(player.bank.read().withdraw_as_note);
+    }
+
     #[test]
     fn test_outgoing_packet_encode_raw() {
         let packet = OutgoingGamePacket::fixed(10, vec![1, 2, 3]);
@@ -2202,6 +2191,16 @@ fn test_outgoing_packet_variable_encode_raw() {
         assert_eq!(encoded[2], 1); // data start
     }
 
+    #[test]
+    fn test_outgoing_packet_short_length_encode_raw() {
+        // Payloads of 256 bytes or more need a short length
+        let encoded = OutgoingGamePacket::variable(20, vec![0; 300]).encode_raw();
+        assert_eq!(&encoded[..3], &[20, 1, 44]);
+
+        let encoded = OutgoingGamePacket::variable_short(81, vec![1, 2]).encode_raw();
+        assert_eq!(encoded, vec![81, 0, 2, 1, 2]);
+    }
+
     #[test]
     fn test_incoming_packet() {
         let packet = IncomingGamePacket::new(3, vec![1]);
@@ -2284,6 +2283,64 @@ fn test_process_walk() {
         assert!(!movement.running);
     }
 
+    #[test]
+    fn test_walk_is_queued_not_teleported() {
+        let handler = GamePacketHandler::new();
+        let player = Arc::new(Player::new(1, 1, "walker".to_string()));
+        player.teleport(Location::new(3220, 3218, 0));
+
+        let mut buffer = PacketBuffer::with_capacity(7);
+        buffer.write_ushort_le(3222);
+        buffer.write_short_a(3218);
+        buffer.write_byte_s(0);
+        buffer.write_byte(-2);
+        buffer.write_byte(1);
+
+        let packet = IncomingGamePacket::new(98, buffer.as_bytes().to_vec());
+        let result = handler.process_with_player(&packet, &player).unwrap();
+        assert_eq!(
+            result.movement.unwrap().checkpoints(),
+            vec![(3222, 3218), (3220, 3219)]
+        );
+
+        // Queued rather than moved: two steps east, then north-west and west
+        assert_eq!(player.location(), Location::new(3220, 3218, 0));
+        assert_eq!(player.walking_queue.read().len(), 4);
+        assert!(!*player.running.read());
+    }
+
+    #[test]
+    fn test_blocked_and_distant_walks() {
+        let collision = Arc::new(CollisionMap::new());
+        for y in 3210..=3226 {
+            collision.add_wall(3221, y, 0, 2);
+        }
+        let handler = GamePacketHandler::with_collision(collision);
+        let player = Arc::new(Player::new(1, 1, "walker".to_string()));
+        player.teleport(Location::new(3220, 3218, 0));
+
+        let walk_to = |x: u16, y: u16| {
+            let mut buffer = PacketBuffer::with_capacity(5);
+            buffer.write_ushort_le(x);
+            buffer.write_short_a(y);
+            buffer.write_byte_s(0);
+            IncomingGamePacket::new(98, buffer.as_bytes().to_vec())
+        };
+
+        // Straight through the wall is replaced by a path around it
+        handler
+            .process_with_player(&walk_to(3223, 3218), &player)
+            .unwrap();
+        assert!(player.walking_queue.read().len() > 3);
+
+        // Far outside the scene is refused outright
+        handler
+            .process_with_player(&walk_to(3400, 3218), &player)
+            .unwrap();
+        assert!(player.walking_queue.read().is_empty());
+        assert_eq!(player.location(), Location::new(3220, 3218, 0));
+    }
+
     #[test]
     fn test_packet_result_empty() {
         let result = PacketResult::empty();
@@ -2373,7 +2430,7 @@ fn test_build_bank_slot_clear() {
     #[test]
     fn test_build_bank_full_update() {
         let bank = crate::game::bank::Bank::new();
-        let packet = build_bank_full_update(&bank);
+        let packet = build_bank_full_update(&bank.get_all_items());
         assert_eq!(packet.opcode, OutgoingOpcode::BankUpdate.as_u8());
         // Empty bank should have 2 bytes (item count = 0)
         assert_eq!(packet.data.len(), 2);
@@ -2381,8 +2438,7 @@ fn test_build_bank_full_update() {
 
     #[test]
     fn test_build_bank_tab_info() {
-        let bank = crate::game::bank::Bank::new();
-        let packet = build_bank_tab_info(&bank);
+        let packet = build_bank_tab_info(&[0; crate::game::bank::BANK_TAB_COUNT]);
         assert_eq!(packet.opcode, OutgoingOpcode::BankTabInfo.as_u8());
         // 1 byte for tab count + 9 tabs * 2 bytes each = 19 bytes
         assert_eq!(packet.data.len(), 19);
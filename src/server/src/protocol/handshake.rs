@@ -8,6 +8,7 @@
 
 use crate::error::{ProtocolError, Result, RustscapeError};
 use crate::net::buffer::PacketBuffer;
+use crate::protocol::revision::Protocols;
 
 /// Handshake opcodes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -124,14 +125,14 @@ pub fn encode(&self) -> Vec<u8> {
 
 /// Handshake protocol handler
 pub struct HandshakeHandler {
-    /// Expected client revision
-    expected_revision: u32,
+    /// Accepted client revisions
+    protocols: Protocols,
 }
 
 impl HandshakeHandler {
     /// Create a new handshake handler
-    pub fn new(expected_revision: u32) -> Self {
-        Self { expected_revision }
+    pub fn new(protocols: Protocols) -> Self {
+        Self { protocols }
     }
 
     /// Parse a handshake request from a buffer
@@ -182,7 +183,7 @@ pub fn parse_request(&self, opcode: u8, data: &[u8]) -> Result<HandshakeRequest>
     /// Process a JS5 handshake and generate response
     pub fn process_js5(&self, request: &HandshakeRequest) -> HandshakeResponse {
         if let HandshakeRequest::Js5 { revision } = request {
-            if *revision == self.expected_revision {
+            if self.validate_revision(*revision) {
                 HandshakeResponse::Js5Success
             } else {
                 // Return "out of date" error code (6)
@@ -196,7 +197,7 @@ pub fn process_js5(&self, request: &HandshakeRequest) -> HandshakeResponse {
     /// Process a login handshake and generate response
     pub fn process_login(&self, request: &HandshakeRequest) -> (HandshakeResponse, u64) {
         if let HandshakeRequest::Login { revision } = request {
-            if *revision == self.expected_revision {
+            if self.validate_revision(*revision) {
                 // Generate random server key
                 let server_key: u64 = rand::random();
                 (HandshakeResponse::LoginSuccess { server_key }, server_key)
@@ -211,18 +212,18 @@ pub fn process_login(&self, request: &HandshakeRequest) -> (HandshakeResponse, u
 
     /// Validate a client revision
     pub fn validate_revision(&self, revision: u32) -> bool {
-        revision == self.expected_revision
+        self.protocols.supports(revision)
     }
 
-    /// Get the expected revision
-    pub fn expected_revision(&self) -> u32 {
-        self.expected_revision
+    /// Get the accepted revisions
+    pub fn revisions(&self) -> Vec<u32> {
+        self.protocols.revisions()
     }
 }
 
 impl Default for HandshakeHandler {
     fn default() -> Self {
-        Self::new(530)
+        Self::new(Protocols::default())
     }
 }
 
@@ -250,7 +251,7 @@ fn test_handshake_opcode_as_u8() {
 
     #[test]
     fn test_parse_js5_request() {
-        let handler = HandshakeHandler::new(530);
+        let handler = HandshakeHandler::default();
         let data = 530u32.to_be_bytes();
 
         let request = handler.parse_request(15, &data).unwrap();
@@ -263,7 +264,7 @@ fn test_parse_js5_request() {
 
     #[test]
     fn test_parse_login_request() {
-        let handler = HandshakeHandler::new(530);
+        let handler = HandshakeHandler::default();
         let data = 530u32.to_be_bytes();
 
         let request = handler.parse_request(14, &data).unwrap();
@@ -276,7 +277,7 @@ fn test_parse_login_request() {
 
     #[test]
     fn test_process_js5_success() {
-        let handler = HandshakeHandler::new(530);
+        let handler = HandshakeHandler::default();
         let request = HandshakeRequest::Js5 { revision: 530 };
 
         let response = handler.process_js5(&request);
@@ -285,7 +286,7 @@ fn test_process_js5_success() {
 
     #[test]
     fn test_process_js5_wrong_revision() {
-        let handler = HandshakeHandler::new(530);
+        let handler = HandshakeHandler::default();
         let request = HandshakeRequest::Js5 { revision: 500 };
 
         let response = handler.process_js5(&request);
@@ -298,7 +299,7 @@ fn test_process_js5_wrong_revision() {
 
     #[test]
     fn test_process_login_success() {
-        let handler = HandshakeHandler::new(530);
+        let handler = HandshakeHandler::default();
         let request = HandshakeRequest::Login { revision: 530 };
 
         let (response, server_key) = handler.process_login(&request);
@@ -308,7 +309,7 @@ fn test_process_login_success() {
 
     #[test]
     fn test_process_login_wrong_revision() {
-        let handler = HandshakeHandler::new(530);
+        let handler = HandshakeHandler::default();
         let request = HandshakeRequest::Login { revision: 500 };
 
         let (response, server_key) = handler.process_login(&request);
@@ -322,7 +323,7 @@ fn test_process_login_wrong_revision() {
 
     #[test]
     fn test_validate_revision() {
-        let handler = HandshakeHandler::new(530);
+        let handler = HandshakeHandler::default();
         assert!(handler.validate_revision(530));
         assert!(!handler.validate_revision(500));
     }
@@ -16,6 +16,15 @@
 use crate::error::{CacheError, ProtocolError, Result, RustscapeError};
 use crate::net::buffer::PacketBuffer;
 
+/// Size of a JS5 response block on the wire
+pub const JS5_BLOCK_SIZE: usize = 512;
+
+/// Marker byte that starts every block after the first
+pub const JS5_BLOCK_MARKER: u8 = 0xFF;
+
+/// Size of the response header (index, archive, settings, length)
+pub const JS5_HEADER_SIZE: usize = 8;
+
 /// JS5 request opcodes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -137,6 +146,31 @@ pub fn new(
         }
     }
 
+    /// Build a response from raw cache container data
+    ///
+    /// The container starts with a 5-byte header (compression type and length),
+    /// which is moved into the response header; the remainder is the file data.
+    pub fn from_container(index: u8, archive: u16, data: &[u8], priority: bool) -> Result<Self> {
+        if data.len() < 5 {
+            return Err(RustscapeError::Cache(CacheError::Corrupted(format!(
+                "File data too short for index {} archive {}",
+                index, archive
+            ))));
+        }
+
+        let compression = data[0];
+        let length = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+
+        Ok(Self::new(
+            index,
+            archive,
+            compression,
+            length,
+            data[5..].to_vec(),
+            priority,
+        ))
+    }
+
     /// Encode the response to bytes for sending to the client
     ///
     /// The response format is:
@@ -146,61 +180,75 @@ pub fn new(
     /// - length (4 bytes, big-endian)
     /// - data (with 0xFF markers every 512 bytes)
     pub fn encode(&self) -> Vec<u8> {
-        let mut buffer = PacketBuffer::with_capacity(self.data.len() + 16);
-
-        // Header
-        buffer.write_ubyte(self.index);
-        buffer.write_ushort(self.archive);
+        self.encode_with_key(0)
+    }
 
-        // Settings byte: compression type (bits 0-6) | priority flag (bit 7)
+    /// Encode the response, XORing every byte with the JS5 encryption key
+    ///
+    /// The client XORs everything it reads from the JS5 stream once a key has
+    /// been set, so the header and the 0xFF block markers are encrypted too.
+    pub fn encode_with_key(&self, key: u8) -> Vec<u8> {
         let settings = self.compression | if self.priority { 0x80 } else { 0x00 };
-        buffer.write_ubyte(settings);
-
-        // Length
-        buffer.write_uint(self.length);
-
-        // Data with block markers
-        // The client expects a 0xFF marker after every 512 bytes of payload
-        let mut offset = 8; // We've already written 8 bytes (header)
-        for &byte in &self.data {
-            if offset == 512 {
-                buffer.write_ubyte(0xFF);
-                offset = 1;
-            }
-            buffer.write_ubyte(byte);
-            offset += 1;
-        }
-
-        buffer.as_bytes().to_vec()
+        self.encode_blocks(self.index, self.archive, settings, self.length, key)
     }
 
     /// Encode for checksum table response (different format)
     pub fn encode_checksum_table(&self) -> Vec<u8> {
-        let mut buffer = PacketBuffer::with_capacity(self.data.len() + 16);
-
-        // Header for checksum table
-        buffer.write_ubyte(255);
-        buffer.write_ushort(255);
+        self.encode_checksum_table_with_key(0)
+    }
 
-        // Settings byte (no compression for checksum table)
+    /// Encode a checksum table response, XORing every byte with the JS5 encryption key
+    pub fn encode_checksum_table_with_key(&self, key: u8) -> Vec<u8> {
+        // No compression for checksum table, length is the raw data length
         let settings = if self.priority { 0x80 } else { 0x00 };
-        buffer.write_ubyte(settings);
+        self.encode_blocks(255, 255, settings, self.data.len() as u32, key)
+    }
+
+    /// Write the header and data split into 512-byte blocks, then apply the key
+    fn encode_blocks(
+        &self,
+        index: u8,
+        archive: u16,
+        settings: u8,
+        length: u32,
+        key: u8,
+    ) -> Vec<u8> {
+        let mut buffer = PacketBuffer::with_capacity(
+            JS5_HEADER_SIZE + self.data.len() + self.data.len() / (JS5_BLOCK_SIZE - 1) + 1,
+        );
 
-        // Length of data
-        buffer.write_uint(self.data.len() as u32);
+        // Header
+        buffer.write_ubyte(index);
+        buffer.write_ushort(archive);
+        buffer.write_ubyte(settings);
+        buffer.write_uint(length);
 
-        // Data with block markers (starting at offset 10 for checksum table)
-        let mut offset = 10;
+        // Data with block markers
+        // The client expects a 0xFF marker after every 512 bytes of the stream
+        let mut offset = JS5_HEADER_SIZE;
         for &byte in &self.data {
-            if offset == 512 {
-                buffer.write_ubyte(0xFF);
+            if offset == JS5_BLOCK_SIZE {
+                buffer.write_ubyte(JS5_BLOCK_MARKER);
                 offset = 1;
             }
             buffer.write_ubyte(byte);
             offset += 1;
         }
 
-        buffer.as_bytes().to_vec()
+        let mut encoded = buffer.as_bytes().to_vec();
+        xor_encrypt(&mut encoded, key);
+        encoded
+    }
+}
+
+/// Apply the JS5 XOR encryption key to a buffer in place
+///
+/// A key of 0 leaves the data untouched.
+pub fn xor_encrypt(data: &mut [u8], key: u8) {
+    if key != 0 {
+        for byte in data.iter_mut() {
+            *byte ^= key;
+        }
     }
 }
 
@@ -354,13 +402,7 @@ fn handle_file_request(&mut self, data: &[u8], priority: bool) -> Result<Option<
 
         // Process the request immediately and return the response
         let response = self.get_file_response(&request)?;
-        let encoded = if request.is_checksum_table() {
-            response.encode_checksum_table()
-        } else {
-            response.encode()
-        };
-
-        Ok(Some(encoded))
+        Ok(Some(self.encode_response(&request, &response)))
     }
 
     /// Get a file response for a request
@@ -389,51 +431,23 @@ fn get_file_response(&self, request: &Js5FileRequest) -> Result<Js5FileResponse>
 
     /// Build a file response from raw cache data
     fn build_response(&self, request: &Js5FileRequest, data: &[u8]) -> Result<Js5FileResponse> {
-        if data.len() < 5 {
-            return Err(RustscapeError::Cache(CacheError::Corrupted(format!(
-                "File data too short for index {} archive {}",
-                request.index, request.archive
-            ))));
-        }
-
-        // Parse compression header from cache data
-        let compression = data[0];
-        let length = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
-
-        // The rest is the actual file data (possibly compressed)
-        let file_data = if data.len() > 5 {
-            data[5..].to_vec()
-        } else {
-            Vec::new()
-        };
+        Js5FileResponse::from_container(request.index, request.archive, data, request.priority)
+    }
 
-        // Apply encryption if set
-        let file_data = if self.encryption_key != 0 {
-            file_data.iter().map(|&b| b ^ self.encryption_key).collect()
+    /// Encode a response with the current encryption key
+    fn encode_response(&self, request: &Js5FileRequest, response: &Js5FileResponse) -> Vec<u8> {
+        if request.is_checksum_table() {
+            response.encode_checksum_table_with_key(self.encryption_key)
         } else {
-            file_data
-        };
-
-        Ok(Js5FileResponse::new(
-            request.index,
-            request.archive,
-            compression,
-            length,
-            file_data,
-            request.priority,
-        ))
+            response.encode_with_key(self.encryption_key)
+        }
     }
 
     /// Process the next queued request
     pub fn process_queue(&mut self) -> Result<Option<Vec<u8>>> {
         if let Some(request) = self.queue.pop() {
             let response = self.get_file_response(&request)?;
-            let encoded = if request.is_checksum_table() {
-                response.encode_checksum_table()
-            } else {
-                response.encode()
-            };
-            Ok(Some(encoded))
+            Ok(Some(self.encode_response(&request, &response)))
         } else {
             Ok(None)
         }
@@ -583,4 +597,93 @@ fn test_js5_response_block_markers() {
             marker_pos
         );
     }
+
+    /// Decode a JS5 response the way the client does: XOR every byte read
+    /// from the stream with the key, then strip the 0xFF block markers.
+    fn client_decode(stream: &[u8], key: u8) -> (u8, u16, u8, u32, Vec<u8>) {
+        let bytes: Vec<u8> = stream.iter().map(|&b| b ^ key).collect();
+
+        let index = bytes[0];
+        let archive = u16::from_be_bytes([bytes[1], bytes[2]]);
+        let settings = bytes[3];
+        let length = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+
+        let mut data = Vec::new();
+        let mut block_offset = JS5_HEADER_SIZE;
+        for &byte in &bytes[JS5_HEADER_SIZE..] {
+            if block_offset == JS5_BLOCK_SIZE {
+                assert_eq!(byte, JS5_BLOCK_MARKER, "expected block marker");
+                block_offset = 1;
+                continue;
+            }
+            data.push(byte);
+            block_offset += 1;
+        }
+
+        (index, archive, settings, length, data)
+    }
+
+    #[test]
+    fn test_js5_encrypted_response_matches_client_decoder() {
+        let data: Vec<u8> = (0..2000).map(|i| (i * 7 % 256) as u8).collect();
+        let response = Js5FileResponse::new(7, 300, 2, data.len() as u32, data.clone(), true);
+
+        for key in [0u8, 0x5A, 0xFF] {
+            let encoded = response.encode_with_key(key);
+            let (index, archive, settings, length, decoded) = client_decode(&encoded, key);
+
+            assert_eq!(index, 7);
+            assert_eq!(archive, 300);
+            assert_eq!(settings, 0x82);
+            assert_eq!(length, 2000);
+            assert_eq!(decoded, data);
+        }
+    }
+
+    #[test]
+    fn test_js5_encryption_covers_header_and_markers() {
+        let data = vec![0u8; 600];
+        let response = Js5FileResponse::new(1, 1, 0, data.len() as u32, data, false);
+
+        let plain = response.encode();
+        let encrypted = response.encode_with_key(0x3C);
+
+        assert_eq!(plain.len(), encrypted.len());
+        assert_eq!(encrypted[0], 1 ^ 0x3C);
+        assert_eq!(encrypted[JS5_BLOCK_SIZE], JS5_BLOCK_MARKER ^ 0x3C);
+        assert!(plain.iter().zip(&encrypted).all(|(p, e)| p ^ 0x3C == *e));
+    }
+
+    #[test]
+    fn test_js5_checksum_table_matches_client_decoder() {
+        let data: Vec<u8> = (0..700).map(|i| (i % 256) as u8).collect();
+        let response = Js5FileResponse::new(255, 255, 0, 0, data.clone(), false);
+
+        let encoded = response.encode_checksum_table_with_key(0x11);
+        let (index, archive, settings, length, decoded) = client_decode(&encoded, 0x11);
+
+        assert_eq!((index, archive, settings), (255, 255, 0));
+        assert_eq!(length, 700);
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_js5_handler_applies_encryption_key() {
+        let path = std::env::temp_dir().join("rustscape_js5_test");
+        let cache = Arc::new(CacheStore::new(&path).unwrap());
+        let mut handler = Js5Handler::new(cache);
+
+        handler
+            .process(Js5Opcode::SetEncryption.as_u8(), &[0x42, 0, 0])
+            .unwrap();
+        assert_eq!(handler.encryption_key(), 0x42);
+
+        let encoded = handler
+            .process(Js5Opcode::FileRequestPriority.as_u8(), &[255, 0, 255])
+            .unwrap()
+            .unwrap();
+        let (index, archive, settings, _, _) = client_decode(&encoded, 0x42);
+
+        assert_eq!((index, archive, settings), (255, 255, 0x80));
+    }
 }
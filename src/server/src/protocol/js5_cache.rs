@@ -0,0 +1,373 @@
+//! Shared pre-encoded JS5 response cache
+//!
+//! Encoding a cache file into chunked JS5 frames is the same work for every
+//! session, and during startup spikes many web clients request the same
+//! archives at once. This cache keeps the encoded `Js5FileResponse` bytes in
+//! `bytes::Bytes` so every session can send them without copying:
+//! - Reference tables and the checksum table (index 255) are pinned permanently;
+//!   requests for reference tables of indices the cache doesn't have are
+//!   refused so they can't grow the pinned set
+//! - All other archives are held in an LRU bounded by total size in bytes
+//!
+//! Sessions that set a JS5 encryption key get a copy with the key XORed in,
+//! applied as a single pass over the cached frames.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::cache::CacheStore;
+use crate::error::{CacheError, Result};
+use crate::protocol::js5::{xor_encrypt, Js5FileRequest, Js5FileResponse};
+
+/// Cache key for an encoded response
+///
+/// The priority flag is part of the encoded settings byte, so priority and
+/// normal responses for the same archive are cached separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ResponseKey {
+    index: u8,
+    archive: u16,
+    priority: bool,
+}
+
+impl From<&Js5FileRequest> for ResponseKey {
+    fn from(request: &Js5FileRequest) -> Self {
+        Self {
+            index: request.index,
+            archive: request.archive,
+            priority: request.priority,
+        }
+    }
+}
+
+/// Size-bounded least-recently-used map of encoded responses
+#[derive(Debug, Default)]
+struct LruResponses {
+    /// Encoded responses and the tick they were last used
+    entries: HashMap<ResponseKey, (Bytes, u64)>,
+    /// Usage order (tick -> key), oldest first
+    order: BTreeMap<u64, ResponseKey>,
+    /// Monotonic usage counter
+    tick: u64,
+    /// Total size of cached responses in bytes
+    size: usize,
+}
+
+impl LruResponses {
+    /// Look up a response and mark it as recently used
+    fn get(&mut self, key: &ResponseKey) -> Option<Bytes> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (data, last_used) = self.entries.get_mut(key)?;
+        self.order.remove(last_used);
+        self.order.insert(tick, *key);
+        *last_used = tick;
+        Some(data.clone())
+    }
+
+    /// Insert a response, evicting the least recently used ones beyond `capacity`
+    fn insert(&mut self, key: ResponseKey, data: Bytes, capacity: usize) {
+        if data.len() > capacity {
+            return;
+        }
+
+        if let Some((old, last_used)) = self.entries.remove(&key) {
+            self.order.remove(&last_used);
+            self.size -= old.len();
+        }
+
+        while self.size + data.len() > capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some((evicted, _)) = self.entries.remove(&oldest) {
+                self.size -= evicted.len();
+            }
+        }
+
+        self.tick += 1;
+        self.size += data.len();
+        self.order.insert(self.tick, key);
+        self.entries.insert(key, (data, self.tick));
+    }
+}
+
+/// Response cache statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Js5ResponseCacheStats {
+    /// Number of pinned responses (reference tables and checksum table)
+    pub pinned: usize,
+    /// Number of responses in the LRU
+    pub cached: usize,
+    /// Total size of the LRU in bytes
+    pub cached_bytes: usize,
+    /// Requests answered from the cache
+    pub hits: u64,
+    /// Requests that had to be encoded
+    pub misses: u64,
+}
+
+/// Process-wide cache of encoded JS5 responses
+pub struct Js5ResponseCache {
+    /// Cache store the responses are encoded from
+    cache: Arc<CacheStore>,
+    /// Pinned responses for index 255
+    pinned: DashMap<ResponseKey, Bytes>,
+    /// Everything else
+    lru: Mutex<LruResponses>,
+    /// Maximum LRU size in bytes
+    capacity_bytes: usize,
+    /// Cache hits
+    hits: AtomicU64,
+    /// Cache misses
+    misses: AtomicU64,
+}
+
+impl Js5ResponseCache {
+    /// Create a new response cache with the given LRU capacity in bytes
+    pub fn new(cache: Arc<CacheStore>, capacity_bytes: usize) -> Self {
+        Self {
+            cache,
+            pinned: DashMap::new(),
+            lru: Mutex::new(LruResponses::default()),
+            capacity_bytes,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Get the encoded response for a request
+    pub fn get(&self, request: &Js5FileRequest) -> Result<Bytes> {
+        let key = ResponseKey::from(request);
+
+        if request.is_reference_table() {
+            if !request.is_checksum_table()
+                && request.archive as usize >= self.cache.index_count().min(255)
+            {
+                return Err(CacheError::ArchiveNotFound {
+                    index: request.index,
+                    archive: request.archive,
+                }
+                .into());
+            }
+
+            if let Some(data) = self.pinned.get(&key) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(data.clone());
+            }
+
+            let data = self.encode(request)?;
+            self.pinned.insert(key, data.clone());
+            return Ok(data);
+        }
+
+        if let Some(data) = self.lru.lock().get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(data);
+        }
+
+        // Encode outside the lock; concurrent misses for the same archive
+        // produce identical bytes, so the last insert simply wins
+        let data = self.encode(request)?;
+        self.lru
+            .lock()
+            .insert(key, data.clone(), self.capacity_bytes);
+        Ok(data)
+    }
+
+    /// Get the encoded response for a request with a session's JS5 encryption key applied
+    ///
+    /// With no key set the shared bytes are returned as-is.
+    pub fn get_encrypted(&self, request: &Js5FileRequest, key: u8) -> Result<Bytes> {
+        let data = self.get(request)?;
+        if key == 0 {
+            return Ok(data);
+        }
+
+        let mut encrypted = BytesMut::from(&data[..]);
+        xor_encrypt(&mut encrypted, key);
+        Ok(encrypted.freeze())
+    }
+
+    /// Encode and pin the checksum table and every reference table
+    pub fn warm(&self) -> Result<()> {
+        for priority in [true, false] {
+            self.get(&Js5FileRequest::new(255, 255, priority))?;
+            for index in 0..self.cache.index_count().min(255) {
+                self.get(&Js5FileRequest::new(255, index as u16, priority))?;
+            }
+        }
+
+        info!(pinned = self.pinned.len(), "Pinned JS5 reference tables");
+        Ok(())
+    }
+
+    /// Get cache statistics
+    pub fn stats(&self) -> Js5ResponseCacheStats {
+        let lru = self.lru.lock();
+        Js5ResponseCacheStats {
+            pinned: self.pinned.len(),
+            cached: lru.entries.len(),
+            cached_bytes: lru.size,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Encode a response from the cache store
+    fn encode(&self, request: &Js5FileRequest) -> Result<Bytes> {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let encoded = if request.is_checksum_table() {
+            let data = self.cache.get_checksum_table()?;
+            Js5FileResponse::new(255, 255, 0, data.len() as u32, data, request.priority)
+                .encode_checksum_table()
+        } else if request.is_reference_table() {
+            // `get` only lets archives below the index count through
+            let data = self.cache.get_reference_table(request.archive as u8)?;
+            Js5FileResponse::from_container(255, request.archive, &data, request.priority)?.encode()
+        } else {
+            let data = self.cache.get_file(request.index, request.archive as u32)?;
+            Js5FileResponse::from_container(
+                request.index,
+                request.archive,
+                &data,
+                request.priority,
+            )?
+            .encode()
+        };
+
+        debug!(
+            index = request.index,
+            archive = request.archive,
+            size = encoded.len(),
+            "Encoded JS5 response"
+        );
+
+        Ok(Bytes::from(encoded))
+    }
+}
+
+impl std::fmt::Debug for Js5ResponseCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Js5ResponseCache")
+            .field("capacity_bytes", &self.capacity_bytes)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    fn test_cache(capacity_bytes: usize) -> Js5ResponseCache {
+        let path = temp_dir().join("rustscape_js5_cache_test");
+        Js5ResponseCache::new(Arc::new(CacheStore::new(&path).unwrap()), capacity_bytes)
+    }
+
+    fn key(archive: u16) -> ResponseKey {
+        ResponseKey {
+            index: 1,
+            archive,
+            priority: false,
+        }
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut lru = LruResponses::default();
+        lru.insert(key(1), Bytes::from(vec![0u8; 40]), 100);
+        lru.insert(key(2), Bytes::from(vec![0u8; 40]), 100);
+
+        // Touch 1 so 2 becomes the oldest
+        assert!(lru.get(&key(1)).is_some());
+
+        lru.insert(key(3), Bytes::from(vec![0u8; 40]), 100);
+        assert!(lru.get(&key(1)).is_some());
+        assert!(lru.get(&key(2)).is_none());
+        assert!(lru.get(&key(3)).is_some());
+        assert_eq!(lru.size, 80);
+    }
+
+    #[test]
+    fn test_lru_skips_oversized_entries() {
+        let mut lru = LruResponses::default();
+        lru.insert(key(1), Bytes::from(vec![0u8; 200]), 100);
+        assert!(lru.entries.is_empty());
+        assert_eq!(lru.size, 0);
+    }
+
+    #[test]
+    fn test_response_cache_hits_share_bytes() {
+        let cache = test_cache(1024 * 1024);
+        let request = Js5FileRequest::new(2, 10, false);
+
+        let first = cache.get(&request).unwrap();
+        let second = cache.get(&request).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.as_ptr(), second.as_ptr());
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.cached, 1);
+    }
+
+    #[test]
+    fn test_reference_tables_are_pinned() {
+        let cache = test_cache(0);
+        cache.warm().unwrap();
+
+        let stats = cache.stats();
+        assert!(stats.pinned > 0);
+        assert_eq!(stats.cached, 0);
+
+        let misses = stats.misses;
+        cache.get(&Js5FileRequest::new(255, 255, true)).unwrap();
+        assert_eq!(cache.stats().misses, misses);
+    }
+
+    #[test]
+    fn test_unknown_reference_tables_are_refused() {
+        let cache = test_cache(0);
+        let index_count = cache.cache.index_count() as u16;
+
+        // 256 + 2 would read as reference table 2 if the archive were truncated
+        for archive in [index_count, 254, 256 + 2, u16::MAX] {
+            assert!(cache
+                .get(&Js5FileRequest::new(255, archive, false))
+                .is_err());
+        }
+        assert_eq!(cache.stats().pinned, 0);
+        assert_eq!(cache.stats().misses, 0);
+
+        assert!(cache.get(&Js5FileRequest::new(255, 2, false)).is_ok());
+        assert_eq!(cache.stats().pinned, 1);
+    }
+
+    #[test]
+    fn test_encrypted_matches_direct_encoding() {
+        let cache = test_cache(1024 * 1024);
+        let request = Js5FileRequest::new(255, 255, false);
+
+        let plain = cache.get(&request).unwrap();
+        let encrypted = cache.get_encrypted(&request, 0x5A).unwrap();
+
+        let data = cache.cache.get_checksum_table().unwrap();
+        let expected = Js5FileResponse::new(255, 255, 0, data.len() as u32, data, false)
+            .encode_checksum_table_with_key(0x5A);
+        assert_eq!(&encrypted[..], &expected[..]);
+
+        // The shared copy is left untouched
+        assert_eq!(cache.get(&request).unwrap(), plain);
+    }
+}
@@ -0,0 +1,178 @@
+//! Lobby login
+//!
+//! A lobby login (login type 19) authenticates the account without entering
+//! a world. The server answers with the lobby response block, followed by the
+//! world list, and the connection stays in the lobby so the client can refresh
+//! the world list until the player picks a world and logs into it directly.
+//!
+//! Lobby response layout:
+//! - Response code (2 = success)
+//! - Payload size (byte)
+//! - Rights, member flag
+//! - Membership days remaining (short)
+//! - Days since last login (short, `NEVER_LOGGED_IN` for a first login)
+//! - Last login IPv4 address (int, 0 if unknown)
+//! - Unread messages (short)
+//! - Display name (string)
+
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+
+use crate::auth::{Account, LoginActivity};
+use crate::error::LoginResponse;
+use crate::net::buffer::PacketBuffer;
+
+/// Days since last login sent for an account's first login
+pub const NEVER_LOGGED_IN: u16 = u16::MAX;
+
+/// Lobby response block sent after a successful lobby login
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LobbyResponse {
+    /// Player rights (0=normal, 1=mod, 2=admin)
+    pub rights: u8,
+    /// Member status
+    pub member: bool,
+    /// Days of membership remaining
+    pub membership_days: u16,
+    /// Days since the previous login (`NEVER_LOGGED_IN` if none)
+    pub last_login_days: u16,
+    /// Previous login address as an IPv4 integer (0 if unknown)
+    pub last_ip: u32,
+    /// Unread messages
+    pub unread_messages: u16,
+    /// Display name
+    pub display_name: String,
+}
+
+impl LobbyResponse {
+    /// Build the response for an account as of `now`
+    pub fn new(account: &Account, activity: &LoginActivity, now: DateTime<Utc>) -> Self {
+        let membership_days = activity
+            .membership_expires
+            .map(|expires| days_between(now, expires))
+            .unwrap_or(0);
+        let last_login_days = activity
+            .last_login
+            .map(|last| days_between(last, now))
+            .unwrap_or(NEVER_LOGGED_IN);
+        let last_ip = match activity.last_ip {
+            Some(IpAddr::V4(ip)) => u32::from(ip),
+            _ => 0,
+        };
+
+        Self {
+            rights: account.rights,
+            member: account.member,
+            membership_days,
+            last_login_days,
+            last_ip,
+            unread_messages: activity.unread_messages,
+            display_name: account.username.clone(),
+        }
+    }
+
+    /// Encode the response to bytes
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = PacketBuffer::with_capacity(16 + self.display_name.len());
+        payload.write_ubyte(self.rights);
+        payload.write_ubyte(if self.member { 1 } else { 0 });
+        payload.write_ushort(self.membership_days);
+        payload.write_ushort(self.last_login_days);
+        payload.write_uint(self.last_ip);
+        payload.write_ushort(self.unread_messages);
+        payload.write_string(&self.display_name);
+
+        let mut buffer = PacketBuffer::with_capacity(payload.len() + 2);
+        buffer.write_ubyte(LoginResponse::Success.as_u8());
+        buffer.write_ubyte(payload.len() as u8);
+        buffer.write_bytes(payload.as_bytes());
+        buffer.as_bytes().to_vec()
+    }
+
+    /// Decode a response payload (after the response code and size)
+    pub fn decode(payload: &[u8]) -> Self {
+        let mut buffer = PacketBuffer::from_bytes(payload);
+        Self {
+            rights: buffer.read_ubyte(),
+            member: buffer.read_ubyte() == 1,
+            membership_days: buffer.read_ushort(),
+            last_login_days: buffer.read_ushort(),
+            last_ip: buffer.read_int() as u32,
+            unread_messages: buffer.read_ushort(),
+            display_name: buffer.read_string(),
+        }
+    }
+}
+
+/// Whole days from `from` to `to`, clamped to the range of a short
+fn days_between(from: DateTime<Utc>, to: DateTime<Utc>) -> u16 {
+    (to - from).num_days().clamp(0, u16::MAX as i64 - 1) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_first_login() {
+        let account = Account::dev_account(1, "zezima");
+        let response = LobbyResponse::new(&account, &LoginActivity::default(), now());
+
+        assert_eq!(response.last_login_days, NEVER_LOGGED_IN);
+        assert_eq!(response.last_ip, 0);
+        assert_eq!(response.membership_days, 0);
+        assert_eq!(response.display_name, "zezima");
+    }
+
+    #[test]
+    fn test_previous_login_and_membership() {
+        let mut account = Account::dev_account(1, "zezima");
+        account.rights = 0;
+        let activity = LoginActivity {
+            last_login: Some(now() - Duration::hours(50)),
+            last_ip: Some("10.1.2.3".parse().unwrap()),
+            unread_messages: 3,
+            membership_expires: Some(now() + Duration::days(30)),
+        };
+        let response = LobbyResponse::new(&account, &activity, now());
+
+        assert_eq!(response.last_login_days, 2);
+        assert_eq!(response.last_ip, 0x0A01_0203);
+        assert_eq!(response.unread_messages, 3);
+        assert_eq!(response.membership_days, 30);
+
+        // Lapsed membership and IPv6 addresses are sent as 0
+        let activity = LoginActivity {
+            last_ip: Some("::1".parse().unwrap()),
+            membership_expires: Some(now() - Duration::days(3)),
+            ..activity
+        };
+        let response = LobbyResponse::new(&account, &activity, now());
+        assert_eq!(response.last_ip, 0);
+        assert_eq!(response.membership_days, 0);
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let response = LobbyResponse {
+            rights: 1,
+            member: true,
+            membership_days: 14,
+            last_login_days: 0,
+            last_ip: 0x7F00_0001,
+            unread_messages: 2,
+            display_name: "Zezima".to_string(),
+        };
+        let data = response.encode();
+
+        assert_eq!(data[0], LoginResponse::Success.as_u8());
+        assert_eq!(data[1] as usize, data.len() - 2);
+        assert_eq!(LobbyResponse::decode(&data[2..]), response);
+    }
+}
@@ -1,10 +1,10 @@
 //! Login protocol handler
 //!
 //! Handles the login process after the initial handshake:
-//! 1. Client sends login type (normal or reconnection)
+//! 1. Client sends login type (normal, reconnection or lobby)
 //! 2. Client sends encrypted login block containing:
 //!    - ISAAC seeds
-//!    - Credentials (username/password)
+//!    - Credentials (username/password, authenticator code if 2FA is on)
 //!    - Client information
 //! 3. Server validates credentials and responds with success/failure
 //! 4. On success, server sets up ISAAC cipher and player session
@@ -26,6 +26,8 @@ pub enum LoginType {
     Normal = 16,
     /// Reconnection (client already has cached data)
     Reconnect = 18,
+    /// Lobby login (authenticate before choosing a world)
+    Lobby = 19,
 }
 
 impl LoginType {
@@ -34,6 +36,7 @@ pub fn from_u8(value: u8) -> Option<Self> {
         match value {
             16 => Some(Self::Normal),
             18 => Some(Self::Reconnect),
+            19 => Some(Self::Lobby),
             _ => None,
         }
     }
@@ -59,6 +62,8 @@ pub struct LoginBlock {
     pub username: String,
     /// Password
     pub password: String,
+    /// Authenticator code (accounts with two-factor authentication)
+    pub authenticator: Option<u32>,
     /// Client information
     pub client_info: ClientInfo,
     /// UID (unique client identifier)
@@ -75,6 +80,7 @@ pub fn new(login_type: LoginType) -> Self {
             isaac_seeds: [0; 4],
             username: String::new(),
             password: String::new(),
+            authenticator: None,
             client_info: ClientInfo::default(),
             uid: 0,
         }
@@ -260,6 +266,11 @@ fn parse_rsa_block(&self, block: &mut LoginBlock, data: &[u8]) -> Result<()> {
         block.username = buffer.read_string();
         block.password = buffer.read_string();
 
+        // Read authenticator code, if present
+        if buffer.remaining() >= 4 {
+            block.authenticator = Some(buffer.read_int() as u32);
+        }
+
         debug!(
             username = %block.username,
             "Parsed login credentials"
@@ -427,6 +438,7 @@ mod tests {
     fn test_login_type_from_u8() {
         assert_eq!(LoginType::from_u8(16), Some(LoginType::Normal));
         assert_eq!(LoginType::from_u8(18), Some(LoginType::Reconnect));
+        assert_eq!(LoginType::from_u8(19), Some(LoginType::Lobby));
         assert_eq!(LoginType::from_u8(0), None);
     }
 
@@ -434,6 +446,7 @@ fn test_login_type_from_u8() {
     fn test_login_type_as_u8() {
         assert_eq!(LoginType::Normal.as_u8(), 16);
         assert_eq!(LoginType::Reconnect.as_u8(), 18);
+        assert_eq!(LoginType::Lobby.as_u8(), 19);
     }
 
     #[test]
@@ -548,6 +561,7 @@ fn test_login_block_creation() {
         assert_eq!(block.login_type, LoginType::Normal);
         assert_eq!(block.revision, 0);
         assert!(block.username.is_empty());
+        assert!(block.authenticator.is_none());
     }
 
     #[test]
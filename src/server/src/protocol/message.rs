@@ -0,0 +1,169 @@
+//! Server -> client messages
+//!
+//! Game logic describes what the client should be told as a `ServerMessage`;
+//! the session's `Protocol` turns it into the packet for the client's
+//! revision (see `protocol::revision`). Messages carry plain data, so they
+//! can be built while a container lock is held and encoded later.
+
+use crate::game::bank::{Bank, BankItemInfo};
+use crate::game::equipment::Equipment;
+use crate::game::inventory::Inventory;
+use crate::game::player::Location;
+use crate::protocol::game::{CONTAINER_EQUIPMENT, CONTAINER_INVENTORY};
+
+/// A message from the server to one client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerMessage {
+    /// Line of text in the chat box
+    SystemMessage(String),
+    /// Log the client out
+    Logout,
+    /// Token the client presents to resume after a dropped connection
+    ResumeToken(String),
+    /// Load the map region around a location
+    MapRegion(Location),
+    /// Skill level and experience
+    Skill {
+        skill_id: u8,
+        level: u8,
+        experience: i32,
+    },
+    /// Run energy percentage
+    RunEnergy(u8),
+    /// Right-click option on other players
+    PlayerOption {
+        slot: u8,
+        text: String,
+        priority: bool,
+    },
+    /// A single item container slot (item 0 clears the slot)
+    ContainerSlot {
+        container: u16,
+        slot: u16,
+        item_id: u16,
+        amount: u32,
+    },
+    /// Every slot of an item container, as (slot, item ID, amount)
+    Container {
+        container: u16,
+        items: Vec<(u16, u16, u32)>,
+    },
+    /// Open the bank interface
+    BankOpen { capacity: u16 },
+    /// Bank withdraw settings
+    BankSettings {
+        note_mode: bool,
+        placeholders: bool,
+        withdraw_x_amount: u32,
+    },
+    /// Number of items in each bank tab
+    BankTabs(Vec<u16>),
+    /// Every occupied bank slot
+    BankContents(Vec<BankItemInfo>),
+    /// A single bank slot (item 0 clears the slot)
+    BankSlot {
+        tab: u8,
+        slot: u16,
+        item_id: u16,
+        amount: u32,
+        placeholder: bool,
+    },
+    /// Item appeared on the ground
+    GroundItemSpawn {
+        item_id: u16,
+        amount: u32,
+        location: Location,
+    },
+    /// Item removed from the ground
+    GroundItemRemove { item_id: u16, location: Location },
+    /// Player synchronization body (bit section followed by update blocks)
+    PlayerUpdate(Vec<u8>),
+}
+
+impl ServerMessage {
+    /// A chat box message
+    pub fn system(message: impl Into<String>) -> Self {
+        Self::SystemMessage(message.into())
+    }
+
+    /// A single inventory slot
+    pub fn inventory_slot(slot: u16, item_id: u16, amount: u32) -> Self {
+        Self::ContainerSlot {
+            container: CONTAINER_INVENTORY,
+            slot,
+            item_id,
+            amount,
+        }
+    }
+
+    /// The whole inventory
+    pub fn inventory(inventory: &Inventory) -> Self {
+        Self::Container {
+            container: CONTAINER_INVENTORY,
+            items: inventory
+                .as_slice()
+                .iter()
+                .enumerate()
+                .map(|(slot, item)| (slot as u16, item.item_id, item.amount))
+                .collect(),
+        }
+    }
+
+    /// Every equipment slot
+    pub fn equipment(equipment: &Equipment) -> Self {
+        Self::Container {
+            container: CONTAINER_EQUIPMENT,
+            items: equipment
+                .as_slice()
+                .iter()
+                .enumerate()
+                .map(|(slot, item)| (slot as u16, item.item_id, item.amount))
+                .collect(),
+        }
+    }
+
+    /// A bank's withdraw settings
+    pub fn bank_settings(bank: &Bank) -> Self {
+        Self::BankSettings {
+            note_mode: bank.withdraw_as_note,
+            placeholders: bank.placeholders_enabled,
+            withdraw_x_amount: bank.withdraw_x_amount,
+        }
+    }
+
+    /// A bank's tab sizes
+    pub fn bank_tabs(bank: &Bank) -> Self {
+        Self::BankTabs(bank.tabs.iter().map(|tab| tab.items.len() as u16).collect())
+    }
+
+    /// A bank's contents
+    pub fn bank_contents(bank: &Bank) -> Self {
+        Self::BankContents(bank.get_all_items())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_snapshots() {
+        let mut inventory = Inventory::new();
+        inventory.add(995, 100, true).unwrap();
+
+        match ServerMessage::inventory(&inventory) {
+            ServerMessage::Container { container, items } => {
+                assert_eq!(container, CONTAINER_INVENTORY);
+                assert_eq!(items.len(), inventory.as_slice().len());
+                assert_eq!(items[0], (0, 995, 100));
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        let bank = Bank::new();
+        assert_eq!(
+            ServerMessage::bank_tabs(&bank),
+            ServerMessage::BankTabs(vec![0; bank.tabs.len()])
+        );
+    }
+}
@@ -4,11 +4,19 @@
 //! - Handshake protocol (initial connection negotiation)
 //! - JS5 protocol (cache file serving)
 //! - Login protocol (authentication and session setup)
+//! - Lobby login (account details before world selection)
 //! - Game protocol (in-game packet handling)
+//! - Incoming game packet registry (framing and dispatch)
+//! - Server messages and the per-revision protocols that encode them
 
 pub mod game;
 pub mod handshake;
 pub mod js5;
+pub mod js5_cache;
+pub mod lobby;
 pub mod login;
 pub mod login_init;
+pub mod message;
 pub mod packets;
+pub mod registry;
+pub mod revision;
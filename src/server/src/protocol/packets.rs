@@ -3,9 +3,6 @@
 //! Defines packet headers, sizes, and common packet structures used
 //! throughout the Rustscape protocol.
 
-use std::collections::HashMap;
-use std::sync::OnceLock;
-
 use crate::net::buffer::PacketBuffer;
 
 /// Packet size type
@@ -367,6 +364,31 @@ fn decode(buffer: &mut PacketBuffer) -> Result<Self, PacketDecodeError> {
     }
 }
 
+/// Item unequip packet (opcode 42)
+#[derive(Debug, Clone)]
+pub struct ItemUnequipPacket {
+    pub slot: u16,
+    pub item_id: u16,
+}
+
+impl IncomingPacket for ItemUnequipPacket {
+    const OPCODE: u8 = 42;
+    const SIZE: PacketSize = PacketSize::Fixed(4);
+
+    fn decode(buffer: &mut PacketBuffer) -> Result<Self, PacketDecodeError> {
+        if buffer.remaining() < 4 {
+            return Err(PacketDecodeError::InsufficientData {
+                expected: 4,
+                actual: buffer.remaining(),
+            });
+        }
+        Ok(Self {
+            slot: buffer.read_ushort_le(),
+            item_id: buffer.read_ushort_le(),
+        })
+    }
+}
+
 /// Inventory swap packet (opcode 214 - same as bank move, distinguished by context)
 #[derive(Debug, Clone)]
 pub struct InventorySwapPacket {
@@ -556,6 +578,84 @@ fn decode(buffer: &mut PacketBuffer) -> Result<Self, PacketDecodeError> {
     }
 }
 
+/// Walk here packet (opcode 98, click on the game screen)
+#[derive(Debug, Clone)]
+pub struct WalkPacket {
+    pub dest_x: u16,
+    pub dest_y: u16,
+    pub running: bool,
+    /// Waypoint deltas after the first step
+    pub waypoints: Vec<(i8, i8)>,
+}
+
+impl IncomingPacket for WalkPacket {
+    const OPCODE: u8 = 98;
+    const SIZE: PacketSize = PacketSize::Fixed(8);
+
+    fn decode(buffer: &mut PacketBuffer) -> Result<Self, PacketDecodeError> {
+        if buffer.remaining() < 5 {
+            return Err(PacketDecodeError::InsufficientData {
+                expected: 5,
+                actual: buffer.remaining(),
+            });
+        }
+        let dest_x = buffer.read_ushort_le();
+        let dest_y = buffer.read_short_a();
+        let running = buffer.read_byte_s() == 1;
+
+        let mut waypoints = Vec::new();
+        while buffer.remaining() >= 2 {
+            let dx = buffer.read_byte();
+            let dy = buffer.read_byte();
+            waypoints.push((dx, dy));
+        }
+
+        Ok(Self {
+            dest_x,
+            dest_y,
+            running,
+            waypoints,
+        })
+    }
+}
+
+/// Minimap walk packet (opcode 14, same layout as walk here)
+#[derive(Debug, Clone)]
+pub struct MinimapWalkPacket(pub WalkPacket);
+
+impl IncomingPacket for MinimapWalkPacket {
+    const OPCODE: u8 = 14;
+    const SIZE: PacketSize = PacketSize::Fixed(8);
+
+    fn decode(buffer: &mut PacketBuffer) -> Result<Self, PacketDecodeError> {
+        WalkPacket::decode(buffer).map(Self)
+    }
+}
+
+/// Mouse click packet (opcode 86)
+#[derive(Debug, Clone)]
+pub struct MouseClickPacket {
+    /// Time since last click, right-click flag, x and y packed together
+    pub packed: i32,
+}
+
+impl IncomingPacket for MouseClickPacket {
+    const OPCODE: u8 = 86;
+    const SIZE: PacketSize = PacketSize::Fixed(4);
+
+    fn decode(buffer: &mut PacketBuffer) -> Result<Self, PacketDecodeError> {
+        if buffer.remaining() < 4 {
+            return Err(PacketDecodeError::InsufficientData {
+                expected: 4,
+                actual: buffer.remaining(),
+            });
+        }
+        Ok(Self {
+            packed: buffer.read_int(),
+        })
+    }
+}
+
 // ============ Common Outgoing Packets ============
 
 /// System message packet (opcode 253)
@@ -667,60 +767,16 @@ pub fn new(skill_id: u8, level: u8, experience: i32) -> Self {
 
 // ============ Packet Registry ============
 
-/// Static packet size registry for incoming packets
-static INCOMING_SIZES: OnceLock<HashMap<u8, PacketSize>> = OnceLock::new();
-
-/// Get the incoming packet sizes registry
-pub fn incoming_packet_sizes() -> &'static HashMap<u8, PacketSize> {
-    INCOMING_SIZES.get_or_init(|| {
-        let mut map = HashMap::new();
-
-        // Register known packet sizes
-        map.insert(0, PacketSize::Fixed(0)); // Keep-alive
-        map.insert(3, PacketSize::Fixed(1)); // Focus change
-        map.insert(4, PacketSize::VariableByte); // Chat
-        map.insert(14, PacketSize::Fixed(8)); // Walk to position
-        map.insert(17, PacketSize::Fixed(2)); // NPC examine
-        map.insert(21, PacketSize::Fixed(2)); // Item examine
-        map.insert(39, PacketSize::Fixed(6)); // Object action 1
-        map.insert(41, PacketSize::Fixed(4)); // Item equip
-        map.insert(43, PacketSize::Fixed(11)); // Bank withdraw
-        map.insert(52, PacketSize::VariableByte); // Command
-        map.insert(55, PacketSize::Fixed(1)); // Bank tab select
-        map.insert(77, PacketSize::Fixed(0)); // Map region loaded
-        map.insert(86, PacketSize::Fixed(4)); // Mouse click
-        map.insert(98, PacketSize::Fixed(8)); // Walk here
-        map.insert(115, PacketSize::Fixed(1)); // Bank withdraw mode
-        map.insert(116, PacketSize::VariableByte); // Bank search
-        map.insert(117, PacketSize::Fixed(1)); // Bank note mode
-        map.insert(121, PacketSize::VariableByte); // Mouse movement
-        map.insert(129, PacketSize::Fixed(0)); // Bank deposit all
-        map.insert(145, PacketSize::Fixed(4)); // Item drop
-        map.insert(150, PacketSize::Fixed(6)); // Item action 1
-        map.insert(164, PacketSize::Fixed(2)); // Button click
-        map.insert(185, PacketSize::Fixed(0)); // Bank close
-        map.insert(195, PacketSize::Fixed(0)); // Bank deposit equipment
-        map.insert(210, PacketSize::Fixed(0)); // Close interface
-        map.insert(214, PacketSize::Fixed(5)); // Bank move item
-        map.insert(233, PacketSize::Fixed(8)); // Bank deposit
-        map.insert(236, PacketSize::Fixed(6)); // Ground item action
-        map.insert(243, PacketSize::Fixed(4)); // Inventory swap
-
-        map
-    })
-}
-
-/// Get the packet size for an opcode
+/// Get the framing for an incoming packet opcode
+///
+/// Sizes come from the game packet registry (`game::incoming_packets`).
 pub fn get_packet_size(opcode: u8) -> PacketSize {
-    incoming_packet_sizes()
-        .get(&opcode)
-        .copied()
-        .unwrap_or(PacketSize::Unknown)
+    crate::protocol::game::incoming_packets().size(opcode)
 }
 
 /// Check if a packet opcode is known
 pub fn is_known_packet(opcode: u8) -> bool {
-    incoming_packet_sizes().contains_key(&opcode)
+    crate::protocol::game::incoming_packets().contains(opcode)
 }
 
 #[cfg(test)]
@@ -0,0 +1,313 @@
+//! Incoming game packet registry
+//!
+//! Every client -> server game packet is declared once, by registering the
+//! typed handler for its `IncomingPacket` struct. The struct supplies the
+//! opcode and framing, so the same declaration is used to:
+//! - Frame the inbound stream (`size`)
+//! - Decode the payload into the typed packet
+//! - Route it to the handler, with or without player context
+//!
+//! Opcodes that are not registered cannot be framed or dispatched and are
+//! reported as `ProtocolError::InvalidOpcode`.
+
+use std::sync::Arc;
+
+use tracing::{debug, trace};
+
+use crate::error::{ProtocolError, Result, RustscapeError};
+use crate::game::player::Player;
+use crate::protocol::game::{GamePacketHandler, IncomingGamePacket, PacketResult};
+use crate::protocol::packets::{IncomingPacket, PacketSize};
+
+/// Decodes a packet and runs its handler
+type Dispatch = Box<
+    dyn Fn(&GamePacketHandler, &IncomingGamePacket, Option<&Arc<Player>>) -> Result<PacketResult>
+        + Send
+        + Sync,
+>;
+
+/// A registered incoming packet
+pub struct PacketDefinition {
+    /// Packet opcode
+    pub opcode: u8,
+    /// Framing on the wire
+    pub size: PacketSize,
+    /// Name used in logs
+    pub name: &'static str,
+    dispatch: Dispatch,
+}
+
+impl std::fmt::Debug for PacketDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PacketDefinition")
+            .field("opcode", &self.opcode)
+            .field("size", &self.size)
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+/// Incoming game packets by opcode
+pub struct PacketRegistry {
+    definitions: Vec<Option<PacketDefinition>>,
+}
+
+impl PacketRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self {
+            definitions: (0..=u8::MAX).map(|_| None).collect(),
+        }
+    }
+
+    /// Register a packet handled without player context
+    pub fn on<P>(&mut self, handler: fn(&GamePacketHandler, P) -> Result<PacketResult>) -> &mut Self
+    where
+        P: IncomingPacket + 'static,
+    {
+        self.insert(
+            P::OPCODE,
+            P::SIZE,
+            packet_name::<P>(),
+            Box::new(move |handler_ctx, packet, _| handler(handler_ctx, decode::<P>(packet)?)),
+        )
+    }
+
+    /// Register a packet that acts on the player who sent it
+    ///
+    /// Dispatched without a player, the packet is decoded and then ignored.
+    pub fn on_player<P>(
+        &mut self,
+        handler: fn(&GamePacketHandler, P, &Arc<Player>) -> Result<PacketResult>,
+    ) -> &mut Self
+    where
+        P: IncomingPacket + 'static,
+    {
+        self.insert(
+            P::OPCODE,
+            P::SIZE,
+            packet_name::<P>(),
+            Box::new(move |handler_ctx, packet, player| {
+                let decoded = decode::<P>(packet)?;
+                match player {
+                    Some(player) => handler(handler_ctx, decoded, player),
+                    None => {
+                        debug!(
+                            opcode = P::OPCODE,
+                            packet = packet_name::<P>(),
+                            "Packet requires player context"
+                        );
+                        Ok(PacketResult::empty())
+                    }
+                }
+            }),
+        )
+    }
+
+    /// Register a packet whose framing is known but which is not handled yet
+    ///
+    /// The packet is read off the wire and dropped.
+    pub fn unhandled(&mut self, opcode: u8, size: PacketSize, name: &'static str) -> &mut Self {
+        self.insert(
+            opcode,
+            size,
+            name,
+            Box::new(move |_, _, _| {
+                debug!(opcode = opcode, packet = name, "Unimplemented game packet");
+                Ok(PacketResult::empty())
+            }),
+        )
+    }
+
+    fn insert(
+        &mut self,
+        opcode: u8,
+        size: PacketSize,
+        name: &'static str,
+        dispatch: Dispatch,
+    ) -> &mut Self {
+        assert!(
+            size != PacketSize::Unknown,
+            "{} (opcode {}) registered without a size",
+            name,
+            opcode
+        );
+
+        let slot = &mut self.definitions[opcode as usize];
+        if let Some(existing) = slot {
+            panic!(
+                "opcode {} registered twice ({} and {})",
+                opcode, existing.name, name
+            );
+        }
+
+        *slot = Some(PacketDefinition {
+            opcode,
+            size,
+            name,
+            dispatch,
+        });
+        self
+    }
+
+    /// Get the definition for an opcode
+    pub fn get(&self, opcode: u8) -> Option<&PacketDefinition> {
+        self.definitions[opcode as usize].as_ref()
+    }
+
+    /// Get the framing for an opcode (`Unknown` if unregistered)
+    pub fn size(&self, opcode: u8) -> PacketSize {
+        self.get(opcode)
+            .map(|definition| definition.size)
+            .unwrap_or(PacketSize::Unknown)
+    }
+
+    /// Check if an opcode is registered
+    pub fn contains(&self, opcode: u8) -> bool {
+        self.get(opcode).is_some()
+    }
+
+    /// Iterate over the registered packets in opcode order
+    pub fn iter(&self) -> impl Iterator<Item = &PacketDefinition> {
+        self.definitions.iter().flatten()
+    }
+
+    /// Decode a packet and route it to its handler
+    pub fn dispatch(
+        &self,
+        handler: &GamePacketHandler,
+        packet: &IncomingGamePacket,
+        player: Option<&Arc<Player>>,
+    ) -> Result<PacketResult> {
+        let definition = self.get(packet.opcode).ok_or(RustscapeError::Protocol(
+            ProtocolError::InvalidOpcode(packet.opcode),
+        ))?;
+
+        trace!(
+            opcode = packet.opcode,
+            packet = definition.name,
+            size = packet.data.len(),
+            "Dispatching game packet"
+        );
+
+        (definition.dispatch)(handler, packet, player)
+    }
+}
+
+impl Default for PacketRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decode a packet's payload into its typed form
+fn decode<P: IncomingPacket>(packet: &IncomingGamePacket) -> Result<P> {
+    P::decode(&mut packet.buffer()).map_err(|e| {
+        RustscapeError::Protocol(ProtocolError::MalformedPacket(format!(
+            "{} (opcode {}): {}",
+            packet_name::<P>(),
+            packet.opcode,
+            e
+        )))
+    })
+}
+
+/// Short type name of a packet struct, e.g. `ChatPacket`
+fn packet_name<P>() -> &'static str {
+    let name = std::any::type_name::<P>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::packets::{ButtonClickPacket, FocusChangePacket};
+
+    fn focus(_: &GamePacketHandler, packet: FocusChangePacket) -> Result<PacketResult> {
+        Ok(PacketResult::with_chat(packet.focused.to_string()))
+    }
+
+    fn button(
+        _: &GamePacketHandler,
+        packet: ButtonClickPacket,
+        player: &Arc<Player>,
+    ) -> Result<PacketResult> {
+        Ok(PacketResult::with_chat(format!(
+            "{} {}",
+            player.username(),
+            packet.button_id
+        )))
+    }
+
+    fn registry() -> PacketRegistry {
+        let mut registry = PacketRegistry::new();
+        registry
+            .on(focus)
+            .on_player(button)
+            .unhandled(17, PacketSize::Fixed(2), "NpcExamine");
+        registry
+    }
+
+    #[test]
+    fn test_declaration_gives_framing() {
+        let registry = registry();
+        assert_eq!(registry.size(3), PacketSize::Fixed(1));
+        assert_eq!(registry.size(164), PacketSize::Fixed(2));
+        assert_eq!(registry.size(17), PacketSize::Fixed(2));
+        assert_eq!(registry.size(4), PacketSize::Unknown);
+        assert_eq!(registry.get(3).unwrap().name, "FocusChangePacket");
+        assert_eq!(
+            registry.iter().map(|d| d.opcode).collect::<Vec<_>>(),
+            vec![3, 17, 164]
+        );
+    }
+
+    #[test]
+    fn test_dispatch_decodes_typed_packet() {
+        let registry = registry();
+        let handler = GamePacketHandler::new();
+        let player = Arc::new(Player::new(1, 1, "alice".to_string()));
+
+        let result = registry
+            .dispatch(&handler, &IncomingGamePacket::new(3, vec![1]), None)
+            .unwrap();
+        assert_eq!(result.chat_message.as_deref(), Some("true"));
+
+        let result = registry
+            .dispatch(
+                &handler,
+                &IncomingGamePacket::new(164, vec![0x04, 0xD2]),
+                Some(&player),
+            )
+            .unwrap();
+        assert_eq!(result.chat_message.as_deref(), Some("alice 1234"));
+
+        // Player packets are decoded but not handled without a player
+        let result = registry
+            .dispatch(&handler, &IncomingGamePacket::new(164, vec![0, 1]), None)
+            .unwrap();
+        assert!(result.chat_message.is_none());
+    }
+
+    #[test]
+    fn test_dispatch_reports_unregistered_and_malformed() {
+        let registry = registry();
+        let handler = GamePacketHandler::new();
+
+        assert!(matches!(
+            registry.dispatch(&handler, &IncomingGamePacket::new(255, vec![]), None),
+            Err(RustscapeError::Protocol(ProtocolError::InvalidOpcode(255)))
+        ));
+        assert!(matches!(
+            registry.dispatch(&handler, &IncomingGamePacket::new(3, vec![]), None),
+            Err(RustscapeError::Protocol(ProtocolError::MalformedPacket(_)))
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "registered twice")]
+    fn test_duplicate_opcode_panics() {
+        let mut registry = registry();
+        registry.unhandled(3, PacketSize::Fixed(1), "Duplicate");
+    }
+}
@@ -0,0 +1,318 @@
+//! Client revisions
+//!
+//! Everything that differs between client revisions sits behind the
+//! `Protocol` trait, so game logic is written once:
+//! - Inbound framing, and decoding of a revision's packets into the
+//!   server's own packet set (`game::incoming_packets`)
+//! - Encoding of `ServerMessage`s into the revision's packets
+//! - The pre-ISAAC login initialization sequence
+//!
+//! A connection's protocol is chosen at the JS5 / login handshake from the
+//! revision the client sends, out of the server's `Protocols`. Revisions
+//! that share the handshake and login block layout can be added by
+//! registering another implementation; revision 530 is the only one built in.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::protocol::game::{
+    build_bank_full_update, build_bank_open, build_bank_settings, build_bank_slot_update,
+    build_bank_tab_info, build_ground_item_remove, build_ground_item_spawn,
+    build_inventory_full_update, build_inventory_slot_update, build_logout, build_map_region,
+    build_player_option, build_resume_token, build_run_energy, build_skill_update,
+    build_system_message, incoming_packets, IncomingGamePacket, OutgoingGamePacket, OutgoingOpcode,
+};
+use crate::protocol::login_init::{InitialPlayerState, LoginInitializer};
+use crate::protocol::message::ServerMessage;
+use crate::protocol::packets::PacketSize;
+
+/// Wire format of one client revision
+pub trait Protocol: Send + Sync {
+    /// Client revision this protocol speaks
+    fn revision(&self) -> u32;
+
+    /// Framing of an incoming packet (`Unknown` if the opcode isn't known)
+    fn incoming_size(&self, opcode: u8) -> PacketSize;
+
+    /// Decode a framed incoming packet into the server's packet set
+    ///
+    /// Returns `None` for packets the server has no use for.
+    fn decode(&self, packet: IncomingGamePacket) -> Option<IncomingGamePacket>;
+
+    /// Encode a message for this revision
+    ///
+    /// Returns `None` if the revision has no equivalent packet; the message
+    /// is then not sent.
+    fn encode(&self, message: &ServerMessage) -> Option<OutgoingGamePacket>;
+
+    /// Build the framed, unencrypted packets sent right after a successful login
+    fn login_init(&self, state: &InitialPlayerState) -> Vec<Vec<u8>>;
+}
+
+impl fmt::Debug for dyn Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Protocol({})", self.revision())
+    }
+}
+
+/// Revision 530, the server's native protocol
+///
+/// The server's packet set and packet builders are the 530 layouts, so
+/// decoding is the identity and encoding maps each message to its builder.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Revision530;
+
+impl Protocol for Revision530 {
+    fn revision(&self) -> u32 {
+        530
+    }
+
+    fn incoming_size(&self, opcode: u8) -> PacketSize {
+        incoming_packets().size(opcode)
+    }
+
+    fn decode(&self, packet: IncomingGamePacket) -> Option<IncomingGamePacket> {
+        Some(packet)
+    }
+
+    fn encode(&self, message: &ServerMessage) -> Option<OutgoingGamePacket> {
+        let packet = match message {
+            ServerMessage::SystemMessage(text) => build_system_message(text),
+            ServerMessage::Logout => build_logout(),
+            ServerMessage::ResumeToken(token) => build_resume_token(token),
+            ServerMessage::MapRegion(location) => build_map_region(*location),
+            ServerMessage::Skill {
+                skill_id,
+                level,
+                experience,
+            } => build_skill_update(*skill_id, *level, *experience),
+            ServerMessage::RunEnergy(energy) => build_run_energy(*energy),
+            ServerMessage::PlayerOption {
+                slot,
+                text,
+                priority,
+            } => build_player_option(*slot, text, *priority),
+            ServerMessage::ContainerSlot {
+                container,
+                slot,
+                item_id,
+                amount,
+            } => build_inventory_slot_update(*container, *slot, *item_id, *amount),
+            ServerMessage::Container { container, items } => {
+                build_inventory_full_update(*container, items)
+            }
+            ServerMessage::BankOpen { capacity } => build_bank_open(*capacity),
+            ServerMessage::BankSettings {
+                note_mode,
+                placeholders,
+                withdraw_x_amount,
+            } => build_bank_settings(*note_mode, *placeholders, *withdraw_x_amount),
+            ServerMessage::BankTabs(sizes) => build_bank_tab_info(sizes),
+            ServerMessage::BankContents(items) => build_bank_full_update(items),
+            ServerMessage::BankSlot {
+                tab,
+                slot,
+                item_id,
+                amount,
+                placeholder,
+            } => build_bank_slot_update(*tab, *slot, *item_id, *amount, *placeholder),
+            ServerMessage::GroundItemSpawn {
+                item_id,
+                amount,
+                location,
+            } => build_ground_item_spawn(*item_id, *amount, location),
+            ServerMessage::GroundItemRemove { item_id, location } => {
+                build_ground_item_remove(*item_id, location)
+            }
+            ServerMessage::PlayerUpdate(body) => OutgoingGamePacket::variable_short(
+                OutgoingOpcode::PlayerUpdate.as_u8(),
+                body.clone(),
+            ),
+        };
+        Some(packet)
+    }
+
+    fn login_init(&self, state: &InitialPlayerState) -> Vec<Vec<u8>> {
+        let mut initializer = LoginInitializer::new();
+        initializer.build_init_sequence(state);
+        initializer.get_packets(None)
+    }
+}
+
+/// The client revisions a server accepts
+#[derive(Clone)]
+pub struct Protocols {
+    by_revision: HashMap<u32, Arc<dyn Protocol>>,
+}
+
+impl Protocols {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self {
+            by_revision: HashMap::new(),
+        }
+    }
+
+    /// Add a protocol, replacing any registered for the same revision
+    pub fn register(&mut self, protocol: Arc<dyn Protocol>) -> &mut Self {
+        self.by_revision.insert(protocol.revision(), protocol);
+        self
+    }
+
+    /// Get the protocol for a client revision
+    pub fn get(&self, revision: u32) -> Option<Arc<dyn Protocol>> {
+        self.by_revision.get(&revision).cloned()
+    }
+
+    /// Check if a client revision is supported
+    pub fn supports(&self, revision: u32) -> bool {
+        self.by_revision.contains_key(&revision)
+    }
+
+    /// Supported revisions, in ascending order
+    pub fn revisions(&self) -> Vec<u32> {
+        let mut revisions: Vec<u32> = self.by_revision.keys().copied().collect();
+        revisions.sort_unstable();
+        revisions
+    }
+}
+
+impl Default for Protocols {
+    /// The built-in revisions
+    fn default() -> Self {
+        let mut protocols = Self::new();
+        protocols.register(Arc::new(Revision530));
+        protocols
+    }
+}
+
+impl fmt::Debug for Protocols {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Protocols")
+            .field("revisions", &self.revisions())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::player::Location;
+
+    /// A revision that only knows keep-alives and chat box messages, with
+    /// its own opcodes
+    struct TinyRevision;
+
+    impl Protocol for TinyRevision {
+        fn revision(&self) -> u32 {
+            7
+        }
+
+        fn incoming_size(&self, opcode: u8) -> PacketSize {
+            match opcode {
+                200 => PacketSize::Fixed(0),
+                _ => PacketSize::Unknown,
+            }
+        }
+
+        fn decode(&self, packet: IncomingGamePacket) -> Option<IncomingGamePacket> {
+            (packet.opcode == 200).then(|| IncomingGamePacket::new(0, vec![]))
+        }
+
+        fn encode(&self, message: &ServerMessage) -> Option<OutgoingGamePacket> {
+            match message {
+                ServerMessage::SystemMessage(text) => {
+                    Some(OutgoingGamePacket::variable(9, text.as_bytes().to_vec()))
+                }
+                _ => None,
+            }
+        }
+
+        fn login_init(&self, _state: &InitialPlayerState) -> Vec<Vec<u8>> {
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_revision_530_matches_builders() {
+        let protocol = Revision530;
+        let location = Location::new(3222, 3218, 0);
+
+        let cases = [
+            (
+                ServerMessage::system("Hello"),
+                build_system_message("Hello"),
+            ),
+            (
+                ServerMessage::MapRegion(location),
+                build_map_region(location),
+            ),
+            (
+                ServerMessage::inventory_slot(3, 995, 10),
+                build_inventory_slot_update(crate::protocol::game::CONTAINER_INVENTORY, 3, 995, 10),
+            ),
+            (
+                ServerMessage::GroundItemRemove {
+                    item_id: 995,
+                    location,
+                },
+                build_ground_item_remove(995, &location),
+            ),
+        ];
+
+        for (message, expected) in cases {
+            let packet = protocol.encode(&message).unwrap();
+            assert_eq!(packet.encode_raw(), expected.encode_raw(), "{:?}", message);
+        }
+
+        // Player updates are framed with a short length
+        let packet = protocol
+            .encode(&ServerMessage::PlayerUpdate(vec![1, 2, 3]))
+            .unwrap();
+        assert_eq!(packet.encode_raw(), vec![81, 0, 3, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_revision_530_framing_from_registry() {
+        let protocol = Revision530;
+        assert_eq!(protocol.incoming_size(0), PacketSize::Fixed(0));
+        assert_eq!(protocol.incoming_size(52), PacketSize::VariableByte);
+        assert_eq!(protocol.incoming_size(255), PacketSize::Unknown);
+
+        let packet = protocol
+            .decode(IncomingGamePacket::new(3, vec![1]))
+            .unwrap();
+        assert_eq!((packet.opcode, packet.data), (3, vec![1]));
+        assert!(!protocol
+            .login_init(&InitialPlayerState::new(1, 0, false))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_protocols_select_by_revision() {
+        let mut protocols = Protocols::default();
+        assert_eq!(protocols.revisions(), vec![530]);
+        assert!(protocols.get(7).is_none());
+
+        protocols.register(Arc::new(TinyRevision));
+        assert_eq!(protocols.revisions(), vec![7, 530]);
+        assert!(protocols.supports(7));
+
+        // The same message, encoded per revision
+        let message = ServerMessage::system("hi");
+        let native = protocols.get(530).unwrap().encode(&message).unwrap();
+        let tiny = protocols.get(7).unwrap().encode(&message).unwrap();
+        assert_eq!(native.opcode, OutgoingOpcode::SystemMessage.as_u8());
+        assert_eq!((tiny.opcode, tiny.data), (9, b"hi".to_vec()));
+
+        // Messages a revision can't express are skipped
+        let tiny = protocols.get(7).unwrap();
+        assert!(tiny.encode(&ServerMessage::Logout).is_none());
+
+        // Its packets decode into the server's packet set
+        let packet = tiny.decode(IncomingGamePacket::new(200, vec![])).unwrap();
+        assert_eq!(packet.opcode, 0);
+        assert!(tiny.decode(IncomingGamePacket::new(201, vec![])).is_none());
+    }
+}
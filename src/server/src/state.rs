@@ -3,19 +3,36 @@
 //! Contains the shared state used across all server connections.
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use sqlx::postgres::PgPool;
 use tokio::sync::broadcast;
 use tracing::{info, warn};
 
+use crate::auth::coordinator::LoginCoordinator;
+use crate::auth::game_token::GameTokens;
+use crate::auth::password::PasswordPolicy;
+use crate::auth::two_factor::TwoFactor;
 use crate::auth::AuthService;
 use crate::cache::CacheStore;
 use crate::config::ServerConfig;
 use crate::crypto::RsaDecryptor;
 use crate::error::Result;
+use crate::game::pathfinding::CollisionMap;
 use crate::game::persistence::PlayerPersistence;
 use crate::game::world::{GameWorld, WorldSettings};
-use crate::net::session::SessionManager;
+use crate::game::world_list::WorldDirectory;
+use crate::net::admission::LoginAdmission;
+use crate::net::deflate::CompressionMetrics;
+use crate::net::flood::FloodMetrics;
+use crate::net::js5_scheduler::Js5Scheduler;
+use crate::net::leases::LoginLeases;
+use crate::net::metrics::ConnectionMetrics;
+use crate::net::resume::ResumeRegistry;
+use crate::net::session::{SessionManager, DEFAULT_MAX_IDLE_SECS};
+use crate::net::tls::TlsReloader;
+use crate::protocol::js5_cache::Js5ResponseCache;
+use crate::protocol::revision::Protocols;
 
 /// Application state shared across all connections
 pub struct AppState {
@@ -25,14 +42,42 @@ pub struct AppState {
     pub session_manager: SessionManager,
     /// Game cache store
     pub cache: Arc<CacheStore>,
+    /// Shared cache of encoded JS5 responses
+    pub js5_responses: Arc<Js5ResponseCache>,
+    /// Global JS5 bandwidth scheduler
+    pub js5_scheduler: Arc<Js5Scheduler>,
+    /// Packet flood protection counters
+    pub flood_metrics: Arc<FloodMetrics>,
+    /// Connection error counters
+    pub connection_metrics: Arc<ConnectionMetrics>,
+    /// WebSocket compression counters
+    pub compression_metrics: Arc<CompressionMetrics>,
+    /// Players waiting to resume after a dropped connection
+    pub resume: Arc<ResumeRegistry>,
+    /// Login queue and failed-login throttling
+    pub admission: Arc<LoginAdmission>,
+    /// Login coordinator served by this server (None unless enabled)
+    pub coordinator: Option<Arc<LoginCoordinator>>,
+    /// Login leases held for this world's players
+    pub leases: Arc<LoginLeases>,
+    /// Client revisions accepted at the handshake
+    pub protocols: Protocols,
     /// Game world state
     pub world: Arc<GameWorld>,
+    /// Online worlds, kept current by heartbeats
+    pub worlds: Arc<WorldDirectory>,
     /// RSA decryptor for login (None in dev mode)
     pub rsa: Option<Arc<RsaDecryptor>>,
     /// Authentication service
     pub auth: Arc<AuthService>,
+    /// Two-factor authentication for accounts that enrolled it
+    pub two_factor: Arc<TwoFactor>,
+    /// One-time login tokens issued by the REST API
+    pub game_tokens: Arc<GameTokens>,
     /// Player persistence service (None if DB not configured)
     pub persistence: Option<Arc<PlayerPersistence>>,
+    /// TLS acceptor for the WebSocket listener (None if TLS is disabled)
+    pub tls: Option<Arc<TlsReloader>>,
     /// Shutdown signal sender
     pub shutdown_tx: broadcast::Sender<()>,
 }
@@ -41,10 +86,26 @@ impl AppState {
     /// Create a new application state without database persistence
     pub fn new(config: ServerConfig, shutdown_tx: broadcast::Sender<()>) -> Result<Self> {
         let cache = Arc::new(CacheStore::new(&config.cache_path)?);
+        let js5_responses = Arc::new(Js5ResponseCache::new(
+            cache.clone(),
+            config.js5.response_cache_bytes,
+        ));
+        let js5_scheduler = Arc::new(Js5Scheduler::new(&config.js5));
+        let tls = Self::load_tls(&config)?;
+        let resume = Arc::new(ResumeRegistry::new(Duration::from_secs(
+            config.session.resume_grace_secs,
+        )));
+        let session_manager = SessionManager::with_limits(
+            config.session.max_connections_per_ip,
+            DEFAULT_MAX_IDLE_SECS,
+        );
 
         // Create world settings from config
         let world_settings = Self::create_world_settings(&config);
-        let world = Arc::new(GameWorld::with_settings(world_settings)?);
+        let world = Arc::new(
+            GameWorld::with_settings(world_settings)?
+                .with_collision(CollisionMap::from_cache(cache.clone())),
+        );
 
         // Initialize RSA decryptor from config
         let rsa = match RsaDecryptor::from_hex(
@@ -69,19 +130,46 @@ pub fn new(config: ServerConfig, shutdown_tx: broadcast::Sender<()>) -> Result<S
         };
 
         // Initialize auth service
-        let auth = Arc::new(AuthService::new(config.dev_mode));
+        let auth = Arc::new(
+            AuthService::with_max_players(config.dev_mode, config.player_capacity())
+                .with_password_policy(PasswordPolicy::new(&config.password_hash)?),
+        );
         if config.dev_mode {
             info!("Auth service running in DEVELOPMENT mode - all logins accepted");
         }
 
+        let admission = Arc::new(LoginAdmission::new(
+            &config.login,
+            config.player_capacity() as usize,
+        ));
+        let worlds = Arc::new(WorldDirectory::new(&config, None));
+        let two_factor = Arc::new(TwoFactor::new(&config.two_factor, None)?);
+        let game_tokens = Arc::new(GameTokens::new(&config, None));
+        let coordinator = Self::create_coordinator(&config)?;
+        let leases = Arc::new(LoginLeases::from_config(&config, coordinator.clone())?);
+
         Ok(Self {
             config,
-            session_manager: SessionManager::new(),
+            session_manager,
             cache,
+            js5_responses,
+            js5_scheduler,
+            flood_metrics: Arc::new(FloodMetrics::new()),
+            connection_metrics: Arc::new(ConnectionMetrics::new()),
+            compression_metrics: Arc::new(CompressionMetrics::new()),
+            resume,
+            admission,
+            coordinator,
+            leases,
+            protocols: Protocols::default(),
             world,
+            worlds,
             rsa,
             auth,
+            two_factor,
+            game_tokens,
             persistence: None,
+            tls,
             shutdown_tx,
         })
     }
@@ -93,10 +181,26 @@ pub fn with_persistence(
         db_pool: PgPool,
     ) -> Result<Self> {
         let cache = Arc::new(CacheStore::new(&config.cache_path)?);
+        let js5_responses = Arc::new(Js5ResponseCache::new(
+            cache.clone(),
+            config.js5.response_cache_bytes,
+        ));
+        let js5_scheduler = Arc::new(Js5Scheduler::new(&config.js5));
+        let tls = Self::load_tls(&config)?;
+        let resume = Arc::new(ResumeRegistry::new(Duration::from_secs(
+            config.session.resume_grace_secs,
+        )));
+        let session_manager = SessionManager::with_limits(
+            config.session.max_connections_per_ip,
+            DEFAULT_MAX_IDLE_SECS,
+        );
 
         // Create world settings from config
         let world_settings = Self::create_world_settings(&config);
-        let world = Arc::new(GameWorld::with_settings(world_settings)?);
+        let world = Arc::new(
+            GameWorld::with_settings(world_settings)?
+                .with_collision(CollisionMap::from_cache(cache.clone())),
+        );
 
         // Initialize RSA decryptor from config
         let rsa = match RsaDecryptor::from_hex(
@@ -121,7 +225,10 @@ pub fn with_persistence(
         };
 
         // Initialize auth service with database for production auth
-        let auth = Arc::new(AuthService::with_database(config.dev_mode, db_pool.clone()));
+        let auth = Arc::new(
+            AuthService::with_database(config.dev_mode, db_pool.clone(), config.player_capacity())
+                .with_password_policy(PasswordPolicy::new(&config.password_hash)?),
+        );
         if config.dev_mode {
             info!("Auth service running in DEVELOPMENT mode - all logins accepted");
         } else {
@@ -129,21 +236,63 @@ pub fn with_persistence(
         }
 
         // Initialize persistence service
-        let persistence = Arc::new(PlayerPersistence::new(db_pool));
+        let persistence = Arc::new(PlayerPersistence::new(db_pool.clone()));
         info!("Player persistence service initialized");
 
+        let admission = Arc::new(LoginAdmission::new(
+            &config.login,
+            config.player_capacity() as usize,
+        ));
+        let two_factor = Arc::new(TwoFactor::new(&config.two_factor, Some(db_pool.clone()))?);
+        let game_tokens = Arc::new(GameTokens::connect(&config));
+        let coordinator = Self::create_coordinator(&config)?;
+        let leases = Arc::new(LoginLeases::from_config(&config, coordinator.clone())?);
+        let worlds = Arc::new(WorldDirectory::new(&config, Some(db_pool)));
+
         Ok(Self {
             config,
-            session_manager: SessionManager::new(),
+            session_manager,
             cache,
+            js5_responses,
+            js5_scheduler,
+            flood_metrics: Arc::new(FloodMetrics::new()),
+            connection_metrics: Arc::new(ConnectionMetrics::new()),
+            compression_metrics: Arc::new(CompressionMetrics::new()),
+            resume,
+            admission,
+            coordinator,
+            leases,
+            protocols: Protocols::default(),
             world,
+            worlds,
             rsa,
             auth,
+            two_factor,
+            game_tokens,
             persistence: Some(persistence),
+            tls,
             shutdown_tx,
         })
     }
 
+    /// Create the login coordinator if this server is the coordinator
+    fn create_coordinator(config: &ServerConfig) -> Result<Option<Arc<LoginCoordinator>>> {
+        if !config.coordinator.enabled {
+            return Ok(None);
+        }
+        let coordinator = LoginCoordinator::connect(config)?;
+        info!(?coordinator, "Login coordinator enabled");
+        Ok(Some(Arc::new(coordinator)))
+    }
+
+    /// Load the WebSocket TLS certificate if TLS is enabled
+    fn load_tls(config: &ServerConfig) -> Result<Option<Arc<TlsReloader>>> {
+        if !config.tls.enabled {
+            return Ok(None);
+        }
+        Ok(Some(Arc::new(TlsReloader::load(&config.tls)?)))
+    }
+
     /// Check if persistence is enabled
     pub fn has_persistence(&self) -> bool {
         self.persistence.is_some()
@@ -178,8 +327,10 @@ fn create_world_settings(config: &ServerConfig) -> WorldSettings {
             pvp: false,
             dev_mode: config.dev_mode,
             tick_rate_ms: config.tick_rate_ms,
-            max_players: config.max_players as usize,
+            max_players: config.player_capacity() as usize,
             autosave_interval: autosave_ticks,
+            packet_tick_budget: config.packets.tick_budget,
+            packet_queue_capacity: config.packets.queue_capacity,
         }
     }
 }
@@ -283,7 +283,7 @@ fn test_player_creation() {
         let player = Player::new(1, 12345, "testplayer".to_string());
 
         assert_eq!(player.index, 1);
-        assert_eq!(player.session_id, 12345);
+        assert_eq!(player.session_id(), 12345);
         assert_eq!(player.username, "testplayer");
         assert_eq!(player.display_name, "testplayer"); // underscores replaced with spaces
         assert!(player.user_id.is_none()); // No database user ID
@@ -303,7 +303,7 @@ fn test_player_from_player_data() {
         );
 
         assert_eq!(player.index, 1);
-        assert_eq!(player.session_id, 12345);
+        assert_eq!(player.session_id(), 12345);
         assert_eq!(player.user_id, Some(user_id)); // Should have user_id from PlayerData
         assert_eq!(player.display_name, "Test Player");
     }